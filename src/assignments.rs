@@ -0,0 +1,1077 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::key_name;
+use crate::error_handling::{CompilerError, Span};
+use crate::parser::{ImportSpecifier, MethodKind, Node};
+use crate::symboltable::{Meaning, ScopeId, SymbolFlags, SymbolId, SymbolTable};
+use crate::tokenizer::TokenType;
+
+/// Reports assignments that can't be made: to `const` bindings, imports, functions,
+/// classes, enums and namespaces, to `readonly` properties and enum members, through the
+/// index of a readonly array or tuple, and to anything that isn't a reference at all, like
+/// `f() = 1`. Each error has the code tsc gives it, and points at the declaration of the
+/// binding or property too when it is known.
+///
+/// Properties are found through the declared type of the name they are read from, so
+/// `this.x`, `point.x` with `point: Readonly<Point>` and `config.limits.max` are checked.
+/// The exports of a namespace are properties too, read-only when they are `const`, and so
+/// is every export read through an `import * as`. A name declared without an annotation
+/// is followed into its initializer when that is an object literal or an assertion: writes
+/// to the elements of the readonly arrays and tuples found there are reported, and the type
+/// checker reports the rest from the types it infers. A `readonly` property can still be
+/// assigned through `this` in the constructor of the class declaring it.
+///
+/// `table` must come from binding `program`, or a tree `program` was cloned from.
+pub fn check_assignments(program: &Node, table: &SymbolTable) -> Vec<CompilerError> {
+    let mut checker = Checker {
+        table,
+        declarations: HashMap::new(),
+        initializers: HashMap::new(),
+        namespace_imports: HashSet::new(),
+        class: None,
+        this: None,
+        errors: Vec::new(),
+    };
+    checker.collect_declarations(program);
+    checker.visit(program);
+    checker.errors
+}
+
+/// What the properties of a value are looked up in.
+#[derive(Debug, Clone, Copy)]
+enum Shape<'a> {
+    Annotation(&'a Node, ScopeId), // a type, with the scope its names are looked up in
+    Instance(&'a Node),            // of a class declaration
+    Class(&'a Node),               // the class itself, with its static members
+    Enum(SymbolId),
+    Namespace(ScopeId),         // the scope holding a namespace's exports
+    Module,                     // what `import * as` gives, every export of which is read-only
+    Literal(&'a Node, ScopeId), // an object literal, with the scope of the types it names
+    Const(&'a Node),            // an object or array literal asserted `as const`
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Property<'a> {
+    readonly: bool,
+    span: Span, // of its declaration
+    shape: Option<Shape<'a>>,
+}
+
+/// The class `this` refers to.
+#[derive(Debug, Clone, Copy)]
+struct This<'a> {
+    class: &'a Node,
+    is_static: bool,
+    in_constructor: bool,
+}
+
+struct Checker<'a> {
+    table: &'a SymbolTable,
+    // Classes, interfaces, aliases, enums and namespaces
+    declarations: HashMap<SymbolId, Vec<&'a Node>>,
+    initializers: HashMap<SymbolId, &'a Node>, // of variables declared without an annotation
+    namespace_imports: HashSet<SymbolId>,
+    class: Option<&'a Node>, // whose members are being checked
+    this: Option<This<'a>>,
+    errors: Vec<CompilerError>,
+}
+
+impl<'a> Checker<'a> {
+    fn collect_declarations(&mut self, node: &'a Node) {
+        match node {
+            Node::ClassDeclaration { .. }
+            | Node::InterfaceDeclaration { .. }
+            | Node::TypeAliasDeclaration { .. }
+            | Node::EnumDeclaration { .. }
+            | Node::NamespaceDeclaration { .. } => {
+                if let Some(symbol) = self.table.symbol_of(node) {
+                    self.declarations.entry(symbol).or_default().push(node);
+                }
+            }
+            Node::VariableDeclaration { declarations, .. } => {
+                for declarator in declarations {
+                    let (None, Some(init)) = (&declarator.type_annotation, &declarator.init) else {
+                        continue;
+                    };
+                    if let Some(symbol) = self.table.symbol_of(&declarator.id) {
+                        self.initializers.entry(symbol).or_insert(init);
+                    }
+                }
+            }
+            Node::ImportDeclaration { specifiers, .. } => {
+                for specifier in specifiers {
+                    if let ImportSpecifier::Namespace(local, span) = specifier {
+                        let symbol = self
+                            .table
+                            .lookup(SymbolTable::GLOBAL, local, Meaning::Value)
+                            .filter(|&symbol| self.table.symbol(symbol).span == *span);
+                        self.namespace_imports.extend(symbol);
+                    }
+                }
+            }
+            _ => {}
+        }
+        for child in node.children() {
+            self.collect_declarations(child);
+        }
+    }
+
+    fn visit(&mut self, node: &'a Node) {
+        match node {
+            Node::AssignmentExpression { left, .. } => self.assignment_target(left),
+            Node::UpdateExpression { argument, .. } => self.update_target(argument),
+            Node::ForInStatement { left, .. } | Node::ForOfStatement { left, .. }
+                if !matches!(left.as_ref(), Node::VariableDeclaration { .. }) =>
+            {
+                self.pattern_target(left)
+            }
+            Node::ClassDeclaration { .. } => {
+                let outer = self.class.replace(node);
+                self.visit_children(node);
+                self.class = outer;
+                return;
+            }
+            // Methods of object literals have the object as `this`
+            Node::ObjectExpression(_) => {
+                let outer = self.class.take();
+                self.visit_children(node);
+                self.class = outer;
+                return;
+            }
+            Node::MethodDefinition {
+                kind, modifiers, ..
+            } => {
+                let this = self.class.map(|class| This {
+                    class,
+                    is_static: modifiers.is_static,
+                    in_constructor: *kind == MethodKind::Constructor,
+                });
+                return self.visit_with_this(node, this);
+            }
+            Node::PropertyDefinition { modifiers, .. } => {
+                let this = self.class.map(|class| This {
+                    class,
+                    is_static: modifiers.is_static,
+                    in_constructor: false,
+                });
+                return self.visit_with_this(node, this);
+            }
+            Node::FunctionDeclaration { .. } => return self.visit_with_this(node, None),
+            Node::ArrowFunctionExpression { .. } => {
+                let this = self.this.map(|this| This {
+                    in_constructor: false,
+                    ..this
+                });
+                return self.visit_with_this(node, this);
+            }
+            _ => {}
+        }
+        self.visit_children(node);
+    }
+
+    fn visit_children(&mut self, node: &'a Node) {
+        for child in node.children() {
+            self.visit(child);
+        }
+    }
+
+    fn visit_with_this(&mut self, node: &'a Node, this: Option<This<'a>>) {
+        let outer = std::mem::replace(&mut self.this, this);
+        self.visit_children(node);
+        self.this = outer;
+    }
+
+    fn assignment_target(&mut self, target: &'a Node) {
+        match without_assertions(target) {
+            target @ (Node::Identifier(..)
+            | Node::MemberExpression { .. }
+            | Node::ObjectPattern(_)
+            | Node::ArrayPattern(_)) => self.pattern_target(target),
+            // Reported by the parser
+            Node::ChainExpression(_) => {}
+            _ => self.error(
+                2364,
+                "The left-hand side of an assignment expression must be a variable or a property access.".to_string(),
+                target.span(),
+            ),
+        }
+    }
+
+    fn update_target(&mut self, target: &'a Node) {
+        match without_assertions(target) {
+            target @ (Node::Identifier(..) | Node::MemberExpression { .. }) => {
+                self.reference_target(target)
+            }
+            Node::ChainExpression(_) => self.error(
+                2777,
+                "The operand of an increment or decrement operator may not be an optional property access.".to_string(),
+                target.span(),
+            ),
+            _ => self.error(
+                2357,
+                "The operand of an increment or decrement operator must be a variable or a property access.".to_string(),
+                target.span(),
+            ),
+        }
+    }
+
+    /// Checks every reference a destructuring assignment writes to.
+    fn pattern_target(&mut self, target: &'a Node) {
+        match target {
+            Node::ObjectPattern(elements) => {
+                for element in elements {
+                    self.pattern_target(element);
+                }
+            }
+            Node::ArrayPattern(elements) => {
+                for element in elements.iter().flatten() {
+                    self.pattern_target(element);
+                }
+            }
+            Node::PatternProperty { value, .. } => self.pattern_target(value),
+            Node::AssignmentPattern { left, .. } => self.pattern_target(left),
+            Node::RestElement(argument) => self.pattern_target(argument),
+            target => self.reference_target(without_assertions(target)),
+        }
+    }
+
+    fn reference_target(&mut self, target: &'a Node) {
+        match target {
+            Node::Identifier(name, span) => {
+                let Some(symbol) = self.table.symbol_of(target) else {
+                    return;
+                };
+                let declared = self.table.symbol(symbol);
+                let flags = declared.flags;
+                let (code, kind) = if flags.contains(SymbolFlags::IMPORT) {
+                    (2632, "an import")
+                } else if flags.contains(SymbolFlags::BLOCK_SCOPED_VARIABLE) && declared.is_constant
+                {
+                    (2588, "a constant")
+                } else if flags.intersects(
+                    SymbolFlags::VARIABLE
+                        | SymbolFlags::BLOCK_SCOPED_VARIABLE
+                        | SymbolFlags::PARAMETER,
+                ) {
+                    return;
+                } else if flags.contains(SymbolFlags::FUNCTION) {
+                    (2630, "a function")
+                } else if flags.contains(SymbolFlags::CLASS) {
+                    (2629, "a class")
+                } else if flags.contains(SymbolFlags::ENUM) {
+                    (2628, "an enum")
+                } else if flags.contains(SymbolFlags::NAMESPACE) {
+                    (2631, "a namespace")
+                } else {
+                    return;
+                };
+                let error = CompilerError::new(
+                    format!("Cannot assign to '{}' because it is {}.", name, kind),
+                    *span,
+                )
+                .with_code(code)
+                .with_related(format!("'{}' is declared here.", name), declared.span);
+                self.errors.push(error);
+            }
+            Node::MemberExpression {
+                object,
+                property,
+                computed,
+                ..
+            } => {
+                let Some(shape) = self.shape_of(object) else {
+                    return;
+                };
+                // The type checker's types know which properties are read-only, but not
+                // which arrays and tuples are
+                let readonly_index = self.readonly_index(shape, 0);
+                if !has_declared_shape(object, self.table)
+                    && !readonly_index
+                        .as_ref()
+                        .is_some_and(|(_, is_array)| *is_array)
+                {
+                    return;
+                }
+                let name = key_name(property, *computed);
+                let found = name.and_then(|name| self.property(shape, name, 0));
+                let (Some(name), Some(found)) = (name, found) else {
+                    // Writing a key no property declares goes through an index signature
+                    if let Some((type_text, _)) = readonly_index {
+                        self.error(
+                            2542,
+                            format!(
+                                "Index signature in type '{}' only permits reading.",
+                                type_text
+                            ),
+                            target.span(),
+                        );
+                    }
+                    return;
+                };
+                if !found.readonly || self.initializes(object, name) {
+                    return;
+                }
+                let span = match property.as_ref() {
+                    Node::Identifier(_, span) => *span,
+                    _ => target.span(),
+                };
+                let error = CompilerError::new(
+                    format!(
+                        "Cannot assign to '{}' because it is a read-only property.",
+                        name
+                    ),
+                    span,
+                )
+                .with_code(2540)
+                .with_related(format!("'{}' is declared here.", name), found.span);
+                self.errors.push(error);
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether `object.name` is a property the constructor running it may initialize.
+    fn initializes(&self, object: &Node, name: &str) -> bool {
+        match (object, self.this) {
+            (Node::ThisExpression, Some(this)) => {
+                this.in_constructor
+                    && !this.is_static
+                    && self.class_member(this.class, name, false).is_some()
+            }
+            _ => false,
+        }
+    }
+
+    fn shape_of(&self, expression: &'a Node) -> Option<Shape<'a>> {
+        match expression {
+            Node::Identifier(..) => self.symbol_shape(self.table.symbol_of(expression)?),
+            Node::ThisExpression => {
+                let this = self.this?;
+                Some(if this.is_static {
+                    Shape::Class(this.class)
+                } else {
+                    Shape::Instance(this.class)
+                })
+            }
+            Node::MemberExpression {
+                object,
+                property,
+                computed,
+                ..
+            } => {
+                let name = key_name(property, *computed)?;
+                self.property(self.shape_of(object)?, name, 0)?.shape
+            }
+            Node::NonNullExpression(expression) => self.shape_of(expression),
+            _ => None,
+        }
+    }
+
+    fn symbol_shape(&self, symbol: SymbolId) -> Option<Shape<'a>> {
+        let declared = self.table.symbol(symbol);
+        let flags = declared.flags;
+        if flags.contains(SymbolFlags::ENUM) {
+            return Some(Shape::Enum(symbol));
+        }
+        if flags.contains(SymbolFlags::CLASS) {
+            return self.class_declaration(symbol).map(Shape::Class);
+        }
+        if self.namespace_imports.contains(&symbol) {
+            return Some(Shape::Module);
+        }
+        // Only a namespace, not merged with a value that has members of its own
+        if flags.contains(SymbolFlags::NAMESPACE) && !flags.intersects(SymbolFlags::FUNCTION) {
+            // Each declaration's body scope is inside the exports shared by all of them
+            let declaration = self.declarations.get(&symbol)?.first()?;
+            let exports = self.table.scope(self.table.scope_of(declaration)?).parent?;
+            return Some(Shape::Namespace(exports));
+        }
+        let scope = self.table.declaring_scope(symbol);
+        match &declared.type_annotation {
+            Some(type_annotation) => Some(Shape::Annotation(type_annotation, scope)),
+            None => self.value_shape(self.initializers.get(&symbol)?, scope),
+        }
+    }
+
+    /// What the properties of the value of `expression`, written in `scope`, are looked up
+    /// in, when it is one `check_assignments` can follow.
+    fn value_shape(&self, expression: &'a Node, scope: ScopeId) -> Option<Shape<'a>> {
+        match expression {
+            Node::ObjectExpression(_) => Some(Shape::Literal(expression, scope)),
+            Node::AsExpression {
+                expression,
+                type_annotation,
+            }
+            | Node::TypeAssertion {
+                expression,
+                type_annotation,
+            } => match type_annotation.as_ref() {
+                Node::KeywordType(TokenType::Const) => const_shape(expression),
+                type_annotation => Some(Shape::Annotation(type_annotation, scope)),
+            },
+            Node::SatisfiesExpression { expression, .. } => self.value_shape(expression, scope),
+            Node::Identifier(..) | Node::ThisExpression | Node::MemberExpression { .. } => {
+                self.shape_of(expression)
+            }
+            _ => None,
+        }
+    }
+
+    fn class_declaration(&self, symbol: SymbolId) -> Option<&'a Node> {
+        self.declarations
+            .get(&symbol)?
+            .iter()
+            .copied()
+            .find(|declaration| matches!(declaration, Node::ClassDeclaration { .. }))
+    }
+
+    /// The class an `extends` clause names.
+    fn base_class(&self, class: &Node) -> Option<&'a Node> {
+        let Node::ClassDeclaration {
+            extends: Some(base),
+            ..
+        } = class
+        else {
+            return None;
+        };
+        self.class_declaration(self.table.symbol_of(base)?)
+    }
+
+    fn property(&self, shape: Shape<'a>, name: &str, depth: usize) -> Option<Property<'a>> {
+        // Recursive types and circular bases end somewhere
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        match shape {
+            Shape::Annotation(node, scope) => self.type_property(node, scope, name, depth),
+            Shape::Instance(class) | Shape::Class(class) => {
+                let is_static = matches!(shape, Shape::Class(_));
+                self.class_member(class, name, is_static).or_else(|| {
+                    let base = self.base_class(class)?;
+                    let base = if is_static {
+                        Shape::Class(base)
+                    } else {
+                        Shape::Instance(base)
+                    };
+                    self.property(base, name, depth + 1)
+                })
+            }
+            Shape::Enum(symbol) => self
+                .declarations
+                .get(&symbol)?
+                .iter()
+                .find_map(|declaration| match declaration {
+                    Node::EnumDeclaration { members, .. } => {
+                        members.iter().find(|member| member.name == name)
+                    }
+                    _ => None,
+                })
+                .map(|member| Property {
+                    readonly: true,
+                    span: member.span,
+                    shape: None,
+                }),
+            Shape::Namespace(exports) => {
+                let symbol = *self.table.scope(exports).symbols.get(name)?;
+                let declared = self.table.symbol(symbol);
+                if !declared.flags.has_meaning(Meaning::Value) {
+                    return None;
+                }
+                Some(Property {
+                    readonly: declared.flags.contains(SymbolFlags::BLOCK_SCOPED_VARIABLE)
+                        && declared.is_constant,
+                    span: declared.span,
+                    shape: self.symbol_shape(symbol),
+                })
+            }
+            Shape::Module => Some(Property {
+                readonly: true,
+                span: Span::default(),
+                shape: None,
+            }),
+            Shape::Literal(object, scope) => self.literal_property(object, scope, name),
+            // Every property of a constant is read-only
+            Shape::Const(Node::ObjectExpression(properties)) => {
+                properties.iter().rev().find_map(|property| match property {
+                    Node::Property {
+                        key,
+                        computed,
+                        value,
+                        ..
+                    } if key_name(key, *computed) == Some(name) => Some(Property {
+                        readonly: true,
+                        span: key.span(),
+                        shape: const_shape(value),
+                    }),
+                    _ => None,
+                })
+            }
+            Shape::Const(Node::ArrayExpression(elements)) => {
+                let shape = if name == "length" {
+                    None
+                } else {
+                    const_shape(elements.get(name.parse::<usize>().ok()?)?.as_ref()?)
+                };
+                Some(Property {
+                    readonly: true,
+                    span: Span::default(),
+                    shape,
+                })
+            }
+            Shape::Const(_) => None,
+        }
+    }
+
+    /// A property an object literal writes, as the last of its members with that name
+    /// declares it. An accessor without a setter is read-only.
+    fn literal_property(
+        &self,
+        object: &'a Node,
+        scope: ScopeId,
+        name: &str,
+    ) -> Option<Property<'a>> {
+        let Node::ObjectExpression(properties) = object else {
+            return None;
+        };
+        let mut found = None;
+        let mut has_setter = false;
+        for property in properties {
+            match property {
+                Node::Property {
+                    key,
+                    computed,
+                    value,
+                    ..
+                } if key_name(key, *computed) == Some(name) => {
+                    found = Some(Property {
+                        readonly: false,
+                        span: key.span(),
+                        shape: self.value_shape(value, scope),
+                    });
+                    has_setter = false;
+                }
+                Node::MethodDefinition {
+                    key,
+                    computed,
+                    kind,
+                    ..
+                } if key_name(key, *computed) == Some(name) => {
+                    has_setter |= *kind == MethodKind::Set;
+                    if *kind != MethodKind::Set {
+                        found = Some(Property {
+                            readonly: *kind == MethodKind::Get,
+                            span: key.span(),
+                            shape: None,
+                        });
+                    }
+                }
+                // What a spread gives isn't known here
+                Node::SpreadElement(_) => found = None,
+                _ => {}
+            }
+        }
+        found.map(|property| Property {
+            readonly: property.readonly && !has_setter,
+            ..property
+        })
+    }
+
+    /// A member the class declares itself, including constructor parameter properties. An
+    /// accessor without a setter is read-only.
+    fn class_member(&self, class: &'a Node, name: &str, is_static: bool) -> Option<Property<'a>> {
+        let Node::ClassDeclaration { members, .. } = class else {
+            return None;
+        };
+        let scope = self.table.scope_of(class).unwrap_or(SymbolTable::GLOBAL);
+        let mut getter = None;
+        let mut has_setter = false;
+        for member in members {
+            match member {
+                Node::PropertyDefinition {
+                    key,
+                    computed,
+                    type_annotation,
+                    modifiers,
+                    ..
+                } if modifiers.is_static == is_static && key_name(key, *computed) == Some(name) => {
+                    return Some(Property {
+                        readonly: modifiers.readonly,
+                        span: key.span(),
+                        shape: type_annotation
+                            .as_deref()
+                            .map(|type_annotation| Shape::Annotation(type_annotation, scope)),
+                    });
+                }
+                Node::MethodDefinition {
+                    kind: MethodKind::Constructor,
+                    params,
+                    ..
+                } if !is_static => {
+                    let parameter = params.iter().find(|param| {
+                        (param.modifiers.readonly || param.modifiers.accessibility.is_some())
+                            && param.name() == Some(name)
+                    });
+                    if let Some(parameter) = parameter {
+                        let scope = self.table.scope_of(member).unwrap_or(scope);
+                        return Some(Property {
+                            readonly: parameter.modifiers.readonly,
+                            span: parameter.binding.span(),
+                            shape: parameter
+                                .type_annotation
+                                .as_deref()
+                                .map(|type_annotation| Shape::Annotation(type_annotation, scope)),
+                        });
+                    }
+                }
+                Node::MethodDefinition {
+                    key,
+                    computed,
+                    kind,
+                    return_type,
+                    modifiers,
+                    ..
+                } if modifiers.is_static == is_static && key_name(key, *computed) == Some(name) => {
+                    let property = Property {
+                        readonly: false,
+                        span: key.span(),
+                        shape: return_type.as_deref().map(|return_type| {
+                            Shape::Annotation(
+                                return_type,
+                                self.table.scope_of(member).unwrap_or(scope),
+                            )
+                        }),
+                    };
+                    match kind {
+                        MethodKind::Get => getter = Some(property),
+                        MethodKind::Set => has_setter = true,
+                        _ => return Some(property),
+                    }
+                }
+                _ => {}
+            }
+        }
+        getter.map(|getter| Property {
+            readonly: !has_setter,
+            ..getter
+        })
+    }
+
+    fn type_property(
+        &self,
+        node: &'a Node,
+        scope: ScopeId,
+        name: &str,
+        depth: usize,
+    ) -> Option<Property<'a>> {
+        match node {
+            Node::ParenthesizedType(inner) => self.type_property(inner, scope, name, depth),
+            Node::TypeReference {
+                name: type_name,
+                type_arguments,
+                ..
+            } => {
+                let Some(symbol) = self.table.lookup(scope, type_name, Meaning::Type) else {
+                    return match (type_name.as_str(), type_arguments.as_slice()) {
+                        ("Readonly", [argument]) => {
+                            let property = self.type_property(argument, scope, name, depth + 1)?;
+                            Some(Property {
+                                readonly: true,
+                                ..property
+                            })
+                        }
+                        ("ReadonlyArray", [_]) if name == "length" => Some(Property {
+                            readonly: true,
+                            span: Span::default(),
+                            shape: None,
+                        }),
+                        _ => None,
+                    };
+                };
+                self.declarations
+                    .get(&symbol)?
+                    .iter()
+                    .find_map(|declaration| self.declared_property(declaration, name, depth + 1))
+            }
+            Node::ObjectType { members } => members_property(members, scope, name),
+            Node::UnionType(types) | Node::IntersectionType(types) => {
+                let found: Vec<Property> = types
+                    .iter()
+                    .filter_map(|t| self.type_property(t, scope, name, depth + 1))
+                    .collect();
+                let readonly = found.iter().any(|property| property.readonly);
+                found.into_iter().next().map(|property| Property {
+                    readonly,
+                    ..property
+                })
+            }
+            Node::TypeOperator {
+                operator,
+                type_annotation,
+            } if operator == "readonly" => {
+                // Every element of a readonly array or tuple is read-only, and so is its length
+                let shape = match type_annotation.as_ref() {
+                    Node::ArrayType(_) if name == "length" => None,
+                    Node::ArrayType(_) => return None,
+                    tuple => self.type_property(tuple, scope, name, depth + 1)?.shape,
+                };
+                Some(Property {
+                    readonly: true,
+                    span: Span::default(),
+                    shape,
+                })
+            }
+            Node::TupleType(elements) => {
+                if name == "length" {
+                    return Some(Property {
+                        readonly: false,
+                        span: Span::default(),
+                        shape: None,
+                    });
+                }
+                let element = elements.get(name.parse::<usize>().ok()?)?;
+                let element = match element {
+                    Node::NamedTupleMember { element_type, .. } => element_type,
+                    element => element,
+                };
+                Some(Property {
+                    readonly: false,
+                    span: Span::default(),
+                    shape: Some(Shape::Annotation(element, scope)),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// A property of the type a class, interface or type alias declares.
+    fn declared_property(
+        &self,
+        declaration: &'a Node,
+        name: &str,
+        depth: usize,
+    ) -> Option<Property<'a>> {
+        let scope = self.table.scope_of(declaration)?;
+        match declaration {
+            Node::ClassDeclaration { .. } => {
+                self.property(Shape::Instance(declaration), name, depth)
+            }
+            Node::InterfaceDeclaration {
+                extends, members, ..
+            } => members_property(members, scope, name).or_else(|| {
+                extends
+                    .iter()
+                    .find_map(|base| self.type_property(base, scope, name, depth))
+            }),
+            Node::TypeAliasDeclaration {
+                type_annotation, ..
+            } => self.type_property(type_annotation, scope, name, depth),
+            _ => None,
+        }
+    }
+
+    /// How a readonly array, tuple or index signature type is written, if `shape` is one,
+    /// and whether it is an array or tuple.
+    fn readonly_index(&self, shape: Shape<'a>, depth: usize) -> Option<(String, bool)> {
+        let (node, scope) = match shape {
+            Shape::Annotation(node, scope) => (node, scope),
+            Shape::Const(array @ Node::ArrayExpression(_)) => {
+                return Some((const_text(array), true))
+            }
+            _ => return None,
+        };
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        match node {
+            Node::ParenthesizedType(inner) => {
+                self.readonly_index(Shape::Annotation(inner, scope), depth + 1)
+            }
+            Node::TypeOperator {
+                operator,
+                type_annotation,
+            } if operator == "readonly"
+                && matches!(
+                    type_annotation.as_ref(),
+                    Node::ArrayType(_) | Node::TupleType(_)
+                ) =>
+            {
+                Some((type_text(node), true))
+            }
+            Node::TypeReference {
+                name,
+                type_arguments,
+                ..
+            } => match self.table.lookup(scope, name, Meaning::Type) {
+                None => match (name.as_str(), type_arguments.as_slice()) {
+                    ("ReadonlyArray", [_]) => Some((type_text(node), true)),
+                    ("Readonly", [Node::ArrayType(element)]) => Some((
+                        format!("readonly {}", type_text(&Node::ArrayType(element.clone()))),
+                        true,
+                    )),
+                    _ => None,
+                },
+                Some(symbol) => self
+                    .declarations
+                    .get(&symbol)?
+                    .iter()
+                    .find_map(|declaration| {
+                        let scope = self.table.scope_of(declaration)?;
+                        match declaration {
+                            Node::InterfaceDeclaration {
+                                extends, members, ..
+                            } => has_readonly_index(members)
+                                .then(|| (type_text(node), false))
+                                .or_else(|| {
+                                    extends.iter().find_map(|base| {
+                                        self.readonly_index(
+                                            Shape::Annotation(base, scope),
+                                            depth + 1,
+                                        )
+                                    })
+                                }),
+                            Node::TypeAliasDeclaration {
+                                type_annotation, ..
+                            } => self.readonly_index(
+                                Shape::Annotation(type_annotation, scope),
+                                depth + 1,
+                            ),
+                            _ => None,
+                        }
+                    }),
+            },
+            Node::ObjectType { members } => {
+                has_readonly_index(members).then(|| (type_text(node), false))
+            }
+            Node::UnionType(types) => types
+                .iter()
+                .find_map(|t| self.readonly_index(Shape::Annotation(t, scope), depth + 1)),
+            _ => None,
+        }
+    }
+
+    fn error(&mut self, code: u32, message: String, span: Span) {
+        self.errors
+            .push(CompilerError::new(message, span).with_code(code));
+    }
+}
+
+const MAX_DEPTH: usize = 32;
+
+/// A property of an interface or object type literal.
+fn members_property<'a>(members: &'a [Node], scope: ScopeId, name: &str) -> Option<Property<'a>> {
+    members.iter().find_map(|member| match member {
+        Node::PropertySignature {
+            key,
+            value,
+            readonly,
+            computed,
+            ..
+        } if key_name(key, *computed) == Some(name) => Some(Property {
+            readonly: *readonly,
+            span: key.span(),
+            shape: value
+                .as_deref()
+                .map(|value| Shape::Annotation(value, scope)),
+        }),
+        Node::MethodSignature { key, computed, .. } if key_name(key, *computed) == Some(name) => {
+            Some(Property {
+                readonly: false,
+                span: key.span(),
+                shape: None,
+            })
+        }
+        _ => None,
+    })
+}
+
+/// What the properties of `value`, asserted `as const`, are looked up in, if it has any.
+fn const_shape(value: &Node) -> Option<Shape<'_>> {
+    match value {
+        Node::ObjectExpression(_) | Node::ArrayExpression(_) => Some(Shape::Const(value)),
+        Node::AsExpression { expression, .. } | Node::SatisfiesExpression { expression, .. } => {
+            const_shape(expression)
+        }
+        _ => None,
+    }
+}
+
+/// The type of `value` asserted `as const`, the way tsc writes it.
+fn const_text(value: &Node) -> String {
+    match value {
+        Node::Literal {
+            token_type: TokenType::StringLiteral,
+            value,
+            ..
+        } => format!("\"{}\"", value.trim_matches('"')),
+        Node::Literal { value, .. } => value.clone(),
+        Node::ArrayExpression(elements) => format!(
+            "readonly [{}]",
+            elements
+                .iter()
+                .map(|element| element.as_ref().map_or("undefined".to_string(), const_text))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        _ => "{ ... }".to_string(),
+    }
+}
+
+fn has_readonly_index(members: &[Node]) -> bool {
+    members
+        .iter()
+        .any(|member| matches!(member, Node::IndexSignature { readonly: true, .. }))
+}
+
+/// Whether `check_assignments` looks the properties of `object` up in declarations: those
+/// of `this`, classes, enums, namespaces, imports and annotated names, and of the
+/// properties of those. The type checker checks writes to properties of anything else
+/// through its resolved type.
+pub fn has_declared_shape(object: &Node, table: &SymbolTable) -> bool {
+    match object {
+        Node::ThisExpression => true,
+        Node::Identifier(..) => table.symbol_of(object).is_some_and(|symbol| {
+            let declared = table.symbol(symbol);
+            declared.type_annotation.is_some()
+                || declared.flags.intersects(
+                    SymbolFlags::CLASS
+                        | SymbolFlags::ENUM
+                        | SymbolFlags::NAMESPACE
+                        | SymbolFlags::IMPORT,
+                )
+        }),
+        Node::MemberExpression { object, .. } | Node::NonNullExpression(object) => {
+            has_declared_shape(object, table)
+        }
+        _ => false,
+    }
+}
+
+/// `x!`, `x as T`, `<T>x` and `x satisfies T` are still the reference `x`.
+fn without_assertions(node: &Node) -> &Node {
+    match node {
+        Node::NonNullExpression(expression)
+        | Node::AsExpression { expression, .. }
+        | Node::TypeAssertion { expression, .. }
+        | Node::SatisfiesExpression { expression, .. } => without_assertions(expression),
+        node => node,
+    }
+}
+
+/// A type the way it would be written in source, for messages.
+fn type_text(node: &Node) -> String {
+    match node {
+        Node::KeywordType(token_type) => match token_type {
+            TokenType::NumberType => "number",
+            TokenType::StringType => "string",
+            TokenType::BooleanType => "boolean",
+            TokenType::Null => "null",
+            TokenType::Undefined => "undefined",
+            TokenType::Any => "any",
+            TokenType::Unknown => "unknown",
+            TokenType::Never => "never",
+            TokenType::Void => "void",
+            TokenType::BigInt => "bigint",
+            TokenType::Symbol => "symbol",
+            _ => "object",
+        }
+        .to_string(),
+        Node::TypeReference {
+            name,
+            type_arguments,
+            ..
+        } if type_arguments.is_empty() => name.clone(),
+        Node::TypeReference {
+            name,
+            type_arguments,
+            ..
+        } => format!("{}<{}>", name, join_types(type_arguments, ", ")),
+        Node::ArrayType(element) => match element.as_ref() {
+            Node::UnionType(_) | Node::IntersectionType(_) | Node::FunctionType { .. } => {
+                format!("({})[]", type_text(element))
+            }
+            element => format!("{}[]", type_text(element)),
+        },
+        Node::TupleType(elements) => format!("[{}]", join_types(elements, ", ")),
+        Node::NamedTupleMember {
+            name,
+            element_type,
+            optional,
+            rest,
+        } => format!(
+            "{}{}{}: {}",
+            if *rest { "..." } else { "" },
+            name,
+            if *optional { "?" } else { "" },
+            type_text(element_type)
+        ),
+        Node::OptionalType(element) => format!("{}?", type_text(element)),
+        Node::RestType(element) => format!("...{}", type_text(element)),
+        Node::TypeOperator {
+            operator,
+            type_annotation,
+        } => format!("{} {}", operator, type_text(type_annotation)),
+        Node::UnionType(types) => join_types(types, " | "),
+        Node::IntersectionType(types) => join_types(types, " & "),
+        Node::ParenthesizedType(inner) => format!("({})", type_text(inner)),
+        Node::LiteralType(literal) => match literal.as_ref() {
+            Node::Literal {
+                token_type: TokenType::StringLiteral,
+                value,
+                ..
+            } => format!("\"{}\"", value),
+            Node::Literal { value, .. } => value.clone(),
+            _ => "unknown".to_string(),
+        },
+        _ => "{ ... }".to_string(),
+    }
+}
+
+fn join_types(types: &[Node], separator: &str) -> String {
+    types
+        .iter()
+        .map(type_text)
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binder::bind;
+    use crate::parser::Parser;
+    use crate::tokenizer::Tokenizer;
+
+    /// The codes of the errors `check_assignments` gives for `source`.
+    fn errors(source: &str) -> Vec<u32> {
+        let tokens = Tokenizer::new(source.to_string()).tokenize();
+        let program = Parser::new(tokens).parse().result.expect("parses");
+        let table = bind(&program).result.expect("binds");
+        check_assignments(&program, &table)
+            .iter()
+            .map(|error| error.code.unwrap_or(0))
+            .collect()
+    }
+
+    #[test]
+    fn exports_read_through_a_namespace_are_checked() {
+        let namespace = "namespace N { export const q = 1; export let r = 1; }";
+        assert_eq!(errors(&format!("{} N.q = 2;", namespace)), [2540]);
+        assert_eq!(errors(&format!("{} N.r = 2;", namespace)), []);
+        let import = "import * as ns from './m';";
+        assert_eq!(
+            errors(&format!("{} ns.x = 4; ns.x++; [ns.x] = [1];", import)),
+            [2540, 2540, 2540]
+        );
+        assert_eq!(errors("import { o } from './m'; o.x = 4;"), []);
+    }
+
+    #[test]
+    fn readonly_arrays_inside_unannotated_literals_are_checked() {
+        let source = "declare const xs: readonly number[];
+            const p = { xs, ys: [1, 2] as const, zs: [1] as readonly number[], ws: [1] };";
+        assert_eq!(errors(&format!("{} p.xs[0] = 1;", source)), [2542]);
+        assert_eq!(errors(&format!("{} p.ys[0] = 1;", source)), [2540]);
+        assert_eq!(errors(&format!("{} p.zs[0] = 1;", source)), [2542]);
+        assert_eq!(errors(&format!("{} p.ws[0] = 1;", source)), []);
+    }
+}
@@ -0,0 +1,122 @@
+use crate::error_handling::Span;
+use crate::parser::{Node, VariableDeclarator, VariableKind};
+use crate::tokenizer::TokenType;
+
+// Nodes the lowering passes add to a program. They have no span, so they can't be looked
+// up in a symbol table; see `SymbolTable`.
+
+pub fn identifier(name: &str) -> Node {
+    Node::Identifier(name.to_string(), Span::default())
+}
+
+pub fn string(value: &str) -> Node {
+    Node::Literal {
+        token_type: TokenType::StringLiteral,
+        value: value.to_string(),
+        span: Span::default(),
+    }
+}
+
+pub fn number(value: usize) -> Node {
+    Node::Literal {
+        token_type: TokenType::NumberLiteral,
+        value: value.to_string(),
+        span: Span::default(),
+    }
+}
+
+pub fn boolean(value: bool) -> Node {
+    Node::Literal {
+        token_type: if value {
+            TokenType::True
+        } else {
+            TokenType::False
+        },
+        value: value.to_string(),
+        span: Span::default(),
+    }
+}
+
+pub fn null() -> Node {
+    Node::Literal {
+        token_type: TokenType::Null,
+        value: "null".to_string(),
+        span: Span::default(),
+    }
+}
+
+pub fn undefined() -> Node {
+    Node::Literal {
+        token_type: TokenType::Undefined,
+        value: "undefined".to_string(),
+        span: Span::default(),
+    }
+}
+
+/// `object.property`
+pub fn member(object: Node, property: &str) -> Node {
+    Node::MemberExpression {
+        object: Box::new(object),
+        property: Box::new(identifier(property)),
+        computed: false,
+        optional: false,
+    }
+}
+
+pub fn call(callee: Node, arguments: Vec<Node>) -> Node {
+    Node::CallExpression {
+        callee: Box::new(callee),
+        type_arguments: Vec::new(),
+        arguments,
+        optional: false,
+    }
+}
+
+pub fn binary(left: Node, operator: &str, right: Node) -> Node {
+    Node::BinaryExpression {
+        left: Box::new(left),
+        operator: operator.to_string(),
+        right: Box::new(right),
+    }
+}
+
+/// `target = value`, as an expression.
+pub fn assignment(target: Node, value: Node) -> Node {
+    Node::AssignmentExpression {
+        left: Box::new(target),
+        operator: "=".to_string(),
+        right: Box::new(value),
+    }
+}
+
+pub fn statement(expression: Node) -> Node {
+    Node::ExpressionStatement(Box::new(expression))
+}
+
+/// `let a, b;`
+pub fn let_declaration(names: Vec<String>) -> Node {
+    Node::VariableDeclaration {
+        kind: VariableKind::Let,
+        declarations: names
+            .into_iter()
+            .map(|name| VariableDeclarator {
+                id: Box::new(Node::Identifier(name, Span::default())),
+                type_annotation: None,
+                init: None,
+            })
+            .collect(),
+    }
+}
+
+/// The name a property key stands for, unless it is only known at runtime.
+pub fn key_name(key: &Node, computed: bool) -> Option<&str> {
+    match key {
+        Node::Identifier(name, _) if !computed => Some(name),
+        Node::Literal {
+            token_type: TokenType::StringLiteral | TokenType::NumberLiteral,
+            value,
+            ..
+        } => Some(value),
+        _ => None,
+    }
+}
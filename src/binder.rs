@@ -0,0 +1,1226 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::error_handling::{CompilerError, CompilerResult, Span};
+use crate::parser::{CatchClause, ImportSpecifier, Node, Parameter, TypeParameter, VariableKind};
+use crate::symboltable::{Meaning, Scope, ScopeId, Symbol, SymbolFlags, SymbolId, SymbolTable};
+
+/// Built-in values every program can refer to without declaring them.
+const GLOBALS: &[&str] = &[
+    "globalThis",
+    "console",
+    "Object",
+    "Function",
+    "Array",
+    "String",
+    "Number",
+    "Boolean",
+    "Symbol",
+    "BigInt",
+    "Math",
+    "JSON",
+    "Date",
+    "RegExp",
+    "Promise",
+    "Map",
+    "Set",
+    "WeakMap",
+    "WeakSet",
+    "Reflect",
+    "Proxy",
+    "Error",
+    "TypeError",
+    "RangeError",
+    "SyntaxError",
+    "ReferenceError",
+    "NaN",
+    "Infinity",
+    "isNaN",
+    "isFinite",
+    "parseInt",
+    "parseFloat",
+    "setTimeout",
+    "clearTimeout",
+    "queueMicrotask",
+];
+
+/// How a name was declared.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DeclarationKind {
+    Var,
+    Let,
+    Const,
+    Parameter,
+    Function,
+    Class,
+    Interface,
+    TypeAlias,
+    Enum,
+    ConstEnum,
+    EnumMember,
+    Namespace,
+    TypeNamespace,
+    TypeParameter,
+    Import,
+}
+
+impl DeclarationKind {
+    fn flags(self) -> SymbolFlags {
+        match self {
+            DeclarationKind::Var => SymbolFlags::VARIABLE,
+            DeclarationKind::Let | DeclarationKind::Const => SymbolFlags::BLOCK_SCOPED_VARIABLE,
+            DeclarationKind::Parameter => SymbolFlags::PARAMETER,
+            DeclarationKind::Function => SymbolFlags::FUNCTION,
+            DeclarationKind::Class => SymbolFlags::CLASS,
+            DeclarationKind::Interface => SymbolFlags::INTERFACE,
+            DeclarationKind::TypeAlias => SymbolFlags::TYPE_ALIAS,
+            DeclarationKind::Enum => SymbolFlags::ENUM,
+            DeclarationKind::ConstEnum => SymbolFlags::ENUM | SymbolFlags::CONST_ENUM,
+            DeclarationKind::EnumMember => SymbolFlags::ENUM_MEMBER,
+            DeclarationKind::Namespace => SymbolFlags::NAMESPACE,
+            DeclarationKind::TypeNamespace => SymbolFlags::TYPE_NAMESPACE,
+            DeclarationKind::TypeParameter => SymbolFlags::TYPE_PARAMETER,
+            DeclarationKind::Import => SymbolFlags::IMPORT,
+        }
+    }
+
+    fn is_constant(self) -> bool {
+        !matches!(
+            self,
+            DeclarationKind::Var
+                | DeclarationKind::Let
+                | DeclarationKind::Parameter
+                | DeclarationKind::Function
+        )
+    }
+}
+
+/// Whether a symbol can't be used until its declaration has run.
+fn has_tdz(flags: SymbolFlags) -> bool {
+    flags.intersects(SymbolFlags::BLOCK_SCOPED_VARIABLE | SymbolFlags::CLASS | SymbolFlags::ENUM)
+}
+
+/// How far a symbol is initialized at the point being bound.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Initialization {
+    /// Usable from the start of its scope: `var`s, functions, parameters, imports and
+    /// ambient declarations.
+    Hoisted,
+    Uninitialized,
+    Initialized,
+    /// Declared in an earlier `case` of a switch, which may not have run.
+    MaybeInitialized,
+}
+
+/// Resolves every identifier in `program` to the symbol it declares or refers to.
+///
+/// Each function, block, class member and namespace gets a scope, registered with the node
+/// that introduces it. `var` declarations belong to the enclosing function, or to the
+/// global scope at the top level; `let`, `const`, classes and functions inside a block
+/// belong to the block. Every name is declared when its scope is entered, so a use can
+/// come before the declaration.
+///
+/// `let`, `const`, classes and enums can't be used before their declaration has run. A
+/// use that comes first in the same function is an error. One in a nested function
+/// created before the declaration, including a hoisted function declaration, depends on
+/// when the function is called, so it is marked with `SymbolTable::add_tdz_check` for
+/// `insert_tdz_checks` to check at runtime; so is one after a declaration in an earlier
+/// `case` of a switch.
+///
+/// Interfaces, type aliases and type parameters are declared alongside values, and a
+/// name can have a symbol with each meaning: expressions look up values, and type
+/// references types. Declarations of one name in a scope merge when TypeScript allows it,
+/// as for an interface and a class, and are reported otherwise.
+///
+/// Identifiers map to their symbol with `SymbolTable::symbol_of`, both where they are used
+/// and where they are declared, and so do declarations and the type references that could
+/// be resolved. Property names and non-computed keys are not bound.
+pub fn bind(program: &Node) -> CompilerResult<SymbolTable> {
+    let mut binder = Binder {
+        table: SymbolTable::new(),
+        states: Vec::new(),
+        scope: SymbolTable::GLOBAL,
+        merged: HashMap::new(),
+        merged_namespaces: HashMap::new(),
+        merging_in: None,
+        eager: HashSet::new(),
+        ambient: false,
+        in_type: false,
+        errors: Vec::new(),
+    };
+    for name in GLOBALS {
+        binder.declare_in(
+            SymbolTable::BUILTINS,
+            name,
+            DeclarationKind::Var,
+            Span::default(),
+        );
+    }
+    if let Node::Program(body) = program {
+        binder.hoist_globals(body);
+        binder.hoist_vars(body);
+        binder.statements(body);
+    }
+    CompilerResult::new(Some(binder.table), binder.errors)
+}
+
+struct Binder {
+    table: SymbolTable,
+    states: Vec<Initialization>, // indexed by SymbolId
+    scope: ScopeId,              // the innermost scope
+    // Scopes shared by the declarations of a merged enum, by the scope they are declared
+    // in and their name
+    merged: HashMap<(ScopeId, String), ScopeId>,
+    // The scopes of exports of each declaration of a merged namespace, keyed the same way
+    merged_namespaces: HashMap<(ScopeId, String), Vec<ScopeId>>,
+    // For an exported namespace or enum, the scope to key its merging by in place of the
+    // current one: the first exports scope of the namespace it is exported from
+    merging_in: Option<ScopeId>,
+    // Function scopes that run as soon as they're created: static field initializers
+    eager: HashSet<ScopeId>,
+    ambient: bool, // binding declarations without runtime code
+    in_type: bool, // binding a `typeof` query, which reads no value at runtime
+    errors: Vec<CompilerError>,
+}
+
+impl Binder {
+    fn enter_scope(&mut self, kind: Scope, node: &Node) {
+        self.scope = self.table.add_scope(kind, self.scope, Some(node));
+    }
+
+    fn exit_scope(&mut self) {
+        if let Some(parent) = self.table.scope(self.scope).parent {
+            self.scope = parent;
+        }
+    }
+
+    /// The scope `var` declarations of the current one belong to.
+    fn function_scope(&self) -> ScopeId {
+        self.table
+            .ancestors(self.scope)
+            .find(|id| !matches!(self.table.scope(*id).kind, Scope::Block))
+            .unwrap_or(SymbolTable::GLOBAL)
+    }
+
+    fn declare(&mut self, name: &str, kind: DeclarationKind, span: Span) -> SymbolId {
+        self.declare_in(self.scope, name, kind, span)
+    }
+
+    fn declare_in(
+        &mut self,
+        scope: ScopeId,
+        name: &str,
+        kind: DeclarationKind,
+        span: Span,
+    ) -> SymbolId {
+        let symbol = Symbol {
+            name: name.to_string(),
+            flags: kind.flags(),
+            type_annotation: None,
+            scope: self.table.scope(scope).kind.clone(),
+            is_constant: kind.is_constant(),
+            span,
+            value_type: None,
+            declared_type: None,
+        };
+        let id = match self.table.insert(scope, symbol) {
+            Ok(id) => id,
+            Err((message, code)) => {
+                // Duplicate enum members are reported by `enums`
+                if kind != DeclarationKind::EnumMember {
+                    self.errors
+                        .push(CompilerError::new(message, span).with_code(code));
+                }
+                return self.table.scope(scope).symbols[name];
+            }
+        };
+        if id.0 == self.states.len() {
+            self.states.push(Initialization::Hoisted);
+        }
+        if has_tdz(kind.flags()) && !self.ambient {
+            self.states[id.0] = Initialization::Uninitialized;
+        }
+        id
+    }
+
+    fn declare_type_params(&mut self, type_params: &[TypeParameter]) {
+        for type_param in type_params {
+            self.declare(
+                &type_param.name,
+                DeclarationKind::TypeParameter,
+                type_param.span,
+            );
+        }
+    }
+
+    /// Records the declared type of a name, unless an earlier declaration gave it one.
+    fn annotate(&mut self, binding: &Node, type_annotation: &Node) {
+        if let Some(symbol) = self.table.symbol_of(binding) {
+            let symbol = self.table.symbol_mut(symbol);
+            if symbol.type_annotation.is_none() {
+                symbol.type_annotation = Some(type_annotation.clone());
+            }
+        }
+    }
+
+    fn initialize(&mut self, symbol: SymbolId) {
+        if self.states[symbol.0] != Initialization::Hoisted {
+            self.states[symbol.0] = Initialization::Initialized;
+        }
+    }
+
+    fn initialize_pattern(&mut self, pattern: &Node) {
+        for identifier in pattern.binding_identifiers() {
+            if let Some(symbol) = self.table.symbol_of(identifier) {
+                self.initialize(symbol);
+            }
+        }
+    }
+
+    /// Declares every identifier in a binding target, and binds them to their symbols.
+    fn declare_pattern(&mut self, scope: ScopeId, pattern: &Node, kind: DeclarationKind) {
+        for identifier in pattern.binding_identifiers() {
+            if let Node::Identifier(name, span) = identifier {
+                let symbol = self.declare_in(scope, name, kind, *span);
+                self.table.bind_node(identifier, symbol);
+            }
+        }
+    }
+
+    /// Declares the members of `declare global` blocks anywhere in the program.
+    fn hoist_globals(&mut self, statements: &[Node]) {
+        for statement in statements {
+            match statement {
+                Node::GlobalAugmentation(body) => {
+                    let scope = std::mem::replace(&mut self.scope, SymbolTable::GLOBAL);
+                    self.ambient = true;
+                    self.hoist_vars(body);
+                    self.declare_lexical(body);
+                    self.ambient = false;
+                    self.scope = scope;
+                }
+                Node::AmbientModuleDeclaration {
+                    body: Some(body), ..
+                } => self.hoist_globals(body),
+                _ => {}
+            }
+        }
+    }
+
+    /// Declares the `var`s of a function body, wherever they are nested outside of inner
+    /// functions, in the current function scope.
+    fn hoist_vars(&mut self, statements: &[Node]) {
+        let scope = self.function_scope();
+        for statement in statements {
+            self.hoist_var(scope, statement);
+        }
+    }
+
+    fn hoist_var(&mut self, scope: ScopeId, statement: &Node) {
+        match statement {
+            Node::VariableDeclaration {
+                kind: VariableKind::Var,
+                declarations,
+            } => {
+                for declarator in declarations {
+                    self.declare_pattern(scope, &declarator.id, DeclarationKind::Var);
+                }
+            }
+            Node::FunctionDeclaration { .. }
+            | Node::ArrowFunctionExpression { .. }
+            | Node::MethodDefinition { .. }
+            | Node::PropertyDefinition { .. }
+            | Node::ClassDeclaration { .. }
+            | Node::NamespaceDeclaration { .. }
+            | Node::AmbientModuleDeclaration { .. }
+            | Node::GlobalAugmentation(_) => {}
+            statement => {
+                for child in statement.children() {
+                    self.hoist_var(scope, child);
+                }
+            }
+        }
+    }
+
+    /// Declares the `let`, `const`, function, class, enum and namespace declarations and
+    /// the imports of a statement list in the current scope.
+    fn declare_lexical(&mut self, statements: &[Node]) {
+        for statement in statements {
+            self.declare_statement(statement);
+        }
+    }
+
+    fn declare_statement(&mut self, statement: &Node) {
+        let (name, span, kind) = match statement {
+            Node::VariableDeclaration { kind, declarations } if *kind != VariableKind::Var => {
+                let kind = match kind {
+                    VariableKind::Const => DeclarationKind::Const,
+                    _ => DeclarationKind::Let,
+                };
+                for declarator in declarations {
+                    self.declare_pattern(self.scope, &declarator.id, kind);
+                }
+                return;
+            }
+            Node::ImportDeclaration { specifiers, .. } => {
+                for specifier in specifiers {
+                    let (ImportSpecifier::Default(local, span)
+                    | ImportSpecifier::Namespace(local, span)
+                    | ImportSpecifier::Named { local, span, .. }) = specifier;
+                    self.declare(local, DeclarationKind::Import, *span);
+                }
+                return;
+            }
+            Node::ExportNamedDeclaration {
+                declaration: Some(declaration),
+                ..
+            }
+            | Node::ExportDefaultDeclaration(declaration) => {
+                return self.declare_statement(declaration)
+            }
+            Node::AmbientDeclaration(declaration) => {
+                let ambient = std::mem::replace(&mut self.ambient, true);
+                self.declare_statement(declaration);
+                self.ambient = ambient;
+                return;
+            }
+            Node::FunctionDeclaration { name, span, .. } => (name, span, DeclarationKind::Function),
+            Node::ClassDeclaration { name, span, .. } => (name, span, DeclarationKind::Class),
+            Node::EnumDeclaration {
+                name,
+                span,
+                is_const,
+                ..
+            } => (
+                name,
+                span,
+                if *is_const {
+                    DeclarationKind::ConstEnum
+                } else {
+                    DeclarationKind::Enum
+                },
+            ),
+            Node::InterfaceDeclaration { name, span, .. } => {
+                (name, span, DeclarationKind::Interface)
+            }
+            Node::TypeAliasDeclaration { name, span, .. } => {
+                (name, span, DeclarationKind::TypeAlias)
+            }
+            Node::NamespaceDeclaration {
+                name, span, body, ..
+            } if is_instantiated(body) => (name, span, DeclarationKind::Namespace),
+            Node::NamespaceDeclaration { name, span, .. } => {
+                (name, span, DeclarationKind::TypeNamespace)
+            }
+            _ => return,
+        };
+        if !name.is_empty() {
+            let symbol = self.declare(name, kind, *span);
+            self.table.bind_node(statement, symbol);
+        }
+    }
+
+    /// Declares a statement list's names in the current scope, then binds its statements.
+    fn statements(&mut self, statements: &[Node]) {
+        self.declare_lexical(statements);
+        for statement in statements {
+            self.visit(statement);
+        }
+    }
+
+    fn reference(&mut self, identifier: &Node) {
+        let Node::Identifier(name, span) = identifier else {
+            return;
+        };
+        let Some(symbol) = self.table.lookup(self.scope, name, Meaning::Value) else {
+            let (message, code) = if self.table.lookup(self.scope, name, Meaning::Type).is_some() {
+                (
+                    format!(
+                        "'{}' only refers to a type, but is being used as a value here.",
+                        name
+                    ),
+                    2693,
+                )
+            } else if self
+                .table
+                .lookup(self.scope, name, Meaning::Namespace)
+                .is_some()
+            {
+                (format!("Cannot use namespace '{}' as a value.", name), 2708)
+            } else {
+                (format!("Cannot find name '{}'.", name), 2304)
+            };
+            self.errors
+                .push(CompilerError::new(message, *span).with_code(code));
+            return;
+        };
+        self.table.bind_node(identifier, symbol);
+        let state = self.states[symbol.0];
+        if self.in_type || matches!(state, Initialization::Hoisted | Initialization::Initialized) {
+            return;
+        }
+        let declared_in = self.table.declaring_scope(symbol);
+        let deferred = self
+            .table
+            .ancestors(self.scope)
+            .take_while(|scope| *scope != declared_in)
+            .any(|scope| {
+                matches!(self.table.scope(scope).kind, Scope::Function)
+                    && !self.eager.contains(&scope)
+            });
+        let flags = self.table.symbol(symbol).flags;
+        if deferred || state == Initialization::MaybeInitialized {
+            // Enums have no binding to check; their members are inlined or read from an
+            // object that exists from the start
+            if !flags.contains(SymbolFlags::ENUM) {
+                self.table.add_tdz_check(identifier);
+            }
+            return;
+        }
+        let (message, code) = if flags.contains(SymbolFlags::CLASS) {
+            (
+                format!("Class '{}' used before its declaration.", name),
+                2449,
+            )
+        } else if flags.contains(SymbolFlags::ENUM) {
+            (
+                format!("Enum '{}' used before its declaration.", name),
+                2450,
+            )
+        } else {
+            (
+                format!(
+                    "Block-scoped variable '{}' used before its declaration.",
+                    name
+                ),
+                2448,
+            )
+        };
+        self.errors
+            .push(CompilerError::new(message, *span).with_code(code));
+    }
+
+    fn function(
+        &mut self,
+        node: &Node,
+        type_params: &[TypeParameter],
+        params: &[Parameter],
+        return_type: Option<&Node>,
+        body: Option<&Node>,
+        is_arrow: bool,
+    ) {
+        for decorator in params.iter().flat_map(|param| &param.modifiers.decorators) {
+            self.visit(decorator);
+        }
+        self.enter_scope(Scope::Function, node);
+        self.declare_type_params(type_params);
+        if !is_arrow {
+            self.declare("arguments", DeclarationKind::Var, Span::default());
+        }
+        let scope = self.scope;
+        for param in params {
+            // `this` parameters only give `this` a type
+            if param.name() != Some("this") {
+                self.declare_pattern(scope, &param.binding, DeclarationKind::Parameter);
+            }
+        }
+        for type_param in type_params {
+            for constraint in [&type_param.constraint, &type_param.default]
+                .into_iter()
+                .flatten()
+            {
+                self.types(constraint);
+            }
+        }
+        for param in params {
+            self.pattern(&param.binding);
+            if let Some(init) = &param.init {
+                self.visit(init);
+            }
+            if let Some(type_annotation) = &param.type_annotation {
+                self.types(type_annotation);
+                self.annotate(&param.binding, type_annotation);
+            }
+        }
+        if let Some(return_type) = return_type {
+            self.types(return_type);
+        }
+        match body {
+            Some(Node::BlockStatement(statements, _)) => {
+                self.hoist_vars(statements);
+                self.statements(statements);
+            }
+            Some(expression) => self.visit(expression),
+            None => {}
+        }
+        self.exit_scope();
+    }
+
+    /// The expressions inside a binding pattern: defaults and computed keys. Its
+    /// identifiers are bound when they are declared.
+    fn pattern(&mut self, pattern: &Node) {
+        match pattern {
+            Node::Identifier(..) => {}
+            Node::PatternProperty {
+                key,
+                computed,
+                value,
+            } => {
+                if *computed {
+                    self.visit(key);
+                }
+                self.pattern(value);
+            }
+            Node::AssignmentPattern { left, right } => {
+                self.pattern(left);
+                self.visit(right);
+            }
+            pattern => {
+                for child in pattern.children() {
+                    self.pattern(child);
+                }
+            }
+        }
+    }
+
+    /// Binds the type names in a type, and the values it refers to in `typeof` queries and
+    /// computed keys. Dotted names and unknown types are left to the checker.
+    fn types(&mut self, node: &Node) {
+        match node {
+            Node::TypeReference {
+                name,
+                type_arguments,
+                ..
+            } => {
+                if let Some(symbol) = self.table.lookup(self.scope, name, Meaning::Type) {
+                    self.table.bind_node(node, symbol);
+                }
+                for type_argument in type_arguments {
+                    self.types(type_argument);
+                }
+            }
+            Node::TypeQuery(entity) => {
+                let in_type = std::mem::replace(&mut self.in_type, true);
+                self.visit(entity);
+                self.in_type = in_type;
+            }
+            Node::PropertySignature {
+                key,
+                computed,
+                value,
+                ..
+            } => {
+                if *computed {
+                    self.visit(key);
+                }
+                if let Some(value) = value {
+                    self.types(value);
+                }
+            }
+            Node::MethodSignature {
+                key,
+                computed: true,
+                ..
+            } => {
+                self.visit(key);
+                for child in node.children() {
+                    if !std::ptr::eq(child, key.as_ref()) {
+                        self.types(child);
+                    }
+                }
+            }
+            node => {
+                for child in node.children() {
+                    self.types(child);
+                }
+            }
+        }
+    }
+
+    /// The scope holding the exports of a namespace or the members of an enum, shared by
+    /// every declaration merged with it.
+    fn merged_scope(&mut self, name: &str, node: Option<&Node>) -> ScopeId {
+        let key = (
+            self.merging_in.take().unwrap_or(self.scope),
+            name.to_string(),
+        );
+        if let Some(&scope) = self.merged.get(&key) {
+            return scope;
+        }
+        let scope = self.table.add_scope(Scope::Block, self.scope, node);
+        self.merged.insert(key, scope);
+        scope
+    }
+
+    fn visit(&mut self, node: &Node) {
+        match node {
+            Node::Identifier(..) => self.reference(node),
+            node if is_type(node) => self.types(node),
+            Node::FunctionDeclaration {
+                type_params,
+                params,
+                return_type,
+                body,
+                ..
+            } => {
+                // The function exists from the start of its scope, before any of the
+                // scope's declarations have run
+                let outer: Vec<(SymbolId, Initialization)> = self
+                    .table
+                    .scope(self.scope)
+                    .symbols
+                    .values()
+                    .map(|symbol| (*symbol, self.states[symbol.0]))
+                    .filter(|(_, state)| *state != Initialization::Hoisted)
+                    .collect();
+                for (symbol, _) in &outer {
+                    self.states[symbol.0] = Initialization::Uninitialized;
+                }
+                self.function(
+                    node,
+                    type_params,
+                    params,
+                    return_type.as_deref(),
+                    body.as_deref(),
+                    false,
+                );
+                for (symbol, state) in outer {
+                    self.states[symbol.0] = state;
+                }
+            }
+            Node::ArrowFunctionExpression {
+                type_params,
+                params,
+                body,
+                return_type,
+                ..
+            } => self.function(
+                node,
+                type_params,
+                params,
+                return_type.as_deref(),
+                Some(body),
+                true,
+            ),
+            Node::MethodDefinition {
+                key,
+                computed,
+                type_params,
+                params,
+                return_type,
+                body,
+                modifiers,
+                ..
+            } => {
+                for decorator in &modifiers.decorators {
+                    self.visit(decorator);
+                }
+                if *computed {
+                    self.visit(key);
+                }
+                self.function(
+                    node,
+                    type_params,
+                    params,
+                    return_type.as_deref(),
+                    body.as_deref(),
+                    false,
+                );
+            }
+            Node::PropertyDefinition {
+                key,
+                computed,
+                type_annotation,
+                value,
+                modifiers,
+                ..
+            } => {
+                for decorator in &modifiers.decorators {
+                    self.visit(decorator);
+                }
+                if *computed {
+                    self.visit(key);
+                }
+                if let Some(type_annotation) = type_annotation {
+                    self.types(type_annotation);
+                }
+                // An initializer runs like a method of the instance, or for a static field,
+                // of the class as soon as it is defined
+                if let Some(value) = value {
+                    self.enter_scope(Scope::Function, node);
+                    if modifiers.is_static {
+                        self.eager.insert(self.scope);
+                    }
+                    self.visit(value);
+                    self.exit_scope();
+                }
+            }
+            Node::ClassDeclaration {
+                type_params,
+                extends,
+                super_type_arguments,
+                implements,
+                members,
+                decorators,
+                ..
+            } => {
+                // Type parameters are in scope in the heritage clauses too
+                self.enter_scope(Scope::Block, node);
+                self.declare_type_params(type_params);
+                for decorator in decorators {
+                    self.visit(decorator);
+                }
+                if let Some(extends) = extends {
+                    self.visit(extends);
+                }
+                for type_node in super_type_arguments.iter().chain(implements) {
+                    self.types(type_node);
+                }
+                // Members run after the class binding is initialized
+                if let Some(symbol) = self.table.symbol_of(node) {
+                    self.initialize(symbol);
+                }
+                for member in members {
+                    self.visit(member);
+                }
+                self.exit_scope();
+            }
+            Node::NamespaceDeclaration {
+                name,
+                body,
+                is_declare,
+                ..
+            } => {
+                // Exported members are shared by every declaration of the namespace, and
+                // the others are local to this one. Each declaration has its own scope of
+                // exports inside the scope it's declared in, holding those of all of them.
+                // Everything an ambient namespace declares is exported.
+                let ambient = *is_declare || self.ambient;
+                let key = (self.merging_in.take().unwrap_or(self.scope), name.clone());
+                let exports = self.table.add_scope(Scope::Block, self.scope, None);
+                let merged = self.merged_namespaces.entry(key).or_default();
+                if let Some(&first) = merged.first() {
+                    let shared: Vec<SymbolId> =
+                        self.table.scope(first).symbols.values().copied().collect();
+                    for symbol in shared {
+                        self.table.share(exports, symbol);
+                    }
+                }
+                merged.push(exports);
+                let merged = merged.clone();
+                let outer = std::mem::replace(&mut self.scope, exports);
+                for statement in body {
+                    if let Some(declaration) = exported_declaration(statement, ambient) {
+                        if let Node::VariableDeclaration {
+                            kind: VariableKind::Var,
+                            declarations,
+                        } = declaration
+                        {
+                            for declarator in declarations {
+                                self.declare_pattern(exports, &declarator.id, DeclarationKind::Var);
+                            }
+                        } else {
+                            self.declare_statement(declaration);
+                        }
+                    }
+                }
+                // Exports are properties of the namespace object, without a dead zone
+                let exported: Vec<SymbolId> = self
+                    .table
+                    .scope(exports)
+                    .symbols
+                    .values()
+                    .copied()
+                    .collect();
+                for &symbol in &exported {
+                    self.states[symbol.0] = Initialization::Hoisted;
+                    for &other in &merged {
+                        self.table.share(other, symbol);
+                    }
+                }
+                // Nested namespaces and enums merge with those exported from the
+                // other declarations
+                let group = merged[0];
+                self.scope = self.table.add_scope(Scope::Function, exports, Some(node));
+                let locals: Vec<&Node> = body
+                    .iter()
+                    .filter(|statement| {
+                        !matches!(statement, Node::ExportNamedDeclaration { .. })
+                            && exported_declaration(statement, ambient).is_none()
+                    })
+                    .collect();
+                for statement in &locals {
+                    self.hoist_var(self.scope, statement);
+                    self.declare_statement(statement);
+                }
+                for statement in body {
+                    if let Some(declaration) = exported_declaration(statement, ambient) {
+                        if matches!(
+                            declaration,
+                            Node::NamespaceDeclaration { .. } | Node::EnumDeclaration { .. }
+                        ) {
+                            self.merging_in = Some(group);
+                        }
+                    }
+                    self.visit(statement);
+                }
+                self.scope = outer;
+            }
+            Node::EnumDeclaration { name, members, .. } => {
+                if let Some(symbol) = self.table.symbol_of(node) {
+                    self.initialize(symbol);
+                }
+                let scope = self.merged_scope(name, Some(node));
+                let outer = std::mem::replace(&mut self.scope, scope);
+                for member in members {
+                    self.declare(&member.name, DeclarationKind::EnumMember, member.span);
+                }
+                for init in members.iter().filter_map(|member| member.init.as_deref()) {
+                    self.visit(init);
+                }
+                self.scope = outer;
+            }
+            Node::AmbientModuleDeclaration { body, .. } => {
+                if let Some(body) = body {
+                    let ambient = std::mem::replace(&mut self.ambient, true);
+                    self.enter_scope(Scope::Function, node);
+                    self.hoist_vars(body);
+                    self.statements(body);
+                    self.exit_scope();
+                    self.ambient = ambient;
+                }
+            }
+            Node::GlobalAugmentation(body) => {
+                // Declared up front by `hoist_globals`
+                let scope = std::mem::replace(&mut self.scope, SymbolTable::GLOBAL);
+                for statement in body {
+                    self.visit(statement);
+                }
+                self.scope = scope;
+            }
+            Node::VariableDeclaration { declarations, .. } => {
+                for declarator in declarations {
+                    self.pattern(&declarator.id);
+                    if let Some(type_annotation) = &declarator.type_annotation {
+                        self.types(type_annotation);
+                        self.annotate(&declarator.id, type_annotation);
+                    }
+                    if let Some(init) = &declarator.init {
+                        self.visit(init);
+                    }
+                    self.initialize_pattern(&declarator.id);
+                }
+            }
+            Node::BlockStatement(statements, _) => {
+                self.enter_scope(Scope::Block, node);
+                self.statements(statements);
+                self.exit_scope();
+            }
+            Node::ForStatement {
+                init,
+                test,
+                update,
+                body,
+            } => {
+                self.enter_scope(Scope::Block, node);
+                if let Some(init) = init {
+                    self.declare_statement(init);
+                    self.visit(init);
+                }
+                for part in [test, update].into_iter().flatten() {
+                    self.visit(part);
+                }
+                self.visit(body);
+                self.exit_scope();
+            }
+            Node::ForInStatement { left, right, body }
+            | Node::ForOfStatement {
+                left, right, body, ..
+            } => {
+                self.visit(right);
+                self.enter_scope(Scope::Block, node);
+                self.declare_statement(left);
+                self.visit(left);
+                self.visit(body);
+                self.exit_scope();
+            }
+            Node::SwitchStatement {
+                discriminant,
+                cases,
+            } => {
+                self.visit(discriminant);
+                self.enter_scope(Scope::Block, node);
+                for case in cases {
+                    self.declare_lexical(&case.consequent);
+                }
+                for case in cases {
+                    if let Some(test) = &case.test {
+                        self.visit(test);
+                    }
+                    for statement in &case.consequent {
+                        self.visit(statement);
+                    }
+                    // Later cases can be jumped to without running this one
+                    let scope = self.scope;
+                    for symbol in self.table.scope(scope).symbols.values() {
+                        if self.states[symbol.0] == Initialization::Initialized {
+                            self.states[symbol.0] = Initialization::MaybeInitialized;
+                        }
+                    }
+                }
+                self.exit_scope();
+            }
+            Node::TryStatement {
+                block,
+                handler,
+                finalizer,
+            } => {
+                self.visit(block);
+                if let Some(CatchClause { param, body }) = handler {
+                    self.enter_scope(Scope::Block, body);
+                    if let Some(param) = param {
+                        self.declare_pattern(self.scope, param, DeclarationKind::Let);
+                        self.initialize_pattern(param);
+                        self.pattern(param);
+                    }
+                    // The body shares the parameter's scope
+                    if let Node::BlockStatement(statements, _) = body.as_ref() {
+                        self.statements(statements);
+                    }
+                    self.exit_scope();
+                }
+                if let Some(finalizer) = finalizer {
+                    self.visit(finalizer);
+                }
+            }
+            Node::MemberExpression {
+                object,
+                property,
+                computed,
+                ..
+            } => {
+                self.visit(object);
+                if *computed {
+                    self.visit(property);
+                }
+            }
+            Node::Property {
+                key,
+                computed,
+                value,
+                ..
+            } => {
+                if *computed {
+                    self.visit(key);
+                }
+                self.visit(value);
+            }
+            // Targets of an assignment pattern are references, unlike in a declaration
+            Node::PatternProperty {
+                key,
+                computed,
+                value,
+            } => {
+                if *computed {
+                    self.visit(key);
+                }
+                self.visit(value);
+            }
+            Node::ExportNamedDeclaration {
+                declaration,
+                specifiers,
+                source,
+                ..
+            } => match declaration {
+                Some(declaration) => self.visit(declaration),
+                None if source.is_none() => {
+                    for specifier in specifiers {
+                        let declared = [Meaning::Value, Meaning::Type, Meaning::Namespace]
+                            .into_iter()
+                            .any(|meaning| {
+                                self.table
+                                    .lookup(self.scope, &specifier.local, meaning)
+                                    .is_some()
+                            });
+                        if !declared {
+                            self.errors.push(
+                                CompilerError::new(
+                                    format!("Cannot find name '{}'.", specifier.local),
+                                    specifier.span,
+                                )
+                                .with_code(2304),
+                            );
+                        }
+                    }
+                }
+                None => {}
+            },
+            Node::ExportDefaultDeclaration(declaration) => self.visit(declaration),
+            Node::InterfaceDeclaration { type_params, .. }
+            | Node::TypeAliasDeclaration { type_params, .. } => {
+                self.enter_scope(Scope::Block, node);
+                self.declare_type_params(type_params);
+                for child in node.children() {
+                    self.types(child);
+                }
+                self.exit_scope();
+            }
+            Node::AmbientDeclaration(declaration) => {
+                let ambient = std::mem::replace(&mut self.ambient, true);
+                self.visit(declaration);
+                self.ambient = ambient;
+            }
+            Node::ImportDeclaration { .. } | Node::ExportAllDeclaration { .. } => {}
+            node => {
+                for child in node.children() {
+                    self.visit(child);
+                }
+            }
+        }
+    }
+}
+
+/// Whether a namespace declares anything with a runtime value, which gives it an object.
+/// The declaration a statement in a namespace body exports, if any.
+fn exported_declaration(statement: &Node, ambient: bool) -> Option<&Node> {
+    match statement {
+        Node::ExportNamedDeclaration {
+            declaration: Some(declaration),
+            ..
+        } => Some(declaration),
+        Node::ExportNamedDeclaration { .. } => None,
+        statement => ambient.then_some(statement),
+    }
+}
+
+pub(crate) fn is_instantiated(body: &[Node]) -> bool {
+    body.iter().any(|statement| match statement {
+        Node::InterfaceDeclaration { .. }
+        | Node::TypeAliasDeclaration { .. }
+        | Node::EnumDeclaration { is_const: true, .. }
+        | Node::ImportDeclaration {
+            is_type_only: true, ..
+        } => false,
+        Node::ExportNamedDeclaration {
+            declaration: Some(declaration),
+            ..
+        } => is_instantiated(std::slice::from_ref(declaration.as_ref())),
+        Node::ExportNamedDeclaration {
+            is_type_only: true, ..
+        } => false,
+        Node::NamespaceDeclaration { body, .. } => is_instantiated(body),
+        _ => true,
+    })
+}
+
+pub(crate) fn is_type(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::TypeReference { .. }
+            | Node::KeywordType(_)
+            | Node::ThisType
+            | Node::UnionType(_)
+            | Node::IntersectionType(_)
+            | Node::ParenthesizedType(_)
+            | Node::FunctionType { .. }
+            | Node::ConstructorType { .. }
+            | Node::ObjectType { .. }
+            | Node::ArrayType(_)
+            | Node::TupleType(_)
+            | Node::OptionalType(_)
+            | Node::RestType(_)
+            | Node::NamedTupleMember { .. }
+            | Node::LiteralType(_)
+            | Node::TypeQuery(_)
+            | Node::TypeOperator { .. }
+            | Node::IndexedAccessType { .. }
+            | Node::TypePredicate { .. }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::tokenizer::Tokenizer;
+
+    fn parse(source: &str) -> Node {
+        let tokens = Tokenizer::new(source.to_string()).tokenize();
+        Parser::new(tokens).parse().result.expect("parses")
+    }
+
+    fn codes(source: &str) -> Vec<u32> {
+        let errors = bind(&parse(source)).errors;
+        errors.iter().map(|error| error.code.unwrap_or(0)).collect()
+    }
+
+    /// The identifiers named `name` in `node`, in source order.
+    fn identifiers<'a>(node: &'a Node, name: &str, found: &mut Vec<&'a Node>) {
+        match node {
+            Node::Identifier(identifier, _) if identifier == name => found.push(node),
+            node => {
+                for child in node.children() {
+                    identifiers(child, name, found);
+                }
+            }
+        }
+    }
+
+    /// The symbols the identifiers named `name` in `source` were bound to, in source order.
+    fn bindings(source: &str, name: &str) -> Vec<Option<SymbolId>> {
+        let program = parse(source);
+        let table = bind(&program).result.expect("binds");
+        let mut found = Vec::new();
+        identifiers(&program, name, &mut found);
+        found.iter().map(|node| table.symbol_of(node)).collect()
+    }
+
+    #[test]
+    fn block_declarations_shadow_outer_ones_only_inside_the_block() {
+        let symbols = bindings("let x = 1; { let x = 'a'; x; } x;", "x");
+        assert_eq!(symbols.len(), 4);
+        assert!(symbols.iter().all(Option::is_some));
+        assert_eq!(symbols[1], symbols[2]);
+        assert_eq!(symbols[0], symbols[3]);
+        assert_ne!(symbols[0], symbols[1]);
+    }
+
+    #[test]
+    fn vars_belong_to_the_enclosing_function() {
+        let source = "function f() { { var v = 1; } return v; } function g() { var v; }";
+        let symbols = bindings(source, "v");
+        assert_eq!(symbols[0], symbols[1]);
+        assert_ne!(symbols[0], symbols[2]);
+        assert!(codes(source).is_empty());
+    }
+
+    #[test]
+    fn names_are_looked_up_by_meaning() {
+        assert_eq!(codes("missing;"), [2304]);
+        assert_eq!(codes("interface I {} I;"), [2693]);
+        assert_eq!(codes("namespace N { export type T = 1; } N;"), [2708]);
+        // A type and a value can share a name
+        assert!(codes("type P = number; const P = 1; let p: P = P;").is_empty());
+        assert_eq!(codes("let d = 1; let d = 2;"), [2451]);
+    }
+
+    #[test]
+    fn uses_before_the_declaration_has_run_are_errors_or_checked_at_runtime() {
+        assert_eq!(codes("x; let x = 1;"), [2448]);
+        assert_eq!(codes("new C(); class C {}"), [2449]);
+        assert_eq!(codes("E.A; enum E { A }"), [2450]);
+
+        // A function may be called after the declaration has run, which only shows at runtime
+        let program = parse("function f() { return y; } let y = 1; f();");
+        let table = bind(&program).result.expect("binds");
+        let mut found = Vec::new();
+        identifiers(&program, "y", &mut found);
+        assert!(table.needs_tdz_check(found[0]));
+        assert!(!table.needs_tdz_check(found[1]));
+        // One created after the declaration has run can't see it uninitialized
+        let program = parse("let z = 1; const g = () => z;");
+        let table = bind(&program).result.expect("binds");
+        let mut found = Vec::new();
+        identifiers(&program, "z", &mut found);
+        assert!(!table.needs_tdz_check(found[1]));
+    }
+}
@@ -0,0 +1,2393 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::assignments::has_declared_shape;
+use crate::ast::key_name;
+use crate::binder::is_type;
+use crate::error_handling::{CompilerError, Span};
+use crate::flow::{
+    build_flow, is_part_of, is_same_reference, property_of, unwrapped, FlowGraph, FlowId, FlowNode,
+};
+use crate::narrowing::{
+    by_assignment, by_discriminant, by_equality, by_property, by_truthiness, by_typeof, to_subtype,
+};
+use crate::parser::{MethodKind, Node, VariableDeclarator, VariableKind};
+use crate::symboltable::{Meaning, ScopeId, SymbolFlags, SymbolId, SymbolTable};
+use crate::tokenizer::TokenType;
+use crate::type_lowering::{builtin_instance_type, TypeLowering};
+use crate::types_checking::{
+    const_assertion, instantiate_call, literal_type, pattern_bindings, satisfies, type_assertion,
+    ObjectType, PropertyType, Type,
+};
+
+/// Computes the type of every expression in `program` and reports values that aren't
+/// assignable to where they go: initializers of annotated variables, parameters and
+/// properties, assignments, arguments, and returned values. Calls with the wrong number
+/// of arguments and calls of values that can't be called are reported too. Each error has
+/// the code tsc gives it.
+///
+/// Names declared without an annotation get the type of their initializer, with literals
+/// widened unless they are `const`; the parameters of a function passed where a function
+/// type is expected get that type's parameter types. Functions without a return type
+/// return the union of what their `return` statements give. Object literals are object
+/// types of their properties, which are widened the same way unless the property they are
+/// written for has a literal type.
+///
+/// References to variables, parameters and their properties are narrowed along the flow of
+/// the program: by `typeof`, equality, truthiness, `instanceof`, `in`, discriminant
+/// properties, type guards and assertion functions, and by assigning them. A function whose
+/// return type needs a value reports an end its body can reach, where a switch over every
+/// member of a union and a call of a function returning `never` don't go past.
+///
+/// Run after `resolve_types`; `table` must come from binding `program`, or a tree `program`
+/// was cloned from.
+pub fn check_types(program: &Node, table: &SymbolTable) -> Vec<CompilerError> {
+    let flow = build_flow(program);
+    let reassigned = flow
+        .reassigned()
+        .iter()
+        .filter_map(|target| table.symbol_of(target))
+        .collect();
+    let mut checker = Checker {
+        table,
+        lowering: TypeLowering::new(program, table),
+        flow,
+        reassigned,
+        flow_types: HashMap::new(),
+        joining: 0,
+        incomplete: None,
+        assigned: HashMap::new(),
+        returns: HashMap::new(),
+        declarations: HashMap::new(),
+        inferred: HashMap::new(),
+        functions: HashMap::new(),
+        contexts: Vec::new(),
+        constraints: Vec::new(),
+        this: None,
+        scope: SymbolTable::GLOBAL,
+        errors: Vec::new(),
+    };
+    checker.collect_declarations(program);
+    checker.visit(program);
+    checker.errors
+}
+
+/// The function whose body is being checked.
+struct FunctionContext {
+    declared: Option<Type>, // what its `return` statements must give, if annotated
+    returned: Vec<Type>,
+}
+
+/// The types a reference has where branches join, for each type it is narrowed from, or
+/// `None` where control can't get.
+type JoinedTypes = Vec<(Type, Option<Type>)>;
+
+struct Checker<'a> {
+    table: &'a SymbolTable,
+    // Lowers types written inside expressions; `resolve_types` has reported their errors
+    lowering: TypeLowering<'a>,
+    flow: FlowGraph<'a>,
+    reassigned: HashSet<SymbolId>, // variables and parameters assigned after their declaration
+    flow_types: HashMap<(FlowId, String), JoinedTypes>,
+    joining: usize, // how many joins are being worked out, each inside the one before
+    // The outermost join being worked out, a loop, that the type of the innermost depends on
+    incomplete: Option<usize>,
+    assigned: HashMap<*const Node, Type>, // the values of declarations and assignments
+    returns: HashMap<*const Node, Type>,  // of calls, with type guards and assertions as written
+    declarations: HashMap<SymbolId, &'a Node>, // the first of functions, classes and namespaces
+    inferred: HashMap<SymbolId, Type>,    // of names declared without an annotation
+    functions: HashMap<*const Node, Type>, // signatures, once their bodies are checked
+    contexts: Vec<FunctionContext>,
+    constraints: Vec<HashMap<String, Type>>, // of the type parameters in scope
+    this: Option<Type>,
+    scope: ScopeId,
+    errors: Vec<CompilerError>,
+}
+
+impl<'a> Checker<'a> {
+    fn collect_declarations(&mut self, node: &'a Node) {
+        if matches!(
+            node,
+            Node::FunctionDeclaration { .. }
+                | Node::ClassDeclaration { .. }
+                | Node::NamespaceDeclaration { .. }
+        ) {
+            if let Some(symbol) = self.table.symbol_of(node) {
+                self.declarations.entry(symbol).or_insert(node);
+            }
+        }
+        for child in node.children() {
+            self.collect_declarations(child);
+        }
+    }
+
+    fn visit(&mut self, node: &'a Node) {
+        let outer = self.scope;
+        if let Some(scope) = self.table.scope_of(node) {
+            self.scope = scope;
+        }
+        self.statement(node);
+        self.scope = outer;
+    }
+
+    fn statement(&mut self, node: &'a Node) {
+        match node {
+            Node::VariableDeclaration { kind, declarations } => {
+                for declarator in declarations {
+                    self.declarator(kind, declarator);
+                }
+            }
+            Node::FunctionDeclaration { .. } => {
+                self.function(node, None);
+            }
+            Node::ClassDeclaration { .. } => self.class(node),
+            Node::ExpressionStatement(expression) | Node::ThrowStatement(expression) => {
+                self.expression(expression, None);
+            }
+            Node::ReturnStatement(argument) => self.return_statement(argument.as_deref()),
+            Node::IfStatement {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                self.expression(condition, None);
+                self.visit(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.visit(else_branch);
+                }
+            }
+            Node::WhileStatement { test, body } | Node::DoWhileStatement { body, test } => {
+                self.expression(test, None);
+                self.visit(body);
+            }
+            Node::ForStatement {
+                init,
+                test,
+                update,
+                body,
+            } => {
+                if let Some(init) = init {
+                    match init.as_ref() {
+                        Node::VariableDeclaration { .. } => self.visit(init),
+                        init => {
+                            self.expression(init, None);
+                        }
+                    }
+                }
+                for expression in test.iter().chain(update) {
+                    self.expression(expression, None);
+                }
+                self.visit(body);
+            }
+            Node::ForOfStatement {
+                left,
+                right,
+                body,
+                is_await,
+            } => {
+                let element = self
+                    .expression(right, None)
+                    .element_type(None)
+                    .unwrap_or(Type::Any);
+                let element = if *is_await {
+                    awaited(&element)
+                } else {
+                    element
+                };
+                self.loop_variable(left, &element);
+                self.visit(body);
+            }
+            Node::ForInStatement { left, right, body } => {
+                self.expression(right, None);
+                self.loop_variable(left, &Type::String);
+                self.visit(body);
+            }
+            Node::SwitchStatement {
+                discriminant,
+                cases,
+            } => {
+                self.expression(discriminant, None);
+                for case in cases {
+                    if let Some(test) = &case.test {
+                        self.expression(test, None);
+                    }
+                    for statement in &case.consequent {
+                        self.visit(statement);
+                    }
+                }
+            }
+            Node::TryStatement {
+                block,
+                handler,
+                finalizer,
+            } => {
+                self.visit(block);
+                if let Some(handler) = handler {
+                    self.visit(&handler.body);
+                }
+                if let Some(finalizer) = finalizer {
+                    self.visit(finalizer);
+                }
+            }
+            Node::EnumDeclaration { members, .. } => {
+                for init in members.iter().filter_map(|member| member.init.as_deref()) {
+                    self.expression(init, None);
+                }
+            }
+            Node::ExportDefaultDeclaration(declaration) => match declaration.as_ref() {
+                Node::FunctionDeclaration { .. } | Node::ClassDeclaration { .. } => {
+                    self.visit(declaration)
+                }
+                expression => {
+                    self.expression(expression, None);
+                }
+            },
+            // Ambient declarations only describe values that exist elsewhere
+            Node::AmbientDeclaration(_)
+            | Node::AmbientModuleDeclaration { .. }
+            | Node::GlobalAugmentation(_)
+            | Node::NamespaceDeclaration {
+                is_declare: true, ..
+            }
+            | Node::ImportDeclaration { .. }
+            | Node::InterfaceDeclaration { .. }
+            | Node::TypeAliasDeclaration { .. } => {}
+            Node::Program(_)
+            | Node::BlockStatement(..)
+            | Node::NamespaceDeclaration { .. }
+            | Node::ExportNamedDeclaration { .. }
+            | Node::LabeledStatement { .. } => {
+                for child in node.children() {
+                    self.visit(child);
+                }
+            }
+            node => {
+                self.expression(node, None);
+            }
+        }
+    }
+
+    fn declarator(&mut self, kind: &VariableKind, declarator: &'a VariableDeclarator) {
+        let declared = declarator
+            .type_annotation
+            .as_ref()
+            .map(|annotation| self.lowering.lower(annotation, self.scope));
+        let Some(init) = &declarator.init else {
+            return;
+        };
+        let value = self.expression(init, declared.as_ref());
+        self.assigned.insert(&*declarator.id, value.clone());
+        match declared {
+            Some(declared) => self.check_assignable(&value, &declared, declarator.id.span()),
+            // `let` and `var` can be given other values of the literal's primitive later
+            None if *kind == VariableKind::Const => self.bind(&declarator.id, &value),
+            None => self.bind(&declarator.id, &value.widened()),
+        }
+    }
+
+    /// Gives the names `target` declares their part of `value`.
+    fn bind(&mut self, target: &Node, value: &Type) {
+        if let Some(symbol) = self.table.symbol_of(target) {
+            self.inferred.insert(symbol, value.clone());
+            return;
+        }
+        let Ok(bindings) = pattern_bindings(target, value) else {
+            return;
+        };
+        for identifier in target.binding_identifiers() {
+            let Node::Identifier(name, _) = identifier else {
+                continue;
+            };
+            let bound = bindings.iter().find(|(bound, _)| bound == name);
+            if let (Some(symbol), Some((_, bound))) = (self.table.symbol_of(identifier), bound) {
+                self.inferred.insert(symbol, bound.clone());
+            }
+        }
+    }
+
+    fn loop_variable(&mut self, left: &'a Node, element: &Type) {
+        match left {
+            Node::VariableDeclaration { declarations, .. } => {
+                for declarator in declarations {
+                    self.bind(&declarator.id, element);
+                }
+            }
+            target => {
+                self.expression(target, None);
+            }
+        }
+    }
+
+    fn return_statement(&mut self, argument: Option<&'a Node>) {
+        let declared = self
+            .contexts
+            .last()
+            .and_then(|context| context.declared.clone());
+        let value = match argument {
+            Some(argument) => self.expression(argument, declared.as_ref()),
+            None => Type::Undefined,
+        };
+        self.returned(value, argument.map(Node::span).unwrap_or_default());
+    }
+
+    fn returned(&mut self, value: Type, span: Span) {
+        let Some(context) = self.contexts.last_mut() else {
+            return;
+        };
+        context.returned.push(value.clone());
+        if let Some(declared) = context.declared.clone() {
+            self.check_assignable(&value, &declared, span);
+        }
+    }
+
+    /// The type a reference to `symbol` reads.
+    fn symbol_type(&mut self, symbol: SymbolId) -> Type {
+        if let Some(inferred) = self.inferred.get(&symbol) {
+            return inferred.clone();
+        }
+        // Functions without a return type return what their bodies do
+        if let Some(&declaration) = self.declarations.get(&symbol) {
+            if matches!(
+                declaration,
+                Node::FunctionDeclaration {
+                    return_type: None,
+                    body: Some(_),
+                    ..
+                }
+            ) {
+                return self.function(declaration, None);
+            }
+        }
+        self.table
+            .symbol(symbol)
+            .value_type
+            .clone()
+            .unwrap_or(Type::Any)
+    }
+
+    /// Checks the body of a function, method or arrow function and gives its signature.
+    /// Unannotated parameters take their types from `expected`, the function type it is
+    /// passed or assigned to, when there is one. Each function is checked once, and one
+    /// referred to from its own body is seen with the return type it is annotated with.
+    fn function(&mut self, node: &'a Node, expected: Option<&Type>) -> Type {
+        if let Some(signature) = self.functions.get(&(node as *const Node)) {
+            return signature.clone();
+        }
+        let (type_params, params, return_type, body, is_async, is_generator) = match node {
+            Node::FunctionDeclaration {
+                type_params,
+                params,
+                return_type,
+                body,
+                is_async,
+                is_generator,
+                ..
+            }
+            | Node::MethodDefinition {
+                type_params,
+                params,
+                return_type,
+                body,
+                is_async,
+                is_generator,
+                ..
+            } => (
+                type_params,
+                params,
+                return_type,
+                body.as_deref(),
+                *is_async,
+                *is_generator,
+            ),
+            Node::ArrowFunctionExpression {
+                type_params,
+                params,
+                return_type,
+                body,
+                is_async,
+                ..
+            } => (
+                type_params,
+                params,
+                return_type,
+                Some(body.as_ref()),
+                *is_async,
+                false,
+            ),
+            _ => return Type::Any,
+        };
+        let outer_scope = self.scope;
+        self.scope = self.table.scope_of(node).unwrap_or(outer_scope);
+        let method = matches!(node, Node::MethodDefinition { .. });
+        let mut signature = self.lowering.signature(
+            type_params,
+            params,
+            return_type.as_deref(),
+            method,
+            self.scope,
+        );
+        let Type::Function {
+            params: param_types,
+            ..
+        } = &mut signature
+        else {
+            return signature;
+        };
+        let contextual = expected.and_then(contextual_signature);
+
+        let mut index = 0;
+        for param in params {
+            if param.name() == Some("this") {
+                continue;
+            }
+            let annotated = param
+                .type_annotation
+                .as_ref()
+                .map(|annotation| self.lowering.lower(annotation, self.scope));
+            let mut inferred = None;
+            if annotated.is_none() && !param.rest {
+                inferred = contextual.and_then(|contextual| parameter_type(contextual, index));
+            }
+            if let Some(init) = &param.init {
+                let value = self.expression(init, annotated.as_ref().or(inferred.as_ref()));
+                match &annotated {
+                    Some(annotated) => {
+                        self.check_assignable(&value, annotated, param.binding.span())
+                    }
+                    None if inferred.is_none() => inferred = Some(value.widened()),
+                    None => {}
+                }
+            }
+            if let Some(inferred) = inferred {
+                self.bind(&param.binding, &inferred);
+                if let Some(param_type) = param_types.get_mut(index) {
+                    *param_type = if param.optional || param.init.is_some() {
+                        Type::union(vec![inferred, Type::Undefined])
+                    } else {
+                        inferred
+                    };
+                }
+            }
+            if !param.rest {
+                index += 1;
+            }
+        }
+
+        // Until its body is checked, the function is seen with its annotated return type
+        self.functions.insert(node, signature.clone());
+        let Type::Function {
+            type_params: lowered_params,
+            return_type: declared_return,
+            ..
+        } = &mut signature
+        else {
+            return signature;
+        };
+        self.constraints.push(
+            lowered_params
+                .iter()
+                .filter_map(|type_param| {
+                    let constraint = type_param.constraint.clone()?;
+                    Some((type_param.name.clone(), constraint))
+                })
+                .collect(),
+        );
+        // Generators give their values with `yield`, which isn't checked yet
+        let declared = match return_type {
+            Some(_) if is_generator => None,
+            Some(_) if is_async => Some(awaited(declared_return)),
+            Some(_) => Some(declared_return.returned_value()),
+            None => None,
+        };
+        self.contexts.push(FunctionContext {
+            declared,
+            returned: Vec::new(),
+        });
+        // Only arrow functions see the `this` of the code around them
+        let outer_this = if matches!(node, Node::FunctionDeclaration { .. }) {
+            self.this.take()
+        } else {
+            self.this.clone()
+        };
+        match body {
+            Some(body @ Node::BlockStatement(..)) => self.visit(body),
+            Some(expression) => {
+                let declared = self.contexts.last().unwrap().declared.clone();
+                let value = self.expression(expression, declared.as_ref());
+                self.returned(value, expression.span());
+            }
+            None => {}
+        }
+        self.this = outer_this;
+        let context = self.contexts.pop().unwrap();
+        if let (Some(Node::BlockStatement(..)), Some(declared), Some(annotation)) =
+            (body, &context.declared, return_type)
+        {
+            let returns = !context.returned.is_empty();
+            self.check_end(node, declared, returns, span_or(annotation, node.span()));
+        }
+        self.constraints.pop();
+        self.scope = outer_scope;
+
+        if return_type.is_none() && body.is_some() {
+            let returned = if is_generator {
+                Type::Any
+            } else if context.returned.iter().all(|t| *t == Type::Undefined) {
+                Type::Void
+            } else {
+                Type::union(context.returned.iter().map(Type::widened).collect())
+            };
+            **declared_return = if is_async && !is_generator {
+                Type::Custom("Promise".to_string(), vec![returned])
+            } else {
+                returned
+            };
+        }
+        self.functions.insert(node, signature.clone());
+        signature
+    }
+
+    fn class(&mut self, node: &'a Node) {
+        let Node::ClassDeclaration {
+            type_params,
+            extends,
+            members,
+            decorators,
+            ..
+        } = node
+        else {
+            return;
+        };
+        let outer_scope = self.scope;
+        self.scope = self.table.scope_of(node).unwrap_or(outer_scope);
+        for expression in decorators.iter().chain(extends.as_deref()) {
+            self.expression(expression, None);
+        }
+        let instance = self
+            .table
+            .symbol_of(node)
+            .and_then(|symbol| self.table.symbol(symbol).declared_type.clone())
+            .unwrap_or(Type::Any);
+        let constraints = match self
+            .lowering
+            .signature(type_params, &[], None, false, self.scope)
+        {
+            Type::Function { type_params, .. } => type_params
+                .into_iter()
+                .filter_map(|type_param| Some((type_param.name, type_param.constraint?)))
+                .collect(),
+            _ => HashMap::new(),
+        };
+        self.constraints.push(constraints);
+
+        for member in members {
+            let is_static = match member {
+                Node::MethodDefinition { modifiers, .. }
+                | Node::PropertyDefinition { modifiers, .. } => modifiers.is_static,
+                _ => false,
+            };
+            let this = if is_static {
+                Type::Any
+            } else {
+                instance.clone()
+            };
+            let outer_this = self.this.replace(this);
+            match member {
+                Node::MethodDefinition { modifiers, .. } => {
+                    for decorator in &modifiers.decorators {
+                        self.expression(decorator, None);
+                    }
+                    self.function(member, None);
+                }
+                Node::PropertyDefinition {
+                    key,
+                    type_annotation,
+                    value,
+                    modifiers,
+                    ..
+                } => {
+                    for decorator in &modifiers.decorators {
+                        self.expression(decorator, None);
+                    }
+                    let declared = type_annotation
+                        .as_ref()
+                        .map(|annotation| self.lowering.lower(annotation, self.scope));
+                    if let Some(value) = value {
+                        let class_scope = self.scope;
+                        self.scope = self.table.scope_of(member).unwrap_or(class_scope);
+                        let value_type = self.expression(value, declared.as_ref());
+                        self.scope = class_scope;
+                        if let Some(declared) = &declared {
+                            self.check_assignable(&value_type, declared, key.span());
+                        }
+                    }
+                }
+                _ => {}
+            }
+            self.this = outer_this;
+        }
+        self.constraints.pop();
+        self.scope = outer_scope;
+    }
+
+    /// The type of an expression. `expected` is the type of where its value goes, which
+    /// array literals and functions are typed by.
+    fn expression(&mut self, node: &'a Node, expected: Option<&Type>) -> Type {
+        match node {
+            Node::Literal {
+                token_type, value, ..
+            } => literal_type(token_type, value),
+            Node::Identifier(..) => match self.table.symbol_of(node) {
+                Some(symbol) => {
+                    let declared = self.symbol_type(symbol);
+                    self.narrowed(node, declared)
+                }
+                None => Type::Any,
+            },
+            Node::ThisExpression => self.this.clone().unwrap_or(Type::Any),
+            Node::ArrayExpression(elements) => self.array(elements, expected),
+            Node::ObjectExpression(properties) => self.object(properties, expected),
+            Node::SpreadElement(argument) => self.expression(argument, None),
+            Node::BinaryExpression {
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.expression(left, None);
+                let right = self.expression(right, None);
+                binary_type(operator, &left, &right)
+            }
+            Node::LogicalExpression {
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.expression(left, expected);
+                let right = self.expression(right, expected);
+                match operator.as_str() {
+                    "&&" => Type::union(vec![falsy_part(&left), right]),
+                    "||" => Type::union(vec![truthy_part(&left), right]),
+                    _ => Type::nullish_coalescing(&left, &right),
+                }
+            }
+            Node::UnaryExpression { operator, argument } => {
+                let operand = self.expression(argument, None);
+                match operator.as_str() {
+                    "!" | "delete" => Type::Boolean,
+                    "typeof" => Type::String,
+                    "void" => Type::Undefined,
+                    "-" => match argument.as_ref() {
+                        Node::Literal {
+                            token_type: TokenType::NumberLiteral | TokenType::BigInt,
+                            value,
+                            ..
+                        } => Type::Literal(format!("-{}", value)),
+                        _ => binary_type("-", &operand, &operand),
+                    },
+                    "~" => binary_type("~", &operand, &operand),
+                    _ => Type::Number,
+                }
+            }
+            Node::UpdateExpression { argument, .. } => {
+                let operand = match argument.as_ref() {
+                    Node::MemberExpression { .. } => self.member_target(argument),
+                    _ => self.expression(argument, None),
+                };
+                binary_type("+", &operand.widened(), &Type::Number)
+            }
+            Node::AssignmentExpression {
+                left,
+                operator,
+                right,
+            } => self.assignment(left, operator, right),
+            Node::ConditionalExpression {
+                test,
+                consequent,
+                alternate,
+            } => {
+                self.expression(test, None);
+                let consequent = self.expression(consequent, expected);
+                let alternate = self.expression(alternate, expected);
+                Type::union(vec![consequent, alternate])
+            }
+            Node::CallExpression { .. } => self.call(node),
+            Node::NewExpression { .. } => self.new_expression(node),
+            Node::MemberExpression { .. } => self.member(node),
+            Node::ChainExpression(expression) => self.expression(expression, expected),
+            Node::AsExpression {
+                expression,
+                type_annotation,
+            }
+            | Node::TypeAssertion {
+                type_annotation,
+                expression,
+            } => self.assertion(expression, type_annotation),
+            Node::SatisfiesExpression {
+                expression,
+                type_annotation,
+            } => {
+                let target = self.lowering.lower(type_annotation, self.scope);
+                let source = self.expression(expression, Some(&target));
+                if let Err(message) = satisfies(&source, &target) {
+                    self.error(1360, message, expression.span());
+                }
+                source
+            }
+            Node::NonNullExpression(expression) => self.expression(expression, None).non_nullable(),
+            Node::ArrowFunctionExpression { .. } => self.function(node, expected),
+            Node::FunctionDeclaration { .. } => {
+                let outer_this = self.this.take();
+                let signature = self.function(node, expected);
+                self.this = outer_this;
+                signature
+            }
+            Node::ClassDeclaration { .. } => {
+                self.class(node);
+                Type::Any
+            }
+            Node::AwaitExpression(argument) => awaited(&self.expression(argument, None)),
+            node if is_type(node) => Type::Any,
+            node => {
+                for child in node.children() {
+                    self.expression(child, None);
+                }
+                Type::Any
+            }
+        }
+    }
+
+    fn array(&mut self, elements: &'a [Option<Node>], expected: Option<&Type>) -> Type {
+        // A literal in a tuple's place is a tuple, and its elements are typed by the tuple's
+        let tuple = expected.and_then(|expected| {
+            expected_member(expected, |t| match t {
+                Type::Tuple { .. } => Some(t.clone()),
+                _ => None,
+            })
+        });
+        let element_context = expected.and_then(|expected| {
+            expected_member(expected, |t| match t {
+                Type::Array(element) => Some((**element).clone()),
+                _ => None,
+            })
+        });
+        let mut types = Vec::new();
+        let mut spread = false;
+        for element in elements {
+            match element {
+                None => types.push(Type::Undefined),
+                Some(Node::SpreadElement(argument)) => match self.expression(argument, None) {
+                    Type::Tuple {
+                        elements,
+                        rest: None,
+                        ..
+                    } => types.extend(elements),
+                    iterated => {
+                        spread = true;
+                        types.push(iterated.element_type(None).unwrap_or(Type::Any));
+                    }
+                },
+                Some(element) => {
+                    let context = tuple
+                        .as_ref()
+                        .and_then(|tuple| tuple.element_type(Some(types.len())).ok())
+                        .or(element_context.clone());
+                    types.push(self.expression(element, context.as_ref()));
+                }
+            }
+        }
+        if tuple.is_some() && !spread {
+            return Type::tuple(types);
+        }
+        if types.is_empty() {
+            // `let xs = []` can be given elements of any type later
+            let element = if expected.is_some() {
+                Type::Never
+            } else {
+                Type::Any
+            };
+            return Type::Array(Box::new(element));
+        }
+        let element = Type::union(types);
+        if expected.is_some_and(has_literals) {
+            Type::Array(Box::new(element))
+        } else {
+            Type::Array(Box::new(element.widened()))
+        }
+    }
+
+    /// The type of an object literal. Its properties are typed by the properties of the
+    /// same name in `expected`, and later ones replace earlier ones, including those a
+    /// spread gives. Spreading a value whose members aren't known makes it `any`.
+    fn object(&mut self, properties: &'a [Node], expected: Option<&Type>) -> Type {
+        // Methods of object literals have the object as `this`, which isn't typed yet
+        let outer_this = self.this.take();
+        let mut object = ObjectType::default();
+        let mut known = true;
+        for property in properties {
+            match property {
+                Node::Property {
+                    key,
+                    computed,
+                    value,
+                    ..
+                } => {
+                    if *computed {
+                        self.expression(key, None);
+                    }
+                    let name = key_name(key, *computed);
+                    let context = name.and_then(|name| {
+                        expected.and_then(|expected| {
+                            expected_member(expected, |t| t.property_type(name))
+                        })
+                    });
+                    let value_type = self.expression(value, context.as_ref());
+                    let value_type = match &context {
+                        Some(context) if has_literals(context) => value_type,
+                        _ => value_type.widened(),
+                    };
+                    if let Some(name) = name {
+                        set_property(&mut object, name, value_type, false);
+                    }
+                }
+                Node::MethodDefinition {
+                    key,
+                    computed,
+                    kind,
+                    ..
+                } => {
+                    if *computed {
+                        self.expression(key, None);
+                    }
+                    let name = key_name(key, *computed);
+                    let context = name.and_then(|name| {
+                        expected.and_then(|expected| {
+                            expected_member(expected, |t| t.property_type(name))
+                        })
+                    });
+                    let context = match kind {
+                        MethodKind::Method => context,
+                        _ => None,
+                    };
+                    let signature = self.function(property, context.as_ref());
+                    let Type::Function {
+                        params,
+                        return_type,
+                        ..
+                    } = &signature
+                    else {
+                        continue;
+                    };
+                    let value_type = match kind {
+                        MethodKind::Get => (**return_type).clone(),
+                        MethodKind::Set => params.first().cloned().unwrap_or(Type::Any),
+                        _ => signature.clone(),
+                    };
+                    if let Some(name) = name {
+                        set_property(&mut object, name, value_type, false);
+                        // An accessor without a setter is read-only
+                        let has_setter = properties.iter().any(|other| {
+                            matches!(other, Node::MethodDefinition { key, computed, kind: MethodKind::Set, .. }
+                                if key_name(key, *computed) == Some(name))
+                        });
+                        if let Some(property) = object.properties.last_mut() {
+                            property.readonly = *kind == MethodKind::Get && !has_setter;
+                        }
+                    }
+                }
+                Node::SpreadElement(argument) => match self.expression(argument, None) {
+                    Type::Shape(spread) => {
+                        for property in &spread.get().properties {
+                            let value_type = property.value_type.clone();
+                            set_property(
+                                &mut object,
+                                &property.name,
+                                value_type,
+                                property.optional,
+                            );
+                        }
+                    }
+                    Type::Null | Type::Undefined => {}
+                    _ => known = false,
+                },
+                _ => {}
+            }
+        }
+        self.this = outer_this;
+        if known {
+            Type::Shape(self.table.shapes().intern(object))
+        } else {
+            Type::Any
+        }
+    }
+
+    fn assignment(&mut self, left: &'a Node, operator: &str, right: &'a Node) -> Type {
+        // Assignments to constants, functions and the like are reported by `assignments`
+        let target = match left {
+            Node::Identifier(..) => self
+                .table
+                .symbol_of(left)
+                .filter(|&symbol| {
+                    let declared = self.table.symbol(symbol);
+                    declared.flags.intersects(
+                        SymbolFlags::VARIABLE
+                            | SymbolFlags::BLOCK_SCOPED_VARIABLE
+                            | SymbolFlags::PARAMETER,
+                    ) && !declared.is_constant
+                })
+                .map(|symbol| self.symbol_type(symbol)),
+            Node::MemberExpression { .. } => Some(self.member_target(left)),
+            _ => None,
+        };
+        let value = match operator {
+            "=" => self.expression(right, target.as_ref()),
+            "&&=" | "||=" | "??=" => {
+                let current = target.clone().unwrap_or(Type::Any);
+                let value = self.expression(right, target.as_ref());
+                match operator {
+                    "&&=" => Type::union(vec![falsy_part(&current), value]),
+                    "||=" => Type::union(vec![truthy_part(&current), value]),
+                    _ => Type::nullish_coalescing(&current, &value),
+                }
+            }
+            _ => {
+                let current = target.clone().unwrap_or(Type::Any);
+                let value = self.expression(right, None);
+                binary_type(operator.trim_end_matches('='), &current, &value)
+            }
+        };
+        if let Some(target) = &target {
+            self.check_assignable(&value, target, left.span());
+        }
+        self.assigned.insert(left, value.clone());
+        value
+    }
+
+    fn member(&mut self, node: &'a Node) -> Type {
+        self.member_of(node).0
+    }
+
+    /// The type of a property being written to. Writing to a `readonly` one is reported
+    /// here where `check_assignments` can't find its declaration, as with `mk().r = 1` or
+    /// a property of a variable declared without an annotation.
+    fn member_target(&mut self, node: &'a Node) -> Type {
+        let (value, object_type) = self.member_of(node);
+        let Node::MemberExpression {
+            object,
+            property,
+            computed,
+            ..
+        } = node
+        else {
+            return value;
+        };
+        let name = key_name(property, *computed);
+        if let (Some(object_type), Some(name)) = (object_type, name) {
+            if !has_declared_shape(object, self.table) && object_type.is_readonly_property(name) {
+                let message = format!(
+                    "Cannot assign to '{}' because it is a read-only property.",
+                    name
+                );
+                self.error(2540, message, span_or(property, node.span()));
+                // As with tsc, what is assigned isn't checked against it too
+                return Type::Any;
+            }
+        }
+        value
+    }
+
+    /// The type of a property read, and of the object it is read from, if it has one.
+    fn member_of(&mut self, node: &'a Node) -> (Type, Option<Type>) {
+        let Node::MemberExpression {
+            object,
+            property,
+            computed,
+            optional,
+        } = node
+        else {
+            return (Type::Any, None);
+        };
+        // `E.A` for an enum `E`
+        if let (Node::Identifier(..), Node::Identifier(member, _), false) =
+            (object.as_ref(), property.as_ref(), computed)
+        {
+            let enum_type = self
+                .table
+                .symbol_of(object)
+                .filter(|&symbol| self.table.symbol(symbol).flags.contains(SymbolFlags::ENUM))
+                .and_then(|symbol| self.table.symbol(symbol).declared_type.clone());
+            if let Some(Type::Enum(name)) = enum_type {
+                return (Type::EnumMember(name, member.clone()), None);
+            }
+        }
+        // `A.x` for a namespace `A`, which has nothing but its exports
+        if let (Some(exports), Node::Identifier(member, span), false) =
+            (self.namespace_exports(object), property.as_ref(), computed)
+        {
+            let exported = self
+                .table
+                .scope(exports)
+                .symbols
+                .get(member)
+                .is_some_and(|symbol| self.table.symbol(*symbol).flags.has_meaning(Meaning::Value));
+            if !exported {
+                let message = format!(
+                    "Property '{}' does not exist on type 'typeof {}'.",
+                    member,
+                    qualified_name(object)
+                );
+                self.error(2339, message, *span);
+                return (Type::Any, None);
+            }
+        }
+        let object_type = self.expression(object, None);
+        let base = if *optional {
+            object_type.non_nullable()
+        } else {
+            object_type.clone()
+        };
+        let result = if *computed {
+            let index = self.expression(property, None);
+            match (&base, &index) {
+                (Type::Tuple { .. }, Type::Literal(value)) => {
+                    base.property_type(value).unwrap_or(Type::Any)
+                }
+                (Type::Array(_) | Type::Tuple { .. } | Type::String, index)
+                    if matches!(index.widened(), Type::Number) =>
+                {
+                    base.element_type(None).unwrap_or(Type::Any)
+                }
+                (_, Type::Literal(value)) if value.starts_with('"') => base
+                    .property_type(value.trim_matches('"'))
+                    .unwrap_or(Type::Any),
+                _ => Type::Any,
+            }
+        } else if let Node::Identifier(name, span) = property.as_ref() {
+            base.property_type(name).unwrap_or_else(|| {
+                if has_known_members(&base) {
+                    let message = format!("Property '{}' does not exist on type '{}'.", name, base);
+                    self.error(2339, message, *span);
+                }
+                Type::Any
+            })
+        } else {
+            Type::Any
+        };
+        let result = self.narrowed(node, result);
+        if *optional {
+            (Type::optional_chain(&[object_type], result), Some(base))
+        } else {
+            (result, Some(base))
+        }
+    }
+
+    /// The scope holding the exports of the namespace `node` refers to, `A` or `A.B`, if
+    /// it's only a namespace and not merged with a value that has members of its own.
+    fn namespace_exports(&self, node: &Node) -> Option<ScopeId> {
+        let symbol = match node {
+            Node::Identifier(..) => self.table.symbol_of(node)?,
+            Node::MemberExpression {
+                object,
+                property,
+                computed: false,
+                ..
+            } => {
+                let Node::Identifier(name, _) = property.as_ref() else {
+                    return None;
+                };
+                let exports = self.namespace_exports(object)?;
+                *self.table.scope(exports).symbols.get(name)?
+            }
+            _ => return None,
+        };
+        let flags = self.table.symbol(symbol).flags;
+        if !flags.contains(SymbolFlags::NAMESPACE)
+            || flags.intersects(SymbolFlags::FUNCTION | SymbolFlags::CLASS | SymbolFlags::ENUM)
+        {
+            return None;
+        }
+        // Each declaration's body scope is inside the exports shared by all of them
+        let declaration = self.declarations.get(&symbol)?;
+        self.table.scope(self.table.scope_of(declaration)?).parent
+    }
+
+    fn call(&mut self, node: &'a Node) -> Type {
+        let Node::CallExpression {
+            callee,
+            type_arguments,
+            arguments,
+            optional,
+        } = node
+        else {
+            return Type::Any;
+        };
+        if let Node::SuperExpression = callee.as_ref() {
+            for argument in arguments {
+                self.expression(argument, None);
+            }
+            return Type::Void;
+        }
+        let callee_type = self.expression(callee, None);
+        let callable = if *optional {
+            callee_type.non_nullable()
+        } else {
+            callee_type.clone()
+        };
+        let type_arguments: Vec<Type> = type_arguments
+            .iter()
+            .map(|argument| self.lowering.lower(argument, self.scope))
+            .collect();
+        let result = self.invoke(&callable, &type_arguments, arguments, callee.span());
+        self.returns.insert(node, result.clone());
+        let result = result.returned_value();
+        if *optional {
+            Type::optional_chain(&[callee_type], result)
+        } else {
+            result
+        }
+    }
+
+    fn new_expression(&mut self, node: &'a Node) -> Type {
+        let Node::NewExpression {
+            callee,
+            type_arguments,
+            arguments,
+        } = node
+        else {
+            return Type::Any;
+        };
+        let class = self
+            .table
+            .symbol_of(callee)
+            .and_then(|symbol| self.declarations.get(&symbol).copied())
+            .filter(|declaration| matches!(declaration, Node::ClassDeclaration { .. }));
+        let signatures = match class {
+            Some(class) => vec![self.constructor(class)],
+            // A value with construct signatures
+            None => match self.expression(callee, None) {
+                Type::Shape(shape) => shape.get().construct_signatures.clone(),
+                _ => Vec::new(),
+            },
+        };
+        if signatures.is_empty() {
+            for argument in arguments {
+                self.expression(argument, None);
+            }
+            return Type::Any;
+        }
+        let type_arguments: Vec<Type> = type_arguments
+            .iter()
+            .map(|argument| self.lowering.lower(argument, self.scope))
+            .collect();
+        self.invoke_overloads(&signatures, &type_arguments, arguments, callee.span())
+    }
+
+    /// The signature `new` calls a class with, generic over the class's type parameters
+    /// and returning an instance of it.
+    fn constructor(&mut self, class: &'a Node) -> Type {
+        let Node::ClassDeclaration {
+            type_params,
+            extends,
+            members,
+            ..
+        } = class
+        else {
+            return Type::Any;
+        };
+        let scope = self.table.scope_of(class).unwrap_or(self.scope);
+        let Type::Function {
+            type_params: lowered_params,
+            ..
+        } = self
+            .lowering
+            .signature(type_params, &[], None, false, scope)
+        else {
+            return Type::Any;
+        };
+        let instance = self
+            .table
+            .symbol_of(class)
+            .and_then(|symbol| self.table.symbol(symbol).declared_type.clone())
+            .unwrap_or(Type::Any);
+        let constructor = members.iter().find(|member| {
+            matches!(
+                member,
+                Node::MethodDefinition {
+                    kind: MethodKind::Constructor,
+                    ..
+                }
+            )
+        });
+        let (params, required, rest) = match constructor {
+            Some(constructor @ Node::MethodDefinition { params, .. }) => {
+                let scope = self.table.scope_of(constructor).unwrap_or(scope);
+                match self.lowering.signature(&[], params, None, false, scope) {
+                    Type::Function {
+                        params,
+                        required,
+                        rest,
+                        ..
+                    } => (params, required, rest),
+                    _ => return Type::Any,
+                }
+            }
+            // The base class's constructor, which isn't looked into yet
+            _ if extends.is_some() => (
+                Vec::new(),
+                0,
+                Some(Box::new(Type::Array(Box::new(Type::Any)))),
+            ),
+            _ => (Vec::new(), 0, None),
+        };
+        Type::Function {
+            type_params: lowered_params,
+            params,
+            required,
+            rest,
+            return_type: Box::new(instance),
+            method: false,
+        }
+    }
+
+    /// Checks the arguments of a call of a value of type `callee`, and gives what the call
+    /// returns. `span` is where the callee is.
+    fn invoke(
+        &mut self,
+        callee: &Type,
+        type_arguments: &[Type],
+        arguments: &'a [Node],
+        span: Span,
+    ) -> Type {
+        // An object type is called through its call signatures
+        if let Type::Shape(shape) = callee {
+            let signatures = shape.get().call_signatures.clone();
+            if !signatures.is_empty() {
+                return self.invoke_overloads(&signatures, type_arguments, arguments, span);
+            }
+        }
+        let Type::Function { type_params, .. } = callee else {
+            // Calls through values whose members aren't known can't be checked
+            if callee.is_primitive()
+                || matches!(
+                    callee,
+                    Type::Void
+                        | Type::Never
+                        | Type::Array(_)
+                        | Type::Tuple { .. }
+                        | Type::Enum(_)
+                        | Type::EnumMember(..)
+                        | Type::Shape(_)
+                )
+            {
+                self.error(
+                    2349,
+                    format!(
+                        "This expression is not callable. Type '{}' has no call signatures.",
+                        callee
+                    ),
+                    span,
+                );
+            }
+            for argument in arguments {
+                self.expression(argument, None);
+            }
+            return Type::Any;
+        };
+
+        // Functions passed to a generic function are typed once the other arguments have
+        // given its type parameters types
+        let mut argument_types: Vec<Option<Type>> = vec![None; arguments.len()];
+        let deferred = !type_params.is_empty() && type_arguments.is_empty();
+        for (index, argument) in arguments.iter().enumerate() {
+            if deferred && is_context_sensitive(argument) {
+                continue;
+            }
+            let context = instantiate_call(callee, type_arguments, &[])
+                .ok()
+                .and_then(|signature| parameter_type(&erase_type_params(&signature), index));
+            argument_types[index] = Some(self.argument(argument, context.as_ref()));
+        }
+        if deferred {
+            let known: Vec<Type> = argument_types
+                .iter()
+                .map(|t| t.clone().unwrap_or(Type::Any))
+                .collect();
+            let partial = instantiate_call(callee, &[], &known)
+                .map(|signature| erase_type_params(&signature))
+                .ok();
+            for (index, argument) in arguments.iter().enumerate() {
+                if argument_types[index].is_none() {
+                    let context = partial
+                        .as_ref()
+                        .and_then(|signature| parameter_type(signature, index));
+                    argument_types[index] = Some(self.argument(argument, context.as_ref()));
+                }
+            }
+        }
+        let argument_types: Vec<Type> = argument_types.into_iter().flatten().collect();
+
+        let signature = match instantiate_call(callee, type_arguments, &argument_types) {
+            Ok(signature) => signature,
+            Err(message) => {
+                // Inferred type arguments fall back to the constraints they break, so only
+                // explicit ones get here for their constraints
+                let code = if message.starts_with("Expected") {
+                    2558
+                } else {
+                    2344
+                };
+                self.error(code, format!("{}.", message), span);
+                erase_type_params(callee)
+            }
+        };
+        let Type::Function {
+            params,
+            required,
+            rest,
+            return_type,
+            ..
+        } = &signature
+        else {
+            return Type::Any;
+        };
+
+        // The number of elements a spread argument gives isn't known
+        if !arguments
+            .iter()
+            .any(|argument| matches!(argument, Node::SpreadElement(_)))
+        {
+            let count = arguments.len();
+            if count < *required || (rest.is_none() && count > params.len()) {
+                let (code, message) = if rest.is_some() {
+                    (
+                        2555,
+                        format!(
+                            "Expected at least {} arguments, but got {}.",
+                            required, count
+                        ),
+                    )
+                } else {
+                    let expected = if *required == params.len() {
+                        required.to_string()
+                    } else {
+                        format!("{}-{}", required, params.len())
+                    };
+                    (
+                        2554,
+                        format!("Expected {} arguments, but got {}.", expected, count),
+                    )
+                };
+                let span = match arguments.get(params.len()) {
+                    Some(extra) if count > params.len() => span_or(extra, span),
+                    _ => span,
+                };
+                self.error(code, message, span);
+            }
+        }
+        for (index, (argument, argument_type)) in arguments.iter().zip(&argument_types).enumerate()
+        {
+            if matches!(argument, Node::SpreadElement(_)) {
+                break;
+            }
+            let Some(param) = parameter_type(&signature, index) else {
+                break;
+            };
+            if !self.is_assignable(argument_type, &param) {
+                let message = format!(
+                    "Argument of type '{}' is not assignable to parameter of type '{}'.",
+                    displayed(argument_type, &param),
+                    param
+                );
+                self.error(2345, message, span_or(argument, span));
+            }
+        }
+        (**return_type).clone()
+    }
+
+    /// `invoke` with the first of `signatures` the arguments are right for, as a call of an
+    /// overloaded function is checked. The arguments are checked again for each overload
+    /// tried, and when none fits, the call is reported with what was wrong for the last.
+    fn invoke_overloads(
+        &mut self,
+        signatures: &[Type],
+        type_arguments: &[Type],
+        arguments: &'a [Node],
+        span: Span,
+    ) -> Type {
+        if let [signature] = signatures {
+            return self.invoke(signature, type_arguments, arguments, span);
+        }
+        let errors = self.errors.len();
+        // What checking the arguments against an overload that doesn't fit left behind
+        let saved = (
+            self.functions.clone(),
+            self.inferred.clone(),
+            self.flow_types.clone(),
+        );
+        let mut failures = Vec::new();
+        for signature in signatures {
+            let returned = self.invoke(signature, type_arguments, arguments, span);
+            if self.errors.len() == errors {
+                return returned;
+            }
+            failures = self.errors.split_off(errors);
+            (self.functions, self.inferred, self.flow_types) = saved.clone();
+        }
+        let mut error =
+            CompilerError::new("No overload matches this call.".to_string(), span).with_code(2769);
+        for failure in failures {
+            let message = format!(
+                "The last overload gave the following error: {}",
+                failure.message
+            );
+            error = error.with_related(
+                message,
+                Span {
+                    row: failure.row,
+                    col: failure.col,
+                    file: failure.file,
+                },
+            );
+        }
+        self.errors.push(error);
+        Type::Any
+    }
+
+    fn argument(&mut self, argument: &'a Node, context: Option<&Type>) -> Type {
+        match argument {
+            Node::SpreadElement(argument) => {
+                self.expression(argument, None);
+                Type::Any
+            }
+            argument => self.expression(argument, context),
+        }
+    }
+
+    fn assertion(&mut self, expression: &'a Node, annotation: &'a Node) -> Type {
+        if let Node::KeywordType(TokenType::Const) = annotation {
+            self.expression(expression, None);
+            return match const_assertion(expression, self.table.shapes()) {
+                Ok(asserted) => asserted,
+                Err(message) => {
+                    self.error(1355, message, expression.span());
+                    Type::Any
+                }
+            };
+        }
+        let target = self.lowering.lower(annotation, self.scope);
+        // A literal may be asserted to another literal of its primitive
+        let source = self.expression(expression, None).widened();
+        if let Err(message) = type_assertion(&source, &target) {
+            self.error(2352, message, expression.span());
+        }
+        target
+    }
+
+    /// The type `reference` has where it is read, narrowed from `declared` by the
+    /// conditions and assignments control passes on the way there.
+    fn narrowed(&mut self, reference: &'a Node, declared: Type) -> Type {
+        let Some(flow) = self.flow.reference(reference) else {
+            return declared;
+        };
+        if !self.is_narrowable(reference) {
+            return declared;
+        }
+        self.flow_type(reference, &declared, flow, &mut HashMap::new())
+            .unwrap_or(declared)
+    }
+
+    /// Whether `reference` is a variable, a parameter, `this`, or a property of one.
+    fn is_narrowable(&self, reference: &Node) -> bool {
+        match unwrapped(reference) {
+            identifier @ Node::Identifier(..) => {
+                self.table.symbol_of(identifier).is_some_and(|symbol| {
+                    self.table.symbol(symbol).flags.intersects(
+                        SymbolFlags::VARIABLE
+                            | SymbolFlags::BLOCK_SCOPED_VARIABLE
+                            | SymbolFlags::PARAMETER,
+                    )
+                })
+            }
+            Node::ThisExpression => true,
+            Node::MemberExpression {
+                object,
+                property,
+                computed,
+                ..
+            } => key_name(property, *computed).is_some() && self.is_narrowable(object),
+            _ => false,
+        }
+    }
+
+    /// The type of `reference` at `flow`, or `None` where control can't get. Loops are
+    /// followed around once: a loop whose type is still being worked out adds nothing.
+    fn flow_type(
+        &mut self,
+        reference: &'a Node,
+        declared: &Type,
+        flow: FlowId,
+        labels: &mut HashMap<FlowId, (Option<Type>, Option<usize>)>,
+    ) -> Option<Type> {
+        match self.flow.node(flow).clone() {
+            FlowNode::Start(Some(container)) if self.is_never_reassigned(reference) => {
+                self.flow_type(reference, declared, container, labels)
+            }
+            FlowNode::Start(_) => Some(declared.clone()),
+            FlowNode::Unreachable => None,
+            FlowNode::Label { antecedents, .. } => {
+                let key = self.reference_key(reference).map(|key| (flow, key));
+                let cached = key
+                    .as_ref()
+                    .and_then(|key| self.flow_types.get(key))
+                    .and_then(|known| known.iter().find(|(from, _)| from == declared));
+                if let Some((_, known)) = cached {
+                    return known.clone();
+                }
+                if let Some((known, depends)) = labels.get(&flow) {
+                    self.incomplete = self.incomplete.into_iter().chain(*depends).min();
+                    return known.clone();
+                }
+                let depth = self.joining;
+                self.joining += 1;
+                labels.insert(flow, (None, Some(depth)));
+                let outer = self.incomplete.take();
+                let types: Vec<Type> = antecedents
+                    .iter()
+                    .filter_map(|&antecedent| {
+                        self.flow_type(reference, declared, antecedent, labels)
+                    })
+                    .collect();
+                let joined = (!types.is_empty()).then(|| Type::union(types));
+                self.joining -= 1;
+                let depends = self.incomplete.filter(|&outermost| outermost < depth);
+                self.incomplete = outer.into_iter().chain(depends).min();
+                labels.insert(flow, (joined.clone(), depends));
+                // What a loop still being worked out gave may differ when it's reached afresh
+                if let (Some(key), None) = (key, depends) {
+                    let known = self.flow_types.entry(key).or_default();
+                    known.push((declared.clone(), joined.clone()));
+                }
+                joined
+            }
+            FlowNode::Assignment { target, antecedent } => {
+                if is_same_reference(self.table, reference, target) {
+                    return Some(match self.assigned.get(&(target as *const Node)) {
+                        Some(assigned) => by_assignment(declared, assigned),
+                        None => declared.clone(),
+                    });
+                }
+                if is_part_of(self.table, reference, target) {
+                    return Some(declared.clone());
+                }
+                self.flow_type(reference, declared, antecedent, labels)
+            }
+            FlowNode::Condition {
+                condition,
+                assume_true,
+                antecedent,
+            } => {
+                let t = self.flow_type(reference, declared, antecedent, labels)?;
+                Some(self.narrow(t, reference, condition, assume_true))
+            }
+            FlowNode::SwitchClause {
+                switch,
+                clause,
+                antecedent,
+            } => {
+                let t = self.flow_type(reference, declared, antecedent, labels)?;
+                Some(self.narrow_by_switch(t, reference, switch, clause))
+            }
+            FlowNode::Call { call, antecedent } => {
+                if self.never_returns(call) {
+                    return None;
+                }
+                let t = self.flow_type(reference, declared, antecedent, labels)?;
+                Some(self.narrow_by_assertion(t, reference, call))
+            }
+        }
+    }
+
+    /// What identifies `reference` across the flow graph: its symbol and the properties read
+    /// of it.
+    fn reference_key(&self, reference: &Node) -> Option<String> {
+        match unwrapped(reference) {
+            identifier @ Node::Identifier(..) => {
+                let symbol = self.table.symbol_of(identifier)?;
+                Some(symbol.0.to_string())
+            }
+            Node::ThisExpression => Some("this".to_string()),
+            Node::MemberExpression {
+                object,
+                property,
+                computed,
+                ..
+            } => {
+                let object = self.reference_key(object)?;
+                Some(format!("{}.{}", object, key_name(property, *computed)?))
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether `reference` is a constant, or a `let` or parameter nothing assigns to after
+    /// its declaration, whose narrowing holds inside the functions written where it is
+    /// narrowed.
+    fn is_never_reassigned(&self, reference: &Node) -> bool {
+        let reference = unwrapped(reference);
+        if !matches!(reference, Node::Identifier(..)) {
+            return false;
+        }
+        self.table.symbol_of(reference).is_some_and(|id| {
+            let symbol = self.table.symbol(id);
+            symbol.is_constant
+                || symbol
+                    .flags
+                    .intersects(SymbolFlags::BLOCK_SCOPED_VARIABLE | SymbolFlags::PARAMETER)
+                    && !self.reassigned.contains(&id)
+        })
+    }
+
+    /// Narrows `t`, the type of `reference`, by `condition` being true or false.
+    fn narrow(
+        &mut self,
+        t: Type,
+        reference: &'a Node,
+        condition: &'a Node,
+        assume_true: bool,
+    ) -> Type {
+        let table = self.table;
+        let same = |node: &Node| is_same_reference(table, reference, node);
+        match condition {
+            Node::BinaryExpression {
+                left,
+                operator,
+                right,
+            } => match operator.as_str() {
+                "===" | "!==" | "==" | "!=" => {
+                    let assume_equal = assume_true != operator.starts_with('!');
+                    let loose = operator.len() == 2;
+                    for (operand, value) in [(left, right), (right, left)] {
+                        if let Node::UnaryExpression { operator, argument } = operand.as_ref() {
+                            if let (
+                                "typeof",
+                                Node::Literal {
+                                    token_type: TokenType::StringLiteral,
+                                    value,
+                                    ..
+                                },
+                            ) = (operator.as_str(), value.as_ref())
+                            {
+                                if same(argument) {
+                                    return by_typeof(&t, value.trim_matches('"'), assume_equal);
+                                }
+                            }
+                            continue;
+                        }
+                        if same(operand) {
+                            let value = self.value_type(value);
+                            return by_equality(&t, &value, loose, assume_equal);
+                        }
+                        if let Some(name) = property_of(table, operand, reference) {
+                            let value = self.value_type(value);
+                            return by_discriminant(&t, name, |property| {
+                                by_equality(property, &value, loose, assume_equal)
+                            });
+                        }
+                    }
+                    t
+                }
+                "instanceof" if same(left) => match self.instance_type(right) {
+                    Some(instance) => to_subtype(&t, &instance, assume_true),
+                    None => t,
+                },
+                "in" if same(right) => match left.as_ref() {
+                    Node::Literal {
+                        token_type: TokenType::StringLiteral,
+                        value,
+                        ..
+                    } => by_property(&t, value.trim_matches('"'), assume_true),
+                    _ => t,
+                },
+                _ => t,
+            },
+            Node::CallExpression {
+                callee, arguments, ..
+            } => {
+                let Some(Type::Predicate {
+                    parameter,
+                    asserts: false,
+                    guarded: Some(guarded),
+                }) = self.returns.get(&(condition as *const Node))
+                else {
+                    return t;
+                };
+                match guarded_argument(*parameter, callee, arguments) {
+                    Some(argument) if same(argument) => to_subtype(&t, guarded, assume_true),
+                    _ => t,
+                }
+            }
+            condition if same(condition) => by_truthiness(&t, assume_true),
+            condition => match property_of(table, condition, reference) {
+                Some(name) => {
+                    by_discriminant(&t, name, |property| by_truthiness(property, assume_true))
+                }
+                None => t,
+            },
+        }
+    }
+
+    /// Narrows `t`, the type of `reference`, into the case of `switch` at `clause`, or past
+    /// every case for `None`. A `default` case is reached when none of the others match.
+    fn narrow_by_switch(
+        &mut self,
+        t: Type,
+        reference: &'a Node,
+        switch: &'a Node,
+        clause: Option<usize>,
+    ) -> Type {
+        let Node::SwitchStatement {
+            discriminant,
+            cases,
+        } = switch
+        else {
+            return t;
+        };
+        if let Some(Some(test)) = clause.map(|index| &cases[index].test) {
+            return self.narrow_by_case(t, reference, discriminant, test, true);
+        }
+        cases
+            .iter()
+            .filter_map(|case| case.test.as_deref())
+            .fold(t, |t, test| {
+                self.narrow_by_case(t, reference, discriminant, test, false)
+            })
+    }
+
+    fn narrow_by_case(
+        &mut self,
+        t: Type,
+        reference: &'a Node,
+        discriminant: &'a Node,
+        test: &'a Node,
+        assume_true: bool,
+    ) -> Type {
+        let table = self.table;
+        if let Node::UnaryExpression { operator, argument } = discriminant {
+            return match (operator.as_str(), test) {
+                (
+                    "typeof",
+                    Node::Literal {
+                        token_type: TokenType::StringLiteral,
+                        value,
+                        ..
+                    },
+                ) if is_same_reference(table, reference, argument) => {
+                    by_typeof(&t, value.trim_matches('"'), assume_true)
+                }
+                _ => t,
+            };
+        }
+        if is_same_reference(table, reference, discriminant) {
+            let value = self.value_type(test);
+            return by_equality(&t, &value, false, assume_true);
+        }
+        match property_of(table, discriminant, reference) {
+            Some(name) => {
+                let value = self.value_type(test);
+                by_discriminant(&t, name, |property| {
+                    by_equality(property, &value, false, assume_true)
+                })
+            }
+            None => t,
+        }
+    }
+
+    /// Narrows `t`, the type of `reference`, past a call of an assertion function.
+    fn narrow_by_assertion(&mut self, t: Type, reference: &'a Node, call: &'a Node) -> Type {
+        let Node::CallExpression {
+            callee, arguments, ..
+        } = call
+        else {
+            return t;
+        };
+        let Some(Type::Predicate {
+            parameter,
+            asserts: true,
+            guarded,
+        }) = self.returns.get(&(call as *const Node)).cloned()
+        else {
+            return t;
+        };
+        let Some(argument) = guarded_argument(parameter, callee, arguments) else {
+            return t;
+        };
+        match guarded {
+            Some(guarded) if is_same_reference(self.table, reference, argument) => {
+                to_subtype(&t, &guarded, true)
+            }
+            Some(_) => t,
+            // `asserts x`: the argument was a true condition
+            None => self.narrow(t, reference, argument, true),
+        }
+    }
+
+    /// The type of a value a reference is compared to. Only literals, enum members and the
+    /// declared types of names tell anything.
+    fn value_type(&mut self, node: &'a Node) -> Type {
+        match node {
+            Node::Literal {
+                token_type, value, ..
+            } => literal_type(token_type, value),
+            Node::UnaryExpression { operator, argument } if operator == "-" => {
+                match argument.as_ref() {
+                    Node::Literal {
+                        token_type: TokenType::NumberLiteral | TokenType::BigInt,
+                        value,
+                        ..
+                    } => Type::Literal(format!("-{}", value)),
+                    _ => Type::Any,
+                }
+            }
+            Node::Identifier(..) => match self.table.symbol_of(node) {
+                Some(symbol) => self.symbol_type(symbol),
+                None => Type::Any,
+            },
+            Node::MemberExpression {
+                object,
+                property,
+                computed: false,
+                ..
+            } => {
+                let enum_type = self
+                    .table
+                    .symbol_of(object)
+                    .filter(|&symbol| self.table.symbol(symbol).flags.contains(SymbolFlags::ENUM))
+                    .and_then(|symbol| self.table.symbol(symbol).declared_type.clone());
+                match (enum_type, property.as_ref()) {
+                    (Some(Type::Enum(name)), Node::Identifier(member, _)) => {
+                        Type::EnumMember(name, member.clone())
+                    }
+                    _ => Type::Any,
+                }
+            }
+            _ => Type::Any,
+        }
+    }
+
+    /// The type of the instances of the class or built-in constructor `instanceof` tests
+    /// for.
+    fn instance_type(&self, class: &Node) -> Option<Type> {
+        let Node::Identifier(name, _) = class else {
+            return None;
+        };
+        let Some(id) = self.table.symbol_of(class) else {
+            return Some(Type::Custom(name.clone(), Vec::new()));
+        };
+        if self.table.declaring_scope(id) == SymbolTable::BUILTINS {
+            return builtin_instance_type(name);
+        }
+        let symbol = self.table.symbol(id);
+        if symbol.flags.contains(SymbolFlags::CLASS) {
+            symbol.declared_type.clone()
+        } else {
+            None
+        }
+    }
+
+    fn never_returns(&self, call: &Node) -> bool {
+        self.returns.get(&(call as *const Node)) == Some(&Type::Never)
+    }
+
+    /// Whether control can get to `flow`. It can't past a call of a function returning
+    /// `never`, nor past a switch without a `default` whose cases cover every value of what
+    /// it switches on.
+    fn is_reachable(&mut self, flow: FlowId, visited: &mut HashSet<FlowId>) -> bool {
+        match self.flow.node(flow).clone() {
+            FlowNode::Start(_) => true,
+            FlowNode::Unreachable => false,
+            FlowNode::Label { antecedents, .. } => {
+                visited.insert(flow)
+                    && antecedents
+                        .iter()
+                        .any(|&antecedent| self.is_reachable(antecedent, visited))
+            }
+            FlowNode::Assignment { antecedent, .. }
+            | FlowNode::Condition { antecedent, .. }
+            | FlowNode::SwitchClause {
+                clause: Some(_),
+                antecedent,
+                ..
+            } => self.is_reachable(antecedent, visited),
+            FlowNode::SwitchClause {
+                switch,
+                clause: None,
+                antecedent,
+            } => self.is_reachable(antecedent, visited) && !self.is_exhaustive(switch, antecedent),
+            FlowNode::Call { call, antecedent } => {
+                !self.never_returns(call) && self.is_reachable(antecedent, visited)
+            }
+        }
+    }
+
+    /// Whether the cases of `switch`, reached from `flow`, cover every value of what it
+    /// switches on.
+    fn is_exhaustive(&mut self, switch: &'a Node, flow: FlowId) -> bool {
+        let Node::SwitchStatement { discriminant, .. } = switch else {
+            return false;
+        };
+        let reference = match discriminant.as_ref() {
+            Node::UnaryExpression { operator, argument } if operator == "typeof" => argument,
+            discriminant => discriminant,
+        };
+        match self.reference_type(reference, flow) {
+            Some(t) => self.narrow_by_switch(t, reference, switch, None) == Type::Never,
+            None => false,
+        }
+    }
+
+    /// The type of `reference` at `flow`, for a name or a property of one.
+    fn reference_type(&mut self, reference: &'a Node, flow: FlowId) -> Option<Type> {
+        let declared = match unwrapped(reference) {
+            identifier @ Node::Identifier(..) => {
+                let symbol = self.table.symbol_of(identifier)?;
+                self.symbol_type(symbol)
+            }
+            Node::MemberExpression {
+                object,
+                property,
+                computed,
+                ..
+            } => {
+                let object = self.reference_type(object, flow)?;
+                object.property_type(key_name(property, *computed)?)?
+            }
+            _ => return None,
+        };
+        if !self.is_narrowable(reference) {
+            return Some(declared);
+        }
+        let narrowed = self.flow_type(reference, &declared, flow, &mut HashMap::new());
+        Some(narrowed.unwrap_or(declared))
+    }
+
+    /// Reports a function with a return type that needs a value when control can reach the
+    /// end of its body.
+    fn check_end(&mut self, function: &'a Node, declared: &Type, returns: bool, span: Span) {
+        let Some(end) = self.flow.end(function) else {
+            return;
+        };
+        if !self.is_reachable(end, &mut HashSet::new()) {
+            return;
+        }
+        let accepts_undefined =
+            |t: &Type| matches!(t, Type::Any | Type::Unknown | Type::Void | Type::Undefined);
+        let (code, message) = match declared {
+            Type::Never => (
+                2534,
+                "A function returning 'never' cannot have a reachable end point.",
+            ),
+            Type::Union(types) if types.iter().any(accepts_undefined) => return,
+            t if accepts_undefined(t) => return,
+            _ if !returns => (
+                2355,
+                "A function whose declared type is neither 'undefined', 'void', nor 'any' must return a value.",
+            ),
+            _ => (
+                2366,
+                "Function lacks ending return statement and return type does not include 'undefined'.",
+            ),
+        };
+        self.error(code, message.to_string(), span);
+    }
+
+    /// Whether `source` is assignable to `target`, where a type parameter in scope may be
+    /// used as its constraint.
+    fn is_assignable(&self, source: &Type, target: &Type) -> bool {
+        if source.is_assignable_to(target) {
+            return true;
+        }
+        let constraints: HashMap<String, Type> = self
+            .constraints
+            .iter()
+            .flatten()
+            .map(|(name, constraint)| (name.clone(), constraint.clone()))
+            .collect();
+        let apparent = source.substitute(&constraints);
+        apparent != *source && apparent.is_assignable_to(target)
+    }
+
+    fn check_assignable(&mut self, source: &Type, target: &Type, span: Span) {
+        if !self.is_assignable(source, target) {
+            let message = format!(
+                "Type '{}' is not assignable to type '{}'.",
+                displayed(source, target),
+                target
+            );
+            self.error(2322, message, span);
+        }
+    }
+
+    fn error(&mut self, code: u32, message: String, span: Span) {
+        self.errors
+            .push(CompilerError::new(message, span).with_code(code));
+    }
+}
+
+/// The type of `left operator right`, or of a unary operator applied to `left` and `right`
+/// alike.
+fn binary_type(operator: &str, left: &Type, right: &Type) -> Type {
+    let (left, right) = (left.widened(), right.widened());
+    match operator {
+        "+" => match (&left, &right) {
+            (Type::String, _) | (_, Type::String) => Type::String,
+            (Type::Number, Type::Number) => Type::Number,
+            (Type::BigInt, Type::BigInt) => Type::BigInt,
+            _ => Type::Any,
+        },
+        "-" | "*" | "/" | "%" | "**" | "<<" | ">>" | ">>>" | "&" | "|" | "^" | "~" => {
+            if left == Type::BigInt && right == Type::BigInt {
+                Type::BigInt
+            } else {
+                Type::Number
+            }
+        }
+        "," => right,
+        _ => Type::Boolean,
+    }
+}
+
+/// What a value of type `t` can be when it is falsy, which `a && b` gives when `a` is.
+pub fn falsy_part(t: &Type) -> Type {
+    match t {
+        Type::Union(types) => Type::union(types.iter().map(falsy_part).collect()),
+        Type::Boolean => Type::Literal("false".to_string()),
+        Type::String => Type::Literal("\"\"".to_string()),
+        Type::Number => Type::Literal("0".to_string()),
+        Type::BigInt => Type::Literal("0n".to_string()),
+        Type::Literal(value) if is_falsy_literal(value) => t.clone(),
+        Type::Literal(_) => Type::Never,
+        Type::Null | Type::Undefined | Type::Void | Type::Any | Type::Unknown => t.clone(),
+        Type::Param(_) | Type::Enum(_) | Type::EnumMember(..) => t.clone(),
+        _ => Type::Never,
+    }
+}
+
+/// What a value of type `t` can be when it is truthy, which `a || b` gives when `a` is.
+pub fn truthy_part(t: &Type) -> Type {
+    match t {
+        Type::Union(types) => Type::union(types.iter().map(truthy_part).collect()),
+        Type::Boolean => Type::Literal("true".to_string()),
+        Type::Literal(value) if is_falsy_literal(value) => Type::Never,
+        Type::Null | Type::Undefined | Type::Void => Type::Never,
+        _ => t.clone(),
+    }
+}
+
+fn is_falsy_literal(value: &str) -> bool {
+    matches!(value, "false" | "0" | "-0" | "0n" | "\"\"")
+}
+
+/// The type a value of type `t` resolves to when awaited.
+fn awaited(t: &Type) -> Type {
+    match t {
+        Type::Custom(name, arguments) if name == "Promise" || name == "PromiseLike" => {
+            arguments.first().map(awaited).unwrap_or(Type::Any)
+        }
+        Type::Union(types) => Type::union(types.iter().map(awaited).collect()),
+        _ => t.clone(),
+    }
+}
+
+/// The type of the parameter the argument at `index` is passed to.
+fn parameter_type(signature: &Type, index: usize) -> Option<Type> {
+    let Type::Function { params, rest, .. } = signature else {
+        return None;
+    };
+    match params.get(index) {
+        Some(param) => Some(param.clone()),
+        None => rest
+            .as_ref()
+            .map(|rest| rest.element_type(None).unwrap_or(Type::Any)),
+    }
+}
+
+/// A generic signature with its type parameters standing for anything.
+fn erase_type_params(signature: &Type) -> Type {
+    let Type::Function {
+        type_params,
+        params,
+        required,
+        rest,
+        return_type,
+        method,
+    } = signature
+    else {
+        return signature.clone();
+    };
+    let mapping: HashMap<String, Type> = type_params
+        .iter()
+        .map(|type_param| (type_param.name.clone(), Type::Any))
+        .collect();
+    Type::Function {
+        type_params: Vec::new(),
+        params: params.iter().map(|t| t.substitute(&mapping)).collect(),
+        required: *required,
+        rest: rest.as_ref().map(|t| Box::new(t.substitute(&mapping))),
+        return_type: Box::new(return_type.substitute(&mapping)),
+        method: *method,
+    }
+}
+
+/// The function type in `expected` that a function written in its place is typed by.
+fn contextual_signature(expected: &Type) -> Option<&Type> {
+    match expected {
+        Type::Function { .. } => Some(expected),
+        Type::Union(types) => {
+            let mut signatures = types.iter().filter_map(contextual_signature);
+            let signature = signatures.next()?;
+            signatures.next().is_none().then_some(signature)
+        }
+        _ => None,
+    }
+}
+
+/// The first member of `expected`, or `expected` itself, that `select` picks something from.
+fn expected_member<T>(expected: &Type, select: impl Fn(&Type) -> Option<T>) -> Option<T> {
+    match expected {
+        Type::Union(types) => types.iter().find_map(select),
+        _ => select(expected),
+    }
+}
+
+/// Whether an argument's type depends on the parameter it is passed to: a function with
+/// parameters that aren't annotated.
+fn is_context_sensitive(argument: &Node) -> bool {
+    match argument {
+        Node::ArrowFunctionExpression { params, .. } | Node::FunctionDeclaration { params, .. } => {
+            params.iter().any(|param| param.type_annotation.is_none())
+        }
+        _ => false,
+    }
+}
+
+/// Whether every property `t` has is known, so looking up any other is an error.
+fn has_known_members(t: &Type) -> bool {
+    match t {
+        Type::Shape(_) => true,
+        Type::Union(types) | Type::Intersection(types) => types.iter().all(has_known_members),
+        _ => false,
+    }
+}
+
+/// Sets the property `name` of an object literal's type, replacing an earlier one.
+fn set_property(object: &mut ObjectType, name: &str, value_type: Type, optional: bool) {
+    object.properties.retain(|property| property.name != name);
+    object.properties.push(PropertyType {
+        name: name.to_string(),
+        value_type,
+        optional,
+        readonly: false,
+    });
+}
+
+fn has_literals(t: &Type) -> bool {
+    match t {
+        Type::Literal(_) | Type::EnumMember(..) => true,
+        Type::Union(types)
+        | Type::Intersection(types)
+        | Type::Tuple {
+            elements: types, ..
+        }
+        | Type::Custom(_, types) => types.iter().any(has_literals),
+        Type::Array(element) => has_literals(element),
+        _ => false,
+    }
+}
+
+/// `source` as an error shows it: a literal that isn't compared with other literals is
+/// shown as its primitive, as tsc does.
+fn displayed(source: &Type, target: &Type) -> Type {
+    if has_literals(target) {
+        source.clone()
+    } else {
+        source.widened()
+    }
+}
+
+/// The argument a type guard or assertion tells about: the one at `parameter`, or for
+/// `None` the object of a method call.
+fn guarded_argument<'n>(
+    parameter: Option<usize>,
+    callee: &'n Node,
+    arguments: &'n [Node],
+) -> Option<&'n Node> {
+    match parameter {
+        Some(index) => arguments.get(index),
+        None => match unwrapped(callee) {
+            Node::MemberExpression { object, .. } => Some(object),
+            _ => None,
+        },
+    }
+}
+
+/// `A.B` for the namespace reference `node`.
+fn qualified_name(node: &Node) -> String {
+    match node {
+        Node::MemberExpression {
+            object, property, ..
+        } => format!("{}.{}", qualified_name(object), qualified_name(property)),
+        Node::Identifier(name, _) => name.clone(),
+        _ => String::new(),
+    }
+}
+
+/// Where `node` is, or `fallback` if it has no position of its own, like a node that a
+/// lowering pass built.
+fn span_or(node: &Node, fallback: Span) -> Span {
+    match node.span() {
+        span if span == Span::default() => fallback,
+        span => span,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binder::bind;
+    use crate::parser::Parser;
+    use crate::tokenizer::Tokenizer;
+    use crate::type_lowering::resolve_types;
+
+    /// The codes of the errors resolving and checking the types of `source` gives.
+    fn errors(source: &str) -> Vec<u32> {
+        let tokens = Tokenizer::new(source.to_string()).tokenize();
+        let program = Parser::new(tokens).parse().result.expect("parses");
+        let mut table = bind(&program).result.expect("binds");
+        let mut errors = resolve_types(&program, &mut table);
+        errors.extend(check_types(&program, &table));
+        errors.iter().map(|error| error.code.unwrap_or(0)).collect()
+    }
+
+    #[test]
+    fn calls_pick_the_first_overload_that_fits() {
+        let overloads = "function over(x: string): string;
+            function over(x: number): number;
+            function over(x: any): any { return x; }";
+        assert_eq!(
+            errors(&format!(
+                "{} const a: number = over(1); const b: string = over('b');",
+                overloads
+            )),
+            []
+        );
+        assert_eq!(errors(&format!("{} over(true);", overloads)), [2769]);
+        // The implementation's signature isn't one of them
+        assert_eq!(errors(&format!("{} const c: any = over;", overloads)), []);
+    }
+
+    #[test]
+    fn tuple_literals_may_leave_out_optional_elements() {
+        assert_eq!(errors("const t: [number, string?] = [1];"), []);
+        assert_eq!(
+            errors("const t: [number, string?] = [1, 'a', 'b'];"),
+            [2322]
+        );
+        assert_eq!(
+            errors("declare const t: [number, string?]; const s: string = t[1];"),
+            [2322]
+        );
+    }
+
+    #[test]
+    fn tuple_literals_fill_rest_elements_with_their_type() {
+        assert_eq!(
+            errors("const t: [number, ...string[]] = [1, 'a', 'b'];"),
+            []
+        );
+        assert_eq!(errors("const t: [number, ...string[]] = [1, 2];"), [2322]);
+        assert_eq!(errors("const t: [number, ...string[]] = [];"), [2322]);
+    }
+
+    #[test]
+    fn methods_keep_their_overloads() {
+        let source =
+            "class C { m(x: string): string; m(x: number): number; m(x: any): any { return x; } }
+            interface I { f(x: string): string; f(x: number): number; }
+            declare const i: I;
+            const a: number = new C().m(1);
+            const b: number = i.f(2);";
+        assert_eq!(errors(source), []);
+        assert_eq!(errors("interface I { f(x: string): void; f(x: number): void; } declare const i: I; i.f(true);"), [2769]);
+    }
+
+    #[test]
+    fn instanceof_narrows_by_built_in_constructors() {
+        assert_eq!(
+            errors(
+                "declare const d: Date | string;
+                if (d instanceof Date) { const x: Date = d; } else { const s: string = d; }"
+            ),
+            []
+        );
+        assert_eq!(
+            errors(
+                "declare const m: Map<string, number> | number[];
+                if (m instanceof Map) { const x: Map<string, number> = m; }
+                else { const a: number[] = m; }"
+            ),
+            []
+        );
+        assert_eq!(
+            errors(
+                "declare const d: Date | string; if (d instanceof Date) { const s: string = d; }"
+            ),
+            [2322]
+        );
+    }
+
+    #[test]
+    fn closures_keep_the_narrowing_of_names_never_reassigned() {
+        assert_eq!(
+            errors(
+                "function f(x: string | number) {
+                    if (typeof x === 'string') { () => { const s: string = x; }; }
+                }"
+            ),
+            []
+        );
+        assert_eq!(
+            errors(
+                "declare const v: string | number; let y = v;
+                if (typeof y === 'string') { () => { const s: string = y; }; }"
+            ),
+            []
+        );
+        // Where it is assigned again, the closure may run after that
+        assert_eq!(
+            errors(
+                "function f(x: string | number) {
+                    if (typeof x === 'string') { () => { const s: string = x; }; }
+                    x = 1;
+                }"
+            ),
+            [2322]
+        );
+    }
+
+    #[test]
+    fn getters_of_object_literals_without_a_setter_are_read_only() {
+        assert_eq!(
+            errors("const o = { get x() { return 1; } }; o.x = 2;"),
+            [2540]
+        );
+        assert_eq!(
+            errors("const o = { get x() { return 1; }, set x(v: number) {} }; o.x = 2;"),
+            []
+        );
+    }
+
+    #[test]
+    fn constraints_are_checked_at_the_argument_unless_type_arguments_are_given() {
+        let generic = "function f<T extends string>(x: T): T { return x; }";
+        assert_eq!(errors(&format!("{} f(1);", generic)), [2345]);
+        assert_eq!(errors(&format!("{} f<number>(1);", generic)), [2344]);
+    }
+}
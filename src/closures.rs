@@ -0,0 +1,590 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::binder;
+use crate::error_handling::Span;
+use crate::parser::{CatchClause, Node, Parameter, VariableKind};
+use crate::symboltable::{ScopeId, SymbolTable};
+
+/// Where the locals of each function live, for codegen.
+///
+/// Functions are listed in the order they appear in the source, starting with the program
+/// itself. A local that no nested function refers to stays on the stack; the ones in a
+/// function's `environment` are heap-allocated so closures can share them. Module-level
+/// declarations are static and never captured.
+#[derive(Debug, Default)]
+pub struct CaptureAnalysis {
+    pub functions: Vec<FunctionCaptures>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FunctionCaptures {
+    pub name: Option<String>, // None for arrow functions
+    pub parent: Option<usize>,
+    pub is_arrow: bool,
+    /// Locals of this function that nested functions capture. `this` is one when an arrow
+    /// function uses it.
+    pub environment: Vec<CapturedBinding>,
+    /// Variables of enclosing functions this function or its nested functions use, which
+    /// it keeps a reference to the environments of.
+    pub free_variables: Vec<String>,
+}
+
+impl FunctionCaptures {
+    /// Arrow functions have no `this` of their own and use the enclosing function's.
+    pub fn captures_this(&self) -> bool {
+        self.free_variables.iter().any(|name| name == "this")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CapturedBinding {
+    pub name: String,
+    /// A `let` or `const` in a loop head. Each iteration gets a fresh copy, so closures
+    /// created in different iterations see different bindings; a `for (;;)` loop
+    /// initializes the copy from the previous iteration.
+    pub per_iteration: bool,
+}
+
+/// Binds `program` and follows every reference to the scope declaring it. Scopes the binder
+/// gives a function, block, loop or switch belong to the function being analyzed when they
+/// are reached; class field initializers together make up one more function, named after
+/// the class, and so does each namespace declaration.
+pub fn analyze_captures(program: &Node) -> CaptureAnalysis {
+    // Programs with binding errors are still analyzed; the checker reports the errors
+    let table = binder::bind(program).result.unwrap_or_default();
+    let mut analyzer = Analyzer {
+        table,
+        scopes: HashMap::new(),
+        exports: HashSet::new(),
+        functions: vec![FunctionCaptures {
+            name: None,
+            parent: None,
+            is_arrow: false,
+            environment: Vec::new(),
+            free_variables: Vec::new(),
+        }],
+        current: 0,
+    };
+    if let Node::Program(body) = program {
+        for statement in body {
+            analyzer.visit(statement);
+        }
+    }
+    CaptureAnalysis {
+        functions: analyzer.functions,
+    }
+}
+
+/// What the analysis knows of a scope of the symbol table.
+#[derive(Clone, Copy)]
+struct ScopeInfo {
+    function: usize,
+    per_iteration: bool,
+}
+
+struct Analyzer {
+    table: SymbolTable,
+    scopes: HashMap<ScopeId, ScopeInfo>, // of the scopes reached so far
+    exports: HashSet<ScopeId>,           // of the namespaces reached so far
+    functions: Vec<FunctionCaptures>,
+    current: usize,
+}
+
+impl Analyzer {
+    /// Records that the scope `node` introduced, if any, belongs to the current function.
+    fn enter(&mut self, node: &Node, per_iteration: bool) {
+        if let Some(scope) = self.table.scope_of(node) {
+            let info = ScopeInfo {
+                function: self.current,
+                per_iteration,
+            };
+            self.scopes.insert(scope, info);
+            // The exports of a namespace live in a scope around its own
+            if let (Node::NamespaceDeclaration { .. }, Some(exports)) =
+                (node, self.table.scope(scope).parent)
+            {
+                self.exports.insert(exports);
+            }
+        }
+    }
+
+    fn reference(&mut self, identifier: &Node) {
+        let Some(symbol) = self.table.symbol_of(identifier) else {
+            return; // a global, or not a reference
+        };
+        let declared_in = self.table.declaring_scope(symbol);
+        // Namespace exports are properties of the namespace's object, not variables
+        if declared_in.0 <= SymbolTable::GLOBAL.0 || self.exports.contains(&declared_in) {
+            return;
+        }
+        let scope = self
+            .table
+            .ancestors(declared_in)
+            .find_map(|scope| self.scopes.get(&scope).copied())
+            .unwrap_or(ScopeInfo {
+                function: 0,
+                per_iteration: false,
+            });
+        let name = self.table.symbol(symbol).name.clone();
+        self.capture(name, scope);
+    }
+
+    /// `this` belongs to the closest function that isn't an arrow function.
+    fn this(&mut self) {
+        let mut function = self.current;
+        while self.functions[function].is_arrow {
+            match self.functions[function].parent {
+                Some(parent) => function = parent,
+                None => break,
+            }
+        }
+        if function != 0 {
+            let scope = ScopeInfo {
+                function,
+                per_iteration: false,
+            };
+            self.capture("this".to_string(), scope);
+        }
+    }
+
+    fn capture(&mut self, name: String, scope: ScopeInfo) {
+        let owner = scope.function;
+        if owner == self.current {
+            return;
+        }
+        let binding = CapturedBinding {
+            name: name.clone(),
+            per_iteration: scope.per_iteration,
+        };
+        if !self.functions[owner].environment.contains(&binding) {
+            self.functions[owner].environment.push(binding);
+        }
+        // Every function between the use and the declaration passes the binding along
+        let mut function = Some(self.current);
+        while let Some(index) = function.filter(|index| *index != owner) {
+            let captures = &mut self.functions[index];
+            if !captures.free_variables.contains(&name) {
+                captures.free_variables.push(name.clone());
+            }
+            function = captures.parent;
+        }
+    }
+
+    /// `node` is the declaration the binder gave the function's scope to, if it has one.
+    fn function(
+        &mut self,
+        node: Option<&Node>,
+        name: Option<String>,
+        params: &[Parameter],
+        body: &Node,
+        is_arrow: bool,
+    ) {
+        self.functions.push(FunctionCaptures {
+            name,
+            parent: Some(self.current),
+            is_arrow,
+            environment: Vec::new(),
+            free_variables: Vec::new(),
+        });
+        let outer = std::mem::replace(&mut self.current, self.functions.len() - 1);
+        if let Some(node) = node {
+            self.enter(node, false);
+        }
+        for param in params {
+            self.pattern(&param.binding);
+            if let Some(init) = &param.init {
+                self.visit(init);
+            }
+        }
+        match body {
+            // The body shares the function's scope
+            Node::BlockStatement(statements, _) => {
+                for statement in statements {
+                    self.visit(statement);
+                }
+            }
+            expression => self.visit(expression),
+        }
+        self.current = outer;
+    }
+
+    /// The expressions inside a binding pattern: defaults and computed keys.
+    fn pattern(&mut self, pattern: &Node) {
+        match pattern {
+            Node::Identifier(..) => {}
+            Node::PatternProperty {
+                key,
+                computed,
+                value,
+            } => {
+                if *computed {
+                    self.visit(key);
+                }
+                self.pattern(value);
+            }
+            Node::AssignmentPattern { left, right } => {
+                self.pattern(left);
+                self.visit(right);
+            }
+            pattern => {
+                for child in pattern.children() {
+                    self.pattern(child);
+                }
+            }
+        }
+    }
+
+    fn visit(&mut self, node: &Node) {
+        match node {
+            Node::Identifier(..) => self.reference(node),
+            Node::ThisExpression => self.this(),
+            Node::FunctionDeclaration {
+                name,
+                params,
+                body: Some(body),
+                ..
+            } => self.function(Some(node), Some(name.clone()), params, body, false),
+            Node::ArrowFunctionExpression { params, body, .. } => {
+                self.function(Some(node), None, params, body, true)
+            }
+            Node::MethodDefinition {
+                key,
+                computed,
+                params,
+                body,
+                ..
+            } => {
+                if *computed {
+                    self.visit(key);
+                }
+                if let Some(body) = body {
+                    let name = match key.as_ref() {
+                        Node::Identifier(name, _) => Some(name.clone()),
+                        _ => None,
+                    };
+                    self.function(Some(node), name, params, body, false);
+                }
+            }
+            Node::ClassDeclaration {
+                name,
+                extends,
+                members,
+                ..
+            } => {
+                if let Some(extends) = extends {
+                    self.visit(extends);
+                }
+                // Field initializers run like a method, with the instance as `this`
+                let fields: Vec<Node> = members
+                    .iter()
+                    .filter_map(|member| match member {
+                        Node::PropertyDefinition {
+                            value: Some(value), ..
+                        } => Some(Node::ExpressionStatement(value.clone())),
+                        _ => None,
+                    })
+                    .collect();
+                for member in members {
+                    match member {
+                        Node::PropertyDefinition {
+                            key,
+                            computed: true,
+                            ..
+                        } => self.visit(key),
+                        Node::MethodDefinition { .. } => self.visit(member),
+                        _ => {}
+                    }
+                }
+                if !fields.is_empty() {
+                    self.function(
+                        None,
+                        Some(name.clone()),
+                        &[],
+                        &Node::BlockStatement(fields, Span::default()),
+                        false,
+                    );
+                }
+            }
+            Node::NamespaceDeclaration { name, body, .. } => {
+                self.function(
+                    Some(node),
+                    Some(name.clone()),
+                    &[],
+                    &Node::BlockStatement(body.clone(), Span::default()),
+                    false,
+                );
+            }
+            Node::VariableDeclaration { declarations, .. } => {
+                for declarator in declarations {
+                    self.pattern(&declarator.id);
+                    if let Some(init) = &declarator.init {
+                        self.visit(init);
+                    }
+                }
+            }
+            Node::BlockStatement(statements, _) => {
+                self.enter(node, false);
+                for statement in statements {
+                    self.visit(statement);
+                }
+            }
+            Node::ForStatement {
+                init,
+                test,
+                update,
+                body,
+            } => {
+                self.enter(node, init.as_deref().is_some_and(is_lexical));
+                if let Some(init) = init {
+                    self.visit(init);
+                }
+                for part in [test, update].into_iter().flatten() {
+                    self.visit(part);
+                }
+                self.visit(body);
+            }
+            Node::ForInStatement { left, right, body }
+            | Node::ForOfStatement {
+                left, right, body, ..
+            } => {
+                self.visit(right);
+                self.enter(node, is_lexical(left));
+                match left.as_ref() {
+                    Node::VariableDeclaration { declarations, .. } => {
+                        for declarator in declarations {
+                            self.pattern(&declarator.id);
+                        }
+                    }
+                    target => self.visit(target),
+                }
+                self.visit(body);
+            }
+            Node::SwitchStatement {
+                discriminant,
+                cases,
+            } => {
+                self.visit(discriminant);
+                self.enter(node, false);
+                for case in cases {
+                    if let Some(test) = &case.test {
+                        self.visit(test);
+                    }
+                    for statement in &case.consequent {
+                        self.visit(statement);
+                    }
+                }
+            }
+            Node::TryStatement {
+                block,
+                handler,
+                finalizer,
+            } => {
+                self.visit(block);
+                if let Some(CatchClause { param, body }) = handler {
+                    // The parameter is declared in the body's scope
+                    self.enter(body, false);
+                    if let Some(param) = param {
+                        self.pattern(param);
+                    }
+                    self.visit(body);
+                }
+                if let Some(finalizer) = finalizer {
+                    self.visit(finalizer);
+                }
+            }
+            Node::MemberExpression {
+                object,
+                property,
+                computed,
+                ..
+            } => {
+                self.visit(object);
+                if *computed {
+                    self.visit(property);
+                }
+            }
+            Node::Property {
+                key,
+                computed,
+                value,
+                ..
+            } => {
+                if *computed {
+                    self.visit(key);
+                }
+                self.visit(value);
+            }
+            // Targets of an assignment pattern are references, unlike in a declaration
+            Node::PatternProperty {
+                key,
+                computed,
+                value,
+            } => {
+                if *computed {
+                    self.visit(key);
+                }
+                self.visit(value);
+            }
+            Node::ExportNamedDeclaration { declaration, .. } => {
+                if let Some(declaration) = declaration {
+                    self.visit(declaration);
+                }
+            }
+            Node::ExportDefaultDeclaration(declaration) => self.visit(declaration),
+            Node::EnumDeclaration { .. }
+            | Node::InterfaceDeclaration { .. }
+            | Node::TypeAliasDeclaration { .. }
+            | Node::AmbientDeclaration(_)
+            | Node::AmbientModuleDeclaration { .. }
+            | Node::GlobalAugmentation(_)
+            | Node::ImportDeclaration { .. }
+            | Node::ExportAllDeclaration { .. } => {}
+            node => {
+                for child in node.children() {
+                    if !is_type(child) {
+                        self.visit(child);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn is_lexical(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::VariableDeclaration {
+            kind: VariableKind::Let | VariableKind::Const,
+            ..
+        }
+    )
+}
+
+fn is_type(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::TypeReference { .. }
+            | Node::KeywordType(_)
+            | Node::ThisType
+            | Node::UnionType(_)
+            | Node::IntersectionType(_)
+            | Node::ParenthesizedType(_)
+            | Node::FunctionType { .. }
+            | Node::ConstructorType { .. }
+            | Node::ObjectType { .. }
+            | Node::ArrayType(_)
+            | Node::TupleType(_)
+            | Node::OptionalType(_)
+            | Node::RestType(_)
+            | Node::NamedTupleMember { .. }
+            | Node::LiteralType(_)
+            | Node::TypeQuery(_)
+            | Node::TypeOperator { .. }
+            | Node::IndexedAccessType { .. }
+            | Node::TypePredicate { .. }
+    )
+}
+
+impl fmt::Display for CaptureAnalysis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, function) in self.functions.iter().enumerate() {
+            let name = match (&function.name, function.parent) {
+                (_, None) => "<program>",
+                (Some(name), _) => name,
+                (None, _) => "<arrow>",
+            };
+            write!(f, "{} {}", index, name)?;
+            if !function.environment.is_empty() {
+                let environment: Vec<String> = function
+                    .environment
+                    .iter()
+                    .map(|binding| match binding.per_iteration {
+                        true => format!("{} (per iteration)", binding.name),
+                        false => binding.name.clone(),
+                    })
+                    .collect();
+                write!(f, "; environment: {}", environment.join(", "))?;
+            }
+            if !function.free_variables.is_empty() {
+                write!(f, "; captures: {}", function.free_variables.join(", "))?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::tokenizer::Tokenizer;
+
+    fn analyze(source: &str) -> CaptureAnalysis {
+        let tokens = Tokenizer::new(source.to_string()).tokenize();
+        analyze_captures(&Parser::new(tokens).parse().result.expect("parses"))
+    }
+
+    fn function<'a>(analysis: &'a CaptureAnalysis, name: &str) -> &'a FunctionCaptures {
+        analysis
+            .functions
+            .iter()
+            .find(|function| function.name.as_deref() == Some(name))
+            .expect("declares the function")
+    }
+
+    fn names(bindings: &[CapturedBinding]) -> Vec<&str> {
+        bindings
+            .iter()
+            .map(|binding| binding.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn closures_capture_from_the_function_declaring_the_name() {
+        let analysis = analyze(
+            "function f() { let x = 1; var y; function g() { let x = 2; return () => x + y; } }",
+        );
+        assert_eq!(names(&function(&analysis, "f").environment), ["y"]);
+        assert_eq!(names(&function(&analysis, "g").environment), ["x"]);
+        assert_eq!(function(&analysis, "g").free_variables, ["y"]);
+    }
+
+    #[test]
+    fn loop_bindings_are_per_iteration() {
+        let analysis =
+            analyze("function f() { for (let i = 0; i < 3; i++) { var v; g(() => i + v); } }");
+        assert_eq!(
+            function(&analysis, "f").environment,
+            [
+                CapturedBinding {
+                    name: "i".to_string(),
+                    per_iteration: true,
+                },
+                CapturedBinding {
+                    name: "v".to_string(),
+                    per_iteration: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn arrow_functions_capture_this_and_module_declarations_are_never_captured() {
+        let analysis = analyze("let top = 1; class K { m() { return () => this.n + top; } }");
+        assert_eq!(names(&function(&analysis, "m").environment), ["this"]);
+        assert!(analysis.functions[0].environment.is_empty());
+        assert!(analysis.functions.last().unwrap().captures_this());
+    }
+
+    #[test]
+    fn nested_namespaces_declare_their_outermost_name() {
+        let analysis = analyze(
+            "function f() { namespace A.B { export const z = 1; export function h() { return z; } } return () => A; }",
+        );
+        assert_eq!(names(&function(&analysis, "f").environment), ["A"]);
+        // Exports are read through the namespace's object
+        assert!(function(&analysis, "B").environment.is_empty());
+    }
+}
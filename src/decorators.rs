@@ -0,0 +1,699 @@
+use crate::ast::{
+    self, assignment, boolean, call, identifier, key_name, member, null, statement, string,
+    undefined,
+};
+use crate::error_handling::{CompilerError, CompilerResult, Span};
+use crate::names::FreshNames;
+use crate::parser::{MethodKind, Modifiers, Node, Parameter, VariableDeclarator, VariableKind};
+use crate::tokenizer::TokenType;
+
+/// Lowers decorators to calls to the runtime's decorator helpers, placed around the class
+/// they decorate.
+///
+/// By default decorators follow the TC39 proposal as of 2023: each one gets the decorated
+/// value and a `context` object, and may return a replacement, or an initializer for a
+/// field. Decorator expressions are evaluated into temporaries (`$e0`, `$e1`, ...) before
+/// the class, so `@dec method() {}` in class `C` becomes
+///
+/// ```text
+/// let $e0 = [dec];
+/// let $e1 = [];
+/// class C { constructor() { __runInitializers(this, $e1); } method() {} }
+/// __esDecorate(C, null, $e0, { kind: "method", name: "method", static: false, private: false }, null, $e1);
+/// ```
+///
+/// where `$e1` collects the functions given to `context.addInitializer`. A decorated field
+/// `x = 1` becomes `x = __runInitializers(this, $e2, 1)`. Class decorators are applied
+/// after those of its members, through `{ value: C }`, and `C` is reassigned to the
+/// result. As in TypeScript's output, static fields of a class with decorated static
+/// members or class decorators are assigned after the class, once those have been applied.
+/// Instance extra initializers run before any field is initialized: in the initializer of
+/// the first instance field, as `x = (__runInitializers(this, $e1), 1)`, or at the start of
+/// the constructor of a class without instance fields.
+///
+/// With `experimental_decorators` they follow TypeScript's legacy semantics instead:
+/// `__decorate([dec], C.prototype, "method", null)` after the class, parameter decorators
+/// wrapped as `__param(index, dec)` among those of their method, and
+/// `C = __decorate([dec], C)` for the class and its constructor parameters.
+pub fn lower_decorators(program: &Node, experimental_decorators: bool) -> CompilerResult<Node> {
+    let mut lowering = Lowering {
+        experimental_decorators,
+        names: FreshNames::new(program),
+        errors: Vec::new(),
+    };
+    let mut program = program.clone();
+    lowering.lower(&mut program);
+    CompilerResult::new(Some(program), lowering.errors)
+}
+
+struct Lowering {
+    experimental_decorators: bool,
+    names: FreshNames,
+    errors: Vec<CompilerError>,
+}
+
+/// How a decorated class was exported, which decides how it is put back.
+enum Export {
+    None,
+    Named,
+    Default,
+}
+
+/// Statements around a decorated class: evaluating its decorators before it, and applying
+/// them after it.
+#[derive(Default)]
+struct Placement {
+    before: Vec<Node>,
+    after: Vec<Node>,
+}
+
+impl Lowering {
+    fn lower(&mut self, node: &mut Node) {
+        match node {
+            Node::Program(body)
+            | Node::BlockStatement(body, _)
+            | Node::NamespaceDeclaration { body, .. } => self.lower_statements(body),
+            Node::SwitchStatement {
+                discriminant,
+                cases,
+            } => {
+                self.lower(discriminant);
+                for case in cases {
+                    if let Some(test) = case.test.as_deref_mut() {
+                        self.lower(test);
+                    }
+                    self.lower_statements(&mut case.consequent);
+                }
+            }
+            _ => {
+                for child in node.children_mut() {
+                    self.lower(child);
+                }
+            }
+        }
+    }
+
+    fn lower_statements(&mut self, body: &mut Vec<Node>) {
+        for mut statement in std::mem::take(body) {
+            self.lower(&mut statement);
+            let (class, export) = match statement {
+                Node::ExportNamedDeclaration {
+                    declaration: Some(class),
+                    ..
+                } if is_decorated(&class) => (*class, Export::Named),
+                Node::ExportDefaultDeclaration(class) if is_decorated(&class) => {
+                    (*class, Export::Default)
+                }
+                class if is_decorated(&class) => (class, Export::None),
+                statement => {
+                    body.push(statement);
+                    continue;
+                }
+            };
+            self.lower_class(class, export, body);
+        }
+    }
+
+    fn lower_class(&mut self, mut class: Node, export: Export, output: &mut Vec<Node>) {
+        let Node::ClassDeclaration { name, .. } = &mut class else {
+            return;
+        };
+        let class_name = name.clone();
+        // `C` is reassigned once decorated, which an anonymous default export can't be
+        if class_name == "default" {
+            *name = self.next_name();
+        }
+        let binding = Node::Identifier(name.clone(), Span::default());
+        let placement = if self.experimental_decorators {
+            self.legacy_decorators(&mut class, &binding)
+        } else {
+            self.standard_decorators(&mut class, &binding, &class_name)
+        };
+        output.extend(placement.before);
+        output.push(match export {
+            Export::Named => Node::ExportNamedDeclaration {
+                declaration: Some(Box::new(class)),
+                specifiers: Vec::new(),
+                source: None,
+                is_type_only: false,
+            },
+            Export::None | Export::Default => class,
+        });
+        output.extend(placement.after);
+        if let Export::Default = export {
+            output.push(Node::ExportDefaultDeclaration(Box::new(binding)));
+        }
+    }
+
+    fn standard_decorators(&mut self, class: &mut Node, binding: &Node, name: &str) -> Placement {
+        let Node::ClassDeclaration {
+            extends,
+            members,
+            decorators,
+            ..
+        } = class
+        else {
+            return Placement::default();
+        };
+        let mut placement = Placement::default();
+        let class_decorators = std::mem::take(decorators);
+        let class_decorators = (!class_decorators.is_empty()).then(|| {
+            let list = self.declare(array(class_decorators), &mut placement.before);
+            let extra = self.declare(array(Vec::new()), &mut placement.before);
+            (list, extra)
+        });
+        let moves_static_fields = class_decorators.is_some()
+            || members.iter().any(|member| {
+                member_modifiers(member).is_some_and(|m| m.is_static && !m.decorators.is_empty())
+            });
+
+        let mut static_applications = Vec::new();
+        let mut instance_applications = Vec::new();
+        let mut static_fields = Vec::new();
+        let mut static_extra = None;
+        let mut instance_extra = None;
+        for mut member in std::mem::take(members) {
+            if let Node::MethodDefinition { params, .. } = &mut member {
+                for param in params {
+                    if !std::mem::take(&mut param.modifiers.decorators).is_empty() {
+                        self.error("Decorators are not valid here.");
+                    }
+                }
+            }
+            let Some(modifiers) = member_modifiers_mut(&mut member) else {
+                members.push(member);
+                continue;
+            };
+            let is_static = modifiers.is_static;
+            let decorators = std::mem::take(&mut modifiers.decorators);
+            let is_field = matches!(member, Node::PropertyDefinition { .. });
+            if decorators.is_empty() && !(is_field && is_static && moves_static_fields) {
+                members.push(member);
+                continue;
+            }
+            let list = (!decorators.is_empty())
+                .then(|| self.declare(array(decorators), &mut placement.before));
+            let key = self.hoist_key(&mut member, &mut placement.before);
+            let mut initializers = None;
+            if let Some(list) = list {
+                let extra = if is_static {
+                    &mut static_extra
+                } else {
+                    &mut instance_extra
+                };
+                let extra = match extra {
+                    Some(extra) => Node::clone(extra),
+                    None => extra
+                        .insert(self.declare(array(Vec::new()), &mut placement.before))
+                        .clone(),
+                };
+                let kind = match &member {
+                    Node::MethodDefinition {
+                        kind: MethodKind::Get,
+                        ..
+                    } => "getter",
+                    Node::MethodDefinition {
+                        kind: MethodKind::Set,
+                        ..
+                    } => "setter",
+                    Node::MethodDefinition { .. } => "method",
+                    _ => "field",
+                };
+                let context = object(vec![
+                    ("kind", string(kind)),
+                    ("name", key_value(&key)),
+                    ("static", boolean(is_static)),
+                    ("private", boolean(false)),
+                ]);
+                initializers =
+                    is_field.then(|| self.declare(array(Vec::new()), &mut placement.before));
+                let application = call(
+                    identifier("__esDecorate"),
+                    vec![
+                        if is_field { null() } else { binding.clone() },
+                        null(),
+                        list,
+                        context,
+                        initializers.clone().unwrap_or_else(null),
+                        extra,
+                    ],
+                );
+                if is_static {
+                    static_applications.push(statement(application));
+                } else {
+                    instance_applications.push(statement(application));
+                }
+            }
+            let Node::PropertyDefinition { value, .. } = &mut member else {
+                members.push(member);
+                continue;
+            };
+            let mut value = value.take().map_or_else(undefined, |value| *value);
+            if is_static {
+                replace_this(&mut value, binding);
+                if let Some(initializers) = initializers {
+                    value = call(
+                        identifier("__runInitializers"),
+                        vec![binding.clone(), initializers, value],
+                    );
+                }
+                let target = key_access(binding.clone(), key);
+                static_fields.push(statement(assignment(target, value)));
+            } else {
+                if let Some(initializers) = initializers {
+                    value = call(
+                        identifier("__runInitializers"),
+                        vec![Node::ThisExpression, initializers, value],
+                    );
+                }
+                if let Node::PropertyDefinition { value: slot, .. } = &mut member {
+                    *slot = Some(Box::new(value));
+                }
+                members.push(member);
+            }
+        }
+        if let Some(extra) = instance_extra {
+            let run = call(
+                identifier("__runInitializers"),
+                vec![Node::ThisExpression, extra],
+            );
+            let first_field = members.iter_mut().find_map(|member| match member {
+                Node::PropertyDefinition {
+                    value, modifiers, ..
+                } if !modifiers.is_static => Some(value),
+                _ => None,
+            });
+            match first_field {
+                Some(value) => {
+                    let initial = value.take().map_or_else(undefined, |value| *value);
+                    *value = Some(Box::new(Node::BinaryExpression {
+                        left: Box::new(run),
+                        operator: ",".to_string(),
+                        right: Box::new(initial),
+                    }));
+                }
+                None => run_in_constructor(members, statement(run), extends.is_some()),
+            }
+        }
+
+        placement.after.extend(static_applications);
+        placement.after.extend(instance_applications);
+        if let Some((list, extra)) = &class_decorators {
+            let descriptor = self.declare(
+                object(vec![("value", binding.clone())]),
+                &mut placement.after,
+            );
+            let context = object(vec![("kind", string("class")), ("name", string(name))]);
+            placement.after.push(statement(call(
+                identifier("__esDecorate"),
+                vec![
+                    null(),
+                    descriptor.clone(),
+                    list.clone(),
+                    context,
+                    null(),
+                    extra.clone(),
+                ],
+            )));
+            placement.after.push(statement(assignment(
+                binding.clone(),
+                member(descriptor, "value"),
+            )));
+        }
+        if let Some(extra) = static_extra {
+            placement.after.push(statement(call(
+                identifier("__runInitializers"),
+                vec![binding.clone(), extra],
+            )));
+        }
+        placement.after.extend(static_fields);
+        if let Some((_, extra)) = class_decorators {
+            placement.after.push(statement(call(
+                identifier("__runInitializers"),
+                vec![binding.clone(), extra],
+            )));
+        }
+        placement
+    }
+
+    fn legacy_decorators(&mut self, class: &mut Node, binding: &Node) -> Placement {
+        let Node::ClassDeclaration {
+            members,
+            decorators,
+            ..
+        } = class
+        else {
+            return Placement::default();
+        };
+        let mut placement = Placement::default();
+        let mut class_decorators = std::mem::take(decorators);
+        let mut instance_applications = Vec::new();
+        let mut static_applications = Vec::new();
+        let mut decorated_accessors: Vec<(bool, Node)> = Vec::new();
+        for member in members.iter_mut() {
+            let mut decorators = match member_modifiers_mut(member) {
+                Some(modifiers) => std::mem::take(&mut modifiers.decorators),
+                None => continue,
+            };
+            if let Node::MethodDefinition { params, .. } = member {
+                decorators.extend(param_decorators(params));
+            }
+            if let Node::MethodDefinition {
+                kind: MethodKind::Constructor,
+                ..
+            } = member
+            {
+                class_decorators.extend(decorators);
+                continue;
+            }
+            if decorators.is_empty() {
+                continue;
+            }
+            let is_static = member_modifiers(member).is_some_and(|m| m.is_static);
+            let key = self.hoist_key(member, &mut placement.before);
+            if let Node::MethodDefinition {
+                kind: MethodKind::Get | MethodKind::Set,
+                ..
+            } = member
+            {
+                let accessor = (is_static, key.clone());
+                if decorated_accessors.contains(&accessor) {
+                    self.error("Decorators cannot be applied to multiple get/set accessors of the same name.");
+                    continue;
+                }
+                decorated_accessors.push(accessor);
+            }
+            let target = if is_static {
+                binding.clone()
+            } else {
+                ast::member(binding.clone(), "prototype")
+            };
+            let descriptor = match member {
+                Node::PropertyDefinition { .. } => undefined(),
+                _ => null(),
+            };
+            let application = statement(call(
+                identifier("__decorate"),
+                vec![array(decorators), target, key_value(&key), descriptor],
+            ));
+            if is_static {
+                static_applications.push(application);
+            } else {
+                instance_applications.push(application);
+            }
+        }
+        placement.after.extend(instance_applications);
+        placement.after.extend(static_applications);
+        if !class_decorators.is_empty() {
+            let decorated = call(
+                identifier("__decorate"),
+                vec![array(class_decorators), binding.clone()],
+            );
+            placement
+                .after
+                .push(statement(assignment(binding.clone(), decorated)));
+        }
+        placement
+    }
+
+    /// Moves a computed key into a temporary, so it is evaluated once even though both the
+    /// class and the decorator context use it. Returns the key as it is written afterwards.
+    fn hoist_key(&mut self, member: &mut Node, output: &mut Vec<Node>) -> Node {
+        let (Node::MethodDefinition { key, computed, .. }
+        | Node::PropertyDefinition { key, computed, .. }) = member
+        else {
+            return undefined();
+        };
+        if *computed && !matches!(key.as_ref(), Node::Literal { .. }) {
+            let value = std::mem::replace(key.as_mut(), Node::EmptyStatement);
+            **key = self.declare(value, output);
+        }
+        match key.as_ref() {
+            Node::Identifier(name, _) if !*computed => string(name),
+            key => key.clone(),
+        }
+    }
+
+    /// Declares a temporary holding `value`, and returns it.
+    fn declare(&mut self, value: Node, output: &mut Vec<Node>) -> Node {
+        let name = self.next_name();
+        output.push(Node::VariableDeclaration {
+            kind: VariableKind::Let,
+            declarations: vec![VariableDeclarator {
+                id: Box::new(Node::Identifier(name.clone(), Span::default())),
+                type_annotation: None,
+                init: Some(Box::new(value)),
+            }],
+        });
+        Node::Identifier(name, Span::default())
+    }
+
+    fn next_name(&mut self) -> String {
+        self.names.fresh("$e")
+    }
+
+    fn error(&mut self, message: &str) {
+        self.errors
+            .push(CompilerError::new(message.to_string(), Span::default()));
+    }
+}
+
+fn is_decorated(node: &Node) -> bool {
+    let Node::ClassDeclaration {
+        members,
+        decorators,
+        ..
+    } = node
+    else {
+        return false;
+    };
+    !decorators.is_empty()
+        || members.iter().any(|member| {
+            member_modifiers(member).is_some_and(|m| !m.decorators.is_empty())
+                || matches!(member, Node::MethodDefinition { params, .. }
+                    if params.iter().any(|p| !p.modifiers.decorators.is_empty()))
+        })
+}
+
+fn member_modifiers(member: &Node) -> Option<&Modifiers> {
+    match member {
+        Node::MethodDefinition { modifiers, .. } | Node::PropertyDefinition { modifiers, .. } => {
+            Some(modifiers)
+        }
+        _ => None,
+    }
+}
+
+fn member_modifiers_mut(member: &mut Node) -> Option<&mut Modifiers> {
+    match member {
+        Node::MethodDefinition { modifiers, .. } | Node::PropertyDefinition { modifiers, .. } => {
+            Some(modifiers)
+        }
+        _ => None,
+    }
+}
+
+/// `__param(index, dec)` for each decorator of each parameter, which are removed.
+fn param_decorators(params: &mut [Parameter]) -> Vec<Node> {
+    let mut decorators = Vec::new();
+    for (index, param) in params.iter_mut().enumerate() {
+        for decorator in std::mem::take(&mut param.modifiers.decorators) {
+            let index = Node::Literal {
+                token_type: TokenType::NumberLiteral,
+                value: index.to_string(),
+                span: Span::default(),
+            };
+            decorators.push(call(identifier("__param"), vec![index, decorator]));
+        }
+    }
+    decorators
+}
+
+/// Runs `statement` at the start of the constructor, after `super()` in a derived class,
+/// adding a constructor if the class has none.
+fn run_in_constructor(members: &mut Vec<Node>, statement: Node, is_derived: bool) {
+    let constructor = members.iter_mut().find_map(|member| match member {
+        Node::MethodDefinition {
+            kind: MethodKind::Constructor,
+            body: Some(body),
+            ..
+        } => match body.as_mut() {
+            Node::BlockStatement(statements, _) => Some(statements),
+            _ => None,
+        },
+        _ => None,
+    });
+    if let Some(statements) = constructor {
+        let position = statements
+            .iter()
+            .position(|statement| {
+                matches!(statement, Node::ExpressionStatement(call)
+                    if matches!(call.as_ref(), Node::CallExpression { callee, .. }
+                        if matches!(callee.as_ref(), Node::SuperExpression)))
+            })
+            .map_or(0, |index| index + 1);
+        statements.insert(position, statement);
+        return;
+    }
+    let mut params = Vec::new();
+    let mut body = Vec::new();
+    if is_derived {
+        let arguments = Node::Identifier("args".to_string(), Span::default());
+        params.push(Parameter {
+            binding: Box::new(arguments.clone()),
+            type_annotation: None,
+            optional: false,
+            rest: true,
+            init: None,
+            modifiers: Modifiers::default(),
+        });
+        body.push(self::statement(Node::CallExpression {
+            callee: Box::new(Node::SuperExpression),
+            type_arguments: Vec::new(),
+            arguments: vec![Node::SpreadElement(Box::new(arguments))],
+            optional: false,
+        }));
+    }
+    body.push(statement);
+    members.insert(
+        0,
+        Node::MethodDefinition {
+            key: Box::new(Node::Identifier("constructor".to_string(), Span::default())),
+            computed: false,
+            kind: MethodKind::Constructor,
+            type_params: Vec::new(),
+            params,
+            return_type: None,
+            body: Some(Box::new(Node::BlockStatement(body, Span::default()))),
+            modifiers: Modifiers::default(),
+            is_async: false,
+            is_generator: false,
+        },
+    );
+}
+
+/// Replaces `this` with `class` in a static field initializer moved out of its class.
+/// Functions and classes inside it have their own `this`; arrow functions don't.
+fn replace_this(node: &mut Node, class: &Node) {
+    match node {
+        Node::ThisExpression => *node = class.clone(),
+        Node::FunctionDeclaration { .. }
+        | Node::MethodDefinition { .. }
+        | Node::ClassDeclaration { .. } => {}
+        _ => {
+            for child in node.children_mut() {
+                replace_this(child, class);
+            }
+        }
+    }
+}
+
+/// What a key evaluates to, as the decorator context's `name` and `__decorate` take it.
+fn key_value(key: &Node) -> Node {
+    key_name(key, true).map_or_else(|| key.clone(), string)
+}
+
+/// `object.key`, or `object[key]` when the key isn't an identifier.
+fn key_access(object: Node, key: Node) -> Node {
+    match key {
+        Node::Literal {
+            token_type: TokenType::StringLiteral,
+            value,
+            ..
+        } if is_identifier_name(&value) => member(object, &value),
+        key => Node::MemberExpression {
+            object: Box::new(object),
+            property: Box::new(key),
+            computed: true,
+            optional: false,
+        },
+    }
+}
+
+fn is_identifier_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+}
+
+fn array(elements: Vec<Node>) -> Node {
+    Node::ArrayExpression(elements.into_iter().map(Some).collect())
+}
+
+fn object(properties: Vec<(&str, Node)>) -> Node {
+    Node::ObjectExpression(
+        properties
+            .into_iter()
+            .map(|(key, value)| Node::Property {
+                key: Box::new(Node::Identifier(key.to_string(), Span::default())),
+                computed: false,
+                value: Box::new(value),
+                shorthand: false,
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::tokenizer::Tokenizer;
+
+    /// The statements `source` lowers to, printed.
+    fn lower(source: &str, experimental_decorators: bool) -> Vec<String> {
+        let tokens = Tokenizer::new(source.to_string()).tokenize();
+        let program = Parser::new(tokens).parse().result.expect("parses");
+        let lowered = lower_decorators(&program, experimental_decorators);
+        assert!(lowered.errors.is_empty(), "{:?}", lowered.errors);
+        let Some(Node::Program(body)) = lowered.result else {
+            panic!("lowers to a program");
+        };
+        body.iter()
+            .map(|statement| format!("{:?}", statement))
+            .collect()
+    }
+
+    fn applications<'a>(statements: &'a [String], helper: &str) -> Vec<&'a String> {
+        let callee = format!("callee: Identifier(\"{}\"", helper);
+        statements
+            .iter()
+            .filter(|statement| statement.contains(&callee))
+            .collect()
+    }
+
+    #[test]
+    fn legacy_decorators_get_the_prototype_and_the_key_as_a_string() {
+        let statements = lower("class C { @d m() {} @d 1() {} @d static s = 1; }", true);
+        let applications = applications(&statements, "__decorate");
+        assert_eq!(applications.len(), 3);
+        let prototype = r#"MemberExpression { object: Identifier("C", Span { row: 0, col: 0, file: 0 }), property: Identifier("prototype""#;
+        assert!(applications[0].contains(prototype));
+        assert!(applications[0].contains(r#"Literal { token_type: StringLiteral, value: "m""#));
+        assert!(applications[1].contains(r#"Literal { token_type: StringLiteral, value: "1""#));
+        // Fields have no descriptor to pass
+        assert!(!applications[2].contains(prototype));
+        assert!(applications[2].contains(r#"token_type: Undefined"#));
+    }
+
+    #[test]
+    fn decorator_contexts_name_computed_keys_by_their_hoisted_value() {
+        let statements = lower("class C { @d [k + 1]() {} @d n() {} }", false);
+        let applications = applications(&statements, "__esDecorate");
+        assert_eq!(applications.len(), 2);
+        // The key is evaluated once, into a temporary both the class and the context use
+        let Some(declaration) = statements
+            .iter()
+            .find(|statement| statement.contains(r#"BinaryExpression { left: Identifier("k""#))
+        else {
+            panic!("hoists the key");
+        };
+        assert!(declaration.starts_with("VariableDeclaration"));
+        assert!(applications[0].contains(r#"Property { key: Identifier("name", Span { row: 0, col: 0, file: 0 }), computed: false, value: Identifier("$"#));
+        assert!(
+            applications[1].contains(r#"value: Literal { token_type: StringLiteral, value: "n""#)
+        );
+    }
+}
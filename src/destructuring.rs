@@ -0,0 +1,532 @@
+use crate::ast::{let_declaration, number, string, undefined};
+use crate::error_handling::{CompilerError, CompilerResult, Span};
+use crate::names::FreshNames;
+use crate::parser::{Node, Parameter, VariableDeclarator, VariableKind};
+
+/// Lowers destructuring patterns to plain declarations and assignments.
+///
+/// The destructured value is stored in a temporary (`$d0`, `$d1`, ...) and every name in
+/// the pattern is bound to a property access on it: `let { a, b: [c] = d } = e` becomes
+///
+/// ```text
+/// const $d0 = e;
+/// let a = $d0.a;
+/// const $d1 = $d0.b;
+/// const $d2 = $d1 === undefined ? d : $d1;
+/// let c = $d2[0];
+/// ```
+///
+/// An assignment in an expression becomes a comma expression giving the destructured value,
+/// with its temporaries declared by a `let` before the statement it is in, as
+/// `f([a, b] = g())` becomes `let $d0; f(($d0 = g(), a = $d0[0], b = $d0[1], $d0))`.
+///
+/// Array patterns index into the value, so they assume an array-like value rather than
+/// running the iterator protocol. Rest elements call the runtime's `__rest(value, ...keys)`
+/// for objects and `.slice(index)` for arrays. Parameters, catch clauses and for-in/for-of
+/// heads that are patterns take a temporary and are destructured at the top of the body,
+/// along with the default values of the parameters after a pattern.
+pub fn lower_destructuring(program: &Node) -> CompilerResult<Node> {
+    let mut lowering = Lowering {
+        names: FreshNames::new(program),
+        hoisted: Vec::new(),
+        errors: Vec::new(),
+    };
+    let mut program = program.clone();
+    lowering.lower(&mut program);
+    CompilerResult::new(Some(program), lowering.errors)
+}
+
+#[derive(Clone)]
+enum Mode {
+    Declare(VariableKind),
+    Assign,
+}
+
+struct Lowering {
+    names: FreshNames,
+    hoisted: Vec<String>, // temporaries of assignments in the statement being lowered
+    errors: Vec<CompilerError>,
+}
+
+impl Lowering {
+    fn lower(&mut self, node: &mut Node) {
+        match node {
+            Node::Program(body) | Node::BlockStatement(body, _) => self.lower_statements(body),
+            Node::SwitchStatement {
+                discriminant,
+                cases,
+            } => {
+                self.lower(discriminant);
+                for case in cases {
+                    if let Some(test) = case.test.as_deref_mut() {
+                        self.lower(test);
+                    }
+                    self.lower_statements(&mut case.consequent);
+                }
+            }
+            // The loop binds a temporary that is destructured at the top of the body
+            Node::ForInStatement { left, right, body }
+            | Node::ForOfStatement {
+                left, right, body, ..
+            } => {
+                self.lower(right);
+                self.lower(body);
+                let (mut pattern, temporary, mode) = match left.as_mut() {
+                    Node::VariableDeclaration { kind, declarations } => {
+                        match declarations.first_mut().filter(|d| is_pattern(&d.id)) {
+                            Some(declarator) => {
+                                let temporary = Node::Identifier(self.next_name(), Span::default());
+                                let pattern =
+                                    std::mem::replace(declarator.id.as_mut(), temporary.clone());
+                                (pattern, temporary, Mode::Declare(kind.clone()))
+                            }
+                            None => return,
+                        }
+                    }
+                    left if is_pattern(left) => {
+                        let temporary = Node::Identifier(self.next_name(), Span::default());
+                        let declaration = Node::VariableDeclaration {
+                            kind: VariableKind::Const,
+                            declarations: vec![VariableDeclarator {
+                                id: Box::new(temporary.clone()),
+                                type_annotation: None,
+                                init: None,
+                            }],
+                        };
+                        let pattern = std::mem::replace(left, declaration);
+                        (pattern, temporary, Mode::Assign)
+                    }
+                    _ => return,
+                };
+                self.lower(&mut pattern);
+                let mut statements = Vec::new();
+                self.expand(&pattern, temporary, &mode, &mut statements);
+                match body.as_mut() {
+                    Node::BlockStatement(body, _) => {
+                        statements.append(body);
+                        *body = statements;
+                    }
+                    body => {
+                        statements.push(std::mem::replace(body, Node::EmptyStatement));
+                        *body = Node::BlockStatement(statements, Span::default());
+                    }
+                }
+            }
+            // An assignment statement needs no value, so its temporaries can be constants
+            Node::ExpressionStatement(expression) => {
+                let Node::AssignmentExpression { left, right, .. } = expression.as_mut() else {
+                    self.lower(expression);
+                    return;
+                };
+                if !is_pattern(left) {
+                    self.lower(expression);
+                    return;
+                }
+                self.lower(left);
+                self.lower(right);
+                let mut statements = Vec::new();
+                let source = self.temporary(right, &mut statements);
+                self.expand(left, source, &Mode::Assign, &mut statements);
+                *node = Node::BlockStatement(statements, Span::default());
+            }
+            Node::AssignmentExpression { left, right, .. } if is_pattern(left) => {
+                self.lower(left);
+                self.lower(right);
+                let mut statements = Vec::new();
+                let source = self.temporary(right, &mut statements);
+                self.expand(left, source.clone(), &Mode::Assign, &mut statements);
+                let mut expressions: Vec<Node> = statements
+                    .into_iter()
+                    .map(|statement| self.hoist(statement))
+                    .collect();
+                expressions.push(source);
+                *node = expressions
+                    .into_iter()
+                    .reduce(|left, right| Node::BinaryExpression {
+                        left: Box::new(left),
+                        operator: ",".to_string(),
+                        right: Box::new(right),
+                    })
+                    .unwrap_or(Node::EmptyStatement);
+            }
+            Node::FunctionDeclaration {
+                params,
+                body: Some(body),
+                ..
+            }
+            | Node::MethodDefinition {
+                params,
+                body: Some(body),
+                ..
+            } => {
+                let prologue = self.lower_params(params);
+                self.lower(body);
+                prepend(body, prologue);
+            }
+            Node::ArrowFunctionExpression { params, body, .. } => {
+                let prologue = self.lower_params(params);
+                // A concise body has no statement to declare temporaries before
+                let outer = std::mem::take(&mut self.hoisted);
+                self.lower(body);
+                let hoisted = std::mem::replace(&mut self.hoisted, outer);
+                let is_concise = !matches!(body.as_ref(), Node::BlockStatement(..));
+                if is_concise && (!prologue.is_empty() || !hoisted.is_empty()) {
+                    let expression = std::mem::replace(body.as_mut(), Node::EmptyStatement);
+                    let mut statements = vec![Node::ReturnStatement(Some(Box::new(expression)))];
+                    if !hoisted.is_empty() {
+                        statements.insert(0, let_declaration(hoisted));
+                    }
+                    **body = Node::BlockStatement(statements, Span::default());
+                }
+                prepend(body, prologue);
+            }
+            Node::TryStatement {
+                block,
+                handler,
+                finalizer,
+            } => {
+                self.lower(block);
+                if let Some(handler) = handler {
+                    self.lower(&mut handler.body);
+                    if let Some(param) = handler.param.as_deref_mut().filter(|p| is_pattern(p)) {
+                        let temporary = Node::Identifier(self.next_name(), Span::default());
+                        let pattern = std::mem::replace(param, temporary.clone());
+                        self.prepend_bindings(&mut handler.body, vec![(pattern, temporary)]);
+                    }
+                }
+                if let Some(finalizer) = finalizer {
+                    self.lower(finalizer);
+                }
+            }
+            _ => {
+                for child in node.children_mut() {
+                    self.lower(child);
+                }
+            }
+        }
+    }
+
+    fn lower_statements(&mut self, body: &mut Vec<Node>) {
+        let statements = std::mem::take(body);
+        for mut statement in statements {
+            let outer = std::mem::take(&mut self.hoisted);
+            self.lower(&mut statement);
+            let hoisted = std::mem::replace(&mut self.hoisted, outer);
+            if !hoisted.is_empty() {
+                body.push(let_declaration(hoisted));
+            }
+            match statement {
+                Node::VariableDeclaration { kind, declarations }
+                    if declarations.iter().any(|d| is_pattern(&d.id)) =>
+                {
+                    self.lower_declaration(kind, declarations, body)
+                }
+                statement => body.push(statement),
+            }
+        }
+    }
+
+    fn lower_declaration(
+        &mut self,
+        kind: VariableKind,
+        declarations: Vec<VariableDeclarator>,
+        output: &mut Vec<Node>,
+    ) {
+        for declarator in declarations {
+            if !is_pattern(&declarator.id) {
+                output.push(Node::VariableDeclaration {
+                    kind: kind.clone(),
+                    declarations: vec![declarator],
+                });
+                continue;
+            }
+            let Some(init) = declarator.init else {
+                self.errors.push(CompilerError::new(
+                    "A destructuring declaration must have an initializer".to_string(),
+                    Span::default(),
+                ));
+                continue;
+            };
+            let source = self.temporary(&init, output);
+            self.expand(&declarator.id, source, &Mode::Declare(kind.clone()), output);
+        }
+    }
+
+    /// Destructures each `(pattern, value)` with `let` at the start of a function or catch body.
+    fn prepend_bindings(&mut self, body: &mut Node, bindings: Vec<(Node, Node)>) {
+        let mut prologue = Vec::new();
+        for (mut pattern, source) in bindings {
+            self.lower(&mut pattern);
+            self.expand(
+                &pattern,
+                source,
+                &Mode::Declare(VariableKind::Let),
+                &mut prologue,
+            );
+        }
+        prepend(body, prologue);
+    }
+
+    /// Lowers a function's parameters, giving the statements that go at the top of its body.
+    /// A pattern takes a temporary and is destructured there. So that parameters are still
+    /// bound in order, every later parameter's default value goes there too, after it: an
+    /// identifier keeps its place and is assigned its default when it is `undefined`, and a
+    /// pattern's default applies before it is destructured. The names are declared with
+    /// `var`, as a parameter can also be declared by a `var` in the body.
+    fn lower_params(&mut self, params: &mut [Parameter]) -> Vec<Node> {
+        let mut prologue = Vec::new();
+        let mut in_body = false;
+        for param in params.iter_mut() {
+            if let Some(init) = param.init.as_deref_mut() {
+                self.lower(init);
+            }
+            let (mut target, mode) = if is_pattern(&param.binding) {
+                let temporary = Node::Identifier(self.next_name(), Span::default());
+                let mut pattern = std::mem::replace(param.binding.as_mut(), temporary);
+                self.lower(&mut pattern);
+                (pattern, Mode::Declare(VariableKind::Var))
+            } else if in_body && param.init.is_some() {
+                ((*param.binding).clone(), Mode::Assign)
+            } else {
+                continue;
+            };
+            // The first pattern's own default comes before anything moves
+            if in_body {
+                if let Some(init) = param.init.take() {
+                    target = Node::AssignmentPattern {
+                        left: Box::new(target),
+                        right: init,
+                    };
+                }
+            }
+            self.expand(&target, (*param.binding).clone(), &mode, &mut prologue);
+            in_body = true;
+        }
+        prologue
+    }
+
+    /// Binds every target in `pattern` to its part of `source`, which must be side-effect free.
+    fn expand(&mut self, pattern: &Node, source: Node, mode: &Mode, output: &mut Vec<Node>) {
+        match pattern {
+            Node::AssignmentPattern { left, right } => {
+                let value = self.temporary(&source, output);
+                let defaulted = Node::ConditionalExpression {
+                    test: Box::new(Node::BinaryExpression {
+                        left: Box::new(value.clone()),
+                        operator: "===".to_string(),
+                        right: Box::new(undefined()),
+                    }),
+                    consequent: right.clone(),
+                    alternate: Box::new(value),
+                };
+                self.expand(left, defaulted, mode, output);
+            }
+            Node::ObjectPattern(elements) => {
+                let object = self.temporary(&source, output);
+                let mut keys = vec![object.clone()];
+                for element in elements {
+                    match element {
+                        Node::PatternProperty {
+                            key,
+                            computed,
+                            value,
+                        } => {
+                            let (property, computed) = match key.as_ref() {
+                                Node::Identifier(name, _) if !computed => {
+                                    keys.push(string(name));
+                                    ((**key).clone(), false)
+                                }
+                                Node::Literal { .. } => {
+                                    keys.push((**key).clone());
+                                    ((**key).clone(), true)
+                                }
+                                // Evaluated once, even though a rest element needs it too
+                                _ => {
+                                    let key = self.temporary(key, output);
+                                    keys.push(key.clone());
+                                    (key, true)
+                                }
+                            };
+                            let access = Node::MemberExpression {
+                                object: Box::new(object.clone()),
+                                property: Box::new(property),
+                                computed,
+                                optional: false,
+                            };
+                            self.expand(value, access, mode, output);
+                        }
+                        Node::RestElement(argument) => {
+                            let rest = Node::CallExpression {
+                                callee: Box::new(Node::Identifier(
+                                    "__rest".to_string(),
+                                    Span::default(),
+                                )),
+                                type_arguments: Vec::new(),
+                                arguments: keys.clone(),
+                                optional: false,
+                            };
+                            self.expand(argument, rest, mode, output);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Node::ArrayPattern(elements) => {
+                let array = self.temporary(&source, output);
+                for (index, element) in elements.iter().enumerate() {
+                    let index_literal = number(index);
+                    match element {
+                        None => {}
+                        Some(Node::RestElement(argument)) => {
+                            let rest = Node::CallExpression {
+                                callee: Box::new(Node::MemberExpression {
+                                    object: Box::new(array.clone()),
+                                    property: Box::new(Node::Identifier(
+                                        "slice".to_string(),
+                                        Span::default(),
+                                    )),
+                                    computed: false,
+                                    optional: false,
+                                }),
+                                type_arguments: Vec::new(),
+                                arguments: vec![index_literal],
+                                optional: false,
+                            };
+                            self.expand(argument, rest, mode, output);
+                        }
+                        Some(element) => {
+                            let access = Node::MemberExpression {
+                                object: Box::new(array.clone()),
+                                property: Box::new(index_literal),
+                                computed: true,
+                                optional: false,
+                            };
+                            self.expand(element, access, mode, output);
+                        }
+                    }
+                }
+            }
+            target => output.push(match mode {
+                Mode::Declare(kind) => Node::VariableDeclaration {
+                    kind: kind.clone(),
+                    declarations: vec![VariableDeclarator {
+                        id: Box::new(target.clone()),
+                        type_annotation: None,
+                        init: Some(Box::new(source)),
+                    }],
+                },
+                Mode::Assign => Node::ExpressionStatement(Box::new(Node::AssignmentExpression {
+                    left: Box::new(target.clone()),
+                    operator: "=".to_string(),
+                    right: Box::new(source),
+                })),
+            }),
+        }
+    }
+
+    /// Stores `value` in a fresh temporary and returns a reference to it, unless it already
+    /// is one.
+    fn temporary(&mut self, value: &Node, output: &mut Vec<Node>) -> Node {
+        if matches!(value, Node::Identifier(name, _) if self.names.is_generated(name)) {
+            return value.clone();
+        }
+        let name = self.next_name();
+        output.push(Node::VariableDeclaration {
+            kind: VariableKind::Const,
+            declarations: vec![VariableDeclarator {
+                id: Box::new(Node::Identifier(name.clone(), Span::default())),
+                type_annotation: None,
+                init: Some(Box::new(value.clone())),
+            }],
+        });
+        Node::Identifier(name, Span::default())
+    }
+
+    /// An assignment `expand` or `temporary` produced, as an expression. Temporaries are
+    /// assigned instead of declared, and declared before the statement being lowered.
+    fn hoist(&mut self, statement: Node) -> Node {
+        match statement {
+            Node::VariableDeclaration {
+                mut declarations, ..
+            } if declarations.len() == 1 => {
+                let declarator = declarations.remove(0);
+                if let Node::Identifier(name, _) = declarator.id.as_ref() {
+                    self.hoisted.push(name.clone());
+                }
+                Node::AssignmentExpression {
+                    left: declarator.id,
+                    operator: "=".to_string(),
+                    right: declarator.init.unwrap_or_else(|| {
+                        Box::new(Node::Identifier("undefined".to_string(), Span::default()))
+                    }),
+                }
+            }
+            Node::ExpressionStatement(expression) => *expression,
+            statement => statement,
+        }
+    }
+
+    fn next_name(&mut self) -> String {
+        self.names.fresh("$d")
+    }
+}
+
+fn is_pattern(node: &Node) -> bool {
+    matches!(node, Node::ObjectPattern(_) | Node::ArrayPattern(_))
+}
+
+/// Puts `prologue` at the start of a block `body`.
+fn prepend(body: &mut Node, mut prologue: Vec<Node>) {
+    if let Node::BlockStatement(statements, _) = body {
+        prologue.append(statements);
+        *statements = prologue;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::tokenizer::Tokenizer;
+
+    /// The parameters and body statements of the function `source` declares, lowered.
+    fn lower_function(source: &str) -> (Vec<Parameter>, Vec<Node>) {
+        let tokens = Tokenizer::new(source.to_string()).tokenize();
+        let program = Parser::new(tokens).parse().result.expect("parses");
+        let lowered = lower_destructuring(&program);
+        assert!(lowered.errors.is_empty(), "{:?}", lowered.errors);
+        let Some(Node::Program(mut body)) = lowered.result else {
+            panic!("lowers to a program");
+        };
+        let Node::FunctionDeclaration {
+            params,
+            body: Some(body),
+            ..
+        } = body.remove(0)
+        else {
+            panic!("declares a function");
+        };
+        let Node::BlockStatement(statements, _) = *body else {
+            panic!("has a block body");
+        };
+        (params, statements)
+    }
+
+    #[test]
+    fn defaults_after_a_pattern_parameter_run_after_it() {
+        let (params, statements) = lower_function("function g({ a }, b = a) { var a; }");
+        assert!(params.iter().all(|param| param.init.is_none()));
+        assert!(
+            matches!(&statements[0], Node::VariableDeclaration { kind: VariableKind::Var, declarations }
+                if matches!(declarations[0].id.as_ref(), Node::Identifier(name, _) if name == "a"))
+        );
+        assert!(format!("{:?}", statements[2])
+            .starts_with(r#"ExpressionStatement(AssignmentExpression { left: Identifier("b""#));
+    }
+
+    #[test]
+    fn a_pattern_parameter_keeps_its_own_default() {
+        let (params, _) = lower_function("function k(p = 1, { q } = { q: p }) {}");
+        assert!(params.iter().all(|param| param.init.is_some()));
+    }
+}
@@ -0,0 +1,641 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::{assignment, string};
+use crate::error_handling::{CompilerError, CompilerResult, Span};
+use crate::parser::{Node, VariableDeclarator, VariableKind};
+use crate::tokenizer::TokenType;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum EnumValue {
+    Number(f64),
+    String(String),
+    Computed, // Only known at runtime; not allowed in const enums
+}
+
+#[derive(Debug, Clone)]
+pub struct EnumLayout {
+    pub name: String,
+    pub is_const: bool,
+    pub is_declare: bool,
+    pub members: Vec<(String, EnumValue)>,
+}
+
+impl EnumLayout {
+    pub fn value(&self, member: &str) -> Option<&EnumValue> {
+        self.members
+            .iter()
+            .find(|(name, _)| name == member)
+            .map(|(_, value)| value)
+    }
+
+    /// Whether the enum needs an object at runtime; const and ambient enums only exist
+    /// at compile time.
+    pub fn has_runtime_object(&self) -> bool {
+        !self.is_const && !self.is_declare
+    }
+}
+
+/// Evaluates the members of every enum in the program.
+///
+/// Members without an initializer continue from the previous numeric member, starting at
+/// zero. Initializers may be constant expressions over literals and members of enums
+/// declared earlier, including the enum being declared; anything else makes the member
+/// computed, which `const enum`s reject. Declarations of the same enum are merged.
+pub fn evaluate_enums(program: &Node) -> CompilerResult<HashMap<String, EnumLayout>> {
+    let mut enums: HashMap<String, EnumLayout> = HashMap::new();
+    // Enums a declaration has already omitted the first initializer of
+    let mut omitted_first = HashSet::new();
+    let mut errors = Vec::new();
+    let mut declarations = Vec::new();
+    collect_enum_declarations(program, &mut declarations);
+
+    for declaration in declarations {
+        let Node::EnumDeclaration {
+            name,
+            members,
+            is_const,
+            is_declare,
+            ..
+        } = declaration
+        else {
+            continue;
+        };
+        // Taken out while its members are evaluated; `evaluate` finds it as `current`
+        let mut layout = enums.remove(name).unwrap_or_else(|| EnumLayout {
+            name: name.clone(),
+            is_const: *is_const,
+            is_declare: *is_declare,
+            members: Vec::new(),
+        });
+        if layout.is_const != *is_const {
+            errors.push(CompilerError::new(
+                "Enum declarations must all be const or non-const".to_string(),
+                members.first().map(|m| m.span).unwrap_or_default(),
+            ));
+        }
+
+        let mut previous: Option<EnumValue> = None;
+        for (index, member) in members.iter().enumerate() {
+            let value = match &member.init {
+                Some(init) => match evaluate(init, &layout, &enums) {
+                    Some(EnumValue::Number(number)) if *is_const && !number.is_finite() => {
+                        errors.push(CompilerError::new(
+                            "'const' enum member initializer was evaluated to a non-finite value"
+                                .to_string(),
+                            member.span,
+                        ));
+                        EnumValue::Number(number)
+                    }
+                    Some(value) => value,
+                    None if *is_const => {
+                        errors.push(CompilerError::new(
+                            "const enum member initializers must be constant expressions"
+                                .to_string(),
+                            member.span,
+                        ));
+                        EnumValue::Computed
+                    }
+                    None => EnumValue::Computed,
+                },
+                None => match &previous {
+                    None if index == 0 && !omitted_first.insert(name.clone()) => {
+                        errors.push(CompilerError::new(
+                            "In an enum with multiple declarations, only one declaration can omit an initializer for its first enum element".to_string(),
+                            member.span,
+                        ));
+                        EnumValue::Computed
+                    }
+                    None => EnumValue::Number(0.0),
+                    Some(EnumValue::Number(number)) => EnumValue::Number(number + 1.0),
+                    Some(_) => {
+                        errors.push(CompilerError::new(
+                            "Enum member must have initializer".to_string(),
+                            member.span,
+                        ));
+                        EnumValue::Computed
+                    }
+                },
+            };
+            if layout.value(&member.name).is_some() {
+                errors.push(CompilerError::new(
+                    format!("Duplicate identifier '{}'", member.name),
+                    member.span,
+                ));
+            }
+            layout.members.push((member.name.clone(), value.clone()));
+            previous = Some(value);
+        }
+        enums.insert(name.clone(), layout);
+    }
+    CompilerResult::new(Some(enums), errors)
+}
+
+fn collect_enum_declarations<'a>(node: &'a Node, declarations: &mut Vec<&'a Node>) {
+    match node {
+        Node::EnumDeclaration { .. } => declarations.push(node),
+        Node::ExportNamedDeclaration {
+            declaration: Some(declaration),
+            ..
+        } => collect_enum_declarations(declaration, declarations),
+        Node::Program(body) | Node::BlockStatement(body, _) => {
+            for statement in body {
+                collect_enum_declarations(statement, declarations);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Folds a constant enum initializer; `None` means it isn't a constant expression.
+fn evaluate(
+    expression: &Node,
+    current: &EnumLayout,
+    enums: &HashMap<String, EnumLayout>,
+) -> Option<EnumValue> {
+    match expression {
+        Node::Literal {
+            token_type: TokenType::NumberLiteral,
+            value,
+            ..
+        } => parse_number(value).map(EnumValue::Number),
+        Node::Literal {
+            token_type: TokenType::StringLiteral,
+            value,
+            ..
+        } => Some(EnumValue::String(value.clone())),
+        // Template literals without substitutions are plain strings
+        Node::Literal {
+            token_type: TokenType::TemplateLiteral,
+            value,
+            ..
+        } if !value.contains("${") => Some(EnumValue::String(value.clone())),
+        Node::Identifier(name, _) => match name.as_str() {
+            "Infinity" => Some(EnumValue::Number(f64::INFINITY)),
+            "NaN" => Some(EnumValue::Number(f64::NAN)),
+            _ => constant(current.value(name)),
+        },
+        Node::MemberExpression {
+            object,
+            property,
+            computed,
+            ..
+        } => {
+            let Node::Identifier(enum_name, _) = object.as_ref() else {
+                return None;
+            };
+            let layout = if *enum_name == current.name {
+                current
+            } else {
+                enums.get(enum_name)?
+            };
+            let member = match (property.as_ref(), computed) {
+                (Node::Identifier(member, _), false) => member,
+                (
+                    Node::Literal {
+                        token_type: TokenType::StringLiteral,
+                        value,
+                        ..
+                    },
+                    true,
+                ) => value,
+                _ => return None,
+            };
+            constant(layout.value(member))
+        }
+        Node::UnaryExpression { operator, argument } => {
+            let EnumValue::Number(number) = evaluate(argument, current, enums)? else {
+                return None;
+            };
+            match operator.as_str() {
+                "+" => Some(EnumValue::Number(number)),
+                "-" => Some(EnumValue::Number(-number)),
+                "~" => Some(EnumValue::Number(!to_int32(number) as f64)),
+                _ => None,
+            }
+        }
+        Node::BinaryExpression {
+            left,
+            operator,
+            right,
+        } => {
+            let left = evaluate(left, current, enums)?;
+            let right = evaluate(right, current, enums)?;
+            match (left, right) {
+                (EnumValue::Number(left), EnumValue::Number(right)) => {
+                    let value = match operator.as_str() {
+                        "+" => left + right,
+                        "-" => left - right,
+                        "*" => left * right,
+                        "/" => left / right,
+                        "%" => left % right,
+                        "**" => left.powf(right),
+                        "|" => (to_int32(left) | to_int32(right)) as f64,
+                        "&" => (to_int32(left) & to_int32(right)) as f64,
+                        "^" => (to_int32(left) ^ to_int32(right)) as f64,
+                        // Shift counts are taken modulo 32, as in JavaScript
+                        "<<" => to_int32(left).wrapping_shl(to_int32(right) as u32) as f64,
+                        ">>" => to_int32(left).wrapping_shr(to_int32(right) as u32) as f64,
+                        ">>>" => {
+                            (to_int32(left) as u32).wrapping_shr(to_int32(right) as u32) as f64
+                        }
+                        _ => return None,
+                    };
+                    Some(EnumValue::Number(value))
+                }
+                (EnumValue::String(left), EnumValue::String(right)) if operator == "+" => {
+                    Some(EnumValue::String(left + &right))
+                }
+                (EnumValue::String(left), EnumValue::Number(right)) if operator == "+" => {
+                    Some(EnumValue::String(left + &format_number(right)))
+                }
+                (EnumValue::Number(left), EnumValue::String(right)) if operator == "+" => {
+                    Some(EnumValue::String(format_number(left) + &right))
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn constant(value: Option<&EnumValue>) -> Option<EnumValue> {
+    match value? {
+        EnumValue::Computed => None,
+        value => Some(value.clone()),
+    }
+}
+
+/// Replaces `E.A` and `E["A"]` for every `const enum E` with the member's literal value and
+/// drops the const and ambient enum declarations, which have no runtime representation.
+pub fn inline_const_enums(
+    program: &Node,
+    enums: &HashMap<String, EnumLayout>,
+) -> CompilerResult<Node> {
+    let mut program = program.clone();
+    let mut errors = Vec::new();
+    inline(&mut program, enums, &mut errors);
+    CompilerResult::new(Some(program), errors)
+}
+
+/// A `const` or `declare` enum, which has no runtime object to keep a declaration for.
+fn is_compile_time_enum(statement: &Node) -> bool {
+    matches!(
+        statement,
+        Node::EnumDeclaration { is_const: true, .. }
+            | Node::EnumDeclaration {
+                is_declare: true,
+                ..
+            }
+    )
+}
+
+fn inline(node: &mut Node, enums: &HashMap<String, EnumLayout>, errors: &mut Vec<CompilerError>) {
+    match node {
+        Node::Program(body) | Node::BlockStatement(body, _) => {
+            body.retain(|statement| match statement {
+                Node::ExportNamedDeclaration {
+                    declaration: Some(declaration),
+                    ..
+                } => !is_compile_time_enum(declaration),
+                statement => !is_compile_time_enum(statement),
+            });
+            for statement in body {
+                inline(statement, enums, errors);
+            }
+        }
+        Node::MemberExpression {
+            object,
+            property,
+            computed,
+            ..
+        } => {
+            if let Node::Identifier(enum_name, _) = object.as_ref() {
+                if let Some(layout) = enums.get(enum_name).filter(|layout| layout.is_const) {
+                    let member = match (property.as_ref(), *computed) {
+                        (Node::Identifier(member, _), false) => Some(member.clone()),
+                        (
+                            Node::Literal {
+                                token_type: TokenType::StringLiteral,
+                                value,
+                                ..
+                            },
+                            true,
+                        ) => Some(value.clone()),
+                        _ => None,
+                    };
+                    match member.as_deref().and_then(|member| layout.value(member)) {
+                        Some(value) => *node = literal(value),
+                        None => errors.push(CompilerError::new(
+                            match member {
+                                Some(member) => format!(
+                                    "Property '{}' does not exist on type 'typeof {}'",
+                                    member, enum_name
+                                ),
+                                None => "A const enum member can only be accessed using a string literal".to_string(),
+                            },
+                            Span::default(),
+                        )),
+                    }
+                    return;
+                }
+            }
+            inline(object, enums, errors);
+            if *computed {
+                inline(property, enums, errors);
+            }
+        }
+        Node::Identifier(name, _) if enums.get(name.as_str()).is_some_and(|l| l.is_const) => {
+            errors.push(CompilerError::new(
+                "'const' enums can only be used in property or index access expressions"
+                    .to_string(),
+                Span::default(),
+            ));
+        }
+        Node::VariableDeclaration { declarations, .. } => {
+            for declarator in declarations {
+                if let Some(init) = declarator.init.as_deref_mut() {
+                    inline(init, enums, errors);
+                }
+            }
+        }
+        // Types may name const enums freely
+        Node::TypeAliasDeclaration { .. } | Node::InterfaceDeclaration { .. } => {}
+        _ => {
+            for child in node.children_mut() {
+                inline(child, enums, errors);
+            }
+        }
+    }
+}
+
+/// Lowers every enum with a runtime object to the object tsc builds for it:
+///
+/// ```text
+/// const E = {};
+/// E[E["A"] = 0] = "A";
+/// E["B"] = "b";
+/// ```
+///
+/// Numeric and computed members also map their value back to their name; string members
+/// don't. The first declaration of a merged enum creates the object and later ones add to
+/// it. Computed initializers keep their expression, with the members it names read from the
+/// object. Only the enums `evaluate_enums` sees are lowered.
+pub fn lower_enums(program: &Node, enums: &HashMap<String, EnumLayout>) -> CompilerResult<Node> {
+    let mut program = program.clone();
+    lower(&mut program, enums, &mut HashMap::new());
+    CompilerResult::new(Some(program), Vec::new())
+}
+
+/// `lowered` counts the members of each enum lowered so far, which index its layout.
+fn lower(
+    node: &mut Node,
+    enums: &HashMap<String, EnumLayout>,
+    lowered: &mut HashMap<String, usize>,
+) {
+    let (Node::Program(body) | Node::BlockStatement(body, _)) = node else {
+        return;
+    };
+    let mut output = Vec::with_capacity(body.len());
+    for mut statement in std::mem::take(body) {
+        let declaration = match &statement {
+            Node::ExportNamedDeclaration {
+                declaration: Some(declaration),
+                ..
+            } => declaration.as_ref(),
+            statement => statement,
+        };
+        let Node::EnumDeclaration {
+            name,
+            span,
+            members,
+            ..
+        } = declaration
+        else {
+            lower(&mut statement, enums, lowered);
+            output.push(statement);
+            continue;
+        };
+        let Some(layout) = enums.get(name).filter(|layout| layout.has_runtime_object()) else {
+            output.push(statement);
+            continue;
+        };
+        let (name, members) = (name.clone(), members.clone());
+        let offset = match lowered.get(&name) {
+            Some(offset) => *offset,
+            None => {
+                let object = Node::VariableDeclaration {
+                    kind: VariableKind::Const,
+                    declarations: vec![VariableDeclarator {
+                        id: Box::new(Node::Identifier(name.clone(), *span)),
+                        type_annotation: None,
+                        init: Some(Box::new(Node::ObjectExpression(Vec::new()))),
+                    }],
+                };
+                match &mut statement {
+                    Node::ExportNamedDeclaration {
+                        declaration: Some(declaration),
+                        ..
+                    } => **declaration = object,
+                    statement => *statement = object,
+                }
+                output.push(statement);
+                0
+            }
+        };
+        for (index, member) in members.iter().enumerate() {
+            let key = string(&member.name);
+            let assignment = match &layout.members[offset + index].1 {
+                value @ EnumValue::String(_) => assignment(index_of(&name, key), literal(value)),
+                value => {
+                    let value = match (value, &member.init) {
+                        (EnumValue::Computed, Some(init)) => {
+                            let mut init = (**init).clone();
+                            qualify(&mut init, layout);
+                            init
+                        }
+                        (value, _) => literal(value),
+                    };
+                    let forward = assignment(index_of(&name, key.clone()), value);
+                    assignment(index_of(&name, forward), key)
+                }
+            };
+            output.push(Node::ExpressionStatement(Box::new(assignment)));
+        }
+        lowered.insert(name, offset + members.len());
+    }
+    *body = output;
+}
+
+/// Reads the members of `layout` that `expression` names from the enum's object.
+fn qualify(expression: &mut Node, layout: &EnumLayout) {
+    match expression {
+        Node::Identifier(name, span) if layout.value(name).is_some() => {
+            *expression = Node::MemberExpression {
+                object: Box::new(Node::Identifier(layout.name.clone(), *span)),
+                property: Box::new(Node::Identifier(name.clone(), *span)),
+                computed: false,
+                optional: false,
+            };
+        }
+        Node::MemberExpression {
+            object,
+            property,
+            computed,
+            ..
+        } => {
+            qualify(object, layout);
+            if *computed {
+                qualify(property, layout);
+            }
+        }
+        _ => {
+            for child in expression.children_mut() {
+                qualify(child, layout);
+            }
+        }
+    }
+}
+
+/// `object[key]`
+fn index_of(object: &str, key: Node) -> Node {
+    Node::MemberExpression {
+        object: Box::new(Node::Identifier(object.to_string(), Span::default())),
+        property: Box::new(key),
+        computed: true,
+        optional: false,
+    }
+}
+
+fn literal(value: &EnumValue) -> Node {
+    match value {
+        EnumValue::Number(number) if *number < 0.0 => Node::UnaryExpression {
+            operator: "-".to_string(),
+            argument: Box::new(Node::Literal {
+                token_type: TokenType::NumberLiteral,
+                value: format_number(-number),
+                span: Span::default(),
+            }),
+        },
+        EnumValue::Number(number) => Node::Literal {
+            token_type: TokenType::NumberLiteral,
+            value: format_number(*number),
+            span: Span::default(),
+        },
+        EnumValue::String(value) => Node::Literal {
+            token_type: TokenType::StringLiteral,
+            value: value.clone(),
+            span: Span::default(),
+        },
+        EnumValue::Computed => Node::Identifier("undefined".to_string(), Span::default()),
+    }
+}
+
+pub(crate) fn parse_number(value: &str) -> Option<f64> {
+    let lower = value.to_ascii_lowercase();
+    if let Some(hex) = lower.strip_prefix("0x") {
+        return i64::from_str_radix(hex, 16).ok().map(|n| n as f64);
+    }
+    if let Some(binary) = lower.strip_prefix("0b") {
+        return i64::from_str_radix(binary, 2).ok().map(|n| n as f64);
+    }
+    if let Some(octal) = lower.strip_prefix("0o") {
+        return i64::from_str_radix(octal, 8).ok().map(|n| n as f64);
+    }
+    value.parse().ok()
+}
+
+fn to_int32(number: f64) -> i32 {
+    if !number.is_finite() {
+        return 0;
+    }
+    number.trunc() as i64 as i32
+}
+
+fn format_number(number: f64) -> String {
+    if number.fract() == 0.0 && number.is_finite() && number.abs() < 1e21 {
+        format!("{}", number as i64)
+    } else {
+        format!("{}", number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::tokenizer::Tokenizer;
+
+    fn parse(source: &str) -> Node {
+        let tokens = Tokenizer::new(source.to_string()).tokenize();
+        Parser::new(tokens).parse().result.expect("parses")
+    }
+
+    fn evaluate_source(source: &str) -> (HashMap<String, EnumLayout>, Vec<String>) {
+        let evaluated = evaluate_enums(&parse(source));
+        let messages = evaluated.errors.iter().map(|e| e.message.clone()).collect();
+        (evaluated.result.expect("evaluates"), messages)
+    }
+
+    #[test]
+    fn members_continue_from_the_previous_numeric_member() {
+        let (enums, errors) = evaluate_source("enum E { A, B = 1 << 3, C, D = B | C, S = `s` }");
+        assert!(errors.is_empty(), "{:?}", errors);
+        let values: Vec<_> = enums["E"].members.iter().map(|(_, v)| v.clone()).collect();
+        assert_eq!(
+            values,
+            [0.0, 8.0, 9.0, 9.0]
+                .map(EnumValue::Number)
+                .into_iter()
+                .chain([EnumValue::String("s".to_string())])
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn only_one_merged_declaration_can_omit_its_first_initializer() {
+        let (enums, errors) = evaluate_source("enum E { A = 1 } enum E { B }");
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(enums["E"].value("B"), Some(&EnumValue::Number(0.0)));
+
+        let (_, errors) = evaluate_source("enum E { A } enum E { B = 2 } enum E { C }");
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("In an enum with multiple declarations"));
+    }
+
+    #[test]
+    fn runtime_enums_lower_to_objects_that_map_numbers_back_to_names() {
+        let program = parse("enum E { A, S = \"s\", C = A | f() } enum E { D = 4 }");
+        let enums = evaluate_enums(&program).result.unwrap();
+        let Some(Node::Program(body)) = lower_enums(&program, &enums).result else {
+            panic!("lowers to a program");
+        };
+        let statements: Vec<String> = body.iter().map(|s| format!("{:?}", s)).collect();
+        assert_eq!(statements.len(), 5, "{:#?}", statements);
+        assert!(statements[0].starts_with("VariableDeclaration { kind: Const"));
+        assert!(statements[0].contains("init: Some(ObjectExpression([]))"));
+        // E[E["A"] = 0] = "A"
+        let reverse = "ExpressionStatement(AssignmentExpression { left: MemberExpression { object: Identifier(\"E\"";
+        for statement in [&statements[1], &statements[3], &statements[4]] {
+            assert!(statement.starts_with(reverse), "{}", statement);
+            assert!(statement.contains("property: AssignmentExpression"));
+        }
+        // E["S"] = "s"
+        assert!(!statements[2].contains("property: AssignmentExpression"));
+        // Computed initializers read the members they name from the object
+        assert!(statements[3].contains("left: MemberExpression { object: Identifier(\"E\""));
+        assert!(statements[3].contains("property: Identifier(\"A\""));
+    }
+
+    #[test]
+    fn const_enums_are_inlined_and_dropped() {
+        let program = parse("const enum E { A = 2 } let a = E.A;");
+        let enums = evaluate_enums(&program).result.unwrap();
+        let Some(Node::Program(body)) = inline_const_enums(&program, &enums).result else {
+            panic!("inlines into a program");
+        };
+        assert_eq!(body.len(), 1);
+        assert!(
+            format!("{:?}", body[0]).contains("Literal { token_type: NumberLiteral, value: \"2\"")
+        );
+    }
+}
@@ -1,8 +1,37 @@
+/// A source position, for diagnostics reported after parsing.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default)]
+pub struct Span {
+    pub row: usize,
+    pub col: usize,
+    pub file: usize, // which of the files compiled together, e.g. a module's index; 0 for one
+}
+
 #[derive(Debug)]
 pub struct CompilerError {
     pub message: String,
     pub row: usize,
     pub col: usize,
+    pub file: usize,
+    pub code: Option<u32>, // the matching tsc diagnostic, e.g. 2588 for TS2588
+    pub related: Vec<RelatedInformation>,
+    pub severity: Severity,
+}
+
+/// Whether a diagnostic fails the compilation. Lints can be reported as warnings instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Severity {
+    #[default]
+    Error,
+    Warning,
+}
+
+/// Another location an error refers to, such as the declaration a bad assignment targets.
+#[derive(Debug)]
+pub struct RelatedInformation {
+    pub message: String,
+    pub row: usize,
+    pub col: usize,
+    pub file: usize,
 }
 
 #[derive(Debug)]
@@ -11,12 +40,52 @@ pub struct CompilerResult<T> {
     pub errors: Vec<CompilerError>,
 }
 
+impl CompilerError {
+    pub fn new(message: String, span: Span) -> Self {
+        CompilerError {
+            message,
+            row: span.row,
+            col: span.col,
+            file: span.file,
+            code: None,
+            related: Vec::new(),
+            severity: Severity::Error,
+        }
+    }
+
+    pub fn with_code(mut self, code: u32) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    /// Points at `span` as well, unless it is unknown.
+    pub fn with_related(mut self, message: String, span: Span) -> Self {
+        if span != Span::default() {
+            self.related.push(RelatedInformation {
+                message,
+                row: span.row,
+                col: span.col,
+                file: span.file,
+            });
+        }
+        self
+    }
+}
+
 impl<T> CompilerResult<T> {
     pub fn new(result: Option<T>, errors: Vec<CompilerError>) -> Self {
         CompilerResult { result, errors }
     }
 
+    /// Whether any of the diagnostics is an error rather than a warning.
     pub fn has_errors(&self) -> bool {
-        !self.errors.is_empty()
+        self.errors
+            .iter()
+            .any(|error| error.severity == Severity::Error)
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,974 @@
+use std::collections::HashMap;
+
+use crate::ast::key_name;
+use crate::binder::is_type;
+use crate::parser::{Node, Parameter};
+use crate::symboltable::SymbolTable;
+use crate::tokenizer::TokenType;
+
+/// A point in the control flow of a program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FlowId(usize);
+
+/// How control reaches a point of a program, looking back from there to the points before
+/// it.
+#[derive(Debug, Clone)]
+pub enum FlowNode<'a> {
+    /// The start of the program or of a function body. A function expression starts where
+    /// it is written, since what is known there about constants and variables that are
+    /// never reassigned still holds inside it.
+    Start(Option<FlowId>),
+    Unreachable,
+    /// Where branches join; the head of a loop is joined by the end of its body as well.
+    Label {
+        antecedents: Vec<FlowId>,
+        is_loop: bool,
+    },
+    /// A name or property given a value by a declaration, an assignment or a loop.
+    Assignment {
+        target: &'a Node,
+        antecedent: FlowId,
+    },
+    /// Past `condition`, where it was true or false as `assume_true` says.
+    Condition {
+        condition: &'a Node,
+        assume_true: bool,
+        antecedent: FlowId,
+    },
+    /// Into the case of a switch at `clause`, or past every case for `None` when the switch
+    /// has no `default` and none of them matched. Control reaches a `default` only when no
+    /// other case matched.
+    SwitchClause {
+        switch: &'a Node,
+        clause: Option<usize>,
+        antecedent: FlowId,
+    },
+    /// Past a call made as a statement, which may be to an assertion function or one that
+    /// never returns.
+    Call {
+        call: &'a Node,
+        antecedent: FlowId,
+    },
+}
+
+/// The control flow of a program, with the point each reference is read at and the point
+/// each function body ends at.
+pub struct FlowGraph<'a> {
+    nodes: Vec<FlowNode<'a>>,
+    references: HashMap<*const Node, FlowId>,
+    ends: HashMap<*const Node, FlowId>,
+    reassigned: Vec<&'a Node>, // names and properties assigned other than by declaring them
+}
+
+impl<'a> FlowGraph<'a> {
+    pub fn node(&self, id: FlowId) -> &FlowNode<'a> {
+        &self.nodes[id.0]
+    }
+
+    /// Where a name or property access is read, unless it is only assigned to.
+    pub fn reference(&self, node: &Node) -> Option<FlowId> {
+        self.references.get(&(node as *const Node)).copied()
+    }
+
+    /// Where the body of a function ends. It is `Unreachable` when every path through the body
+    /// ends in a `return` or `throw`, as for concise arrow bodies; other dead ends, like
+    /// endless loops and calls that never return, show only when walking back from it.
+    pub fn end(&self, function: &Node) -> Option<FlowId> {
+        self.ends.get(&(function as *const Node)).copied()
+    }
+
+    /// The names and properties assigned to by assignments, updates and loops over existing
+    /// variables, wherever they are written, reachable or not.
+    pub fn reassigned(&self) -> &[&'a Node] {
+        &self.reassigned
+    }
+}
+
+const UNREACHABLE: FlowId = FlowId(0);
+
+/// Builds the flow graph of `program`. The nodes of the graph refer into `program`, and the
+/// side tables are keyed by the nodes of `program` itself, not a copy of it.
+pub fn build_flow(program: &Node) -> FlowGraph<'_> {
+    let mut builder = Builder {
+        graph: FlowGraph {
+            nodes: vec![FlowNode::Unreachable, FlowNode::Start(None)],
+            references: HashMap::new(),
+            ends: HashMap::new(),
+            reassigned: Vec::new(),
+        },
+        current: FlowId(1),
+        jumps: Vec::new(),
+        label: None,
+        assignments: Vec::new(),
+    };
+    builder.statement(program);
+    builder.graph
+}
+
+/// Where `break` and `continue` inside a statement go.
+struct Jump {
+    label: Option<String>,
+    breaks: FlowId,
+    continues: Option<FlowId>, // for loops
+    unlabeled: bool,           // whether a plain `break` leaves it, as for loops and switches
+}
+
+struct Builder<'a> {
+    graph: FlowGraph<'a>,
+    current: FlowId,
+    jumps: Vec<Jump>,
+    label: Option<String>, // of the labeled statement whose body is the next loop
+    // The assignments inside each enclosing `try` block, after any of which its `catch` or
+    // `finally` may start
+    assignments: Vec<Vec<FlowId>>,
+}
+
+impl<'a> Builder<'a> {
+    fn add(&mut self, node: FlowNode<'a>) -> FlowId {
+        self.graph.nodes.push(node);
+        FlowId(self.graph.nodes.len() - 1)
+    }
+
+    fn label(&mut self, is_loop: bool) -> FlowId {
+        self.add(FlowNode::Label {
+            antecedents: Vec::new(),
+            is_loop,
+        })
+    }
+
+    fn join(&mut self, label: FlowId, flow: FlowId) {
+        if flow == UNREACHABLE {
+            return;
+        }
+        if let FlowNode::Label { antecedents, .. } = &mut self.graph.nodes[label.0] {
+            if !antecedents.contains(&flow) {
+                antecedents.push(flow);
+            }
+        }
+    }
+
+    /// `label` once everything joining it has, or the only point joining it.
+    fn finish(&self, label: FlowId) -> FlowId {
+        match self.graph.node(label) {
+            FlowNode::Label {
+                antecedents,
+                is_loop: false,
+            } => match antecedents.as_slice() {
+                [] => UNREACHABLE,
+                [only] => *only,
+                _ => label,
+            },
+            _ => label,
+        }
+    }
+
+    /// Moves past `make`'s node, unless control can't get here.
+    fn flow_to(&mut self, make: impl FnOnce(FlowId) -> FlowNode<'a>) {
+        if self.current != UNREACHABLE {
+            self.current = self.add(make(self.current));
+        }
+    }
+
+    fn assign(&mut self, target: &'a Node) {
+        self.flow_to(|antecedent| FlowNode::Assignment { target, antecedent });
+        if self.current != UNREACHABLE {
+            for assignments in &mut self.assignments {
+                assignments.push(self.current);
+            }
+        }
+    }
+
+    fn bind(&mut self, target: &'a Node) {
+        if let Node::ObjectPattern(_) | Node::ArrayPattern(_) = target {
+            self.pattern_defaults(target);
+        }
+        for identifier in target.binding_identifiers() {
+            self.assign(identifier);
+        }
+    }
+
+    /// Evaluates the default values and computed keys inside a destructuring pattern.
+    fn pattern_defaults(&mut self, pattern: &'a Node) {
+        match pattern {
+            Node::ObjectPattern(elements) => {
+                for element in elements {
+                    self.pattern_defaults(element);
+                }
+            }
+            Node::ArrayPattern(elements) => {
+                for element in elements.iter().flatten() {
+                    self.pattern_defaults(element);
+                }
+            }
+            Node::PatternProperty {
+                key,
+                computed,
+                value,
+            } => {
+                if *computed {
+                    self.expression(key);
+                }
+                self.pattern_defaults(value);
+            }
+            Node::AssignmentPattern { left, right } => {
+                self.expression(right);
+                self.pattern_defaults(left);
+            }
+            Node::RestElement(argument) => self.pattern_defaults(argument),
+            _ => {}
+        }
+    }
+
+    fn statement(&mut self, node: &'a Node) {
+        match node {
+            Node::Program(statements) | Node::BlockStatement(statements, _) => {
+                for statement in statements {
+                    self.statement(statement);
+                }
+            }
+            Node::VariableDeclaration { declarations, .. } => {
+                for declarator in declarations {
+                    if let Some(init) = &declarator.init {
+                        self.expression(init);
+                        self.bind(&declarator.id);
+                    }
+                }
+            }
+            Node::FunctionDeclaration { .. } => self.function(node, false),
+            Node::ClassDeclaration {
+                extends,
+                members,
+                decorators,
+                ..
+            } => {
+                for decorator in decorators {
+                    self.expression(decorator);
+                }
+                if let Some(extends) = extends {
+                    self.expression(extends);
+                }
+                for member in members {
+                    match member {
+                        Node::MethodDefinition { .. } => self.function(member, false),
+                        Node::PropertyDefinition {
+                            key,
+                            computed,
+                            value,
+                            ..
+                        } => {
+                            if *computed {
+                                self.expression(key);
+                            }
+                            if let Some(value) = value {
+                                self.expression(value);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Node::EnumDeclaration { members, .. } => {
+                for member in members {
+                    if let Some(init) = &member.init {
+                        self.expression(init);
+                    }
+                }
+            }
+            Node::NamespaceDeclaration {
+                body,
+                is_declare: false,
+                ..
+            } => {
+                for statement in body {
+                    self.statement(statement);
+                }
+            }
+            Node::ExportNamedDeclaration {
+                declaration: Some(declaration),
+                ..
+            } => self.statement(declaration),
+            Node::ExportDefaultDeclaration(declaration) => self.statement(declaration),
+            Node::ExpressionStatement(expression) => {
+                self.expression(expression);
+                if let Node::CallExpression { .. } = expression.as_ref() {
+                    self.flow_to(|antecedent| FlowNode::Call {
+                        call: expression,
+                        antecedent,
+                    });
+                }
+            }
+            Node::ReturnStatement(argument) => {
+                if let Some(argument) = argument {
+                    self.expression(argument);
+                }
+                self.current = UNREACHABLE;
+            }
+            Node::ThrowStatement(argument) => {
+                self.expression(argument);
+                self.current = UNREACHABLE;
+            }
+            Node::IfStatement {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let (then_label, else_label) = (self.label(false), self.label(false));
+                self.condition(condition, then_label, else_label);
+                self.current = self.finish(then_label);
+                self.statement(then_branch);
+                let then_end = self.current;
+                self.current = self.finish(else_label);
+                if let Some(else_branch) = else_branch {
+                    self.statement(else_branch);
+                }
+                let after = self.label(false);
+                self.join(after, then_end);
+                self.join(after, self.current);
+                self.current = self.finish(after);
+            }
+            Node::WhileStatement { test, body } => {
+                let label = self.label.take();
+                let head = self.loop_head();
+                let (body_label, exit) = (self.label(false), self.label(false));
+                self.condition(test, body_label, exit);
+                self.current = self.finish(body_label);
+                self.loop_body(body, label, exit, head);
+                self.join(head, self.current);
+                self.current = self.finish(exit);
+            }
+            Node::DoWhileStatement { body, test } => {
+                let label = self.label.take();
+                let head = self.loop_head();
+                let (continues, exit) = (self.label(false), self.label(false));
+                self.loop_body(body, label, exit, continues);
+                self.join(continues, self.current);
+                self.current = self.finish(continues);
+                self.condition(test, head, exit);
+                self.current = self.finish(exit);
+            }
+            Node::ForStatement {
+                init,
+                test,
+                update,
+                body,
+            } => {
+                let label = self.label.take();
+                if let Some(init) = init {
+                    self.statement(init);
+                }
+                let head = self.loop_head();
+                let (body_label, exit) = (self.label(false), self.label(false));
+                match test {
+                    Some(test) => self.condition(test, body_label, exit),
+                    None => self.join(body_label, self.current),
+                }
+                self.current = self.finish(body_label);
+                let continues = self.label(false);
+                self.loop_body(body, label, exit, continues);
+                self.join(continues, self.current);
+                self.current = self.finish(continues);
+                if let Some(update) = update {
+                    self.expression(update);
+                }
+                self.join(head, self.current);
+                self.current = self.finish(exit);
+            }
+            Node::ForInStatement { left, right, body }
+            | Node::ForOfStatement {
+                left, right, body, ..
+            } => {
+                let label = self.label.take();
+                self.expression(right);
+                let head = self.loop_head();
+                let exit = self.label(false);
+                self.join(exit, self.current);
+                match left.as_ref() {
+                    Node::VariableDeclaration { declarations, .. } => {
+                        for declarator in declarations {
+                            self.bind(&declarator.id);
+                        }
+                    }
+                    target => self.assignment_target(target),
+                }
+                self.loop_body(body, label, exit, head);
+                self.join(head, self.current);
+                self.current = self.finish(exit);
+            }
+            Node::SwitchStatement {
+                discriminant,
+                cases,
+            } => {
+                self.expression(discriminant);
+                for case in cases {
+                    if let Some(test) = &case.test {
+                        self.expression(test);
+                    }
+                }
+                let before = self.current;
+                let exit = self.label(false);
+                self.jumps.push(Jump {
+                    label: None,
+                    breaks: exit,
+                    continues: None,
+                    unlabeled: true,
+                });
+                let mut fallthrough = UNREACHABLE;
+                for (index, case) in cases.iter().enumerate() {
+                    let clause = self.label(false);
+                    if before != UNREACHABLE {
+                        let entry = self.add(FlowNode::SwitchClause {
+                            switch: node,
+                            clause: Some(index),
+                            antecedent: before,
+                        });
+                        self.join(clause, entry);
+                    }
+                    self.join(clause, fallthrough);
+                    self.current = self.finish(clause);
+                    for statement in &case.consequent {
+                        self.statement(statement);
+                    }
+                    fallthrough = self.current;
+                }
+                self.jumps.pop();
+                self.join(exit, fallthrough);
+                if before != UNREACHABLE && cases.iter().all(|case| case.test.is_some()) {
+                    let unmatched = self.add(FlowNode::SwitchClause {
+                        switch: node,
+                        clause: None,
+                        antecedent: before,
+                    });
+                    self.join(exit, unmatched);
+                }
+                self.current = self.finish(exit);
+            }
+            Node::LabeledStatement { label, body } => match body.as_ref() {
+                Node::WhileStatement { .. }
+                | Node::DoWhileStatement { .. }
+                | Node::ForStatement { .. }
+                | Node::ForInStatement { .. }
+                | Node::ForOfStatement { .. } => {
+                    self.label = Some(label.clone());
+                    self.statement(body);
+                }
+                _ => {
+                    let exit = self.label(false);
+                    self.jumps.push(Jump {
+                        label: Some(label.clone()),
+                        breaks: exit,
+                        continues: None,
+                        unlabeled: false,
+                    });
+                    self.statement(body);
+                    self.jumps.pop();
+                    self.join(exit, self.current);
+                    self.current = self.finish(exit);
+                }
+            },
+            Node::BreakStatement(label) => {
+                let target = self.jumps.iter().rev().find(|jump| match label {
+                    Some(label) => jump.label.as_ref() == Some(label),
+                    None => jump.unlabeled,
+                });
+                if let Some(target) = target.map(|jump| jump.breaks) {
+                    self.join(target, self.current);
+                }
+                self.current = UNREACHABLE;
+            }
+            Node::ContinueStatement(label) => {
+                let target = self.jumps.iter().rev().find(|jump| {
+                    jump.continues.is_some()
+                        && label
+                            .as_ref()
+                            .is_none_or(|label| jump.label.as_ref() == Some(label))
+                });
+                if let Some(target) = target.and_then(|jump| jump.continues) {
+                    self.join(target, self.current);
+                }
+                self.current = UNREACHABLE;
+            }
+            Node::TryStatement {
+                block,
+                handler,
+                finalizer,
+            } => {
+                let before = self.current;
+                self.assignments.push(Vec::new());
+                self.statement(block);
+                let mut assigned = self.assignments.pop().unwrap_or_default();
+                let mut ends = vec![self.current];
+                if let Some(handler) = handler {
+                    // The block may throw before any of its assignments, or after any of them
+                    let start = self.label(false);
+                    self.join(start, before);
+                    for assignment in &assigned {
+                        self.join(start, *assignment);
+                    }
+                    self.current = self.finish(start);
+                    self.assignments.push(Vec::new());
+                    if let Some(param) = &handler.param {
+                        self.bind(param);
+                    }
+                    self.statement(&handler.body);
+                    assigned.extend(self.assignments.pop().unwrap_or_default());
+                    ends.push(self.current);
+                }
+                let after = self.label(false);
+                for end in &ends {
+                    self.join(after, *end);
+                }
+                if let Some(finalizer) = finalizer {
+                    // Whatever threw or returned runs the finalizer too
+                    self.join(after, before);
+                    for assignment in &assigned {
+                        self.join(after, *assignment);
+                    }
+                    self.current = self.finish(after);
+                    self.statement(finalizer);
+                    if ends.iter().all(|end| *end == UNREACHABLE) {
+                        self.current = UNREACHABLE;
+                    }
+                } else {
+                    self.current = self.finish(after);
+                }
+            }
+            node if is_declaration_only(node) => {}
+            node => self.expression(node),
+        }
+    }
+
+    fn loop_head(&mut self) -> FlowId {
+        let head = self.label(true);
+        self.join(head, self.current);
+        self.current = head;
+        head
+    }
+
+    fn loop_body(
+        &mut self,
+        body: &'a Node,
+        label: Option<String>,
+        breaks: FlowId,
+        continues: FlowId,
+    ) {
+        self.jumps.push(Jump {
+            label,
+            breaks,
+            continues: Some(continues),
+            unlabeled: true,
+        });
+        self.statement(body);
+        self.jumps.pop();
+    }
+
+    /// Evaluates `node` as a condition, going on to `when_true` or `when_false`.
+    fn condition(&mut self, node: &'a Node, when_true: FlowId, when_false: FlowId) {
+        match node {
+            Node::UnaryExpression { operator, argument } if operator == "!" => {
+                self.condition(argument, when_false, when_true)
+            }
+            Node::LogicalExpression {
+                left,
+                operator,
+                right,
+            } if operator == "&&" || operator == "||" => {
+                let rest = self.label(false);
+                if operator == "&&" {
+                    self.condition(left, rest, when_false);
+                } else {
+                    self.condition(left, when_true, rest);
+                }
+                self.current = self.finish(rest);
+                self.condition(right, when_true, when_false);
+            }
+            Node::Literal {
+                token_type: TokenType::True,
+                ..
+            } => self.join(when_true, self.current),
+            Node::Literal {
+                token_type: TokenType::False,
+                ..
+            } => self.join(when_false, self.current),
+            _ => {
+                self.expression(node);
+                if self.current == UNREACHABLE {
+                    return;
+                }
+                for (label, assume_true) in [(when_true, true), (when_false, false)] {
+                    let flow = self.add(FlowNode::Condition {
+                        condition: node,
+                        assume_true,
+                        antecedent: self.current,
+                    });
+                    self.join(label, flow);
+                }
+            }
+        }
+        self.current = UNREACHABLE;
+    }
+
+    fn expression(&mut self, node: &'a Node) {
+        match node {
+            Node::Identifier(..) => self.read(node),
+            Node::MemberExpression {
+                object,
+                property,
+                computed,
+                ..
+            } => {
+                self.expression(object);
+                if *computed {
+                    self.expression(property);
+                }
+                self.read(node);
+            }
+            Node::AssignmentExpression {
+                left,
+                operator,
+                right,
+            } => {
+                if operator == "=" {
+                    self.target_parts(left);
+                } else {
+                    self.expression(left);
+                }
+                if matches!(operator.as_str(), "&&=" | "||=" | "??=") {
+                    // The value may not be assigned at all
+                    let (assigns, skips) = (self.label(false), self.label(false));
+                    self.join(assigns, self.current);
+                    self.join(skips, self.current);
+                    self.current = self.finish(assigns);
+                    self.expression(right);
+                    self.assignment_target(left);
+                    let after = self.label(false);
+                    self.join(after, self.current);
+                    self.join(after, skips);
+                    self.current = self.finish(after);
+                } else {
+                    self.expression(right);
+                    self.assignment_target(left);
+                }
+            }
+            Node::UpdateExpression { argument, .. } => {
+                self.expression(argument);
+                self.assignment_target(argument);
+            }
+            Node::LogicalExpression {
+                left,
+                operator,
+                right,
+            } if operator == "&&" || operator == "||" => {
+                let (when_true, when_false) = (self.label(false), self.label(false));
+                self.condition(left, when_true, when_false);
+                let (evaluates, skips) = if operator == "&&" {
+                    (when_true, when_false)
+                } else {
+                    (when_false, when_true)
+                };
+                self.current = self.finish(evaluates);
+                self.expression(right);
+                let after = self.label(false);
+                self.join(after, self.current);
+                self.join(after, self.finish(skips));
+                self.current = self.finish(after);
+            }
+            Node::ConditionalExpression {
+                test,
+                consequent,
+                alternate,
+            } => {
+                let (when_true, when_false) = (self.label(false), self.label(false));
+                self.condition(test, when_true, when_false);
+                self.current = self.finish(when_true);
+                self.expression(consequent);
+                let consequent_end = self.current;
+                self.current = self.finish(when_false);
+                self.expression(alternate);
+                let after = self.label(false);
+                self.join(after, consequent_end);
+                self.join(after, self.current);
+                self.current = self.finish(after);
+            }
+            Node::FunctionDeclaration { .. } | Node::ArrowFunctionExpression { .. } => {
+                self.function(node, true)
+            }
+            Node::ClassDeclaration { .. } => self.statement(node),
+            Node::ObjectExpression(properties) => {
+                for property in properties {
+                    match property {
+                        Node::Property {
+                            key,
+                            computed,
+                            value,
+                            ..
+                        } => {
+                            if *computed {
+                                self.expression(key);
+                            }
+                            self.expression(value);
+                        }
+                        Node::MethodDefinition { .. } => self.function(property, true),
+                        property => self.expression(property),
+                    }
+                }
+            }
+            node if is_type(node) => {}
+            node => {
+                for child in node.children() {
+                    self.expression(child);
+                }
+            }
+        }
+    }
+
+    fn read(&mut self, node: &'a Node) {
+        self.graph.references.insert(node, self.current);
+    }
+
+    /// Evaluates what an assignment to `target` evaluates before the value: the object of a
+    /// property and a computed key.
+    fn target_parts(&mut self, target: &'a Node) {
+        match target {
+            Node::MemberExpression {
+                object,
+                property,
+                computed,
+                ..
+            } => {
+                self.expression(object);
+                if *computed {
+                    self.expression(property);
+                }
+            }
+            Node::ObjectPattern(_) | Node::ArrayPattern(_) => self.pattern_defaults(target),
+            _ => {}
+        }
+    }
+
+    fn assignment_target(&mut self, target: &'a Node) {
+        match target {
+            Node::Identifier(..) | Node::MemberExpression { .. } => {
+                self.graph.reassigned.push(target);
+                self.assign(target);
+            }
+            Node::NonNullExpression(inner) => self.assignment_target(inner),
+            target => {
+                for identifier in target.binding_identifiers() {
+                    self.graph.reassigned.push(identifier);
+                    self.assign(identifier);
+                }
+            }
+        }
+    }
+
+    /// Builds the flow of a function body, which starts afresh: only an expression's body
+    /// looks back to where it is written.
+    fn function(&mut self, node: &'a Node, is_expression: bool) {
+        let (params, body): (&'a [Parameter], Option<&'a Node>) = match node {
+            Node::FunctionDeclaration { params, body, .. }
+            | Node::MethodDefinition { params, body, .. } => (params, body.as_deref()),
+            Node::ArrowFunctionExpression { params, body, .. } => (params, Some(body)),
+            _ => return,
+        };
+        let outer = (
+            self.current,
+            std::mem::take(&mut self.jumps),
+            self.label.take(),
+            std::mem::take(&mut self.assignments),
+        );
+        let container = (is_expression && self.current != UNREACHABLE).then_some(self.current);
+        self.current = self.add(FlowNode::Start(container));
+        for param in params {
+            self.pattern_defaults(&param.binding);
+            if let Some(init) = &param.init {
+                self.expression(init);
+            }
+        }
+        match body {
+            Some(body @ Node::BlockStatement(..)) => self.statement(body),
+            Some(body) => {
+                self.expression(body);
+                self.current = UNREACHABLE;
+            }
+            None => {}
+        }
+        self.graph.ends.insert(node, self.current);
+        (self.current, self.jumps, self.label, self.assignments) = outer;
+    }
+}
+
+fn is_declaration_only(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::InterfaceDeclaration { .. }
+            | Node::TypeAliasDeclaration { .. }
+            | Node::NamespaceDeclaration { .. }
+            | Node::AmbientModuleDeclaration { .. }
+            | Node::GlobalAugmentation(_)
+            | Node::AmbientDeclaration(_)
+            | Node::ImportDeclaration { .. }
+            | Node::ExportNamedDeclaration { .. }
+            | Node::ExportAllDeclaration { .. }
+            | Node::EmptyStatement
+    )
+}
+
+/// Whether `a` and `b` are the same reference: the same name, `this`, or the same property
+/// of the same reference, as in `a.b["c"]`.
+pub fn is_same_reference(table: &SymbolTable, a: &Node, b: &Node) -> bool {
+    match (unwrapped(a), unwrapped(b)) {
+        (a @ Node::Identifier(..), b @ Node::Identifier(..)) => {
+            let symbol = table.symbol_of(a);
+            symbol.is_some() && symbol == table.symbol_of(b)
+        }
+        (Node::ThisExpression, Node::ThisExpression) => true,
+        (
+            Node::MemberExpression {
+                object: a,
+                property: a_property,
+                computed: a_computed,
+                ..
+            },
+            Node::MemberExpression {
+                object: b,
+                property: b_property,
+                computed: b_computed,
+                ..
+            },
+        ) => {
+            let name = key_name(a_property, *a_computed);
+            name.is_some()
+                && name == key_name(b_property, *b_computed)
+                && is_same_reference(table, a, b)
+        }
+        _ => false,
+    }
+}
+
+/// Whether `reference` is a property of `target`, at any depth, whose value may change when
+/// `target` is assigned.
+pub fn is_part_of(table: &SymbolTable, reference: &Node, target: &Node) -> bool {
+    match unwrapped(reference) {
+        Node::MemberExpression { object, .. } => {
+            is_same_reference(table, object, target) || is_part_of(table, object, target)
+        }
+        _ => false,
+    }
+}
+
+/// The name of the property `node` reads of `reference`, as `kind` in `reference.kind`.
+pub fn property_of<'n>(table: &SymbolTable, node: &'n Node, reference: &Node) -> Option<&'n str> {
+    match unwrapped(node) {
+        Node::MemberExpression {
+            object,
+            property,
+            computed,
+            ..
+        } if is_same_reference(table, object, reference) => key_name(property, *computed),
+        _ => None,
+    }
+}
+
+/// `x!` and `x?.y` as references are `x` and `x.y`.
+pub fn unwrapped(node: &Node) -> &Node {
+    match node {
+        Node::NonNullExpression(inner) | Node::ChainExpression(inner) => unwrapped(inner),
+        node => node,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::tokenizer::Tokenizer;
+
+    fn parse(source: &str) -> Node {
+        let tokens = Tokenizer::new(source.to_string()).tokenize();
+        Parser::new(tokens).parse().result.expect("parses")
+    }
+
+    /// The nodes in `node` that `matches`, in source order.
+    fn find<'a>(node: &'a Node, matches: &dyn Fn(&Node) -> bool, found: &mut Vec<&'a Node>) {
+        if matches(node) {
+            found.push(node);
+        }
+        for child in node.children() {
+            find(child, matches, found);
+        }
+    }
+
+    fn named<'a>(program: &'a Node, name: &str) -> Vec<&'a Node> {
+        let mut found = Vec::new();
+        find(
+            program,
+            &|node| matches!(node, Node::Identifier(n, _) | Node::FunctionDeclaration { name: n, .. } if n == name),
+            &mut found,
+        );
+        found
+    }
+
+    #[test]
+    fn function_ends_after_a_return_or_throw_are_unreachable() {
+        let program = parse(
+            "function r() { return 1; }
+            function t() { throw 1; }
+            function b(a) { if (a) return; }
+            const c = () => 1;",
+        );
+        let graph = build_flow(&program);
+        for (name, reachable) in [("r", false), ("t", false), ("b", true)] {
+            let end = graph.end(named(&program, name)[0]).expect("has a body");
+            assert_eq!(
+                !matches!(graph.node(end), FlowNode::Unreachable),
+                reachable,
+                "{}",
+                name
+            );
+        }
+        let mut arrows = Vec::new();
+        find(
+            &program,
+            &|node| matches!(node, Node::ArrowFunctionExpression { .. }),
+            &mut arrows,
+        );
+        let end = graph.end(arrows[0]).expect("has a body");
+        assert!(matches!(graph.node(end), FlowNode::Unreachable));
+    }
+
+    #[test]
+    fn branches_join_at_a_label_and_code_after_a_return_is_unreachable() {
+        let program = parse("function f(a, y) { if (a) { y = 1; } y; return; y; }");
+        let graph = build_flow(&program);
+        let y = named(&program, "y");
+        let joined = graph.reference(y[2]).expect("is read");
+        let FlowNode::Label {
+            antecedents,
+            is_loop: false,
+        } = graph.node(joined)
+        else {
+            panic!("reads after a join");
+        };
+        assert_eq!(antecedents.len(), 2);
+        assert!(antecedents
+            .iter()
+            .any(|id| matches!(graph.node(*id), FlowNode::Assignment { target, .. } if std::ptr::eq(*target, y[1]))));
+        let after_return = graph.reference(y[3]).expect("is read");
+        assert!(matches!(graph.node(after_return), FlowNode::Unreachable));
+    }
+
+    #[test]
+    fn reassigned_lists_assignments_but_not_declarations() {
+        let program = parse("let a = 1; let b; let c = 0; a = 2; for (b of []) {} c++;");
+        let graph = build_flow(&program);
+        let names: Vec<&str> = graph
+            .reassigned()
+            .iter()
+            .filter_map(|node| match node {
+                Node::Identifier(name, _) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(names, ["a", "b", "c"]);
+    }
+}
@@ -2,4 +2,25 @@ pub mod tokenizer;
 pub mod parser;
 pub mod symboltable;
 pub mod types_checking;
-pub mod error_handling;
\ No newline at end of file
+pub mod error_handling;
+pub mod monomorphize;
+pub mod enums;
+pub mod modules;
+pub mod namespaces;
+pub mod destructuring;
+pub mod runtime;
+pub mod switches;
+pub mod state_machines;
+pub mod closures;
+pub mod optional_chains;
+pub mod decorators;
+pub mod binder;
+pub mod tdz;
+pub mod assignments;
+pub mod lints;
+pub mod type_lowering;
+pub mod checker;
+pub mod flow;
+pub mod narrowing;
+pub mod names;
+pub mod ast;
@@ -0,0 +1,568 @@
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use crate::error_handling::{CompilerError, Severity, Span};
+use crate::parser::{CatchClause, ImportSpecifier, MethodKind, Node, Parameter, VariableKind};
+use crate::symboltable::{Meaning, ScopeId, SymbolFlags, SymbolId, SymbolTable};
+
+/// How a lint reports what it finds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintLevel {
+    Off,
+    Warning,
+    Error,
+}
+
+impl FromStr for LintLevel {
+    type Err = String;
+
+    fn from_str(level: &str) -> Result<Self, Self::Err> {
+        match level {
+            "off" => Ok(LintLevel::Off),
+            "warn" | "warning" => Ok(LintLevel::Warning),
+            "error" => Ok(LintLevel::Error),
+            _ => Err(format!(
+                "Unknown lint level '{}', expected 'off', 'warn' or 'error'",
+                level
+            )),
+        }
+    }
+}
+
+/// Which lints `lint` runs, and how loudly, like tsc's `noUnusedLocals`,
+/// `noUnusedParameters` and `allowUnreachableCode`. Everything but shadowing is a warning by
+/// default.
+#[derive(Debug, Clone)]
+pub struct LintOptions {
+    pub unused_locals: LintLevel,
+    pub unused_parameters: LintLevel, // except those named `_` or `_something`
+    pub unused_imports: LintLevel,
+    pub unreachable_code: LintLevel, // after `return`, `throw`, `break` and `continue`
+    pub shadowed_variables: LintLevel,
+}
+
+impl Default for LintOptions {
+    fn default() -> Self {
+        LintOptions {
+            unused_locals: LintLevel::Warning,
+            unused_parameters: LintLevel::Warning,
+            unused_imports: LintLevel::Warning,
+            unreachable_code: LintLevel::Warning,
+            shadowed_variables: LintLevel::Off,
+        }
+    }
+}
+
+impl LintOptions {
+    /// Sets a lint by the name it has on the command line, e.g. `unused-locals`.
+    pub fn set(&mut self, name: &str, level: LintLevel) -> Result<(), String> {
+        let lint = match name {
+            "unused-locals" => &mut self.unused_locals,
+            "unused-parameters" => &mut self.unused_parameters,
+            "unused-imports" => &mut self.unused_imports,
+            "unreachable-code" => &mut self.unreachable_code,
+            "shadowed-variables" => &mut self.shadowed_variables,
+            _ => return Err(format!("Unknown lint '{}'", name)),
+        };
+        *lint = level;
+        Ok(())
+    }
+}
+
+/// Reports unused locals, parameters and imports, unreachable statements and names that
+/// shadow an outer one, each at the level `options` gives it.
+///
+/// A name is used once it is read as a value or named in a type; only assigning it doesn't
+/// count. Exported and ambient declarations are never unused, and neither are top-level
+/// declarations of a script, which other scripts can see. The diagnostics come sorted by
+/// position.
+///
+/// `table` must come from binding `program`, or a tree `program` was cloned from.
+pub fn lint(program: &Node, table: &SymbolTable, options: &LintOptions) -> Vec<CompilerError> {
+    let mut linter = Linter {
+        table,
+        options,
+        writes: HashSet::new(),
+        reads: HashMap::new(),
+        exempt: HashSet::new(),
+        parameters: Vec::new(),
+        imports: Vec::new(),
+        errors: Vec::new(),
+    };
+    linter.visit(program, SymbolTable::GLOBAL, false);
+    linter.unused_locals(is_module(program));
+    linter.unused_parameters();
+    linter.unused_imports();
+    linter.shadowed_variables();
+    let mut errors = linter.errors;
+    errors.sort_by_key(|error| (error.row, error.col));
+    errors
+}
+
+struct Linter<'a> {
+    table: &'a SymbolTable,
+    options: &'a LintOptions,
+    writes: HashSet<*const Node>, // identifiers that declare a name or only assign to it
+    reads: HashMap<SymbolId, usize>,
+    exempt: HashSet<SymbolId>, // exported or ambient
+    parameters: Vec<&'a Node>, // identifiers that should be read
+    imports: Vec<Vec<(&'a str, Span, Option<SymbolId>)>>, // by import declaration
+    errors: Vec<CompilerError>,
+}
+
+impl<'a> Linter<'a> {
+    fn visit(&mut self, node: &'a Node, scope: ScopeId, ambient: bool) {
+        let scope = self.table.scope_of(node).unwrap_or(scope);
+        let ambient = ambient
+            || matches!(
+                node,
+                Node::AmbientDeclaration(_)
+                    | Node::AmbientModuleDeclaration { .. }
+                    | Node::GlobalAugmentation(_)
+                    | Node::NamespaceDeclaration {
+                        is_declare: true,
+                        ..
+                    }
+                    | Node::EnumDeclaration {
+                        is_declare: true,
+                        ..
+                    }
+            );
+        match node {
+            Node::Identifier(..) if !self.writes.contains(&(node as *const Node)) => {
+                self.read(self.table.symbol_of(node))
+            }
+            // `A.B` in a type reads the namespace `A`
+            Node::TypeReference { name, .. } => {
+                let symbol = self.table.symbol_of(node).or_else(|| {
+                    let (namespace, _) = name.split_once('.')?;
+                    self.table.lookup(scope, namespace, Meaning::Namespace)
+                });
+                self.read(symbol);
+            }
+            Node::VariableDeclaration { declarations, .. } => {
+                for declarator in declarations {
+                    for identifier in declarator.id.binding_identifiers() {
+                        self.declare(identifier, ambient);
+                    }
+                }
+            }
+            Node::AssignmentExpression { left, operator, .. } if operator == "=" => {
+                if let Node::Identifier(..) = left.as_ref() {
+                    self.writes.insert(left.as_ref() as *const Node);
+                }
+            }
+            Node::FunctionDeclaration { params, body, .. } => {
+                self.declare_parameters(params, body.is_some() && !ambient)
+            }
+            Node::ArrowFunctionExpression { params, .. } => self.declare_parameters(params, true),
+            // A setter has to take its parameter whether it uses it or not
+            Node::MethodDefinition {
+                params, body, kind, ..
+            } => self.declare_parameters(
+                params,
+                body.is_some() && !ambient && *kind != MethodKind::Set,
+            ),
+            Node::TryStatement {
+                handler:
+                    Some(CatchClause {
+                        param: Some(param), ..
+                    }),
+                ..
+            } => {
+                for identifier in param.binding_identifiers() {
+                    self.declare(identifier, true);
+                }
+            }
+            Node::ExportNamedDeclaration {
+                declaration: Some(declaration),
+                ..
+            }
+            | Node::ExportDefaultDeclaration(declaration) => self.export(declaration),
+            Node::ExportNamedDeclaration {
+                specifiers,
+                source: None,
+                ..
+            } => {
+                for specifier in specifiers {
+                    for meaning in [Meaning::Value, Meaning::Type, Meaning::Namespace] {
+                        if let Some(symbol) = self.table.lookup(scope, &specifier.local, meaning) {
+                            self.exempt.insert(symbol);
+                        }
+                    }
+                }
+            }
+            Node::ImportDeclaration { specifiers, .. } if !specifiers.is_empty() => {
+                let imports = specifiers
+                    .iter()
+                    .map(|specifier| {
+                        let (ImportSpecifier::Default(local, span)
+                        | ImportSpecifier::Namespace(local, span)
+                        | ImportSpecifier::Named { local, span, .. }) = specifier;
+                        let symbol = self.table.scope(scope).symbols.get(local).copied();
+                        (local.as_str(), *span, symbol)
+                    })
+                    .collect();
+                self.imports.push(imports);
+            }
+            Node::Program(statements)
+            | Node::BlockStatement(statements, _)
+            | Node::NamespaceDeclaration {
+                body: statements, ..
+            } => self.unreachable_code(statements),
+            Node::SwitchStatement { cases, .. } => {
+                for case in cases {
+                    self.unreachable_code(&case.consequent);
+                }
+            }
+            _ => {}
+        }
+        if ambient && !matches!(node, Node::Identifier(..)) {
+            if let Some(symbol) = self.table.symbol_of(node) {
+                self.exempt.insert(symbol);
+            }
+        }
+        for child in node.children() {
+            self.visit(child, scope, ambient);
+        }
+    }
+
+    fn read(&mut self, symbol: Option<SymbolId>) {
+        if let Some(symbol) = symbol {
+            *self.reads.entry(symbol).or_default() += 1;
+        }
+    }
+
+    fn declare(&mut self, identifier: &Node, exempt: bool) {
+        self.writes.insert(identifier as *const Node);
+        if let (true, Some(symbol)) = (exempt, self.table.symbol_of(identifier)) {
+            self.exempt.insert(symbol);
+        }
+    }
+
+    /// Declares the names of `params`, which should be read when `checked`. `this`
+    /// parameters and parameter properties never have to be.
+    fn declare_parameters(&mut self, params: &'a [Parameter], checked: bool) {
+        for param in params {
+            let is_property = param.modifiers.accessibility.is_some() || param.modifiers.readonly;
+            for identifier in param.binding.binding_identifiers() {
+                self.declare(identifier, false);
+                if checked && !is_property && param.name() != Some("this") {
+                    self.parameters.push(identifier);
+                }
+            }
+        }
+    }
+
+    fn export(&mut self, declaration: &Node) {
+        match declaration {
+            Node::VariableDeclaration { declarations, .. } => {
+                for declarator in declarations {
+                    for identifier in declarator.id.binding_identifiers() {
+                        if let Some(symbol) = self.table.symbol_of(identifier) {
+                            self.exempt.insert(symbol);
+                        }
+                    }
+                }
+            }
+            declaration => {
+                if let Some(symbol) = self.table.symbol_of(declaration) {
+                    self.exempt.insert(symbol);
+                }
+            }
+        }
+    }
+
+    fn is_unused(&self, symbol: SymbolId) -> bool {
+        !self.reads.contains_key(&symbol) && !self.exempt.contains(&symbol)
+    }
+
+    fn unreachable_code(&mut self, statements: &[Node]) {
+        let Some(end) = statements.iter().position(terminates) else {
+            return;
+        };
+        // Hoisted declarations still take effect, so only the first executable statement
+        // after the end is reported
+        let unreachable = statements[end + 1..].iter().find(|statement| {
+            !matches!(
+                statement,
+                Node::FunctionDeclaration { .. }
+                    | Node::InterfaceDeclaration { .. }
+                    | Node::TypeAliasDeclaration { .. }
+                    | Node::EmptyStatement
+            ) && !matches!(statement, Node::VariableDeclaration {
+                    kind: VariableKind::Var,
+                    declarations,
+                } if declarations.iter().all(|declarator| declarator.init.is_none()))
+        });
+        if let Some(statement) = unreachable {
+            let error =
+                CompilerError::new("Unreachable code detected.".to_string(), statement.span())
+                    .with_code(7027);
+            self.report(self.options.unreachable_code, error);
+        }
+    }
+
+    fn unused_locals(&mut self, is_module: bool) {
+        let locals = SymbolFlags::VARIABLE
+            | SymbolFlags::BLOCK_SCOPED_VARIABLE
+            | SymbolFlags::FUNCTION
+            | SymbolFlags::CLASS
+            | SymbolFlags::INTERFACE
+            | SymbolFlags::TYPE_ALIAS
+            | SymbolFlags::ENUM
+            | SymbolFlags::NAMESPACE
+            | SymbolFlags::TYPE_NAMESPACE;
+        let mut errors = Vec::new();
+        for (id, symbol) in self.table.symbols() {
+            if !self.is_unused(id)
+                || !symbol.flags.intersects(locals)
+                || symbol
+                    .flags
+                    .intersects(SymbolFlags::PARAMETER | SymbolFlags::IMPORT)
+                || symbol.name == "arguments"
+            {
+                continue;
+            }
+            let scope = self.table.declaring_scope(id);
+            if scope == SymbolTable::BUILTINS || (scope == SymbolTable::GLOBAL && !is_module) {
+                continue;
+            }
+            errors.push(if symbol.flags.has_meaning(Meaning::Value) {
+                never_read(&symbol.name, symbol.span)
+            } else {
+                CompilerError::new(
+                    format!("'{}' is declared but never used.", symbol.name),
+                    symbol.span,
+                )
+                .with_code(6196)
+            });
+        }
+        for error in errors {
+            self.report(self.options.unused_locals, error);
+        }
+    }
+
+    fn unused_parameters(&mut self) {
+        for identifier in std::mem::take(&mut self.parameters) {
+            let Node::Identifier(name, span) = identifier else {
+                continue;
+            };
+            let Some(symbol) = self.table.symbol_of(identifier) else {
+                continue;
+            };
+            if self.is_unused(symbol) && !name.starts_with('_') {
+                self.report(self.options.unused_parameters, never_read(name, *span));
+            }
+        }
+    }
+
+    fn unused_imports(&mut self) {
+        for imports in std::mem::take(&mut self.imports) {
+            let unused: Vec<(&str, Span)> = imports
+                .iter()
+                .filter(|(_, _, symbol)| symbol.is_some_and(|symbol| self.is_unused(symbol)))
+                .map(|(name, span, _)| (*name, *span))
+                .collect();
+            if unused.len() > 1 && unused.len() == imports.len() {
+                let error = CompilerError::new(
+                    "All imports in import declaration are unused.".to_string(),
+                    unused[0].1,
+                )
+                .with_code(6192);
+                self.report(self.options.unused_imports, error);
+                continue;
+            }
+            for (name, span) in unused {
+                self.report(self.options.unused_imports, never_read(name, span));
+            }
+        }
+    }
+
+    fn shadowed_variables(&mut self) {
+        let mut errors = Vec::new();
+        for (id, symbol) in self.table.symbols() {
+            if !symbol.flags.has_meaning(Meaning::Value)
+                || symbol
+                    .flags
+                    .intersects(SymbolFlags::ENUM_MEMBER | SymbolFlags::TYPE_PARAMETER)
+                || symbol.name == "arguments"
+            {
+                continue;
+            }
+            let Some(parent) = self.table.scope(self.table.declaring_scope(id)).parent else {
+                continue;
+            };
+            let Some(outer) = self.table.lookup(parent, &symbol.name, Meaning::Value) else {
+                continue;
+            };
+            // Built-ins aren't declared anywhere the shadowing could be a mistake about
+            if self.table.declaring_scope(outer) == SymbolTable::BUILTINS {
+                continue;
+            }
+            let error = CompilerError::new(
+                format!("'{}' is already declared in the upper scope.", symbol.name),
+                symbol.span,
+            )
+            .with_related(
+                format!("'{}' is declared here.", symbol.name),
+                self.table.symbol(outer).span,
+            );
+            errors.push(error);
+        }
+        for error in errors {
+            self.report(self.options.shadowed_variables, error);
+        }
+    }
+
+    fn report(&mut self, level: LintLevel, error: CompilerError) {
+        let severity = match level {
+            LintLevel::Off => return,
+            LintLevel::Warning => Severity::Warning,
+            LintLevel::Error => Severity::Error,
+        };
+        self.errors.push(error.with_severity(severity));
+    }
+}
+
+fn never_read(name: &str, span: Span) -> CompilerError {
+    CompilerError::new(
+        format!("'{}' is declared but its value is never read.", name),
+        span,
+    )
+    .with_code(6133)
+}
+
+/// Whether a file is a module, whose top-level declarations other files can't see.
+fn is_module(program: &Node) -> bool {
+    let Node::Program(body) = program else {
+        return false;
+    };
+    body.iter().any(|statement| {
+        matches!(
+            statement,
+            Node::ImportDeclaration { .. }
+                | Node::ExportNamedDeclaration { .. }
+                | Node::ExportDefaultDeclaration(_)
+                | Node::ExportAllDeclaration { .. }
+        )
+    })
+}
+
+/// Whether control never goes on past `statement` to the one after it.
+fn terminates(statement: &Node) -> bool {
+    match statement {
+        Node::ReturnStatement(_)
+        | Node::ThrowStatement(_)
+        | Node::BreakStatement(_)
+        | Node::ContinueStatement(_) => true,
+        Node::BlockStatement(statements, _) => statements.iter().any(terminates),
+        Node::IfStatement {
+            then_branch,
+            else_branch: Some(else_branch),
+            ..
+        } => terminates(then_branch) && terminates(else_branch),
+        Node::TryStatement {
+            block,
+            handler,
+            finalizer,
+        } => {
+            finalizer.as_deref().is_some_and(terminates)
+                || terminates(block)
+                    && handler
+                        .as_ref()
+                        .is_none_or(|handler| terminates(&handler.body))
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binder::bind;
+    use crate::parser::Parser;
+    use crate::tokenizer::Tokenizer;
+
+    /// The codes and messages of the diagnostics `options` gives for `source`.
+    fn run(source: &str, options: &LintOptions) -> Vec<(u32, String)> {
+        let tokens = Tokenizer::new(source.to_string()).tokenize();
+        let program = Parser::new(tokens).parse().result.expect("parses");
+        let table = bind(&program).result.expect("binds");
+        lint(&program, &table, options)
+            .into_iter()
+            .map(|error| (error.code.unwrap_or(0), error.message))
+            .collect()
+    }
+
+    fn codes(source: &str, options: &LintOptions) -> Vec<u32> {
+        run(source, options)
+            .into_iter()
+            .map(|(code, _)| code)
+            .collect()
+    }
+
+    #[test]
+    fn unused_locals_and_parameters_are_reported() {
+        let source = "function f(a: number, _b: number) { let c = 1; let d = 2; return d; }";
+        let errors = run(source, &LintOptions::default());
+        let messages: Vec<&str> = errors.iter().map(|(_, message)| message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "'a' is declared but its value is never read.",
+                "'c' is declared but its value is never read.",
+            ]
+        );
+        assert!(errors.iter().all(|(code, _)| *code == 6133));
+    }
+
+    #[test]
+    fn only_assigning_a_local_does_not_use_it() {
+        let source = "function f() { let a = 1; a = 2; let b = 1; b = b + 1; }";
+        let errors = run(source, &LintOptions::default());
+        assert_eq!(
+            errors,
+            [(
+                6133,
+                "'a' is declared but its value is never read.".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn statements_after_a_return_are_unreachable() {
+        let source = "function f() { return 1; f(); }";
+        assert_eq!(codes(source, &LintOptions::default()), [7027]);
+    }
+
+    #[test]
+    fn a_declaration_whose_imports_are_all_unused_is_reported_once() {
+        let options = LintOptions::default();
+        let source = "import { a, b } from './x';\nexport const c = 1;";
+        assert_eq!(codes(source, &options), [6192]);
+        let source = "import { a, b } from './x';\nexport const c = a;";
+        assert_eq!(codes(source, &options), [6133]);
+    }
+
+    #[test]
+    fn lints_can_be_turned_off_by_name() {
+        let mut options = LintOptions::default();
+        options.set("unused-locals", LintLevel::Off).unwrap();
+        assert!(codes("function f() { let a = 1; }", &options).is_empty());
+        assert!(options.set("unused-everything", LintLevel::Off).is_err());
+    }
+
+    #[test]
+    fn shadowing_is_only_reported_when_enabled() {
+        let source = "let a = 1; function f() { let a = 2; return a; }";
+        assert!(codes(source, &LintOptions::default()).is_empty());
+        let mut options = LintOptions::default();
+        options
+            .set("shadowed-variables", LintLevel::Warning)
+            .unwrap();
+        let errors = run(source, &options);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].1, "'a' is already declared in the upper scope.");
+    }
+}
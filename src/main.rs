@@ -7,7 +7,7 @@ use zygr::tokenizer;
 fn main() {
     let args=std::env::args().collect::<Vec<String>>();
     if args.len() < 3 {
-        println!("Usage: {} (-t || -p) <filename>", args[0]);
+        println!("Usage: {} (-t || -p || -m || -c || -l || check || build) <filename> [options]", args[0]);
         return;
     }
 
@@ -28,14 +28,182 @@ fn main() {
             let mut tokenizer = tokenizer::Tokenizer::new(text);
             let tokens = tokenizer.tokenize();
             let mut parser = zygr::parser::Parser::new(tokens);
-            let tokens = parser.parse();
-            for token in tokens {
-                println!("{:?}", token);
+            let result = parser.parse();
+            for error in &result.errors {
+                println!("{}:{}:{}: {}", filename, error.row, error.col, error.message);
+            }
+            if let Some(zygr::parser::Node::Program(body)) = result.result {
+                for node in body {
+                    println!("{:?}", node);
+                }
+            }
+
+        }
+        "-m" => {
+            // Debug: report how generics were specialized or boxed
+            let text = std::fs::read_to_string(filename).unwrap();
+            let mut tokenizer = tokenizer::Tokenizer::new(text);
+            let tokens = tokenizer.tokenize();
+            let mut parser = zygr::parser::Parser::new(tokens);
+            let result = parser.parse();
+            for error in &result.errors {
+                println!("{}:{}:{}: {}", filename, error.row, error.col, error.message);
+            }
+            if let Some(program) = result.result {
+                let (_, report) = zygr::monomorphize::monomorphize(&program);
+                print!("{}", report);
+            }
+        }
+        "-c" => {
+            // Debug: report which locals closures capture
+            let text = std::fs::read_to_string(filename).unwrap();
+            let mut tokenizer = tokenizer::Tokenizer::new(text);
+            let tokens = tokenizer.tokenize();
+            let mut parser = zygr::parser::Parser::new(tokens);
+            let result = parser.parse();
+            for error in &result.errors {
+                println!("{}:{}:{}: {}", filename, error.row, error.col, error.message);
+            }
+            if let Some(program) = result.result {
+                print!("{}", zygr::closures::analyze_captures(&program));
+            }
+        }
+        "-l" => {
+            // Debug: load every imported file and print the linked program
+            let graph = match zygr::modules::load_module_graph(std::path::Path::new(filename)) {
+                Ok(graph) => graph,
+                Err(error) => {
+                    println!("{}", error);
+                    return;
+                }
+            };
+            for module in &graph.modules {
+                for error in &module.errors {
+                    println!("{}:{}:{}: {}", module.path.display(), error.row, error.col, error.message);
+                }
+            }
+            print!("{}", graph);
+            let linked = zygr::modules::link(&graph);
+            for error in &linked.errors {
+                println!("{}", error.message);
+            }
+            if let Some(zygr::parser::Node::Program(body)) = linked.result {
+                for node in body {
+                    println!("{:?}", node);
+                }
+            }
+        }
+        "check" => {
+            // Report errors and lints without compiling; `--unused-locals=error` and the
+            // like set how each lint is reported
+            let mut options = zygr::lints::LintOptions::default();
+            for option in &args[3..] {
+                let set = option
+                    .strip_prefix("--")
+                    .and_then(|option| option.split_once('='))
+                    .ok_or_else(|| format!("Expected --<lint>=<off|warn|error>, got '{}'", option))
+                    .and_then(|(name, level)| options.set(name, level.parse()?));
+                if let Err(error) = set {
+                    println!("{}", error);
+                    return;
+                }
+            }
+            let text = std::fs::read_to_string(filename).unwrap();
+            let mut tokenizer = tokenizer::Tokenizer::new(text);
+            let tokens = tokenizer.tokenize();
+            let mut parser = zygr::parser::Parser::new(tokens);
+            let result = parser.parse();
+            let Some(program) = result.result else {
+                print_diagnostics(std::slice::from_ref(filename), &result.errors);
+                std::process::exit(1);
+            };
+            let mut diagnostics = result.errors;
+            let bound = zygr::binder::bind(&program);
+            diagnostics.extend(bound.errors);
+            if let Some(mut table) = bound.result {
+                diagnostics.extend(zygr::type_lowering::resolve_types(&program, &mut table));
+                diagnostics.extend(zygr::assignments::check_assignments(&program, &table));
+                diagnostics.extend(zygr::checker::check_types(&program, &table));
+                diagnostics.extend(zygr::lints::lint(&program, &table, &options));
+            }
+            let checked = zygr::error_handling::CompilerResult::new(Some(()), diagnostics);
+            print_diagnostics(std::slice::from_ref(filename), &checked.errors);
+            if checked.has_errors() {
+                std::process::exit(1);
+            }
+        }
+        "build" => {
+            // Check every module the program imports, link them into one program and print
+            // it lowered, followed by the state machines its coroutines became. As with
+            // tsc's `noEmitOnError`, any error, type errors included, means nothing is printed
+            let mut experimental_decorators = false;
+            for option in &args[3..] {
+                match option.as_str() {
+                    "--experimental-decorators" => experimental_decorators = true,
+                    _ => {
+                        println!("Unknown option '{}'", option);
+                        return;
+                    }
+                }
+            }
+            let graph = match zygr::modules::load_module_graph(std::path::Path::new(filename)) {
+                Ok(graph) => graph,
+                Err(error) => {
+                    println!("{}", error);
+                    std::process::exit(1);
+                }
+            };
+            // Each diagnostic is printed under the module its position is in
+            let paths: Vec<String> = graph
+                .modules
+                .iter()
+                .map(|module| module.path.display().to_string())
+                .collect();
+            let mut failed = false;
+            for module in &graph.modules {
+                print_diagnostics(&paths, &module.errors);
+                let checked = zygr::error_handling::CompilerResult::new(
+                    Some(()),
+                    type_check(&module.program),
+                );
+                print_diagnostics(&paths, &checked.errors);
+                failed |= !module.errors.is_empty() || checked.has_errors();
+            }
+            if failed {
+                std::process::exit(1);
+            }
+            let linked = zygr::modules::link(&graph);
+            let mut diagnostics = linked.errors;
+            let Some(program) = linked.result else {
+                print_diagnostics(&paths, &diagnostics);
+                std::process::exit(1);
+            };
+            // TDZ checks run on the linked program, where the order modules are initialized
+            // in decides whether an import has been declared by the time it is read; spans
+            // tell the modules' nodes apart
+            let checked = pass(zygr::tdz::lower_tdz(&program), &mut diagnostics);
+            let Some(program) = checked else {
+                print_diagnostics(&paths, &diagnostics);
+                std::process::exit(1);
+            };
+            let (program, _) = zygr::monomorphize::monomorphize(&program);
+            let lowered = lower(&program, experimental_decorators, &mut diagnostics);
+            print_diagnostics(&paths, &diagnostics);
+            let built = zygr::error_handling::CompilerResult::new(lowered, diagnostics);
+            if built.has_errors() {
+                std::process::exit(1);
+            }
+            if let Some((zygr::parser::Node::Program(body), machines)) = built.result {
+                for node in body {
+                    println!("{:?}", node);
+                }
+                for (index, machine) in machines.iter().enumerate() {
+                    println!("machine {}: {:?}", index, machine);
+                }
             }
-          
         }
         _ => {
-            println!("Usage: {} (-t || -p) <filename>", args[0]);
+            println!("Usage: {} (-t || -p || -m || -c || -l || check || build) <filename> [options]", args[0]);
         }
         
     }
@@ -43,3 +211,66 @@ fn main() {
 
     
 }
+
+/// Binds a module and reports its type errors.
+fn type_check(program: &zygr::parser::Node) -> Vec<zygr::error_handling::CompilerError> {
+    let bound = zygr::binder::bind(program);
+    let mut diagnostics = bound.errors;
+    if let Some(mut table) = bound.result {
+        diagnostics.extend(zygr::type_lowering::resolve_types(program, &mut table));
+        diagnostics.extend(zygr::assignments::check_assignments(program, &table));
+        diagnostics.extend(zygr::checker::check_types(program, &table));
+    }
+    diagnostics
+}
+
+/// Runs the lowering passes over a linked program, in the order they depend on each other.
+fn lower(
+    program: &zygr::parser::Node,
+    experimental_decorators: bool,
+    errors: &mut Vec<zygr::error_handling::CompilerError>,
+) -> Option<(zygr::parser::Node, Vec<zygr::state_machines::StateMachine>)> {
+    let program = pass(
+        zygr::decorators::lower_decorators(program, experimental_decorators),
+        errors,
+    )?;
+    let enums = pass(zygr::enums::evaluate_enums(&program), errors)?;
+    let program = pass(zygr::enums::inline_const_enums(&program, &enums), errors)?;
+    let program = pass(zygr::enums::lower_enums(&program, &enums), errors)?;
+    let (program, _) = pass(zygr::namespaces::lower_namespaces(&program), errors)?;
+    let program = pass(zygr::destructuring::lower_destructuring(&program), errors)?;
+    let program = pass(zygr::optional_chains::lower_optional_chains(&program), errors)?;
+    pass(zygr::state_machines::lower_coroutines(&program), errors)
+}
+
+fn pass<T>(
+    result: zygr::error_handling::CompilerResult<T>,
+    errors: &mut Vec<zygr::error_handling::CompilerError>,
+) -> Option<T> {
+    errors.extend(result.errors);
+    result.result
+}
+
+/// Prints diagnostics the way tsc does, with the locations they refer to under them. `paths`
+/// names the files positions can be in; nodes the compiler added are in the first.
+fn print_diagnostics(paths: &[String], errors: &[zygr::error_handling::CompilerError]) {
+    let path = |file: usize| paths.get(file).unwrap_or(&paths[0]);
+    for error in errors {
+        let filename = path(error.file);
+        let severity = match error.severity {
+            zygr::error_handling::Severity::Error => "error",
+            zygr::error_handling::Severity::Warning => "warning",
+        };
+        let code = error.code.map(|code| format!(" TS{}", code)).unwrap_or_default();
+        // Declarations without a name token in the tree have no position
+        let location = if error.row == 0 {
+            filename.to_string()
+        } else {
+            format!("{}:{}:{}", filename, error.row, error.col)
+        };
+        println!("{}: {}{}: {}", location, severity, code, error.message);
+        for related in &error.related {
+            println!("    {}:{}:{}: {}", path(related.file), related.row, related.col, related.message);
+        }
+    }
+}
@@ -0,0 +1,929 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::error_handling::{CompilerError, CompilerResult, Span};
+use crate::names::{rename, used_names, FreshNames};
+use crate::namespaces::{collect_ambient_declarations, AmbientDeclarations};
+use crate::parser::{
+    ExportSpecifier, ImportSpecifier, MethodKind, Modifiers, Node, Parser, VariableDeclarator,
+    VariableKind,
+};
+use crate::tokenizer::Tokenizer;
+
+#[derive(Debug)]
+pub struct Module {
+    pub path: PathBuf,
+    pub program: Node,
+    pub dependencies: Vec<Dependency>,
+    pub errors: Vec<CompilerError>, // parse and resolution errors in this file
+}
+
+#[derive(Debug, Clone)]
+pub struct Dependency {
+    pub specifier: String,
+    pub module: usize,
+    pub type_only: bool, // erased at runtime, so it doesn't affect initialization
+}
+
+#[derive(Debug)]
+pub struct ModuleGraph {
+    pub modules: Vec<Module>,
+    pub entry: usize,
+    pub cycles: Vec<Vec<usize>>,
+    pub init_order: Vec<usize>,
+}
+
+impl ModuleGraph {
+    pub fn has_errors(&self) -> bool {
+        self.modules.iter().any(|module| !module.errors.is_empty())
+    }
+}
+
+impl fmt::Display for ModuleGraph {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for &index in &self.init_order {
+            writeln!(f, "init {}", self.modules[index].path.display())?;
+        }
+        for cycle in &self.cycles {
+            let paths: Vec<String> = cycle
+                .iter()
+                .map(|&index| self.modules[index].path.display().to_string())
+                .collect();
+            writeln!(f, "cycle {}", paths.join(" -> "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Resolves a relative module specifier against the importing file: the path itself, then
+/// with `.ts` appended, then `index.ts` inside it. `./x.js` also finds `./x.ts`.
+/// Package imports aren't supported.
+pub fn resolve(from: &Path, specifier: &str) -> Option<PathBuf> {
+    let is_relative =
+        specifier.starts_with("./") || specifier.starts_with("../") || specifier.starts_with('/');
+    if !is_relative {
+        return None;
+    }
+    let base = from.parent().unwrap_or(Path::new(".")).join(specifier);
+    let mut candidates = vec![base.clone()];
+    if let Some(stem) = specifier.strip_suffix(".js") {
+        candidates.push(
+            from.parent()
+                .unwrap_or(Path::new("."))
+                .join(format!("{}.ts", stem)),
+        );
+    }
+    candidates.push(PathBuf::from(format!("{}.ts", base.display())));
+    candidates.push(base.join("index.ts"));
+    candidates
+        .into_iter()
+        .find(|candidate| candidate.is_file())
+        .map(|candidate| candidate.canonicalize().unwrap_or(candidate))
+}
+
+/// Parses the entry file and everything it imports or re-exports, transitively. The
+/// positions in each module, and in the errors reported for it, are in the file of its
+/// index in `modules`.
+///
+/// Only a missing entry file is fatal; parse errors and unresolved imports are recorded on
+/// the module they occur in. Specifiers that don't resolve to a file are accepted if a
+/// `declare module` in any loaded file describes them. Import cycles are legal, as in ES modules: modules are
+/// initialized dependencies-first in import order, and a module already being initialized
+/// is skipped when it's reached again.
+pub fn load_module_graph(entry: &Path) -> Result<ModuleGraph, String> {
+    let entry = entry
+        .canonicalize()
+        .map_err(|error| format!("Cannot read '{}': {}", entry.display(), error))?;
+    let mut modules: Vec<Option<Module>> = Vec::new();
+    let mut indices: HashMap<PathBuf, usize> = HashMap::new();
+    let mut pending = vec![entry.clone()];
+    let mut unresolved = Vec::new();
+    indices.insert(entry.clone(), 0);
+
+    while let Some(path) = pending.pop() {
+        // Indices are handed out in discovery order, so modules may be loaded out of order
+        let index = indices[&path];
+        if modules.len() <= index {
+            modules.resize_with(index + 1, || None);
+        }
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(error) if path == entry => {
+                return Err(format!("Cannot read '{}': {}", path.display(), error))
+            }
+            Err(error) => {
+                modules[index] = Some(Module {
+                    path: path.clone(),
+                    program: Node::Program(Vec::new()),
+                    dependencies: Vec::new(),
+                    errors: vec![CompilerError::new(
+                        format!("Cannot read '{}': {}", path.display(), error),
+                        file_span(index),
+                    )],
+                });
+                continue;
+            }
+        };
+        let tokens = Tokenizer::new(text).tokenize();
+        let parsed = Parser::new(tokens).with_file(index).parse();
+        let program = parsed.result.unwrap_or(Node::Program(Vec::new()));
+        let errors = parsed.errors;
+
+        let mut dependencies = Vec::new();
+        for (specifier, type_only) in module_requests(&program) {
+            let Some(resolved) = resolve(&path, &specifier) else {
+                unresolved.push((index, specifier));
+                continue;
+            };
+            let next = indices.len();
+            let module = *indices.entry(resolved.clone()).or_insert_with(|| {
+                pending.push(resolved);
+                next
+            });
+            dependencies.push(Dependency {
+                specifier,
+                module,
+                type_only,
+            });
+        }
+        modules[index] = Some(Module {
+            path,
+            program,
+            dependencies,
+            errors,
+        });
+    }
+    let mut modules: Vec<Module> = modules.into_iter().flatten().collect();
+
+    // Anything else must be described by a `declare module` somewhere in the program
+    let ambient: Vec<AmbientDeclarations> = modules
+        .iter()
+        .map(|module| collect_ambient_declarations(&module.program))
+        .collect();
+    for (index, specifier) in unresolved {
+        if !ambient
+            .iter()
+            .any(|ambient| ambient.declares_module(&specifier))
+        {
+            modules[index].errors.push(CompilerError::new(
+                format!(
+                    "Cannot find module '{}' or its corresponding type declarations",
+                    specifier
+                ),
+                file_span(index),
+            ));
+        }
+    }
+
+    let init_order = initialization_order(&modules, 0);
+    let cycles = find_cycles(&modules);
+    Ok(ModuleGraph {
+        modules,
+        entry: 0,
+        cycles,
+        init_order,
+    })
+}
+
+/// The modules a program imports from, in source order, with whether every use is type-only.
+fn module_requests(program: &Node) -> Vec<(String, bool)> {
+    let Node::Program(body) = program else {
+        return Vec::new();
+    };
+    let mut requests: Vec<(String, bool)> = Vec::new();
+    for statement in body {
+        let request = match statement {
+            Node::ImportDeclaration {
+                specifiers,
+                source,
+                is_type_only,
+            } => {
+                let all_types = !specifiers.is_empty()
+                    && specifiers.iter().all(|specifier| {
+                        matches!(
+                            specifier,
+                            ImportSpecifier::Named {
+                                is_type_only: true,
+                                ..
+                            }
+                        )
+                    });
+                (source, *is_type_only || all_types)
+            }
+            Node::ExportNamedDeclaration {
+                source: Some(source),
+                is_type_only,
+                ..
+            }
+            | Node::ExportAllDeclaration {
+                source,
+                is_type_only,
+                ..
+            } => (source, *is_type_only),
+            _ => continue,
+        };
+        match requests.iter_mut().find(|(source, _)| source == request.0) {
+            Some(existing) => existing.1 &= request.1,
+            None => requests.push((request.0.clone(), request.1)),
+        }
+    }
+    requests
+}
+
+fn initialization_order(modules: &[Module], entry: usize) -> Vec<usize> {
+    fn visit(modules: &[Module], index: usize, seen: &mut HashSet<usize>, order: &mut Vec<usize>) {
+        if !seen.insert(index) {
+            return;
+        }
+        for dependency in &modules[index].dependencies {
+            if !dependency.type_only {
+                visit(modules, dependency.module, seen, order);
+            }
+        }
+        order.push(index);
+    }
+    let mut order = Vec::new();
+    if entry < modules.len() {
+        visit(modules, entry, &mut HashSet::new(), &mut order);
+    }
+    order
+}
+
+/// Strongly connected components of the runtime import graph that contain a cycle,
+/// found with Tarjan's algorithm.
+fn find_cycles(modules: &[Module]) -> Vec<Vec<usize>> {
+    struct Tarjan<'a> {
+        modules: &'a [Module],
+        index: Vec<Option<usize>>,
+        lowlink: Vec<usize>,
+        stack: Vec<usize>,
+        on_stack: Vec<bool>,
+        next: usize,
+        cycles: Vec<Vec<usize>>,
+    }
+
+    impl Tarjan<'_> {
+        fn connect(&mut self, node: usize) {
+            self.index[node] = Some(self.next);
+            self.lowlink[node] = self.next;
+            self.next += 1;
+            self.stack.push(node);
+            self.on_stack[node] = true;
+
+            let mut self_loop = false;
+            for dependency in &self.modules[node].dependencies {
+                if dependency.type_only {
+                    continue;
+                }
+                let target = dependency.module;
+                self_loop |= target == node;
+                match self.index[target] {
+                    None => {
+                        self.connect(target);
+                        self.lowlink[node] = self.lowlink[node].min(self.lowlink[target]);
+                    }
+                    Some(index) if self.on_stack[target] => {
+                        self.lowlink[node] = self.lowlink[node].min(index);
+                    }
+                    Some(_) => {}
+                }
+            }
+
+            if Some(self.lowlink[node]) == self.index[node] {
+                let mut component = Vec::new();
+                while let Some(member) = self.stack.pop() {
+                    self.on_stack[member] = false;
+                    component.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                if component.len() > 1 || self_loop {
+                    component.reverse();
+                    self.cycles.push(component);
+                }
+            }
+        }
+    }
+
+    let mut tarjan = Tarjan {
+        modules,
+        index: vec![None; modules.len()],
+        lowlink: vec![0; modules.len()],
+        stack: Vec::new(),
+        on_stack: vec![false; modules.len()],
+        next: 0,
+        cycles: Vec::new(),
+    };
+    for node in 0..modules.len() {
+        if tarjan.index[node].is_none() {
+            tarjan.connect(node);
+        }
+    }
+    tarjan.cycles
+}
+
+#[derive(Debug, Clone)]
+enum ExportBinding {
+    Local(String),
+    Reexport { module: usize, name: String },
+    Namespace(usize), // module whose namespace object is exported
+}
+
+/// Links every module into one program, ordered by `init_order`.
+///
+/// Module syntax is erased: exported declarations are unwrapped, and each reference to an
+/// import becomes a reference to the binding the exporting module declares, so it sees
+/// later assignments to it as a live binding does. A top-level name is kept unless
+/// another module uses the same name for something other than importing it, in which
+/// case the declaring module's binding is renamed to a fresh `name$N`. A namespace import
+/// refers to an object created before any module runs, with a getter for each export.
+///
+/// The nodes linking adds are positioned in a file past the last module, each at a row of
+/// its own, so every node of the linked program can be told apart by its span.
+pub fn link(graph: &ModuleGraph) -> CompilerResult<Node> {
+    let linker = Linker::new(graph);
+    let mut spans = AddedSpans {
+        file: graph.modules.len(),
+        rows: 0,
+    };
+    let mut errors = Vec::new();
+    let mut names = FreshNames::new(&graph.modules[graph.entry].program);
+    let mut outputs: Vec<Vec<Node>> = Vec::new();
+    let mut imports: Vec<Vec<(String, ResolvedExport)>> = Vec::new();
+    let mut used = Vec::new();
+
+    for (index, module) in graph.modules.iter().enumerate() {
+        names.add(&module.program);
+        used.push(used_names(&module.program));
+        let mut output = Vec::new();
+        let mut resolved = Vec::new();
+        let statements = match &module.program {
+            Node::Program(statements) => statements.as_slice(),
+            _ => &[],
+        };
+        for statement in statements {
+            match statement {
+                Node::ImportDeclaration {
+                    specifiers, source, ..
+                } => {
+                    let Some(target) = linker.dependency(index, source) else {
+                        continue;
+                    };
+                    for specifier in specifiers {
+                        let (imported, local) = match specifier {
+                            ImportSpecifier::Default(local, _) => ("default", local),
+                            ImportSpecifier::Named {
+                                imported, local, ..
+                            } => (imported.as_str(), local),
+                            ImportSpecifier::Namespace(local, _) => {
+                                resolved.push((local.clone(), ResolvedExport::Namespace(target)));
+                                continue;
+                            }
+                        };
+                        match linker.resolve_export(target, imported, &mut HashSet::new()) {
+                            Some(export) => resolved.push((local.clone(), export)),
+                            None => errors.push(linker.error(
+                                index,
+                                format!(
+                                    "Module '\"{}\"' has no exported member '{}'",
+                                    source, imported
+                                ),
+                            )),
+                        }
+                    }
+                }
+                Node::ExportNamedDeclaration {
+                    declaration: Some(declaration),
+                    ..
+                } => output.push((**declaration).clone()),
+                Node::ExportDefaultDeclaration(declaration) => {
+                    output.push(default_export(declaration, index, &mut spans))
+                }
+                Node::ExportNamedDeclaration { .. } | Node::ExportAllDeclaration { .. } => {}
+                statement => output.push(statement.clone()),
+            }
+        }
+        outputs.push(output);
+        imports.push(resolved);
+    }
+
+    // The names bindings are linked under, where they aren't the ones they're declared with
+    let mut linked: HashMap<(usize, String), String> = HashMap::new();
+    for &index in &graph.init_order {
+        for name in outputs[index].iter().flat_map(declared_names) {
+            let imported_as_itself = |other: usize| {
+                imports[other].iter().any(|(local, export)| {
+                    *local == name
+                        && matches!(export, ResolvedExport::Binding(module, imported)
+                            if *module == index && *imported == name)
+                })
+            };
+            let clashes = (0..graph.modules.len()).any(|other| {
+                other != index && used[other].contains(&name) && !imported_as_itself(other)
+            });
+            if clashes && !linked.contains_key(&(index, name.clone())) {
+                let fresh = names.fresh(&format!("{}$", name));
+                linked.insert((index, name), fresh);
+            }
+        }
+    }
+    let binding_name = |module: usize, name: &str| {
+        linked
+            .get(&(module, name.to_string()))
+            .cloned()
+            .unwrap_or_else(|| name.to_string())
+    };
+
+    // Namespace objects, including those of modules re-exported as a namespace
+    let mut namespaces: Vec<(usize, String)> = Vec::new();
+    let mut pending: Vec<usize> = imports
+        .iter()
+        .flatten()
+        .filter_map(|(_, export)| match export {
+            ResolvedExport::Namespace(module) => Some(*module),
+            ResolvedExport::Binding(..) => None,
+        })
+        .collect();
+    let mut members: Vec<(usize, Vec<(String, ResolvedExport)>)> = Vec::new();
+    while let Some(module) = pending.pop() {
+        if namespaces.iter().any(|(known, _)| *known == module) {
+            continue;
+        }
+        namespaces.push((module, names.fresh("$ns")));
+        let exports = linker.namespace_members(module);
+        pending.extend(exports.iter().filter_map(|(_, export)| match export {
+            ResolvedExport::Namespace(module) => Some(*module),
+            ResolvedExport::Binding(..) => None,
+        }));
+        members.push((module, exports));
+    }
+    let namespace_name = |module: usize| {
+        namespaces
+            .iter()
+            .find(|(known, _)| *known == module)
+            .map(|(_, name)| name.clone())
+            .unwrap_or_default()
+    };
+    let target_name = |export: &ResolvedExport| match export {
+        ResolvedExport::Binding(module, name) => binding_name(*module, name),
+        ResolvedExport::Namespace(module) => namespace_name(*module),
+    };
+
+    let mut body: Vec<Node> = members
+        .iter()
+        .map(|(module, exports)| {
+            namespace_object(&namespace_name(*module), exports, &target_name, &mut spans)
+        })
+        .collect();
+    for &index in &graph.init_order {
+        let mut program = Node::Program(std::mem::take(&mut outputs[index]));
+        let mut renames: Vec<(String, String)> = imports[index]
+            .iter()
+            .map(|(local, export)| (local.clone(), target_name(export)))
+            .collect();
+        for ((module, name), to) in &linked {
+            if *module == index {
+                renames.push((name.clone(), to.clone()));
+            }
+        }
+        // Each name is renamed to one the module doesn't use, so the order doesn't matter
+        for (from, to) in renames {
+            if from != to {
+                rename(&mut program, &from, &to);
+            }
+        }
+        if let Node::Program(statements) = program {
+            body.extend(statements);
+        }
+    }
+    CompilerResult::new(Some(Node::Program(body)), errors)
+}
+
+/// `const $ns = { get x() { return x; }, ... }`, the namespace object of a module, whose
+/// getters read each export where it's declared.
+fn namespace_object(
+    name: &str,
+    exports: &[(String, ResolvedExport)],
+    target_name: &dyn Fn(&ResolvedExport) -> String,
+    spans: &mut AddedSpans,
+) -> Node {
+    let getters = exports
+        .iter()
+        .map(|(exported, export)| Node::MethodDefinition {
+            key: Box::new(Node::Identifier(exported.clone(), spans.next())),
+            computed: false,
+            kind: MethodKind::Get,
+            type_params: Vec::new(),
+            params: Vec::new(),
+            return_type: None,
+            body: Some(Box::new(Node::BlockStatement(
+                vec![Node::ReturnStatement(Some(Box::new(Node::Identifier(
+                    target_name(export),
+                    spans.next(),
+                ))))],
+                spans.next(),
+            ))),
+            modifiers: Modifiers::default(),
+            is_async: false,
+            is_generator: false,
+        })
+        .collect();
+    Node::VariableDeclaration {
+        kind: VariableKind::Const,
+        declarations: vec![VariableDeclarator {
+            id: Box::new(Node::Identifier(name.to_string(), spans.next())),
+            type_annotation: None,
+            init: Some(Box::new(Node::ObjectExpression(getters))),
+        }],
+    }
+}
+
+/// Gives the nodes linking adds positions no other node of the linked program has.
+struct AddedSpans {
+    file: usize,
+    rows: usize,
+}
+
+impl AddedSpans {
+    fn next(&mut self) -> Span {
+        self.rows += 1;
+        Span {
+            row: self.rows,
+            col: 0,
+            file: self.file,
+        }
+    }
+}
+
+enum ResolvedExport {
+    Binding(usize, String), // module and the name it declares the binding under
+    Namespace(usize),
+}
+
+struct Linker<'a> {
+    graph: &'a ModuleGraph,
+    exports: Vec<HashMap<String, ExportBinding>>,
+    star_exports: Vec<Vec<usize>>,
+}
+
+impl<'a> Linker<'a> {
+    fn new(graph: &'a ModuleGraph) -> Self {
+        let mut linker = Linker {
+            graph,
+            exports: Vec::new(),
+            star_exports: Vec::new(),
+        };
+        for index in 0..graph.modules.len() {
+            let (exports, stars) = linker.collect_exports(index);
+            linker.exports.push(exports);
+            linker.star_exports.push(stars);
+        }
+        linker
+    }
+
+    fn dependency(&self, index: usize, specifier: &str) -> Option<usize> {
+        self.graph.modules[index]
+            .dependencies
+            .iter()
+            .find(|dependency| dependency.specifier == specifier)
+            .map(|dependency| dependency.module)
+    }
+
+    fn collect_exports(&self, index: usize) -> (HashMap<String, ExportBinding>, Vec<usize>) {
+        let mut exports = HashMap::new();
+        let mut stars = Vec::new();
+        let Node::Program(body) = &self.graph.modules[index].program else {
+            return (exports, stars);
+        };
+        // Local names that are themselves imports resolve through the importing module
+        let mut imports: HashMap<&str, ExportBinding> = HashMap::new();
+        for statement in body {
+            if let Node::ImportDeclaration {
+                specifiers, source, ..
+            } = statement
+            {
+                let Some(module) = self.dependency(index, source) else {
+                    continue;
+                };
+                for specifier in specifiers {
+                    let (local, binding) = match specifier {
+                        ImportSpecifier::Default(local, _) => (
+                            local,
+                            ExportBinding::Reexport {
+                                module,
+                                name: "default".to_string(),
+                            },
+                        ),
+                        ImportSpecifier::Named {
+                            imported, local, ..
+                        } => (
+                            local,
+                            ExportBinding::Reexport {
+                                module,
+                                name: imported.clone(),
+                            },
+                        ),
+                        ImportSpecifier::Namespace(local, _) => {
+                            (local, ExportBinding::Namespace(module))
+                        }
+                    };
+                    imports.insert(local.as_str(), binding);
+                }
+            }
+        }
+
+        for statement in body {
+            match statement {
+                Node::ExportNamedDeclaration {
+                    declaration: Some(declaration),
+                    ..
+                } => {
+                    for name in declared_names(declaration) {
+                        exports.insert(name.clone(), ExportBinding::Local(name));
+                    }
+                }
+                Node::ExportNamedDeclaration {
+                    declaration: None,
+                    specifiers,
+                    source,
+                    ..
+                } => {
+                    let module = source
+                        .as_ref()
+                        .and_then(|source| self.dependency(index, source));
+                    for ExportSpecifier {
+                        local, exported, ..
+                    } in specifiers
+                    {
+                        let binding = match (source, module) {
+                            (Some(_), Some(module)) => ExportBinding::Reexport {
+                                module,
+                                name: local.clone(),
+                            },
+                            (Some(_), None) => continue,
+                            (None, _) => imports
+                                .get(local.as_str())
+                                .cloned()
+                                .unwrap_or_else(|| ExportBinding::Local(local.clone())),
+                        };
+                        exports.insert(exported.clone(), binding);
+                    }
+                }
+                Node::ExportDefaultDeclaration(declaration) => {
+                    let name = default_name(declaration, index);
+                    let binding = match declaration.as_ref() {
+                        Node::Identifier(local, _) => imports
+                            .get(local.as_str())
+                            .cloned()
+                            .unwrap_or(ExportBinding::Local(name)),
+                        _ => ExportBinding::Local(name),
+                    };
+                    exports.insert("default".to_string(), binding);
+                }
+                Node::ExportAllDeclaration {
+                    exported, source, ..
+                } => {
+                    let Some(module) = self.dependency(index, source) else {
+                        continue;
+                    };
+                    match exported {
+                        Some(exported) => {
+                            exports.insert(exported.clone(), ExportBinding::Namespace(module));
+                        }
+                        None => stars.push(module),
+                    }
+                }
+                _ => {}
+            }
+        }
+        (exports, stars)
+    }
+
+    fn resolve_export(
+        &self,
+        module: usize,
+        name: &str,
+        visited: &mut HashSet<(usize, String)>,
+    ) -> Option<ResolvedExport> {
+        if !visited.insert((module, name.to_string())) {
+            return None;
+        }
+        match self.exports[module].get(name) {
+            Some(ExportBinding::Local(local)) => {
+                return Some(ResolvedExport::Binding(module, local.clone()))
+            }
+            Some(ExportBinding::Reexport { module, name }) => {
+                return self.resolve_export(*module, name, visited)
+            }
+            Some(ExportBinding::Namespace(module)) => {
+                return Some(ResolvedExport::Namespace(*module))
+            }
+            None => {}
+        }
+        // `export *` never re-exports a default
+        if name == "default" {
+            return None;
+        }
+        self.star_exports[module]
+            .iter()
+            .find_map(|&star| self.resolve_export(star, name, visited))
+    }
+
+    /// The values a module exports, with what each resolves to, for its namespace object.
+    /// Types are left out, as are names that more than one `export *` provides.
+    fn namespace_members(&self, module: usize) -> Vec<(String, ResolvedExport)> {
+        let mut exported = HashSet::new();
+        self.export_names(module, &mut HashSet::new(), &mut exported);
+        let mut exported: Vec<String> = exported.into_iter().collect();
+        exported.sort();
+        exported
+            .into_iter()
+            .filter_map(|name| {
+                let export = self.resolve_export(module, &name, &mut HashSet::new())?;
+                match &export {
+                    ResolvedExport::Binding(declaring, local)
+                        if self.declares_type(*declaring, local) =>
+                    {
+                        None
+                    }
+                    _ => Some((name, export)),
+                }
+            })
+            .collect()
+    }
+
+    fn export_names(
+        &self,
+        module: usize,
+        visited: &mut HashSet<usize>,
+        names: &mut HashSet<String>,
+    ) {
+        if !visited.insert(module) {
+            return;
+        }
+        names.extend(self.exports[module].keys().cloned());
+        for &star in &self.star_exports[module] {
+            let mut starred = HashSet::new();
+            self.export_names(star, visited, &mut starred);
+            starred.remove("default");
+            names.extend(starred);
+        }
+    }
+
+    fn declares_type(&self, module: usize, name: &str) -> bool {
+        let Node::Program(body) = &self.graph.modules[module].program else {
+            return false;
+        };
+        body.iter().any(|statement| {
+            let declaration = match statement {
+                Node::ExportNamedDeclaration {
+                    declaration: Some(declaration),
+                    ..
+                } => declaration.as_ref(),
+                Node::ExportDefaultDeclaration(declaration) => declaration.as_ref(),
+                statement => statement,
+            };
+            matches!(declaration,
+                Node::InterfaceDeclaration { name: declared, .. }
+                | Node::TypeAliasDeclaration { name: declared, .. } if declared == name)
+        })
+    }
+
+    fn error(&self, module: usize, message: String) -> CompilerError {
+        CompilerError::new(message, file_span(module))
+    }
+}
+
+/// The declaration an `export default` turns into. Anonymous functions and classes, and
+/// default-exported expressions, are bound to a name private to the module.
+fn default_export(declaration: &Node, module: usize, spans: &mut AddedSpans) -> Node {
+    match declaration {
+        Node::FunctionDeclaration { name, .. } | Node::ClassDeclaration { name, .. }
+            if name == "default" =>
+        {
+            let mut declaration = declaration.clone();
+            if let Node::FunctionDeclaration { name, .. } | Node::ClassDeclaration { name, .. } =
+                &mut declaration
+            {
+                *name = default_binding(module);
+            }
+            declaration
+        }
+        Node::FunctionDeclaration { .. }
+        | Node::ClassDeclaration { .. }
+        | Node::InterfaceDeclaration { .. } => declaration.clone(),
+        expression => Node::VariableDeclaration {
+            kind: VariableKind::Const,
+            declarations: vec![VariableDeclarator {
+                id: Box::new(Node::Identifier(default_binding(module), spans.next())),
+                type_annotation: None,
+                init: Some(Box::new(expression.clone())),
+            }],
+        },
+    }
+}
+
+/// The name the declaration `export default` turns into declares.
+fn default_name(declaration: &Node, module: usize) -> String {
+    match declaration {
+        Node::FunctionDeclaration { name, .. }
+        | Node::ClassDeclaration { name, .. }
+        | Node::InterfaceDeclaration { name, .. }
+            if name != "default" =>
+        {
+            name.clone()
+        }
+        _ => default_binding(module),
+    }
+}
+
+fn default_binding(module: usize) -> String {
+    format!("default${}", module)
+}
+
+/// Where an error about a module as a whole, rather than a place in it, is reported.
+fn file_span(module: usize) -> Span {
+    Span {
+        file: module,
+        ..Span::default()
+    }
+}
+
+/// Names a top-level statement declares.
+fn declared_names(statement: &Node) -> Vec<String> {
+    match statement {
+        Node::FunctionDeclaration { name, .. }
+        | Node::ClassDeclaration { name, .. }
+        | Node::InterfaceDeclaration { name, .. }
+        | Node::TypeAliasDeclaration { name, .. }
+        | Node::EnumDeclaration { name, .. } => vec![name.clone()],
+        Node::NamespaceDeclaration { name, .. } => name
+            .split('.')
+            .next()
+            .map(str::to_string)
+            .into_iter()
+            .collect(),
+        Node::VariableDeclaration { declarations, .. } => declarations
+            .iter()
+            .flat_map(|declarator| declarator.id.bound_names())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tdz::lower_tdz;
+
+    /// Writes `files` to a directory of their own, and loads and links the first.
+    fn link_files(test: &str, files: &[(&str, &str)]) -> (ModuleGraph, Node) {
+        let dir = std::env::temp_dir().join(format!("zygr-{}-{}", test, std::process::id()));
+        std::fs::create_dir_all(&dir).expect("creates the directory");
+        for (name, source) in files {
+            std::fs::write(dir.join(name), source).expect("writes the file");
+        }
+        let graph = load_module_graph(&dir.join(files[0].0));
+        std::fs::remove_dir_all(&dir).expect("removes the directory");
+        let graph = graph.expect("loads");
+        let linked = link(&graph);
+        assert!(linked.errors.is_empty(), "{:?}", linked.errors);
+        let program = linked.result.expect("links");
+        (graph, program)
+    }
+
+    #[test]
+    fn imports_read_before_their_module_runs_are_reported_where_they_are_read() {
+        let (graph, program) = link_files(
+            "cycle",
+            &[
+                ("a.ts", "import { early } from './b';\nexport const a = 1;"),
+                ("b.ts", "import { a } from './a';\nexport const early = a;"),
+            ],
+        );
+        let lowered = lower_tdz(&program);
+        let [error] = lowered.errors.as_slice() else {
+            panic!("{:?}", lowered.errors);
+        };
+        assert_eq!(error.code, Some(2448));
+        assert_eq!((error.row, error.col), (2, 22));
+        assert!(graph.modules[error.file].path.ends_with("b.ts"));
+    }
+
+    #[test]
+    fn namespace_objects_check_the_exports_they_read() {
+        let (_, program) = link_files(
+            "namespace",
+            &[
+                ("a.ts", "import * as b from './b';\nconsole.log(b.late);"),
+                ("b.ts", "export let late = 1;"),
+            ],
+        );
+        let lowered = lower_tdz(&program);
+        assert!(lowered.errors.is_empty(), "{:?}", lowered.errors);
+        let lowered = format!("{:?}", lowered.result.expect("lowers"));
+        assert!(lowered.contains("__tdz"), "{}", lowered);
+    }
+}
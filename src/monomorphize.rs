@@ -0,0 +1,927 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+use crate::error_handling::Span;
+use crate::parser::{Node, Parameter, TypeParameter};
+use crate::tokenizer::TokenType;
+
+/// Instantiations of a single generic beyond this count are considered unbounded.
+const MAX_INSTANTIATIONS: usize = 32;
+/// Type arguments nested deeper than this (`T[][][]...`) are considered unbounded.
+const MAX_TYPE_DEPTH: usize = 6;
+
+#[derive(Debug, Clone)]
+pub struct Instantiation {
+    pub generic: String,
+    pub type_arguments: Vec<String>,
+    pub specialized_name: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct BoxedFallback {
+    pub generic: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Default)]
+pub struct MonomorphizationReport {
+    pub instantiations: Vec<Instantiation>,
+    pub boxed: Vec<BoxedFallback>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GenericKind {
+    Function,
+    Class,
+}
+
+struct Generic {
+    kind: GenericKind,
+    declaration: Node,
+    type_params: Vec<TypeParameter>,
+    instances: Vec<String>,
+    boxed: Option<String>,           // every use goes through the uniform copy
+    partially_boxed: Option<String>, // some uses go through the uniform copy
+}
+
+type Env = HashMap<String, Node>;
+
+/// Specializes top-level generic functions and classes per concrete instantiation.
+///
+/// Each `f<number>` or `Box<string>` reachable from non-generic code gets its own copy of the
+/// declaration with the type parameters substituted, named after the instantiation
+/// (`Box<string>`), and every use is rewritten to that copy. Numeric code therefore never
+/// sees a type variable and can stay unboxed.
+///
+/// A generic falls back to a single uniform copy, with its type parameters erased to `any`,
+/// when it recurses polymorphically (`f<T>` calling `f<T[]>`), when its instantiation set
+/// grows past a fixed bound, or when a use site's type arguments can't be determined.
+pub fn monomorphize(program: &Node) -> (Node, MonomorphizationReport) {
+    let Node::Program(body) = program else {
+        return (program.clone(), MonomorphizationReport::default());
+    };
+    let mut monomorphizer = Monomorphizer::new(body);
+    monomorphizer.detect_polymorphic_recursion();
+
+    let mut rewritten = Vec::new();
+    let mut env = Env::new();
+    for statement in body {
+        if generic_name(statement).is_some() {
+            rewritten.push(statement.clone());
+            continue;
+        }
+        let mut statement = statement.clone();
+        monomorphizer.rewrite(&mut statement, &mut env);
+        rewritten.push(statement);
+    }
+    monomorphizer.drain_worklist();
+
+    // Specializations take the place of their generic declaration
+    let mut output = Vec::new();
+    for statement in rewritten {
+        let Some(name) = generic_name(&statement) else {
+            output.push(statement);
+            continue;
+        };
+        output.extend(monomorphizer.specialized.remove(&name).unwrap_or_default());
+        if let Some(boxed) = monomorphizer.boxed_copies.remove(&name) {
+            output.push(boxed);
+        }
+    }
+
+    let mut report = monomorphizer.report;
+    for (name, generic) in &monomorphizer.generics {
+        if let Some(reason) = generic.boxed.as_ref().or(generic.partially_boxed.as_ref()) {
+            report.boxed.push(BoxedFallback {
+                generic: name.clone(),
+                reason: reason.clone(),
+            });
+        }
+    }
+    report.boxed.sort_by(|a, b| a.generic.cmp(&b.generic));
+    (Node::Program(output), report)
+}
+
+struct Monomorphizer {
+    generics: HashMap<String, Generic>,
+    specialized: HashMap<String, Vec<Node>>,
+    boxed_copies: HashMap<String, Node>,
+    worklist: VecDeque<(String, Node)>,
+    report: MonomorphizationReport,
+}
+
+impl Monomorphizer {
+    fn new(body: &[Node]) -> Self {
+        let mut generics = HashMap::new();
+        for statement in body {
+            let Some(name) = generic_name(statement) else {
+                continue;
+            };
+            let (kind, type_params) = match statement {
+                Node::FunctionDeclaration { type_params, .. } => {
+                    (GenericKind::Function, type_params.clone())
+                }
+                Node::ClassDeclaration { type_params, .. } => {
+                    (GenericKind::Class, type_params.clone())
+                }
+                _ => continue,
+            };
+            generics.insert(
+                name,
+                Generic {
+                    kind,
+                    declaration: statement.clone(),
+                    type_params,
+                    instances: Vec::new(),
+                    boxed: None,
+                    partially_boxed: None,
+                },
+            );
+        }
+        Monomorphizer {
+            generics,
+            specialized: HashMap::new(),
+            boxed_copies: HashMap::new(),
+            worklist: VecDeque::new(),
+            report: MonomorphizationReport::default(),
+        }
+    }
+
+    /// A generic that refers to itself with a type argument built from its own type
+    /// parameters (`f<T>` calling `f<T[]>`) would need infinitely many copies.
+    fn detect_polymorphic_recursion(&mut self) {
+        let mut recursive = Vec::new();
+        for (name, generic) in &self.generics {
+            let own: HashSet<&str> = generic
+                .type_params
+                .iter()
+                .map(|p| p.name.as_str())
+                .collect();
+            let mut declaration = generic.declaration.clone();
+            let mut found = false;
+            visit_self_references(&mut declaration, name, &mut |type_arguments| {
+                found |= type_arguments.iter().any(|argument| {
+                    !matches!(argument, Node::TypeReference { name, type_arguments, .. }
+                        if type_arguments.is_empty() && own.contains(name.as_str()))
+                        && mentions_names(argument, &own)
+                });
+            });
+            if found {
+                recursive.push(name.clone());
+            }
+        }
+        for name in recursive {
+            self.mark_boxed(&name, "polymorphic recursion");
+        }
+    }
+
+    /// Rewrites specializations as they are discovered, then builds the uniform copies of
+    /// generics that need one. Rewriting a uniform copy may discover further work.
+    fn drain_worklist(&mut self) {
+        loop {
+            while let Some((generic, mut declaration)) = self.worklist.pop_front() {
+                self.rewrite_declaration(&mut declaration);
+                self.specialized
+                    .entry(generic)
+                    .or_default()
+                    .push(declaration);
+            }
+            let mut pending: Vec<&String> = self
+                .generics
+                .iter()
+                .filter(|(name, generic)| {
+                    (generic.boxed.is_some() || generic.partially_boxed.is_some())
+                        && !self.boxed_copies.contains_key(*name)
+                })
+                .map(|(name, _)| name)
+                .collect();
+            pending.sort();
+            let Some(name) = pending.first().map(|name| name.to_string()) else {
+                return;
+            };
+            let generic = &self.generics[&name];
+            let mapping: HashMap<String, Node> = generic
+                .type_params
+                .iter()
+                .map(|p| (p.name.clone(), Node::KeywordType(TokenType::Any)))
+                .collect();
+            let mut declaration = generic.declaration.clone();
+            clear_type_params(&mut declaration);
+            substitute(&mut declaration, &mapping);
+            self.boxed_copies.insert(name.clone(), Node::EmptyStatement);
+            self.rewrite_declaration(&mut declaration);
+            self.boxed_copies.insert(name, declaration);
+        }
+    }
+
+    /// Rewrites a (specialized or boxed) declaration, which is no longer generic itself.
+    fn rewrite_declaration(&mut self, declaration: &mut Node) {
+        if let Node::ClassDeclaration { members, .. } = declaration {
+            for member in members {
+                if let Node::MethodDefinition { type_params, .. } = member {
+                    // Method-level type parameters stay uniform: the receiver's instantiation
+                    // is known here, but the method's own type arguments are per call
+                    if !type_params.is_empty() {
+                        let mapping: HashMap<String, Node> = type_params
+                            .drain(..)
+                            .map(|p| (p.name, Node::KeywordType(TokenType::Any)))
+                            .collect();
+                        substitute(member, &mapping);
+                    }
+                }
+            }
+        }
+        self.rewrite(declaration, &mut Env::new());
+    }
+
+    fn rewrite(&mut self, node: &mut Node, env: &mut Env) {
+        match node {
+            Node::VariableDeclaration { declarations, .. } => {
+                for declarator in declarations {
+                    let estimate = declarator
+                        .init
+                        .as_deref()
+                        .and_then(|init| self.estimate(init, env));
+                    if let Some(type_annotation) = declarator.type_annotation.as_deref_mut() {
+                        self.rewrite(type_annotation, env);
+                    }
+                    if let Some(init) = declarator.init.as_deref_mut() {
+                        self.rewrite(init, env);
+                    }
+                    if let Node::Identifier(name, _) = declarator.id.as_ref() {
+                        if let Some(type_annotation) =
+                            declarator.type_annotation.as_deref().cloned().or(estimate)
+                        {
+                            env.insert(name.clone(), type_annotation);
+                        }
+                    } else {
+                        // Pattern defaults may call generics too
+                        self.rewrite(&mut declarator.id, env);
+                        for name in declarator.id.bound_names() {
+                            env.remove(&name);
+                        }
+                    }
+                }
+            }
+            Node::FunctionDeclaration {
+                type_params,
+                params,
+                ..
+            }
+            | Node::MethodDefinition {
+                type_params,
+                params,
+                ..
+            }
+            | Node::ArrowFunctionExpression {
+                type_params,
+                params,
+                ..
+            } => {
+                if !type_params.is_empty() {
+                    // Nested generics are left to the uniform representation
+                    return;
+                }
+                let mut inner = env.clone();
+                bind_params(&mut inner, params);
+                for child in node.children_mut() {
+                    self.rewrite(child, &mut inner);
+                }
+            }
+            Node::CallExpression { .. } | Node::NewExpression { .. } => {
+                self.rewrite_call(node, env)
+            }
+            Node::TypeReference {
+                name,
+                type_arguments,
+                ..
+            } => {
+                for argument in type_arguments.iter_mut() {
+                    self.rewrite(argument, env);
+                }
+                let is_class = self
+                    .generics
+                    .get(name.as_str())
+                    .is_some_and(|generic| generic.kind == GenericKind::Class);
+                if is_class {
+                    let arguments = std::mem::take(type_arguments);
+                    let resolved = if arguments.is_empty() {
+                        None
+                    } else {
+                        Some(arguments)
+                    };
+                    *name = self.resolve(&name.clone(), resolved);
+                }
+            }
+            _ => {
+                for child in node.children_mut() {
+                    self.rewrite(child, env);
+                }
+            }
+        }
+    }
+
+    fn rewrite_call(&mut self, node: &mut Node, env: &mut Env) {
+        let kind = if matches!(node, Node::NewExpression { .. }) {
+            GenericKind::Class
+        } else {
+            GenericKind::Function
+        };
+        let (Node::CallExpression {
+            callee,
+            type_arguments,
+            arguments,
+            ..
+        }
+        | Node::NewExpression {
+            callee,
+            type_arguments,
+            arguments,
+        }) = &*node
+        else {
+            return;
+        };
+        let name = match callee.as_ref() {
+            Node::Identifier(name, _) => self
+                .generics
+                .get(name)
+                .filter(|generic| generic.kind == kind)
+                .map(|_| name.clone()),
+            _ => None,
+        };
+        let inferred = match &name {
+            Some(name) if type_arguments.is_empty() => {
+                let argument_types: Vec<Option<Node>> =
+                    arguments.iter().map(|a| self.estimate(a, env)).collect();
+                self.infer(name, &argument_types)
+            }
+            _ => None,
+        };
+
+        for child in node.children_mut() {
+            self.rewrite(child, env);
+        }
+        let Some(name) = name else {
+            return;
+        };
+        if let Node::CallExpression {
+            callee,
+            type_arguments,
+            ..
+        }
+        | Node::NewExpression {
+            callee,
+            type_arguments,
+            ..
+        } = node
+        {
+            let explicit = std::mem::take(type_arguments);
+            let resolved = if explicit.is_empty() {
+                inferred
+            } else {
+                Some(explicit)
+            };
+            if let Node::Identifier(callee, _) = callee.as_mut() {
+                *callee = self.resolve(&name, resolved);
+            }
+        }
+    }
+
+    /// Returns the name a use of `generic` should refer to after monomorphization.
+    fn resolve(&mut self, generic: &str, type_arguments: Option<Vec<Node>>) -> String {
+        let entry = &self.generics[generic];
+        if entry.boxed.is_some() {
+            return generic.to_string();
+        }
+        let Some(mut type_arguments) = type_arguments else {
+            self.mark_partially_boxed(generic);
+            return generic.to_string();
+        };
+
+        // Fill in defaults, which may refer to earlier type parameters
+        let type_params = entry.type_params.clone();
+        let mut mapping = HashMap::new();
+        for (index, type_param) in type_params.iter().enumerate() {
+            if index >= type_arguments.len() {
+                let Some(default) = &type_param.default else {
+                    self.mark_partially_boxed(generic);
+                    return generic.to_string();
+                };
+                let mut default = (**default).clone();
+                substitute(&mut default, &mapping);
+                type_arguments.push(default);
+            }
+            mapping.insert(type_param.name.clone(), type_arguments[index].clone());
+        }
+        type_arguments.truncate(type_params.len());
+
+        let keys: Vec<String> = type_arguments.iter().map(type_key).collect();
+        let specialized_name = format!("{}<{}>", generic, keys.join(", "));
+        if self.generics[generic].instances.contains(&specialized_name) {
+            return specialized_name;
+        }
+        if type_arguments
+            .iter()
+            .any(|t| type_depth(t) > MAX_TYPE_DEPTH)
+            || self.generics[generic].instances.len() >= MAX_INSTANTIATIONS
+        {
+            self.mark_boxed(generic, "unbounded instantiation set");
+            return generic.to_string();
+        }
+
+        let entry = self.generics.get_mut(generic).unwrap();
+        entry.instances.push(specialized_name.clone());
+        let mut declaration = entry.declaration.clone();
+        clear_type_params(&mut declaration);
+        rename(&mut declaration, &specialized_name);
+        substitute(&mut declaration, &mapping);
+        self.worklist.push_back((generic.to_string(), declaration));
+        self.report.instantiations.push(Instantiation {
+            generic: generic.to_string(),
+            type_arguments: keys,
+            specialized_name: specialized_name.clone(),
+        });
+        specialized_name
+    }
+
+    fn mark_partially_boxed(&mut self, generic: &str) {
+        let entry = self.generics.get_mut(generic).unwrap();
+        if entry.partially_boxed.is_none() {
+            entry.partially_boxed =
+                Some("type arguments could not be determined at a use site".to_string());
+        }
+    }
+
+    fn mark_boxed(&mut self, generic: &str, reason: &str) {
+        let entry = self.generics.get_mut(generic).unwrap();
+        if entry.boxed.is_none() {
+            entry.boxed = Some(reason.to_string());
+        }
+    }
+
+    /// Infers type arguments for a call or `new` from the estimated argument types.
+    fn infer(&self, generic: &str, argument_types: &[Option<Node>]) -> Option<Vec<Node>> {
+        let entry = &self.generics[generic];
+        let params: Vec<Parameter> = match &entry.declaration {
+            Node::FunctionDeclaration { params, .. } => params.clone(),
+            Node::ClassDeclaration { members, .. } => members
+                .iter()
+                .find_map(|member| match member {
+                    Node::MethodDefinition {
+                        kind: crate::parser::MethodKind::Constructor,
+                        params,
+                        ..
+                    } => Some(params.clone()),
+                    _ => None,
+                })
+                .unwrap_or_default(),
+            _ => return None,
+        };
+        let names: HashSet<&str> = entry.type_params.iter().map(|p| p.name.as_str()).collect();
+        let mut inferred: HashMap<String, Node> = HashMap::new();
+        for (param, argument) in params.iter().zip(argument_types) {
+            if let (Some(param), Some(argument)) = (param.type_annotation.as_deref(), argument) {
+                infer_from(param, argument, &names, &mut inferred);
+            }
+        }
+        let mut type_arguments = Vec::new();
+        for type_param in &entry.type_params {
+            match inferred.remove(&type_param.name) {
+                Some(argument) => type_arguments.push(argument),
+                // Trailing parameters may still be filled in from defaults
+                None if type_param.default.is_some() => break,
+                None => return None,
+            }
+        }
+        Some(type_arguments)
+    }
+
+    /// A best-effort static type for `expression`, used to infer type arguments.
+    fn estimate(&self, expression: &Node, env: &Env) -> Option<Node> {
+        match expression {
+            Node::Literal { token_type, .. } => match token_type {
+                TokenType::NumberLiteral => Some(Node::KeywordType(TokenType::NumberType)),
+                TokenType::StringLiteral | TokenType::TemplateLiteral => {
+                    Some(Node::KeywordType(TokenType::StringType))
+                }
+                TokenType::True | TokenType::False => {
+                    Some(Node::KeywordType(TokenType::BooleanType))
+                }
+                TokenType::BigInt => Some(Node::KeywordType(TokenType::BigInt)),
+                _ => None,
+            },
+            Node::Identifier(name, _) => env.get(name).cloned(),
+            Node::NewExpression {
+                callee,
+                type_arguments,
+                arguments,
+            } => {
+                let Node::Identifier(name, _) = callee.as_ref() else {
+                    return None;
+                };
+                let type_arguments =
+                    if type_arguments.is_empty() && self.generics.contains_key(name) {
+                        let argument_types: Vec<Option<Node>> =
+                            arguments.iter().map(|a| self.estimate(a, env)).collect();
+                        self.infer(name, &argument_types)?
+                    } else {
+                        type_arguments.clone()
+                    };
+                Some(Node::TypeReference {
+                    name: name.clone(),
+                    type_arguments,
+                    span: Span::default(),
+                })
+            }
+            Node::CallExpression {
+                callee,
+                type_arguments,
+                arguments,
+                ..
+            } => {
+                let Node::Identifier(name, _) = callee.as_ref() else {
+                    return None;
+                };
+                let generic = self.generics.get(name)?;
+                let Node::FunctionDeclaration {
+                    return_type: Some(return_type),
+                    ..
+                } = &generic.declaration
+                else {
+                    return None;
+                };
+                let type_arguments = if type_arguments.is_empty() {
+                    let argument_types: Vec<Option<Node>> =
+                        arguments.iter().map(|a| self.estimate(a, env)).collect();
+                    self.infer(name, &argument_types)?
+                } else {
+                    type_arguments.clone()
+                };
+                let mapping: HashMap<String, Node> = generic
+                    .type_params
+                    .iter()
+                    .map(|p| p.name.clone())
+                    .zip(type_arguments)
+                    .collect();
+                let mut return_type = (**return_type).clone();
+                substitute(&mut return_type, &mapping);
+                Some(return_type)
+            }
+            Node::BinaryExpression {
+                left,
+                operator,
+                right,
+            } => match operator.as_str() {
+                "+" => {
+                    let left = self.estimate(left, env)?;
+                    let right = self.estimate(right, env)?;
+                    let string = Node::KeywordType(TokenType::StringType);
+                    if left == string || right == string {
+                        Some(string)
+                    } else {
+                        Some(left)
+                    }
+                }
+                "-" | "*" | "/" | "%" | "**" | "<<" | ">>" | ">>>" | "&" | "|" | "^" => {
+                    Some(Node::KeywordType(TokenType::NumberType))
+                }
+                _ => Some(Node::KeywordType(TokenType::BooleanType)),
+            },
+            // Literal types from `as const` aren't estimated
+            Node::AsExpression {
+                type_annotation, ..
+            }
+            | Node::TypeAssertion {
+                type_annotation, ..
+            } => match type_annotation.as_ref() {
+                Node::KeywordType(TokenType::Const) => None,
+                type_annotation => Some(type_annotation.clone()),
+            },
+            Node::SatisfiesExpression { expression, .. } | Node::NonNullExpression(expression) => {
+                self.estimate(expression, env)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for MonomorphizationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for instantiation in &self.instantiations {
+            writeln!(
+                f,
+                "specialized {} as {}",
+                instantiation.generic, instantiation.specialized_name
+            )?;
+        }
+        for boxed in &self.boxed {
+            writeln!(f, "boxed {}: {}", boxed.generic, boxed.reason)?;
+        }
+        Ok(())
+    }
+}
+
+fn generic_name(statement: &Node) -> Option<String> {
+    match statement {
+        Node::FunctionDeclaration {
+            name, type_params, ..
+        }
+        | Node::ClassDeclaration {
+            name, type_params, ..
+        } if !type_params.is_empty() => Some(name.clone()),
+        _ => None,
+    }
+}
+
+fn bind_params(env: &mut Env, params: &[Parameter]) {
+    for param in params {
+        match (param.name(), &param.type_annotation) {
+            (Some(name), Some(type_annotation)) => {
+                env.insert(name.to_string(), (**type_annotation).clone());
+            }
+            // Names bound by a pattern have types this pass doesn't track
+            _ => {
+                for name in param.binding.bound_names() {
+                    env.remove(&name);
+                }
+            }
+        }
+    }
+}
+
+fn clear_type_params(declaration: &mut Node) {
+    if let Node::FunctionDeclaration { type_params, .. }
+    | Node::ClassDeclaration { type_params, .. } = declaration
+    {
+        type_params.clear();
+    }
+}
+
+fn rename(declaration: &mut Node, new_name: &str) {
+    if let Node::FunctionDeclaration { name, .. } | Node::ClassDeclaration { name, .. } =
+        declaration
+    {
+        *name = new_name.to_string();
+    }
+}
+
+/// Replaces references to type parameters with their bound type arguments.
+fn substitute(node: &mut Node, mapping: &HashMap<String, Node>) {
+    if let Node::TypeReference {
+        name,
+        type_arguments,
+        ..
+    } = node
+    {
+        if type_arguments.is_empty() {
+            if let Some(replacement) = mapping.get(name.as_str()) {
+                *node = replacement.clone();
+                return;
+            }
+        }
+    }
+    for child in node.children_mut() {
+        substitute(child, mapping);
+    }
+}
+
+/// Calls `visit` with the type arguments of every reference to `generic` inside `node`.
+fn visit_self_references(node: &mut Node, generic: &str, visit: &mut dyn FnMut(&[Node])) {
+    match node {
+        Node::CallExpression {
+            callee,
+            type_arguments,
+            ..
+        }
+        | Node::NewExpression {
+            callee,
+            type_arguments,
+            ..
+        } if matches!(callee.as_ref(), Node::Identifier(name, _) if name == generic) => {
+            visit(type_arguments)
+        }
+        Node::TypeReference {
+            name,
+            type_arguments,
+            ..
+        } if name == generic => visit(type_arguments),
+        _ => {}
+    }
+    for child in node.children_mut() {
+        visit_self_references(child, generic, visit);
+    }
+}
+
+fn mentions_names(node: &Node, names: &HashSet<&str>) -> bool {
+    let mut node = node.clone();
+    let mut found = false;
+    visit_type_references(&mut node, &mut |name| found |= names.contains(name));
+    found
+}
+
+fn visit_type_references(node: &mut Node, visit: &mut dyn FnMut(&str)) {
+    if let Node::TypeReference { name, .. } = node {
+        visit(name);
+    }
+    for child in node.children_mut() {
+        visit_type_references(child, visit);
+    }
+}
+
+fn infer_from(
+    param: &Node,
+    argument: &Node,
+    names: &HashSet<&str>,
+    inferred: &mut HashMap<String, Node>,
+) {
+    match (param, argument) {
+        (
+            Node::TypeReference {
+                name,
+                type_arguments,
+                ..
+            },
+            _,
+        ) if type_arguments.is_empty() && names.contains(name.as_str()) => {
+            inferred
+                .entry(name.clone())
+                .or_insert_with(|| argument.clone());
+        }
+        (Node::ArrayType(param), Node::ArrayType(argument)) => {
+            infer_from(param, argument, names, inferred)
+        }
+        (Node::ParenthesizedType(param), _) => infer_from(param, argument, names, inferred),
+        (
+            Node::TypeReference {
+                name,
+                type_arguments: params,
+                ..
+            },
+            Node::TypeReference {
+                name: argument_name,
+                type_arguments: arguments,
+                ..
+            },
+        ) if name == argument_name => {
+            for (param, argument) in params.iter().zip(arguments) {
+                infer_from(param, argument, names, inferred);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn type_depth(node: &Node) -> usize {
+    let mut node = node.clone();
+    1 + node
+        .children_mut()
+        .into_iter()
+        .map(|child| type_depth(child))
+        .max()
+        .unwrap_or(0)
+}
+
+/// A canonical spelling of a type annotation, used to name specializations.
+fn type_key(node: &Node) -> String {
+    match node {
+        Node::KeywordType(token_type) => keyword_name(*token_type).to_string(),
+        Node::TypeReference {
+            name,
+            type_arguments,
+            ..
+        } if type_arguments.is_empty() => name.clone(),
+        Node::TypeReference {
+            name,
+            type_arguments,
+            ..
+        } => format!("{}<{}>", name, join_keys(type_arguments, ", ")),
+        Node::ArrayType(element) => format!("{}[]", type_key(element)),
+        Node::ParenthesizedType(inner) => format!("({})", type_key(inner)),
+        Node::UnionType(types) => join_keys(types, " | "),
+        Node::IntersectionType(types) => join_keys(types, " & "),
+        Node::TupleType(types) => format!("[{}]", join_keys(types, ", ")),
+        Node::LiteralType(literal) => match literal.as_ref() {
+            Node::Literal {
+                token_type: TokenType::StringLiteral,
+                value,
+                ..
+            } => format!("\"{}\"", value),
+            Node::Literal { value, .. } => value.clone(),
+            _ => String::new(),
+        },
+        Node::FunctionType {
+            params,
+            return_type,
+            ..
+        } => {
+            let params: Vec<String> = params
+                .iter()
+                .map(|p| match &p.type_annotation {
+                    Some(t) => format!("{}: {}", p.name().unwrap_or("_"), type_key(t)),
+                    None => p.name().unwrap_or("_").to_string(),
+                })
+                .collect();
+            format!("({}) => {}", params.join(", "), type_key(return_type))
+        }
+        _ => format!("{:?}", node),
+    }
+}
+
+fn join_keys(types: &[Node], separator: &str) -> String {
+    types
+        .iter()
+        .map(type_key)
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+fn keyword_name(token_type: TokenType) -> &'static str {
+    match token_type {
+        TokenType::NumberType => "number",
+        TokenType::StringType => "string",
+        TokenType::BooleanType => "boolean",
+        TokenType::Null => "null",
+        TokenType::Undefined => "undefined",
+        TokenType::Any => "any",
+        TokenType::Unknown => "unknown",
+        TokenType::Never => "never",
+        TokenType::Void => "void",
+        TokenType::BigInt => "bigint",
+        TokenType::Symbol => "symbol",
+        _ => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::tokenizer::Tokenizer;
+
+    fn run(source: &str) -> (Vec<Node>, MonomorphizationReport) {
+        let tokens = Tokenizer::new(source.to_string()).tokenize();
+        let program = Parser::new(tokens).parse().result.expect("parses");
+        let (Node::Program(body), report) = monomorphize(&program) else {
+            panic!("monomorphizes to a program");
+        };
+        (body, report)
+    }
+
+    fn function_names(body: &[Node]) -> Vec<&str> {
+        body.iter()
+            .filter_map(|statement| match statement {
+                Node::FunctionDeclaration { name, .. } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn each_instantiation_gets_its_own_copy() {
+        let (body, report) = run("function id<T>(x: T): T { return x; }
+             let a = id<number>(1);
+             let b = id('s');");
+        assert!(report.boxed.is_empty(), "{:?}", report.boxed);
+        let names: Vec<&str> = report
+            .instantiations
+            .iter()
+            .map(|instantiation| instantiation.specialized_name.as_str())
+            .collect();
+        assert_eq!(names, ["id<number>", "id<string>"]);
+        // The generic itself is gone, and its copies have no type parameters left
+        assert_eq!(function_names(&body), ["id<number>", "id<string>"]);
+        let copy = format!("{:?}", body[0]);
+        assert!(copy.contains("type_params: []"));
+        assert!(copy.contains("type_annotation: Some(KeywordType(NumberType))"));
+        // Uses call the copy, inferred type arguments included
+        assert!(format!("{:?}", body[2]).contains("callee: Identifier(\"id<number>\""));
+        assert!(format!("{:?}", body[3]).contains("callee: Identifier(\"id<string>\""));
+    }
+
+    #[test]
+    fn classes_are_specialized_at_construction() {
+        let (body, report) = run(
+            "class Box<T> { value: T; constructor(v: T) { this.value = v; } }
+             let c = new Box<string>('s');",
+        );
+        assert_eq!(report.instantiations.len(), 1);
+        assert_eq!(report.instantiations[0].type_arguments, ["string"]);
+        let debug = format!("{:?}", body);
+        assert!(debug.contains("name: \"Box<string>\""));
+        assert!(debug.contains("callee: Identifier(\"Box<string>\""));
+    }
+
+    #[test]
+    fn polymorphic_recursion_falls_back_to_a_uniform_copy() {
+        let (body, report) = run("function grow<T>(x: T): number { return grow<T[]>([x]); }
+             grow<number>(1);");
+        assert_eq!(report.boxed.len(), 1);
+        assert_eq!(report.boxed[0].generic, "grow");
+        assert_eq!(report.boxed[0].reason, "polymorphic recursion");
+        assert_eq!(function_names(&body), ["grow"]);
+        let copy = format!("{:?}", body[0]);
+        assert!(copy.contains("type_params: []"));
+        assert!(copy.contains("type_annotation: Some(KeywordType(Any))"));
+        assert!(format!("{:?}", body[1]).contains("callee: Identifier(\"grow\""));
+    }
+}
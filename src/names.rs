@@ -0,0 +1,335 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::error_handling::Span;
+use crate::parser::{ImportSpecifier, Node, VariableKind};
+
+/// Names for the temporaries a lowering pass introduces, that no name in the program it
+/// lowers is spelled like.
+///
+/// A temporary is the prefix followed by the lowest number that gives an unused name, so
+/// `$t0` is skipped in a program that declares `let $t0 = 5`. Names are collected from
+/// every identifier, declaration, import and type reference, whatever scope they're in:
+/// a temporary can then be declared anywhere without shadowing or being shadowed.
+pub struct FreshNames {
+    used: HashSet<String>,
+    generated: HashSet<String>,
+    next: HashMap<String, usize>, // the number to try first, by prefix
+}
+
+impl FreshNames {
+    pub fn new(program: &Node) -> Self {
+        let mut used = HashSet::new();
+        collect(program, &mut used);
+        FreshNames {
+            used,
+            generated: HashSet::new(),
+            next: HashMap::new(),
+        }
+    }
+
+    /// A name starting with `prefix` that isn't used yet, and won't be given out again.
+    pub fn fresh(&mut self, prefix: &str) -> String {
+        let next = self.next.entry(prefix.to_string()).or_insert(0);
+        loop {
+            let name = format!("{}{}", prefix, next);
+            *next += 1;
+            if self.used.insert(name.clone()) {
+                self.generated.insert(name.clone());
+                return name;
+            }
+        }
+    }
+
+    /// Whether `name` was given out by `fresh`, rather than written in the program.
+    pub fn is_generated(&self, name: &str) -> bool {
+        self.generated.contains(name)
+    }
+
+    /// Also avoids the names `program` uses, for temporaries shared between programs.
+    pub fn add(&mut self, program: &Node) {
+        collect(program, &mut self.used);
+    }
+}
+
+/// Every name `node` uses, as `FreshNames` collects them.
+pub fn used_names(node: &Node) -> HashSet<String> {
+    let mut used = HashSet::new();
+    collect(node, &mut used);
+    used
+}
+
+fn collect(node: &Node, used: &mut HashSet<String>) {
+    match node {
+        Node::Identifier(name, _)
+        | Node::FunctionDeclaration { name, .. }
+        | Node::ClassDeclaration { name, .. }
+        | Node::InterfaceDeclaration { name, .. }
+        | Node::TypeAliasDeclaration { name, .. }
+        | Node::EnumDeclaration { name, .. }
+        | Node::NamespaceDeclaration { name, .. } => {
+            // `namespace A.B` declares `A`; the rest are members
+            used.extend(name.split('.').next().map(str::to_string));
+        }
+        Node::TypeReference { name, .. } => {
+            used.extend(name.split('.').next().map(str::to_string));
+        }
+        Node::ImportDeclaration { specifiers, .. } => {
+            for specifier in specifiers {
+                let (ImportSpecifier::Default(local, _)
+                | ImportSpecifier::Namespace(local, _)
+                | ImportSpecifier::Named { local, .. }) = specifier;
+                used.insert(local.clone());
+            }
+        }
+        _ => {}
+    }
+    for child in node.children() {
+        collect(child, used);
+    }
+}
+
+/// Renames the binding `from` to `to` in `node`, the scope that declares it or a part of
+/// that scope: its declaration and every reference to it, but not a binding of the same
+/// name in a nested scope. Property names stay as they are, so a shorthand `{ from }`
+/// becomes `{ from: to }`.
+pub fn rename(node: &mut Node, from: &str, to: &str) {
+    let replacement = |span| Node::Identifier(to.to_string(), span);
+    substitute(node, from, &replacement, Some(to), true);
+}
+
+/// Replaces each reference to `name` in `node` with `replacement(span)`, as `rename` does,
+/// but leaves declarations alone.
+pub fn replace_references(node: &mut Node, name: &str, replacement: &dyn Fn(Span) -> Node) {
+    substitute(node, name, replacement, None, true);
+}
+
+fn substitute(
+    node: &mut Node,
+    name: &str,
+    replacement: &dyn Fn(Span) -> Node,
+    rename_to: Option<&str>,
+    is_scope: bool,
+) {
+    if let Some(to) = rename_to {
+        match node {
+            Node::FunctionDeclaration { name: declared, .. }
+            | Node::ClassDeclaration { name: declared, .. }
+            | Node::EnumDeclaration { name: declared, .. }
+            | Node::InterfaceDeclaration { name: declared, .. }
+            | Node::TypeAliasDeclaration { name: declared, .. }
+                if declared == name =>
+            {
+                *declared = to.to_string();
+            }
+            // `namespace A.B` and `A.B.C` are renamed by their first name
+            Node::NamespaceDeclaration { name: declared, .. }
+            | Node::TypeReference { name: declared, .. } => {
+                if let Some(rest) = declared.strip_prefix(name) {
+                    if rest.is_empty() || rest.starts_with('.') {
+                        *declared = format!("{}{}", to, rest);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    if !is_scope && declares(node, name) {
+        return;
+    }
+    let recurse = |node: &mut Node| substitute(node, name, replacement, rename_to, false);
+    match node {
+        Node::Identifier(identifier, span) => {
+            if identifier == name {
+                *node = replacement(*span);
+            }
+        }
+        Node::MemberExpression {
+            object,
+            property,
+            computed,
+            ..
+        } => {
+            recurse(object);
+            if *computed {
+                recurse(property);
+            }
+        }
+        Node::Property {
+            key,
+            computed,
+            value,
+            shorthand,
+        } => {
+            if *computed {
+                recurse(key);
+            }
+            recurse(value);
+            *shorthand &= matches!(
+                (key.as_ref(), value.as_ref()),
+                (Node::Identifier(key, _), Node::Identifier(value, _)) if key == value
+            );
+        }
+        // Non-computed keys name properties, not bindings
+        Node::PatternProperty {
+            key,
+            computed: false,
+            ..
+        }
+        | Node::MethodDefinition {
+            key,
+            computed: false,
+            ..
+        }
+        | Node::PropertyDefinition {
+            key,
+            computed: false,
+            ..
+        }
+        | Node::PropertySignature {
+            key,
+            computed: false,
+            ..
+        }
+        | Node::MethodSignature {
+            key,
+            computed: false,
+            ..
+        } => {
+            let key: *const Node = &**key;
+            for child in node.children_mut() {
+                if !std::ptr::eq(child, key) {
+                    recurse(child);
+                }
+            }
+        }
+        Node::TryStatement {
+            block,
+            handler,
+            finalizer,
+        } => {
+            recurse(block);
+            if let Some(handler) = handler {
+                let param = handler.param.as_deref_mut();
+                if !param.as_ref().is_some_and(|param| binds(param, name)) {
+                    if let Some(param) = param {
+                        recurse(param);
+                    }
+                    recurse(&mut handler.body);
+                }
+            }
+            if let Some(finalizer) = finalizer {
+                recurse(finalizer);
+            }
+        }
+        _ => {
+            for child in node.children_mut() {
+                recurse(child);
+            }
+        }
+    }
+}
+
+fn binds(pattern: &Node, name: &str) -> bool {
+    pattern.bound_names().iter().any(|bound| bound == name)
+}
+
+/// Whether `node` starts a scope that declares `name`: a function, block, loop head or
+/// switch body.
+fn declares(node: &Node, name: &str) -> bool {
+    let declared_in = |statements: &[Node]| {
+        statements.iter().any(|statement| {
+            lexical_names(statement)
+                .iter()
+                .any(|declared| declared == name)
+        })
+    };
+    match node {
+        Node::FunctionDeclaration { params, body, .. }
+        | Node::MethodDefinition { params, body, .. } => {
+            params.iter().any(|param| binds(&param.binding, name))
+                || body
+                    .as_deref()
+                    .is_some_and(|body| function_declares(body, name))
+        }
+        Node::ArrowFunctionExpression { params, body, .. } => {
+            params.iter().any(|param| binds(&param.binding, name)) || function_declares(body, name)
+        }
+        Node::BlockStatement(statements, _) => declared_in(statements),
+        Node::ForStatement {
+            init: Some(head), ..
+        }
+        | Node::ForInStatement { left: head, .. }
+        | Node::ForOfStatement { left: head, .. } => declared_in(std::slice::from_ref(head)),
+        Node::SwitchStatement { cases, .. } => {
+            cases.iter().any(|case| declared_in(&case.consequent))
+        }
+        Node::NamespaceDeclaration { body, .. } => body.iter().any(|statement| {
+            lexical_names(statement)
+                .into_iter()
+                .chain(var_names(statement))
+                .any(|declared| declared == name)
+        }),
+        _ => false,
+    }
+}
+
+fn function_declares(body: &Node, name: &str) -> bool {
+    let Node::BlockStatement(statements, _) = body else {
+        return false;
+    };
+    statements.iter().any(|statement| {
+        lexical_names(statement)
+            .into_iter()
+            .chain(var_names(statement))
+            .any(|declared| declared == name)
+    })
+}
+
+/// Names a statement declares in its own block.
+pub fn lexical_names(statement: &Node) -> Vec<String> {
+    match statement {
+        Node::VariableDeclaration { kind, declarations } if *kind != VariableKind::Var => {
+            declarations
+                .iter()
+                .flat_map(|declarator| declarator.id.bound_names())
+                .collect()
+        }
+        Node::FunctionDeclaration { name, .. }
+        | Node::ClassDeclaration { name, .. }
+        | Node::EnumDeclaration { name, .. } => vec![name.clone()],
+        Node::NamespaceDeclaration { name, .. } => name
+            .split('.')
+            .next()
+            .map(str::to_string)
+            .into_iter()
+            .collect(),
+        Node::ExportNamedDeclaration {
+            declaration: Some(declaration),
+            ..
+        }
+        | Node::ExportDefaultDeclaration(declaration) => lexical_names(declaration),
+        _ => Vec::new(),
+    }
+}
+
+/// `var` names declared anywhere in a statement outside nested functions.
+fn var_names(statement: &Node) -> Vec<String> {
+    match statement {
+        Node::VariableDeclaration {
+            kind: VariableKind::Var,
+            declarations,
+        } => declarations
+            .iter()
+            .flat_map(|declarator| declarator.id.bound_names())
+            .collect(),
+        Node::FunctionDeclaration { .. }
+        | Node::ArrowFunctionExpression { .. }
+        | Node::MethodDefinition { .. }
+        | Node::ClassDeclaration { .. } => Vec::new(),
+        statement => statement
+            .children()
+            .into_iter()
+            .flat_map(var_names)
+            .collect(),
+    }
+}
@@ -0,0 +1,701 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::{assignment, member, statement};
+use crate::binder::{is_instantiated, is_type};
+use crate::error_handling::{CompilerError, CompilerResult, Span};
+use crate::parser::{Node, VariableDeclarator, VariableKind};
+
+/// Declarations that describe values living outside the program. They carry type
+/// information only and never produce code.
+#[derive(Debug, Default)]
+pub struct AmbientDeclarations {
+    pub declarations: Vec<Node>, // declare const/function/class/..., declare namespace
+    pub globals: Vec<Node>,      // members of every `declare global` block
+    pub modules: Vec<(String, Option<Vec<Node>>)>, // declare module "name"
+}
+
+impl AmbientDeclarations {
+    /// Whether a `declare module` covers the specifier; `*` in a module name matches
+    /// anything, as in `declare module "*.css"`.
+    pub fn declares_module(&self, specifier: &str) -> bool {
+        self.modules
+            .iter()
+            .any(|(name, _)| match name.split_once('*') {
+                Some((prefix, suffix)) => {
+                    specifier.len() >= prefix.len() + suffix.len()
+                        && specifier.starts_with(prefix)
+                        && specifier.ends_with(suffix)
+                }
+                None => name == specifier,
+            })
+    }
+}
+
+pub fn collect_ambient_declarations(program: &Node) -> AmbientDeclarations {
+    let mut ambient = AmbientDeclarations::default();
+    if let Node::Program(body) = program {
+        for statement in body {
+            collect_ambient(statement, &mut ambient);
+        }
+    }
+    ambient
+}
+
+fn collect_ambient(statement: &Node, ambient: &mut AmbientDeclarations) {
+    match statement {
+        Node::ExportNamedDeclaration {
+            declaration: Some(declaration),
+            ..
+        } => collect_ambient(declaration, ambient),
+        Node::AmbientDeclaration(declaration) => ambient.declarations.push((**declaration).clone()),
+        Node::NamespaceDeclaration {
+            is_declare: true, ..
+        } => ambient.declarations.push(statement.clone()),
+        Node::GlobalAugmentation(body) => ambient.globals.extend(body.iter().cloned()),
+        Node::AmbientModuleDeclaration { name, body } => {
+            ambient.modules.push((name.clone(), body.clone()))
+        }
+        _ => {}
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MemberKind {
+    Value,
+    Type,
+    Namespace,
+}
+
+#[derive(Debug, Clone)]
+pub struct NamespaceMember {
+    pub kind: MemberKind, // a value's, if it has one, then a namespace's
+    pub exported: bool,
+}
+
+/// The statically known shape of a namespace, merged across all of its declarations.
+#[derive(Debug, Default)]
+pub struct NamespaceLayout {
+    pub members: HashMap<String, NamespaceMember>,
+    pub instantiated: bool, // whether any declaration has runtime code
+}
+
+/// Lowers namespaces to nested objects.
+///
+/// A namespace's shape is fixed at compile time, so its first declaration builds the
+/// whole object at once, `const A = { B: {} }`, with an object for every exported
+/// namespace nested in it. Each declaration's body then runs in a block of its own and
+/// assigns its exports to the object. Exported variables live only on the object, so
+/// they're read and written as `A.x`, as are the exports of other declarations of the same
+/// namespace; everything else stays a local of the block. A namespace merged with a
+/// function, class or enum adds its members to that value instead. Namespaces that only
+/// declare types produce no code, and ambient declarations are dropped, since they only
+/// describe things that exist elsewhere; see `collect_ambient_declarations`.
+pub fn lower_namespaces(
+    program: &Node,
+) -> CompilerResult<(Node, HashMap<String, NamespaceLayout>)> {
+    let Node::Program(body) = program else {
+        return CompilerResult::new(Some((program.clone(), HashMap::new())), Vec::new());
+    };
+    let mut lowering = Lowering {
+        layouts: HashMap::new(),
+        created: HashSet::new(),
+        errors: Vec::new(),
+    };
+    for statement in body {
+        if let Some((name, body)) = namespace(statement) {
+            lowering.collect(&[], name, body);
+        }
+    }
+    // Values a top-level namespace merges with
+    let values: HashSet<String> = body
+        .iter()
+        .filter(|statement| namespace(statement).is_none())
+        .flat_map(|statement| match statement {
+            Node::ExportNamedDeclaration {
+                declaration: Some(declaration),
+                ..
+            } => declared_members(declaration),
+            statement => declared_members(statement),
+        })
+        .filter(|(_, kind)| *kind == MemberKind::Value)
+        .map(|(name, _)| name)
+        .collect();
+
+    let mut output = Vec::new();
+    for statement in body {
+        if is_ambient(statement) {
+            continue;
+        }
+        let Some((name, namespace_body)) = namespace(statement) else {
+            output.push(statement.clone());
+            continue;
+        };
+        if !lowering.layouts[name].instantiated {
+            continue;
+        }
+        let object = Node::Identifier(name.to_string(), Span::default());
+        if lowering.created.insert(name.to_string()) {
+            if values.contains(name) {
+                output.extend(lowering.add_namespaces(name, &object));
+            } else {
+                let declaration = constant(name, lowering.object(name));
+                output.push(match statement {
+                    Node::ExportNamedDeclaration { .. } => Node::ExportNamedDeclaration {
+                        declaration: Some(Box::new(declaration)),
+                        specifiers: Vec::new(),
+                        source: None,
+                        is_type_only: false,
+                    },
+                    _ => declaration,
+                });
+            }
+        }
+        let block = lowering.lower(name, object, namespace_body, &mut Vec::new());
+        output.push(Node::BlockStatement(block, Span::default()));
+    }
+    CompilerResult::new(
+        Some((Node::Program(output), lowering.layouts)),
+        lowering.errors,
+    )
+}
+
+/// A namespace declaration that exists at runtime, possibly exported.
+fn namespace(statement: &Node) -> Option<(&str, &[Node])> {
+    match statement {
+        Node::NamespaceDeclaration {
+            name,
+            body,
+            is_declare: false,
+            ..
+        } => Some((name, body)),
+        Node::ExportNamedDeclaration {
+            declaration: Some(declaration),
+            ..
+        } => namespace(declaration),
+        _ => None,
+    }
+}
+
+fn is_ambient(statement: &Node) -> bool {
+    match statement {
+        Node::AmbientDeclaration(_)
+        | Node::AmbientModuleDeclaration { .. }
+        | Node::GlobalAugmentation(_)
+        | Node::NamespaceDeclaration {
+            is_declare: true, ..
+        } => true,
+        Node::ExportNamedDeclaration {
+            declaration: Some(declaration),
+            ..
+        } => is_ambient(declaration),
+        _ => false,
+    }
+}
+
+struct Lowering {
+    layouts: HashMap<String, NamespaceLayout>,
+    created: HashSet<String>, // namespaces whose object has been made, by qualified name
+    errors: Vec<CompilerError>,
+}
+
+/// The names declared by a function, block or namespace body being lowered, which hide the
+/// members of the namespaces around it.
+struct Frame {
+    names: HashSet<String>,
+    namespace: Option<(String, Node)>, // the namespace a body belongs to, and its object
+}
+
+impl Lowering {
+    fn collect(&mut self, outer: &[String], name: &str, body: &[Node]) {
+        let mut path = outer.to_vec();
+        path.push(name.to_string());
+        let qualified = path.join(".");
+        let layout = self.layouts.entry(qualified.clone()).or_default();
+        layout.instantiated |= is_instantiated(body);
+
+        for statement in body {
+            let (declaration, exported) = match statement {
+                Node::ExportNamedDeclaration {
+                    declaration: Some(declaration),
+                    ..
+                } => (declaration.as_ref(), true),
+                statement => (statement, false),
+            };
+            if is_ambient(declaration) {
+                continue;
+            }
+            if let Some((inner, inner_body)) = namespace(declaration) {
+                self.collect(&path, inner, inner_body);
+            }
+            for (member, kind) in declared_members(declaration) {
+                let layout = self.layouts.get_mut(&qualified).unwrap();
+                let member = layout.members.entry(member).or_insert(NamespaceMember {
+                    kind,
+                    exported: false,
+                });
+                member.exported |= exported;
+                if kind == MemberKind::Value || member.kind == MemberKind::Type {
+                    member.kind = kind;
+                }
+            }
+        }
+    }
+
+    /// The object of the namespace `qualified` as its first declaration makes it, holding
+    /// the objects of the namespaces it exports.
+    fn object(&self, qualified: &str) -> Node {
+        let properties = self
+            .nested(qualified)
+            .into_iter()
+            .map(|name| Node::Property {
+                key: Box::new(Node::Identifier(name.clone(), Span::default())),
+                computed: false,
+                value: Box::new(self.object(&format!("{}.{}", qualified, name))),
+                shorthand: false,
+            })
+            .collect();
+        Node::ObjectExpression(properties)
+    }
+
+    /// Assigns the objects of the namespaces `qualified` exports to `object`, an existing
+    /// value the namespace merges with.
+    fn add_namespaces(&self, qualified: &str, object: &Node) -> Vec<Node> {
+        self.nested(qualified)
+            .into_iter()
+            .map(|name| {
+                let value = self.object(&format!("{}.{}", qualified, name));
+                assign(member(object.clone(), &name), value)
+            })
+            .collect()
+    }
+
+    /// The exported namespaces nested in `qualified` that need an object of their own,
+    /// by name.
+    fn nested(&self, qualified: &str) -> Vec<String> {
+        let mut names: Vec<String> = self.layouts[qualified]
+            .members
+            .iter()
+            .filter(|(name, member)| {
+                member.exported
+                    && member.kind == MemberKind::Namespace
+                    && self.layouts[&format!("{}.{}", qualified, name)].instantiated
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Lowers the body of a declaration of the namespace `qualified`, whose object is
+    /// `object`, to the statements of its block.
+    fn lower(
+        &mut self,
+        qualified: &str,
+        object: Node,
+        body: &[Node],
+        frames: &mut Vec<Frame>,
+    ) -> Vec<Node> {
+        // Exported variables are only reachable through the object
+        let names = body
+            .iter()
+            .flat_map(|statement| match statement {
+                Node::ExportNamedDeclaration {
+                    declaration: Some(declaration),
+                    ..
+                } => match declaration.as_ref() {
+                    Node::VariableDeclaration { .. } => Vec::new(),
+                    declaration => declared_members(declaration),
+                },
+                statement => declared_members(statement),
+            })
+            .map(|(name, _)| name)
+            .collect();
+        frames.push(Frame {
+            names,
+            namespace: Some((qualified.to_string(), object.clone())),
+        });
+        let mut hoisted = Vec::new(); // exported functions, which exist from the start
+        let mut local_namespaces = HashSet::new(); // made in this block
+        let mut output = Vec::new();
+        for statement in body {
+            let (declaration, exported) = match statement {
+                Node::ExportNamedDeclaration {
+                    declaration: Some(declaration),
+                    ..
+                } => (declaration.as_ref(), true),
+                Node::ExportNamedDeclaration { .. } | Node::ExportAllDeclaration { .. } => {
+                    self.errors.push(CompilerError::new(
+                        "Export declarations are not permitted in a namespace".to_string(),
+                        statement.span(),
+                    ));
+                    continue;
+                }
+                statement => (statement, false),
+            };
+            if is_ambient(declaration) {
+                continue;
+            }
+            if let Some((name, inner_body)) = namespace(declaration) {
+                let inner = format!("{}.{}", qualified, name);
+                if !self.layouts[&inner].instantiated {
+                    continue;
+                }
+                let local = Node::Identifier(name.to_string(), Span::default());
+                let inner_object = if exported {
+                    member(object.clone(), name)
+                } else {
+                    local.clone()
+                };
+                // An exported namespace's object is part of this one's, unless it merges
+                // with a value. One that isn't exported is local to this declaration.
+                let merges = self.layouts[qualified].members[name].kind == MemberKind::Value;
+                let first = if exported {
+                    self.created.insert(inner.clone())
+                } else {
+                    local_namespaces.insert(name)
+                };
+                if (merges || !exported) && first {
+                    if merges {
+                        output.extend(self.add_namespaces(&inner, &inner_object));
+                    } else {
+                        output.push(constant(name, self.object(&inner)));
+                    }
+                }
+                let block = self.lower(&inner, inner_object, inner_body, frames);
+                output.push(Node::BlockStatement(block, Span::default()));
+                continue;
+            }
+            match declaration {
+                Node::VariableDeclaration { kind, declarations } if exported => {
+                    for declarator in declarations {
+                        let mut init = declarator.init.as_deref().cloned();
+                        if let Some(init) = &mut init {
+                            self.rewrite(init, frames);
+                        }
+                        if let Node::Identifier(name, _) = declarator.id.as_ref() {
+                            if let Some(init) = init {
+                                output.push(assign(member(object.clone(), name), init));
+                            }
+                            continue;
+                        }
+                        // Patterns are bound locally, then copied to the object
+                        output.push(Node::VariableDeclaration {
+                            kind: kind.clone(),
+                            declarations: vec![VariableDeclarator {
+                                init: init.map(Box::new),
+                                ..declarator.clone()
+                            }],
+                        });
+                        for name in declarator.id.bound_names() {
+                            let value = Node::Identifier(name.clone(), Span::default());
+                            output.push(assign(member(object.clone(), &name), value));
+                        }
+                    }
+                }
+                declaration => {
+                    let mut declaration = declaration.clone();
+                    self.rewrite(&mut declaration, frames);
+                    let name = match &declaration {
+                        Node::FunctionDeclaration { name, .. }
+                        | Node::ClassDeclaration { name, .. }
+                        | Node::EnumDeclaration {
+                            name,
+                            is_const: false,
+                            ..
+                        } if exported => Some(name.clone()),
+                        _ => None,
+                    };
+                    let is_function = matches!(declaration, Node::FunctionDeclaration { .. });
+                    output.push(declaration);
+                    if let Some(name) = name {
+                        let value = Node::Identifier(name.clone(), Span::default());
+                        let export = assign(member(object.clone(), &name), value);
+                        if is_function {
+                            if !hoisted.contains(&export) {
+                                hoisted.push(export);
+                            }
+                        } else {
+                            output.push(export);
+                        }
+                    }
+                }
+            }
+        }
+        frames.pop();
+        hoisted.extend(output);
+        hoisted
+    }
+
+    /// `name` read through the object of the innermost namespace that exports it, unless a
+    /// closer declaration hides it.
+    fn resolve(&self, frames: &[Frame], name: &str, span: Span) -> Option<Node> {
+        for frame in frames.iter().rev() {
+            if frame.names.contains(name) {
+                return None;
+            }
+            let Some((qualified, object)) = &frame.namespace else {
+                continue;
+            };
+            let member_of = self.layouts[qualified].members.get(name);
+            if member_of.is_some_and(|member| member.exported && member.kind != MemberKind::Type) {
+                return Some(Node::MemberExpression {
+                    object: Box::new(object.clone()),
+                    property: Box::new(Node::Identifier(name.to_string(), span)),
+                    computed: false,
+                    optional: false,
+                });
+            }
+        }
+        None
+    }
+
+    fn rewrite(&mut self, node: &mut Node, frames: &mut Vec<Frame>) {
+        match node {
+            Node::Identifier(name, span) => {
+                if let Some(resolved) = self.resolve(frames, name, *span) {
+                    *node = resolved;
+                }
+            }
+            node if is_type(node) => {}
+            Node::MemberExpression {
+                object,
+                property,
+                computed,
+                ..
+            } => {
+                self.rewrite(object, frames);
+                if *computed {
+                    self.rewrite(property, frames);
+                }
+            }
+            Node::Property {
+                key,
+                computed,
+                value,
+                shorthand,
+            } => {
+                if *computed {
+                    self.rewrite(key, frames);
+                }
+                self.rewrite(value, frames);
+                *shorthand &= matches!(value.as_ref(), Node::Identifier(..));
+            }
+            // Non-computed keys name properties, not bindings
+            Node::MethodDefinition {
+                computed: false, ..
+            }
+            | Node::PropertyDefinition {
+                computed: false, ..
+            }
+            | Node::PropertySignature {
+                computed: false, ..
+            }
+            | Node::MethodSignature {
+                computed: false, ..
+            }
+            | Node::PatternProperty {
+                computed: false, ..
+            } => {
+                frames.push(Frame {
+                    names: parameter_names(node),
+                    namespace: None,
+                });
+                // The key comes first
+                for child in node.children_mut().into_iter().skip(1) {
+                    self.rewrite(child, frames);
+                }
+                frames.pop();
+            }
+            Node::FunctionDeclaration { .. }
+            | Node::ArrowFunctionExpression { .. }
+            | Node::MethodDefinition { .. }
+            | Node::BlockStatement(..)
+            | Node::ForStatement { .. }
+            | Node::ForInStatement { .. }
+            | Node::ForOfStatement { .. }
+            | Node::SwitchStatement { .. } => {
+                frames.push(Frame {
+                    names: parameter_names(node),
+                    namespace: None,
+                });
+                for child in node.children_mut() {
+                    self.rewrite(child, frames);
+                }
+                frames.pop();
+            }
+            Node::TryStatement {
+                block,
+                handler,
+                finalizer,
+            } => {
+                self.rewrite(block, frames);
+                if let Some(handler) = handler {
+                    let names: HashSet<String> = handler
+                        .param
+                        .as_deref()
+                        .map(Node::bound_names)
+                        .into_iter()
+                        .flatten()
+                        .collect();
+                    frames.push(Frame {
+                        names,
+                        namespace: None,
+                    });
+                    if let Some(param) = handler.param.as_deref_mut() {
+                        self.rewrite(param, frames);
+                    }
+                    self.rewrite(&mut handler.body, frames);
+                    frames.pop();
+                }
+                if let Some(finalizer) = finalizer {
+                    self.rewrite(finalizer, frames);
+                }
+            }
+            _ => {
+                for child in node.children_mut() {
+                    self.rewrite(child, frames);
+                }
+            }
+        }
+    }
+}
+
+fn constant(name: &str, value: Node) -> Node {
+    Node::VariableDeclaration {
+        kind: VariableKind::Const,
+        declarations: vec![VariableDeclarator {
+            id: Box::new(Node::Identifier(name.to_string(), Span::default())),
+            type_annotation: None,
+            init: Some(Box::new(value)),
+        }],
+    }
+}
+
+/// `target = value;`
+fn assign(target: Node, value: Node) -> Node {
+    statement(assignment(target, value))
+}
+
+/// Names bound by a function's parameters and body, or by the declarations of a block, a
+/// loop head or a switch body.
+fn parameter_names(node: &Node) -> HashSet<String> {
+    let mut names = HashSet::new();
+    let declared = |statement| {
+        declared_members(statement)
+            .into_iter()
+            .map(|(name, _)| name)
+    };
+    let body = match node {
+        Node::FunctionDeclaration { params, body, .. } => {
+            names.extend(params.iter().flat_map(|param| param.binding.bound_names()));
+            body.as_deref()
+        }
+        Node::MethodDefinition { params, body, .. } => {
+            names.extend(params.iter().flat_map(|param| param.binding.bound_names()));
+            body.as_deref()
+        }
+        Node::ArrowFunctionExpression { params, body, .. } => {
+            names.extend(params.iter().flat_map(|param| param.binding.bound_names()));
+            Some(body.as_ref())
+        }
+        Node::BlockStatement(..) => Some(node),
+        Node::ForStatement {
+            init: Some(head), ..
+        }
+        | Node::ForInStatement { left: head, .. }
+        | Node::ForOfStatement { left: head, .. } => {
+            names.extend(declared(head));
+            None
+        }
+        Node::SwitchStatement { cases, .. } => {
+            names.extend(
+                cases
+                    .iter()
+                    .flat_map(|case| &case.consequent)
+                    .flat_map(declared),
+            );
+            None
+        }
+        _ => None,
+    };
+    if let Some(Node::BlockStatement(statements, _)) = body {
+        names.extend(statements.iter().flat_map(declared));
+    }
+    names
+}
+
+/// Names a statement declares, with the meaning each gets.
+fn declared_members(statement: &Node) -> Vec<(String, MemberKind)> {
+    match statement {
+        Node::FunctionDeclaration { name, .. }
+        | Node::ClassDeclaration { name, .. }
+        | Node::EnumDeclaration { name, .. } => vec![(name.clone(), MemberKind::Value)],
+        Node::InterfaceDeclaration { name, .. } | Node::TypeAliasDeclaration { name, .. } => {
+            vec![(name.clone(), MemberKind::Type)]
+        }
+        Node::NamespaceDeclaration { name, .. } => vec![(name.clone(), MemberKind::Namespace)],
+        Node::VariableDeclaration { declarations, .. } => declarations
+            .iter()
+            .flat_map(|declarator| declarator.id.bound_names())
+            .map(|name| (name, MemberKind::Value))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::tokenizer::Tokenizer;
+
+    /// The lowered top-level statements of `source`, debug-printed.
+    fn lower(source: &str) -> Vec<String> {
+        let tokens = Tokenizer::new(source.to_string()).tokenize();
+        let program = Parser::new(tokens).parse().result.expect("parses");
+        let lowered = lower_namespaces(&program);
+        assert!(lowered.errors.is_empty(), "{:?}", lowered.errors);
+        let Some((Node::Program(body), _)) = lowered.result else {
+            panic!("lowers to a program");
+        };
+        body.iter()
+            .map(|statement| format!("{:?}", statement))
+            .collect()
+    }
+
+    #[test]
+    fn the_first_declaration_builds_the_whole_object() {
+        let statements = lower(
+            "namespace A { export const x = 1; const hidden = 2; }
+             namespace A { export namespace B { export const y = x; } }",
+        );
+        assert_eq!(statements.len(), 3, "{:#?}", statements);
+        // const A = { B: {} }
+        assert!(statements[0].starts_with("VariableDeclaration { kind: Const"));
+        assert!(statements[0].contains("key: Identifier(\"B\""));
+        assert!(statements[0].contains("value: ObjectExpression([])"));
+        // Exports are assigned to the object, the rest stays a local of the block
+        assert!(statements[1].starts_with("BlockStatement([ExpressionStatement(AssignmentExpression { left: MemberExpression { object: Identifier(\"A\""));
+        assert!(statements[1].contains("id: Identifier(\"hidden\""));
+        // Another declaration's exports are read through the object
+        assert!(statements[2].contains("right: MemberExpression { object: Identifier(\"A\""));
+        assert!(!statements[2].contains("VariableDeclaration"));
+    }
+
+    #[test]
+    fn a_namespace_merged_with_a_function_adds_to_it() {
+        let statements = lower("function f() {} namespace f { export const a = 1; }");
+        assert_eq!(statements.len(), 2, "{:#?}", statements);
+        assert!(statements[0].starts_with("FunctionDeclaration { name: \"f\""));
+        assert!(statements[1].starts_with("BlockStatement([ExpressionStatement(AssignmentExpression { left: MemberExpression { object: Identifier(\"f\""));
+    }
+
+    #[test]
+    fn type_only_and_ambient_namespaces_produce_no_code() {
+        let statements = lower(
+            "namespace T { export type U = number; }
+             declare namespace D { const z: number; }",
+        );
+        assert!(statements.is_empty(), "{:#?}", statements);
+    }
+}
@@ -0,0 +1,252 @@
+use crate::checker::{falsy_part, truthy_part};
+use crate::types_checking::Type;
+
+/// Narrows `t` by `typeof x === name`, or by `typeof x !== name` unless `assume_true`.
+/// `any` and `unknown` become the type the name stands for, except that `any` stays itself
+/// for `"object"` and `"function"`.
+pub fn by_typeof(t: &Type, name: &str, assume_true: bool) -> Type {
+    let narrowed = members(t)
+        .into_iter()
+        .filter_map(|member| match typeof_matches(&member, name) {
+            Some(matches) => (matches == assume_true).then_some(member),
+            None if !assume_true => Some(member),
+            None => Some(match member {
+                Type::Unknown => typeof_type(name).unwrap_or(member),
+                Type::Any if name != "object" && name != "function" => {
+                    typeof_type(name).unwrap_or(member)
+                }
+                member => member,
+            }),
+        })
+        .collect();
+    Type::union(narrowed)
+}
+
+/// Whether `typeof` gives `name` for the values of `t`, if it gives the same for all of
+/// them.
+fn typeof_matches(t: &Type, name: &str) -> Option<bool> {
+    let kind = match t {
+        Type::String => "string",
+        Type::Number => "number",
+        Type::Boolean => "boolean",
+        Type::BigInt => "bigint",
+        Type::Symbol => "symbol",
+        Type::Undefined | Type::Void => "undefined",
+        Type::Null | Type::Array(_) | Type::Tuple { .. } => "object",
+        Type::Literal(_) => return typeof_matches(&t.widen_literal(), name),
+        Type::Function { .. } => "function",
+        Type::Shape(shape) => {
+            let object = shape.get();
+            if object.call_signatures.is_empty() && object.construct_signatures.is_empty() {
+                "object"
+            } else {
+                "function"
+            }
+        }
+        Type::Custom(custom, _) if custom == "Function" => "function",
+        Type::Custom(..) => "object",
+        _ => return None,
+    };
+    Some(kind == name)
+}
+
+/// The type of the values `typeof` gives `name` for.
+fn typeof_type(name: &str) -> Option<Type> {
+    Some(match name {
+        "string" => Type::String,
+        "number" => Type::Number,
+        "boolean" => Type::Boolean,
+        "bigint" => Type::BigInt,
+        "symbol" => Type::Symbol,
+        "undefined" => Type::Undefined,
+        "object" => Type::union(vec![Type::Object, Type::Null]),
+        "function" => Type::Custom("Function".to_string(), Vec::new()),
+        _ => return None,
+    })
+}
+
+/// Narrows `t` by comparing it to a value of type `value` with `===`, or `==` if `loose`;
+/// unless `assume_true`, by `!==` or `!=`. Only a unit type, a literal, `null`, `undefined`
+/// or an enum member, can be removed by an inequality, and loosely `null` and `undefined`
+/// are equal to each other.
+pub fn by_equality(t: &Type, value: &Type, loose: bool, assume_true: bool) -> Type {
+    let nullish = |t: &Type| matches!(t, Type::Null | Type::Undefined | Type::Void);
+    let loosely_nullish = loose && nullish(value);
+    if assume_true {
+        if matches!(value, Type::Any) {
+            return t.clone();
+        }
+        let narrowed = expanded(t, value)
+            .into_iter()
+            .filter_map(|member| match member {
+                Type::Any | Type::Param(_) => Some(member),
+                Type::Unknown if loosely_nullish => {
+                    Some(Type::union(vec![Type::Null, Type::Undefined]))
+                }
+                Type::Unknown => Some(value.clone()),
+                member if loosely_nullish => nullish(&member).then_some(member),
+                member if value.is_assignable_to(&member) => Some(if is_unit(value) {
+                    value.clone()
+                } else {
+                    member
+                }),
+                member if member.is_assignable_to(value) => Some(member),
+                _ => None,
+            })
+            .collect();
+        Type::union(narrowed)
+    } else if is_unit(value) {
+        let narrowed = expanded(t, value)
+            .into_iter()
+            .filter(|member| {
+                if loosely_nullish {
+                    !nullish(member)
+                } else {
+                    member != value
+                }
+            })
+            .collect();
+        Type::union(narrowed)
+    } else {
+        t.clone()
+    }
+}
+
+fn is_unit(t: &Type) -> bool {
+    matches!(
+        t,
+        Type::Literal(_) | Type::Null | Type::Undefined | Type::EnumMember(..)
+    )
+}
+
+/// The members of `t`, with `boolean` split into `true` and `false` when comparing to
+/// either.
+fn expanded(t: &Type, value: &Type) -> Vec<Type> {
+    let boolean =
+        matches!(value, Type::Literal(literal) if literal == "true" || literal == "false");
+    members(t)
+        .into_iter()
+        .flat_map(|member| match member {
+            Type::Boolean if boolean => vec![
+                Type::Literal("true".to_string()),
+                Type::Literal("false".to_string()),
+            ],
+            member => vec![member],
+        })
+        .collect()
+}
+
+/// Narrows `t` by whether a value of it is truthy. Unlike the falsy part an `&&` gives,
+/// the falsy values of a string or number are still a string or number.
+pub fn by_truthiness(t: &Type, assume_true: bool) -> Type {
+    if assume_true {
+        return truthy_part(t);
+    }
+    let narrowed = members(t)
+        .into_iter()
+        .map(|member| match member {
+            Type::String | Type::Number | Type::BigInt => member,
+            member => falsy_part(&member),
+        })
+        .collect();
+    Type::union(narrowed)
+}
+
+/// Narrows `t` by `name in x`: to the object types that have, or may have, the property,
+/// or unless `assume_true`, to those that may not.
+pub fn by_property(t: &Type, name: &str, assume_true: bool) -> Type {
+    let narrowed = members(t)
+        .into_iter()
+        .filter(|member| match member {
+            Type::Shape(shape) => {
+                let object = shape.get();
+                match object.property(name) {
+                    Some(property) => assume_true || property.optional,
+                    None => !assume_true || object.string_index.is_some(),
+                }
+            }
+            _ => true,
+        })
+        .collect();
+    Type::union(narrowed)
+}
+
+/// Narrows `t` by a condition on its property `name`, keeping the members whose
+/// type of the property `narrow` doesn't make `never`. A member without the property has
+/// it undefined.
+pub fn by_discriminant(t: &Type, name: &str, narrow: impl Fn(&Type) -> Type) -> Type {
+    let narrowed = members(t)
+        .into_iter()
+        .filter(|member| {
+            let property = match member {
+                Type::Null | Type::Undefined => Type::Undefined,
+                Type::Shape(_) => member.property_type(name).unwrap_or(Type::Undefined),
+                _ => return true,
+            };
+            narrow(&property) != Type::Never
+        })
+        .collect();
+    Type::union(narrowed)
+}
+
+/// Narrows `t` to the values that are of `target`, as `instanceof` and type guards do, or
+/// unless `assume_true`, to those that are not. A member `target` is a subtype of becomes
+/// `target`, and a type unrelated to `target` becomes its intersection with it.
+pub fn to_subtype(t: &Type, target: &Type, assume_true: bool) -> Type {
+    if !assume_true {
+        let narrowed = members(t)
+            .into_iter()
+            .filter(|member| {
+                matches!(member, Type::Any | Type::Unknown) || !member.is_assignable_to(target)
+            })
+            .collect();
+        return Type::union(narrowed);
+    }
+    if matches!(t, Type::Any | Type::Unknown) {
+        return target.clone();
+    }
+    let narrowed: Vec<Type> = members(t)
+        .into_iter()
+        .filter_map(|member| {
+            if member.is_assignable_to(target) {
+                Some(member)
+            } else if target.is_assignable_to(&member) {
+                Some(target.clone())
+            } else {
+                None
+            }
+        })
+        .collect();
+    if narrowed.is_empty() && !matches!(t, Type::Union(_)) {
+        return Type::intersection(vec![t.clone(), target.clone()]);
+    }
+    Type::union(narrowed)
+}
+
+/// The type a reference declared `declared` has once assigned a value of type `assigned`:
+/// the members of a declared union the value may be of.
+pub fn by_assignment(declared: &Type, assigned: &Type) -> Type {
+    let Type::Union(types) = declared else {
+        return declared.clone();
+    };
+    let narrowed: Vec<Type> = types
+        .iter()
+        .filter(|member| {
+            members(assigned)
+                .iter()
+                .any(|value| value.is_assignable_to(member))
+        })
+        .cloned()
+        .collect();
+    if narrowed.is_empty() {
+        return declared.clone();
+    }
+    Type::union(narrowed)
+}
+
+fn members(t: &Type) -> Vec<Type> {
+    match t {
+        Type::Union(types) => types.clone(),
+        t => vec![t.clone()],
+    }
+}
@@ -0,0 +1,381 @@
+use crate::ast::{assignment, binary, let_declaration, null, undefined};
+use crate::error_handling::{CompilerResult, Span};
+use crate::names::FreshNames;
+use crate::parser::Node;
+
+/// Lowers optional chains and `??` to conditional expressions, so codegen only sees plain
+/// branches on values it already has.
+///
+/// Each `?.` stores its object in a temporary (`$o0`, `$o1`, ...) and compares it to `null`
+/// with `==`, which matches `undefined` too. `a?.b.c?.(x)` becomes
+///
+/// ```text
+/// ($o0 = a) == null ? undefined : ($o1 = ($o2 = $o0.b).c) == null ? undefined : $o1.call($o2, x)
+/// ```
+///
+/// so an optional call on a property keeps the object as `this`. `a ?? b` becomes
+/// `($o0 = a) != null ? $o0 : b`. The temporaries are declared with `let` at the start of
+/// the enclosing function, or of the enclosing one for parameter defaults.
+pub fn lower_optional_chains(program: &Node) -> CompilerResult<Node> {
+    let mut lowering = Lowering {
+        names: FreshNames::new(program),
+        temporaries: Vec::new(),
+    };
+    let mut program = program.clone();
+    lowering.lower(&mut program);
+    if let Node::Program(body) = &mut program {
+        if !lowering.temporaries.is_empty() {
+            body.insert(
+                0,
+                let_declaration(std::mem::take(&mut lowering.temporaries)),
+            );
+        }
+    }
+    CompilerResult::new(Some(program), Vec::new())
+}
+
+/// One access or call in an optional chain, without the object it applies to.
+enum Link {
+    Member {
+        property: Node,
+        computed: bool,
+        optional: bool,
+    },
+    Call {
+        type_arguments: Vec<Node>,
+        arguments: Vec<Node>,
+        optional: bool,
+    },
+}
+
+struct Lowering {
+    names: FreshNames,
+    temporaries: Vec<String>, // of the function being lowered
+}
+
+impl Lowering {
+    fn lower(&mut self, node: &mut Node) {
+        match node {
+            Node::FunctionDeclaration {
+                params,
+                body: Some(body),
+                ..
+            }
+            | Node::MethodDefinition {
+                params,
+                body: Some(body),
+                ..
+            }
+            | Node::ArrowFunctionExpression { params, body, .. } => {
+                // Defaults are evaluated before the body's declarations exist
+                for init in params
+                    .iter_mut()
+                    .filter_map(|param| param.init.as_deref_mut())
+                {
+                    self.lower(init);
+                }
+                let outer = std::mem::take(&mut self.temporaries);
+                self.lower(body);
+                let temporaries = std::mem::replace(&mut self.temporaries, outer);
+                if temporaries.is_empty() {
+                    return;
+                }
+                if !matches!(body.as_ref(), Node::BlockStatement(..)) {
+                    let expression = std::mem::replace(body.as_mut(), Node::EmptyStatement);
+                    **body = Node::BlockStatement(
+                        vec![Node::ReturnStatement(Some(Box::new(expression)))],
+                        Span::default(),
+                    );
+                }
+                if let Node::BlockStatement(statements, _) = body.as_mut() {
+                    statements.insert(0, let_declaration(temporaries));
+                }
+            }
+            Node::ChainExpression(chain) => {
+                self.lower(chain);
+                let chain = std::mem::replace(chain.as_mut(), Node::EmptyStatement);
+                let (object, links) = flatten(chain);
+                *node = self.short_circuit(object, links);
+            }
+            Node::LogicalExpression {
+                left,
+                operator,
+                right,
+            } if operator == "??" => {
+                self.lower(left);
+                self.lower(right);
+                let temporary = self.temporary();
+                let left = std::mem::replace(left.as_mut(), Node::EmptyStatement);
+                let right = std::mem::replace(right.as_mut(), Node::EmptyStatement);
+                *node = Node::ConditionalExpression {
+                    test: Box::new(binary(assignment(temporary.clone(), left), "!=", null())),
+                    consequent: Box::new(temporary),
+                    alternate: Box::new(right),
+                };
+            }
+            _ => {
+                for child in node.children_mut() {
+                    self.lower(child);
+                }
+            }
+        }
+    }
+
+    /// Applies `links` to `object` in order, branching to `undefined` at each `?.`.
+    fn short_circuit(&mut self, mut object: Node, links: Vec<Link>) -> Node {
+        let mut links = links.into_iter();
+        while let Some(link) = links.next() {
+            let optional = match &link {
+                Link::Member { optional, .. } | Link::Call { optional, .. } => *optional,
+            };
+            if !optional {
+                object = apply(object, link);
+                continue;
+            }
+            let tested = self.temporary();
+            let (stored, link) = match (object, link) {
+                // `a.m?.()` still calls `m` with `a` as `this`
+                (
+                    Node::MemberExpression {
+                        object: receiver,
+                        property,
+                        computed,
+                        ..
+                    },
+                    Link::Call {
+                        type_arguments,
+                        arguments,
+                        ..
+                    },
+                ) => {
+                    let this = self.temporary();
+                    let method = Node::MemberExpression {
+                        object: Box::new(assignment(this.clone(), *receiver)),
+                        property,
+                        computed,
+                        optional: false,
+                    };
+                    let mut arguments = arguments;
+                    arguments.insert(0, this);
+                    let call = Link::Call {
+                        type_arguments,
+                        arguments,
+                        optional: false,
+                    };
+                    let callee = Node::MemberExpression {
+                        object: Box::new(tested.clone()),
+                        property: Box::new(Node::Identifier("call".to_string(), Span::default())),
+                        computed: false,
+                        optional: false,
+                    };
+                    let test = binary(assignment(tested, method), "==", null());
+                    let rest = self.short_circuit(apply(callee, call), links.collect());
+                    return conditional(test, rest);
+                }
+                (object, link) => (assignment(tested.clone(), object), link),
+            };
+            let test = binary(stored, "==", null());
+            let rest = self.short_circuit(apply(tested, link), links.collect());
+            return conditional(test, rest);
+        }
+        object
+    }
+
+    fn temporary(&mut self) -> Node {
+        let name = self.names.fresh("$o");
+        self.temporaries.push(name.clone());
+        Node::Identifier(name, Span::default())
+    }
+}
+
+/// Splits the accesses and calls of a chain from the object they start at.
+fn flatten(mut node: Node) -> (Node, Vec<Link>) {
+    let mut links = Vec::new();
+    loop {
+        node = match node {
+            Node::MemberExpression {
+                object,
+                property,
+                computed,
+                optional,
+            } => {
+                links.push(Link::Member {
+                    property: *property,
+                    computed,
+                    optional,
+                });
+                *object
+            }
+            Node::CallExpression {
+                callee,
+                type_arguments,
+                arguments,
+                optional,
+            } => {
+                links.push(Link::Call {
+                    type_arguments,
+                    arguments,
+                    optional,
+                });
+                *callee
+            }
+            node => {
+                links.reverse();
+                return (node, links);
+            }
+        };
+    }
+}
+
+fn apply(object: Node, link: Link) -> Node {
+    match link {
+        Link::Member {
+            property, computed, ..
+        } => Node::MemberExpression {
+            object: Box::new(object),
+            property: Box::new(property),
+            computed,
+            optional: false,
+        },
+        Link::Call {
+            type_arguments,
+            arguments,
+            ..
+        } => Node::CallExpression {
+            callee: Box::new(object),
+            type_arguments,
+            arguments,
+            optional: false,
+        },
+    }
+}
+
+fn conditional(test: Node, value: Node) -> Node {
+    Node::ConditionalExpression {
+        test: Box::new(test),
+        consequent: Box::new(undefined()),
+        alternate: Box::new(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::tokenizer::{TokenType, Tokenizer};
+
+    /// The lowered top-level statements of `source`.
+    fn lower(source: &str) -> Vec<Node> {
+        let tokens = Tokenizer::new(source.to_string()).tokenize();
+        let program = Parser::new(tokens).parse().result.expect("parses");
+        let Some(Node::Program(body)) = lower_optional_chains(&program).result else {
+            panic!("lowers to a program");
+        };
+        body
+    }
+
+    fn is_identifier(node: &Node, expected: &str) -> bool {
+        matches!(node, Node::Identifier(name, _) if name == expected)
+    }
+
+    fn declared_names(statement: &Node) -> Vec<&str> {
+        let Node::VariableDeclaration { declarations, .. } = statement else {
+            return Vec::new();
+        };
+        declarations
+            .iter()
+            .filter_map(|declarator| match &*declarator.id {
+                Node::Identifier(name, _) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn an_optional_member_access_tests_a_temporary_against_null() {
+        let body = lower("let r = a?.b;");
+        assert_eq!(body.len(), 2);
+        assert_eq!(declared_names(&body[0]), ["$o0"]);
+        let Node::VariableDeclaration { declarations, .. } = &body[1] else {
+            panic!("declares r");
+        };
+        // ($o0 = a) == null ? undefined : $o0.b
+        let Some(Node::ConditionalExpression {
+            test,
+            consequent,
+            alternate,
+        }) = declarations[0].init.as_deref()
+        else {
+            panic!("a conditional: {:?}", declarations[0].init);
+        };
+        let Node::BinaryExpression {
+            left,
+            operator,
+            right,
+        } = &**test
+        else {
+            panic!("a comparison: {:?}", test);
+        };
+        assert_eq!(operator, "==");
+        assert!(matches!(
+            &**right,
+            Node::Literal {
+                token_type: TokenType::Null,
+                ..
+            }
+        ));
+        assert!(
+            matches!(&**left, Node::AssignmentExpression { left, right, .. }
+            if is_identifier(left, "$o0") && is_identifier(right, "a"))
+        );
+        assert!(matches!(
+            &**consequent,
+            Node::Literal {
+                token_type: TokenType::Undefined,
+                ..
+            }
+        ));
+        assert!(
+            matches!(&**alternate, Node::MemberExpression { object, optional: false, .. }
+            if is_identifier(object, "$o0"))
+        );
+    }
+
+    #[test]
+    fn an_optional_call_on_a_property_keeps_its_object_as_this() {
+        let body = lower("function g(o) { return o.m?.(1); }");
+        let debug = format!("{:?}", body[0]);
+        // ... : $o0.call($o1, 1)
+        assert!(debug.contains("callee: MemberExpression { object: Identifier(\"$o0\""));
+        assert!(debug.contains("property: Identifier(\"call\""));
+        assert!(debug.contains("arguments: [Identifier(\"$o1\""));
+        assert!(!debug.contains("optional: true"));
+    }
+
+    #[test]
+    fn temporaries_are_declared_in_the_enclosing_function() {
+        let body = lower("function g(o) { return o ?? 2; }");
+        assert_eq!(body.len(), 1, "nothing is declared at the top level");
+        let Node::FunctionDeclaration {
+            body: Some(block), ..
+        } = &body[0]
+        else {
+            panic!("a function: {:?}", body[0]);
+        };
+        let Node::BlockStatement(statements, _) = &**block else {
+            panic!("a block: {:?}", block);
+        };
+        assert_eq!(declared_names(&statements[0]), ["$o0"]);
+        assert!(format!("{:?}", statements[1]).contains("operator: \"!=\""));
+
+        // A parameter default can't see the function's own locals
+        let body = lower("function f(x = o?.b) { return x; }");
+        assert_eq!(declared_names(&body[0]), ["$o0"]);
+    }
+
+    #[test]
+    fn temporaries_do_not_collide_with_names_in_the_program() {
+        let body = lower("let $o0 = 1; let r = $o0?.b;");
+        assert_eq!(declared_names(&body[0]), ["$o1"]);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::error_handling::{CompilerError, CompilerResult, Span};
 use crate::tokenizer::{Token, TokenType};
 
 #[derive(Debug, PartialEq, Clone)]
@@ -8,9 +9,11 @@ pub enum Node {
     // Declarations
     FunctionDeclaration {
         name: String,
+        span: Span, // of the name
+        type_params: Vec<TypeParameter>,
         params: Vec<Parameter>,
         return_type: Option<Box<Node>>,
-        body: Box<Node>,
+        body: Option<Box<Node>>, // None for overload signatures and ambient functions
         is_async: bool,
         is_generator: bool,
     },
@@ -20,23 +23,93 @@ pub enum Node {
     },
     ClassDeclaration {
         name: String,
+        span: Span, // of the name
+        type_params: Vec<TypeParameter>,
         extends: Option<Box<Node>>,
+        super_type_arguments: Vec<Node>, // `extends Base<T>`
         implements: Vec<Node>,
         members: Vec<Node>,
+        decorators: Vec<Node>,
     },
     InterfaceDeclaration {
         name: String,
+        span: Span, // of the name
+        type_params: Vec<TypeParameter>,
         extends: Vec<Node>,
         members: Vec<Node>,
     },
     TypeAliasDeclaration {
         name: String,
+        span: Span, // of the name
+        type_params: Vec<TypeParameter>,
         type_annotation: Box<Node>,
     },
+    EnumDeclaration {
+        name: String,
+        span: Span, // of the name
+        members: Vec<EnumMember>,
+        is_const: bool,
+        is_declare: bool,
+    },
+
+    NamespaceDeclaration {
+        name: String, // `namespace A.B {}` nests an exported `B` inside `A`
+        span: Span, // of the name
+        body: Vec<Node>,
+        is_declare: bool,
+    },
+    AmbientModuleDeclaration {
+        name: String,            // declare module "name"
+        body: Option<Vec<Node>>, // None for the `declare module "name";` shorthand
+    },
+    GlobalAugmentation(Vec<Node>), // declare global { ... }
+    AmbientDeclaration(Box<Node>), // declare const/let/var/function/class/interface/type
+
+    // Modules
+    ImportDeclaration {
+        specifiers: Vec<ImportSpecifier>, // empty for `import "mod"`
+        source: String,
+        is_type_only: bool,
+    },
+    ExportNamedDeclaration {
+        declaration: Option<Box<Node>>,
+        specifiers: Vec<ExportSpecifier>,
+        source: Option<String>, // re-export
+        is_type_only: bool,
+    },
+    ExportDefaultDeclaration(Box<Node>),
+    ExportAllDeclaration {
+        exported: Option<String>, // `export * as ns from "mod"`
+        source: String,
+        is_type_only: bool,
+    },
+
+    // Class members
+    MethodDefinition {
+        key: Box<Node>,
+        computed: bool,
+        kind: MethodKind,
+        type_params: Vec<TypeParameter>,
+        params: Vec<Parameter>,
+        return_type: Option<Box<Node>>,
+        body: Option<Box<Node>>, // None for overload signatures
+        modifiers: Modifiers,
+        is_async: bool,
+        is_generator: bool,
+    },
+    PropertyDefinition {
+        key: Box<Node>,
+        computed: bool,
+        type_annotation: Option<Box<Node>>,
+        value: Option<Box<Node>>,
+        optional: bool,
+        modifiers: Modifiers,
+    },
 
     // Statements
-    BlockStatement(Vec<Node>),
+    BlockStatement(Vec<Node>, Span), // with the span of its `{`
     ExpressionStatement(Box<Node>),
+    EmptyStatement,
     IfStatement {
         condition: Box<Node>,
         then_branch: Box<Node>,
@@ -52,9 +125,33 @@ pub enum Node {
         test: Box<Node>,
         body: Box<Node>,
     },
+    DoWhileStatement {
+        body: Box<Node>,
+        test: Box<Node>,
+    },
+    // `left` is a VariableDeclaration without initializer, or an assignment target
+    ForInStatement {
+        left: Box<Node>,
+        right: Box<Node>,
+        body: Box<Node>,
+    },
+    ForOfStatement {
+        left: Box<Node>,
+        right: Box<Node>,
+        body: Box<Node>,
+        is_await: bool,
+    },
+    SwitchStatement {
+        discriminant: Box<Node>,
+        cases: Vec<SwitchCase>,
+    },
+    LabeledStatement {
+        label: String,
+        body: Box<Node>,
+    },
     ReturnStatement(Option<Box<Node>>),
-    BreakStatement,
-    ContinueStatement,
+    BreakStatement(Option<String>),
+    ContinueStatement(Option<String>),
     ThrowStatement(Box<Node>),
     TryStatement {
         block: Box<Node>,
@@ -62,11 +159,35 @@ pub enum Node {
         finalizer: Option<Box<Node>>,
     },
 
+    // Patterns
+    ObjectPattern(Vec<Node>), // PatternProperty and a trailing RestElement
+    ArrayPattern(Vec<Option<Node>>), // None for holes
+    PatternProperty {
+        key: Box<Node>,
+        computed: bool,
+        value: Box<Node>, // `{ a }` binds Identifier("a")
+    },
+    AssignmentPattern {
+        left: Box<Node>,
+        right: Box<Node>, // default used when the value is undefined
+    },
+    RestElement(Box<Node>),
+
     // Expressions
-    Identifier(String),
+    ObjectExpression(Vec<Node>), // Property, MethodDefinition and SpreadElement
+    Property {
+        key: Box<Node>,
+        computed: bool,
+        value: Box<Node>,
+        shorthand: bool, // `{ a }`, where the value is Identifier("a")
+    },
+    ArrayExpression(Vec<Option<Node>>), // None for holes
+    SpreadElement(Box<Node>),           // in array literals, object literals and arguments
+    Identifier(String, Span),
     Literal {
         token_type: TokenType,
         value: String,
+        span: Span,
     },
     BinaryExpression {
         left: Box<Node>,
@@ -77,44 +198,117 @@ pub enum Node {
         operator: String,
         argument: Box<Node>,
     },
+    UpdateExpression {
+        operator: String,
+        prefix: bool,
+        argument: Box<Node>,
+    },
+    AssignmentExpression {
+        left: Box<Node>,
+        operator: String,
+        right: Box<Node>,
+    },
     CallExpression {
         callee: Box<Node>,
+        type_arguments: Vec<Node>,
         arguments: Vec<Node>,
+        optional: bool, // f?.()
     },
     MemberExpression {
         object: Box<Node>,
         property: Box<Node>,
         computed: bool,
+        optional: bool, // a?.b, a?.[k]
+    },
+    // The extent of an optional chain: a nullish value at any `?.` inside skips the rest of
+    // it, and the whole chain evaluates to undefined
+    ChainExpression(Box<Node>),
+    // Type assertions; `as const` and `<const>x` have KeywordType(Const) as their type
+    AsExpression {
+        expression: Box<Node>,
+        type_annotation: Box<Node>,
     },
+    TypeAssertion {
+        type_annotation: Box<Node>,
+        expression: Box<Node>,
+    },
+    SatisfiesExpression {
+        expression: Box<Node>,
+        type_annotation: Box<Node>,
+    },
+    NonNullExpression(Box<Node>), // x!
     ArrowFunctionExpression {
+        type_params: Vec<TypeParameter>,
         params: Vec<Parameter>,
-        body: Box<Node>,
+        body: Box<Node>, // a BlockStatement, or the expression of a concise body
         return_type: Option<Box<Node>>,
+        is_async: bool,
+        span: Span, // of its first token
     },
     NewExpression {
         callee: Box<Node>,
+        type_arguments: Vec<Node>,
         arguments: Vec<Node>,
     },
     ThisExpression,
     SuperExpression,
+    AwaitExpression(Box<Node>),
+    YieldExpression {
+        argument: Option<Box<Node>>,
+        delegate: bool, // yield*
+    },
 
     // Types
     TypeReference {
         name: String,
         type_arguments: Vec<Node>,
+        span: Span,
     },
+    KeywordType(TokenType), // number, string, any, void, ...
+    ThisType,
     UnionType(Vec<Node>),
     IntersectionType(Vec<Node>),
+    ParenthesizedType(Box<Node>),
     FunctionType {
+        type_params: Vec<TypeParameter>,
+        params: Vec<Parameter>,
+        return_type: Box<Node>,
+    },
+    ConstructorType {
+        type_params: Vec<TypeParameter>,
         params: Vec<Parameter>,
         return_type: Box<Node>,
     },
     ObjectType {
-        properties: Vec<PropertySignature>,
+        members: Vec<Node>,
     },
     ArrayType(Box<Node>),
     TupleType(Vec<Node>),
+    OptionalType(Box<Node>), // `T?` inside a tuple
+    RestType(Box<Node>),     // `...T` inside a tuple
+    NamedTupleMember {
+        name: String,
+        element_type: Box<Node>,
+        optional: bool,
+        rest: bool,
+    },
     LiteralType(Box<Node>),
+    TypeQuery(Box<Node>), // typeof x.y
+    TypeOperator {
+        operator: String, // keyof, readonly, unique
+        type_annotation: Box<Node>,
+    },
+    IndexedAccessType {
+        object_type: Box<Node>,
+        index_type: Box<Node>,
+    },
+    // `x is T`, `asserts x is T` or `asserts x` as a return type; `this` may be the parameter
+    TypePredicate {
+        parameter: String,
+        asserts: bool,
+        type_annotation: Option<Box<Node>>,
+        span: Span, // of the parameter
+    },
     LogicalExpression {
         left: Box<Node>,
         operator: String,
@@ -124,6 +318,32 @@ pub enum Node {
         key: Box<Node>,
         value: Option<Box<Node>>,
         optional: bool,
+        readonly: bool,
+        computed: bool,
+    },
+    MethodSignature {
+        key: Box<Node>,
+        computed: bool,
+        optional: bool,
+        type_params: Vec<TypeParameter>,
+        params: Vec<Parameter>,
+        return_type: Option<Box<Node>>,
+    },
+    CallSignature {
+        type_params: Vec<TypeParameter>,
+        params: Vec<Parameter>,
+        return_type: Option<Box<Node>>,
+    },
+    ConstructSignature {
+        type_params: Vec<TypeParameter>,
+        params: Vec<Parameter>,
+        return_type: Option<Box<Node>>,
+    },
+    IndexSignature {
+        key_name: String,
+        key_type: Box<Node>,
+        value_type: Box<Node>,
+        readonly: bool,
     },
     ConditionalExpression {
         test: Box<Node>,
@@ -139,17 +359,90 @@ pub enum VariableKind {
     Var,
 }
 
+#[derive(Debug, PartialEq, Clone)]
+pub enum MethodKind {
+    Constructor,
+    Method,
+    Get,
+    Set,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Accessibility {
+    Public,
+    Private,
+    Protected,
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Modifiers {
+    pub accessibility: Option<Accessibility>,
+    pub is_static: bool,
+    pub readonly: bool,
+    pub decorators: Vec<Node>, // `@dec` on a class member or parameter
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct VariableDeclarator {
     pub id: Box<Node>, // Identifier or Pattern
+    pub type_annotation: Option<Box<Node>>,
     pub init: Option<Box<Node>>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Parameter {
-    pub name: String,
+    pub binding: Box<Node>, // Identifier or pattern
     pub type_annotation: Option<Box<Node>>,
     pub optional: bool,
+    pub rest: bool,
+    pub init: Option<Box<Node>>,
+    pub modifiers: Modifiers, // constructor parameter properties
+}
+
+impl Parameter {
+    /// The parameter's name, unless it's a destructuring pattern.
+    pub fn name(&self) -> Option<&str> {
+        match self.binding.as_ref() {
+            Node::Identifier(name, _) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct EnumMember {
+    pub name: String,
+    pub init: Option<Box<Node>>,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ImportSpecifier {
+    Default(String, Span), // with the span of the local name
+    Namespace(String, Span),
+    Named {
+        imported: String,
+        local: String,
+        is_type_only: bool,
+        span: Span,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct ExportSpecifier {
+    pub local: String,
+    pub exported: String,
+    pub is_type_only: bool,
+    pub span: Span, // of the local name
+}
+
+/// `T extends Constraint = Default` in a type parameter list.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TypeParameter {
+    pub name: String,
+    pub span: Span,
+    pub constraint: Option<Box<Node>>,
+    pub default: Option<Box<Node>>,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -165,6 +458,794 @@ pub struct CatchClause {
     pub body: Box<Node>,
 }
 
+#[derive(Debug, PartialEq, Clone)]
+pub struct SwitchCase {
+    pub test: Option<Box<Node>>, // None for `default`
+    pub consequent: Vec<Node>,
+}
+
+impl Node {
+    /// The names a binding target declares: the identifier itself, or every identifier
+    /// inside a destructuring pattern.
+    pub fn bound_names(&self) -> Vec<String> {
+        match self {
+            Node::Identifier(name, _) => vec![name.clone()],
+            Node::ObjectPattern(elements) => elements.iter().flat_map(Node::bound_names).collect(),
+            Node::ArrayPattern(elements) => elements
+                .iter()
+                .flatten()
+                .flat_map(Node::bound_names)
+                .collect(),
+            Node::PatternProperty { value, .. } => value.bound_names(),
+            Node::AssignmentPattern { left, .. } => left.bound_names(),
+            Node::RestElement(argument) => argument.bound_names(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The identifier nodes a binding target declares, as `bound_names` lists them.
+    pub fn binding_identifiers(&self) -> Vec<&Node> {
+        match self {
+            Node::Identifier(..) => vec![self],
+            Node::ObjectPattern(elements) => elements
+                .iter()
+                .flat_map(Node::binding_identifiers)
+                .collect(),
+            Node::ArrayPattern(elements) => elements
+                .iter()
+                .flatten()
+                .flat_map(Node::binding_identifiers)
+                .collect(),
+            Node::PatternProperty { value, .. } => value.binding_identifiers(),
+            Node::AssignmentPattern { left, .. } => left.binding_identifiers(),
+            Node::RestElement(argument) => argument.binding_identifiers(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Where this node starts, as near as the spans of its identifiers tell. Declarations
+    /// are placed at their name.
+    pub fn span(&self) -> Span {
+        match self {
+            Node::Identifier(_, span)
+            | Node::Literal { span, .. }
+            | Node::TypeReference { span, .. }
+            | Node::TypePredicate { span, .. }
+            | Node::BlockStatement(_, span)
+            | Node::ArrowFunctionExpression { span, .. }
+            | Node::FunctionDeclaration { span, .. }
+            | Node::ClassDeclaration { span, .. }
+            | Node::InterfaceDeclaration { span, .. }
+            | Node::TypeAliasDeclaration { span, .. }
+            | Node::EnumDeclaration { span, .. }
+            | Node::NamespaceDeclaration { span, .. } => *span,
+            node => node
+                .children()
+                .into_iter()
+                .map(Node::span)
+                .find(|span| *span != Span::default())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Every node directly owned by this one, including those inside parameters, type
+    /// parameters, declarators and catch clauses, in source order.
+    pub fn children_mut(&mut self) -> Vec<&mut Node> {
+        let mut children: Vec<&mut Node> = Vec::new();
+        match self {
+            Node::Program(body) | Node::BlockStatement(body, _) => children.extend(body),
+            Node::FunctionDeclaration {
+                type_params,
+                params,
+                return_type,
+                body,
+                ..
+            } => {
+                push_type_params(&mut children, type_params);
+                push_params(&mut children, params);
+                children.extend(return_type.as_deref_mut());
+                children.extend(body.as_deref_mut());
+            }
+            Node::VariableDeclaration { declarations, .. } => {
+                for declarator in declarations {
+                    children.push(&mut declarator.id);
+                    children.extend(declarator.type_annotation.as_deref_mut());
+                    children.extend(declarator.init.as_deref_mut());
+                }
+            }
+            Node::ClassDeclaration {
+                type_params,
+                extends,
+                super_type_arguments,
+                implements,
+                members,
+                decorators,
+                ..
+            } => {
+                children.extend(decorators);
+                push_type_params(&mut children, type_params);
+                children.extend(extends.as_deref_mut());
+                children.extend(super_type_arguments);
+                children.extend(implements);
+                children.extend(members);
+            }
+            Node::InterfaceDeclaration {
+                type_params,
+                extends,
+                members,
+                ..
+            } => {
+                push_type_params(&mut children, type_params);
+                children.extend(extends);
+                children.extend(members);
+            }
+            Node::TypeAliasDeclaration {
+                type_params,
+                type_annotation,
+                ..
+            } => {
+                push_type_params(&mut children, type_params);
+                children.push(type_annotation);
+            }
+            Node::EnumDeclaration { members, .. } => {
+                for member in members {
+                    children.extend(member.init.as_deref_mut());
+                }
+            }
+            Node::NamespaceDeclaration { body, .. }
+            | Node::AmbientModuleDeclaration {
+                body: Some(body), ..
+            }
+            | Node::GlobalAugmentation(body) => children.extend(body),
+            Node::AmbientDeclaration(declaration) => children.push(declaration),
+            Node::ExportNamedDeclaration { declaration, .. } => {
+                children.extend(declaration.as_deref_mut());
+            }
+            Node::ExportDefaultDeclaration(declaration) => children.push(declaration),
+            Node::ObjectPattern(elements) => children.extend(elements),
+            Node::ArrayPattern(elements) => children.extend(elements.iter_mut().flatten()),
+            Node::PatternProperty { key, value, .. } => {
+                children.push(key);
+                children.push(value);
+            }
+            Node::AssignmentPattern { left, right } => {
+                children.push(left);
+                children.push(right);
+            }
+            Node::RestElement(argument) | Node::SpreadElement(argument) => children.push(argument),
+            Node::ObjectExpression(elements) => children.extend(elements),
+            Node::ArrayExpression(elements) => children.extend(elements.iter_mut().flatten()),
+            Node::Property { key, value, .. } => {
+                children.push(key);
+                children.push(value);
+            }
+            Node::MethodDefinition {
+                key,
+                type_params,
+                params,
+                return_type,
+                body,
+                modifiers,
+                ..
+            } => {
+                children.extend(&mut modifiers.decorators);
+                children.push(key);
+                push_type_params(&mut children, type_params);
+                push_params(&mut children, params);
+                children.extend(return_type.as_deref_mut());
+                children.extend(body.as_deref_mut());
+            }
+            Node::PropertyDefinition {
+                key,
+                type_annotation,
+                value,
+                modifiers,
+                ..
+            } => {
+                children.extend(&mut modifiers.decorators);
+                children.push(key);
+                children.extend(type_annotation.as_deref_mut());
+                children.extend(value.as_deref_mut());
+            }
+            Node::ExpressionStatement(expression)
+            | Node::ThrowStatement(expression)
+            | Node::ArrayType(expression)
+            | Node::ParenthesizedType(expression)
+            | Node::OptionalType(expression)
+            | Node::RestType(expression)
+            | Node::LiteralType(expression)
+            | Node::TypeQuery(expression) => children.push(expression),
+            Node::ReturnStatement(argument) => children.extend(argument.as_deref_mut()),
+            Node::IfStatement {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                children.push(condition);
+                children.push(then_branch);
+                children.extend(else_branch.as_deref_mut());
+            }
+            Node::ForStatement {
+                init,
+                test,
+                update,
+                body,
+            } => {
+                children.extend(init.as_deref_mut());
+                children.extend(test.as_deref_mut());
+                children.extend(update.as_deref_mut());
+                children.push(body);
+            }
+            Node::WhileStatement { test, body } | Node::DoWhileStatement { body, test } => {
+                children.push(test);
+                children.push(body);
+            }
+            Node::ForInStatement { left, right, body }
+            | Node::ForOfStatement {
+                left, right, body, ..
+            } => {
+                children.push(left);
+                children.push(right);
+                children.push(body);
+            }
+            Node::SwitchStatement {
+                discriminant,
+                cases,
+            } => {
+                children.push(discriminant);
+                for case in cases {
+                    children.extend(case.test.as_deref_mut());
+                    children.extend(case.consequent.iter_mut());
+                }
+            }
+            Node::LabeledStatement { body, .. } => children.push(body),
+            Node::TryStatement {
+                block,
+                handler,
+                finalizer,
+            } => {
+                children.push(block);
+                if let Some(handler) = handler {
+                    children.extend(handler.param.as_deref_mut());
+                    children.push(&mut handler.body);
+                }
+                children.extend(finalizer.as_deref_mut());
+            }
+            Node::BinaryExpression { left, right, .. }
+            | Node::LogicalExpression { left, right, .. }
+            | Node::AssignmentExpression { left, right, .. } => {
+                children.push(left);
+                children.push(right);
+            }
+            Node::UnaryExpression { argument, .. } | Node::UpdateExpression { argument, .. } => {
+                children.push(argument)
+            }
+            Node::AwaitExpression(argument)
+            | Node::ChainExpression(argument)
+            | Node::NonNullExpression(argument) => children.push(argument),
+            Node::AsExpression {
+                expression,
+                type_annotation,
+            }
+            | Node::SatisfiesExpression {
+                expression,
+                type_annotation,
+            } => {
+                children.push(expression);
+                children.push(type_annotation);
+            }
+            Node::TypeAssertion {
+                type_annotation,
+                expression,
+            } => {
+                children.push(type_annotation);
+                children.push(expression);
+            }
+            Node::YieldExpression { argument, .. } => children.extend(argument.as_deref_mut()),
+            Node::CallExpression {
+                callee,
+                type_arguments,
+                arguments,
+                ..
+            }
+            | Node::NewExpression {
+                callee,
+                type_arguments,
+                arguments,
+            } => {
+                children.push(callee);
+                children.extend(type_arguments);
+                children.extend(arguments);
+            }
+            Node::MemberExpression {
+                object, property, ..
+            } => {
+                children.push(object);
+                children.push(property);
+            }
+            Node::ArrowFunctionExpression {
+                type_params,
+                params,
+                body,
+                return_type,
+                ..
+            } => {
+                push_type_params(&mut children, type_params);
+                push_params(&mut children, params);
+                children.extend(return_type.as_deref_mut());
+                children.push(body);
+            }
+            Node::TypeReference { type_arguments, .. } => children.extend(type_arguments),
+            Node::UnionType(types) | Node::IntersectionType(types) | Node::TupleType(types) => {
+                children.extend(types)
+            }
+            Node::FunctionType {
+                type_params,
+                params,
+                return_type,
+            }
+            | Node::ConstructorType {
+                type_params,
+                params,
+                return_type,
+            } => {
+                push_type_params(&mut children, type_params);
+                push_params(&mut children, params);
+                children.push(return_type);
+            }
+            Node::ObjectType { members } => children.extend(members),
+            Node::NamedTupleMember { element_type, .. } => children.push(element_type),
+            Node::TypeOperator {
+                type_annotation, ..
+            } => children.push(type_annotation),
+            Node::TypePredicate {
+                type_annotation, ..
+            } => children.extend(type_annotation.as_deref_mut()),
+            Node::IndexedAccessType {
+                object_type,
+                index_type,
+            } => {
+                children.push(object_type);
+                children.push(index_type);
+            }
+            Node::PropertySignature { key, value, .. } => {
+                children.push(key);
+                children.extend(value.as_deref_mut());
+            }
+            Node::MethodSignature {
+                key,
+                type_params,
+                params,
+                return_type,
+                ..
+            } => {
+                children.push(key);
+                push_type_params(&mut children, type_params);
+                push_params(&mut children, params);
+                children.extend(return_type.as_deref_mut());
+            }
+            Node::CallSignature {
+                type_params,
+                params,
+                return_type,
+            }
+            | Node::ConstructSignature {
+                type_params,
+                params,
+                return_type,
+            } => {
+                push_type_params(&mut children, type_params);
+                push_params(&mut children, params);
+                children.extend(return_type.as_deref_mut());
+            }
+            Node::IndexSignature {
+                key_type,
+                value_type,
+                ..
+            } => {
+                children.push(key_type);
+                children.push(value_type);
+            }
+            Node::ConditionalExpression {
+                test,
+                consequent,
+                alternate,
+            } => {
+                children.push(test);
+                children.push(consequent);
+                children.push(alternate);
+            }
+            Node::EmptyStatement
+            | Node::ImportDeclaration { .. }
+            | Node::AmbientModuleDeclaration { body: None, .. }
+            | Node::ExportAllDeclaration { .. }
+            | Node::BreakStatement(_)
+            | Node::ContinueStatement(_)
+            | Node::Identifier(..)
+            | Node::Literal { .. }
+            | Node::ThisExpression
+            | Node::SuperExpression
+            | Node::KeywordType(_)
+            | Node::ThisType => {}
+        }
+        children
+    }
+
+    /// Shared-reference counterpart of `children_mut`.
+    pub fn children(&self) -> Vec<&Node> {
+        let mut children: Vec<&Node> = Vec::new();
+        match self {
+            Node::Program(body) | Node::BlockStatement(body, _) => children.extend(body),
+            Node::FunctionDeclaration {
+                type_params,
+                params,
+                return_type,
+                body,
+                ..
+            } => {
+                push_type_param_refs(&mut children, type_params);
+                push_param_refs(&mut children, params);
+                children.extend(return_type.as_deref());
+                children.extend(body.as_deref());
+            }
+            Node::VariableDeclaration { declarations, .. } => {
+                for declarator in declarations {
+                    children.push(&declarator.id);
+                    children.extend(declarator.type_annotation.as_deref());
+                    children.extend(declarator.init.as_deref());
+                }
+            }
+            Node::ClassDeclaration {
+                type_params,
+                extends,
+                super_type_arguments,
+                implements,
+                members,
+                decorators,
+                ..
+            } => {
+                children.extend(decorators);
+                push_type_param_refs(&mut children, type_params);
+                children.extend(extends.as_deref());
+                children.extend(super_type_arguments);
+                children.extend(implements);
+                children.extend(members);
+            }
+            Node::InterfaceDeclaration {
+                type_params,
+                extends,
+                members,
+                ..
+            } => {
+                push_type_param_refs(&mut children, type_params);
+                children.extend(extends);
+                children.extend(members);
+            }
+            Node::TypeAliasDeclaration {
+                type_params,
+                type_annotation,
+                ..
+            } => {
+                push_type_param_refs(&mut children, type_params);
+                children.push(type_annotation);
+            }
+            Node::EnumDeclaration { members, .. } => {
+                for member in members {
+                    children.extend(member.init.as_deref());
+                }
+            }
+            Node::NamespaceDeclaration { body, .. }
+            | Node::AmbientModuleDeclaration {
+                body: Some(body), ..
+            }
+            | Node::GlobalAugmentation(body) => children.extend(body),
+            Node::AmbientDeclaration(declaration) => children.push(declaration),
+            Node::ExportNamedDeclaration { declaration, .. } => {
+                children.extend(declaration.as_deref());
+            }
+            Node::ExportDefaultDeclaration(declaration) => children.push(declaration),
+            Node::ObjectPattern(elements) => children.extend(elements),
+            Node::ArrayPattern(elements) => children.extend(elements.iter().flatten()),
+            Node::PatternProperty { key, value, .. } => {
+                children.push(key);
+                children.push(value);
+            }
+            Node::AssignmentPattern { left, right } => {
+                children.push(left);
+                children.push(right);
+            }
+            Node::RestElement(argument) | Node::SpreadElement(argument) => children.push(argument),
+            Node::ObjectExpression(elements) => children.extend(elements),
+            Node::ArrayExpression(elements) => children.extend(elements.iter().flatten()),
+            Node::Property { key, value, .. } => {
+                children.push(key);
+                children.push(value);
+            }
+            Node::MethodDefinition {
+                key,
+                type_params,
+                params,
+                return_type,
+                body,
+                modifiers,
+                ..
+            } => {
+                children.extend(&modifiers.decorators);
+                children.push(key);
+                push_type_param_refs(&mut children, type_params);
+                push_param_refs(&mut children, params);
+                children.extend(return_type.as_deref());
+                children.extend(body.as_deref());
+            }
+            Node::PropertyDefinition {
+                key,
+                type_annotation,
+                value,
+                modifiers,
+                ..
+            } => {
+                children.extend(&modifiers.decorators);
+                children.push(key);
+                children.extend(type_annotation.as_deref());
+                children.extend(value.as_deref());
+            }
+            Node::ExpressionStatement(expression)
+            | Node::ThrowStatement(expression)
+            | Node::ArrayType(expression)
+            | Node::ParenthesizedType(expression)
+            | Node::OptionalType(expression)
+            | Node::RestType(expression)
+            | Node::LiteralType(expression)
+            | Node::TypeQuery(expression) => children.push(expression),
+            Node::ReturnStatement(argument) => children.extend(argument.as_deref()),
+            Node::IfStatement {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                children.push(condition);
+                children.push(then_branch);
+                children.extend(else_branch.as_deref());
+            }
+            Node::ForStatement {
+                init,
+                test,
+                update,
+                body,
+            } => {
+                children.extend(init.as_deref());
+                children.extend(test.as_deref());
+                children.extend(update.as_deref());
+                children.push(body);
+            }
+            Node::WhileStatement { test, body } | Node::DoWhileStatement { body, test } => {
+                children.push(test);
+                children.push(body);
+            }
+            Node::ForInStatement { left, right, body }
+            | Node::ForOfStatement {
+                left, right, body, ..
+            } => {
+                children.push(left);
+                children.push(right);
+                children.push(body);
+            }
+            Node::SwitchStatement {
+                discriminant,
+                cases,
+            } => {
+                children.push(discriminant);
+                for case in cases {
+                    children.extend(case.test.as_deref());
+                    children.extend(case.consequent.iter());
+                }
+            }
+            Node::LabeledStatement { body, .. } => children.push(body),
+            Node::TryStatement {
+                block,
+                handler,
+                finalizer,
+            } => {
+                children.push(block);
+                if let Some(handler) = handler {
+                    children.extend(handler.param.as_deref());
+                    children.push(&handler.body);
+                }
+                children.extend(finalizer.as_deref());
+            }
+            Node::BinaryExpression { left, right, .. }
+            | Node::LogicalExpression { left, right, .. }
+            | Node::AssignmentExpression { left, right, .. } => {
+                children.push(left);
+                children.push(right);
+            }
+            Node::UnaryExpression { argument, .. } | Node::UpdateExpression { argument, .. } => {
+                children.push(argument)
+            }
+            Node::AwaitExpression(argument)
+            | Node::ChainExpression(argument)
+            | Node::NonNullExpression(argument) => children.push(argument),
+            Node::AsExpression {
+                expression,
+                type_annotation,
+            }
+            | Node::SatisfiesExpression {
+                expression,
+                type_annotation,
+            } => {
+                children.push(expression);
+                children.push(type_annotation);
+            }
+            Node::TypeAssertion {
+                type_annotation,
+                expression,
+            } => {
+                children.push(type_annotation);
+                children.push(expression);
+            }
+            Node::YieldExpression { argument, .. } => children.extend(argument.as_deref()),
+            Node::CallExpression {
+                callee,
+                type_arguments,
+                arguments,
+                ..
+            }
+            | Node::NewExpression {
+                callee,
+                type_arguments,
+                arguments,
+            } => {
+                children.push(callee);
+                children.extend(type_arguments);
+                children.extend(arguments);
+            }
+            Node::MemberExpression {
+                object, property, ..
+            } => {
+                children.push(object);
+                children.push(property);
+            }
+            Node::ArrowFunctionExpression {
+                type_params,
+                params,
+                body,
+                return_type,
+                ..
+            } => {
+                push_type_param_refs(&mut children, type_params);
+                push_param_refs(&mut children, params);
+                children.extend(return_type.as_deref());
+                children.push(body);
+            }
+            Node::TypeReference { type_arguments, .. } => children.extend(type_arguments),
+            Node::UnionType(types) | Node::IntersectionType(types) | Node::TupleType(types) => {
+                children.extend(types)
+            }
+            Node::FunctionType {
+                type_params,
+                params,
+                return_type,
+            }
+            | Node::ConstructorType {
+                type_params,
+                params,
+                return_type,
+            } => {
+                push_type_param_refs(&mut children, type_params);
+                push_param_refs(&mut children, params);
+                children.push(return_type);
+            }
+            Node::ObjectType { members } => children.extend(members),
+            Node::NamedTupleMember { element_type, .. } => children.push(element_type),
+            Node::TypeOperator {
+                type_annotation, ..
+            } => children.push(type_annotation),
+            Node::TypePredicate {
+                type_annotation, ..
+            } => children.extend(type_annotation.as_deref()),
+            Node::IndexedAccessType {
+                object_type,
+                index_type,
+            } => {
+                children.push(object_type);
+                children.push(index_type);
+            }
+            Node::PropertySignature { key, value, .. } => {
+                children.push(key);
+                children.extend(value.as_deref());
+            }
+            Node::MethodSignature {
+                key,
+                type_params,
+                params,
+                return_type,
+                ..
+            } => {
+                children.push(key);
+                push_type_param_refs(&mut children, type_params);
+                push_param_refs(&mut children, params);
+                children.extend(return_type.as_deref());
+            }
+            Node::CallSignature {
+                type_params,
+                params,
+                return_type,
+            }
+            | Node::ConstructSignature {
+                type_params,
+                params,
+                return_type,
+            } => {
+                push_type_param_refs(&mut children, type_params);
+                push_param_refs(&mut children, params);
+                children.extend(return_type.as_deref());
+            }
+            Node::IndexSignature {
+                key_type,
+                value_type,
+                ..
+            } => {
+                children.push(key_type);
+                children.push(value_type);
+            }
+            Node::ConditionalExpression {
+                test,
+                consequent,
+                alternate,
+            } => {
+                children.push(test);
+                children.push(consequent);
+                children.push(alternate);
+            }
+            Node::EmptyStatement
+            | Node::ImportDeclaration { .. }
+            | Node::AmbientModuleDeclaration { body: None, .. }
+            | Node::ExportAllDeclaration { .. }
+            | Node::BreakStatement(_)
+            | Node::ContinueStatement(_)
+            | Node::Identifier(..)
+            | Node::Literal { .. }
+            | Node::ThisExpression
+            | Node::SuperExpression
+            | Node::KeywordType(_)
+            | Node::ThisType => {}
+        }
+        children
+    }
+}
+
+fn push_params<'a>(children: &mut Vec<&'a mut Node>, params: &'a mut [Parameter]) {
+    for param in params {
+        children.extend(&mut param.modifiers.decorators);
+        children.push(&mut param.binding);
+        children.extend(param.type_annotation.as_deref_mut());
+        children.extend(param.init.as_deref_mut());
+    }
+}
+
+fn push_type_params<'a>(children: &mut Vec<&'a mut Node>, type_params: &'a mut [TypeParameter]) {
+    for type_param in type_params {
+        children.extend(type_param.constraint.as_deref_mut());
+        children.extend(type_param.default.as_deref_mut());
+    }
+}
+
+fn push_param_refs<'a>(children: &mut Vec<&'a Node>, params: &'a [Parameter]) {
+    for param in params {
+        children.extend(&param.modifiers.decorators);
+        children.push(&param.binding);
+        children.extend(param.type_annotation.as_deref());
+        children.extend(param.init.as_deref());
+    }
+}
+
+fn push_type_param_refs<'a>(children: &mut Vec<&'a Node>, type_params: &'a [TypeParameter]) {
+    for type_param in type_params {
+        children.extend(type_param.constraint.as_deref());
+        children.extend(type_param.default.as_deref());
+    }
+}
+
 impl Iterator for Node {
     type Item = Node;
 
@@ -173,37 +1254,2777 @@ impl Iterator for Node {
     }
 }
 
+type ParseResult<T> = Result<T, CompilerError>;
+
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    errors: Vec<CompilerError>,
+    function: FunctionContext,
+    // Set in a `for` head, where `in` starts a for-in loop instead of a binary expression
+    no_in: bool,
+    file: usize, // put in every span, to tell files compiled together apart
+}
+
+/// Type parameters, parameters and return type of an arrow function.
+type ArrowHead = (Vec<TypeParameter>, Vec<Parameter>, Option<Box<Node>>);
+
+/// What the body of the function being parsed allows: the statements a `break` or
+/// `continue` can target, and whether `await` and `yield` are operators.
+#[derive(Default)]
+struct FunctionContext {
+    labels: Vec<(String, bool)>, // label, and whether it labels an iteration statement
+    iterations: usize,
+    breakables: usize, // iterations and switches
+    is_async: bool,
+    is_generator: bool,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Parser { tokens, current: 0 }
+        let tokens = tokens
+            .into_iter()
+            .filter(|token| {
+                !matches!(
+                    token.token_type,
+                    TokenType::LineComment | TokenType::BlockComment
+                )
+            })
+            .collect();
+        Parser {
+            tokens,
+            current: 0,
+            errors: Vec::new(),
+            // Modules allow `await` at the top level
+            function: FunctionContext {
+                is_async: true,
+                ..Default::default()
+            },
+            no_in: false,
+            file: 0,
+        }
     }
-    pub fn parse(&mut self) -> Node {
-        self.program()
+    /// Marks every position the parser gives as being in `file`.
+    pub fn with_file(mut self, file: usize) -> Self {
+        self.file = file;
+        self
+    }
+    pub fn parse(&mut self) -> CompilerResult<Node> {
+        let program = self.program();
+        CompilerResult::new(Some(program), std::mem::take(&mut self.errors))
     }
     fn program(&mut self) -> Node {
         let mut body = Vec::new();
         while !self.is_at_end() {
-            body.push(self.declaration());
+            match self.declaration() {
+                Ok(node) => body.push(node),
+                Err(error) => {
+                    self.errors.push(error);
+                    self.synchronize();
+                }
+            }
         }
         Node::Program(body)
     }
 
-    fn declaration(&mut self) -> Node {
+    fn declaration(&mut self) -> ParseResult<Node> {
         match self.tokens[self.current].token_type {
-            TokenType::Function => self.function_declaration(),
+            TokenType::Function => self.function_declaration(false),
+            TokenType::Async if self.starts_async_function() => self.function_declaration(false),
+            TokenType::Import
+                if !matches!(
+                    self.peek_next().token_type,
+                    TokenType::LeftParen | TokenType::Dot
+                ) =>
+            {
+                self.import_declaration()
+            }
+            TokenType::Export => self.export_declaration(),
+            TokenType::Const if self.peek_next().token_type == TokenType::Enum => {
+                self.advance();
+                self.enum_declaration(true, false)
+            }
             TokenType::Let | TokenType::Const | TokenType::Var => self.variable_declaration(),
-            TokenType::Class => {}
-            TokenType::Interface => {}
-            TokenType::Type => {}
-            _ => {}
+            TokenType::Class => self.class_declaration(false),
+            TokenType::At => self.decorated_declaration(),
+            TokenType::Interface => self.interface_declaration(),
+            TokenType::Enum => self.enum_declaration(false, false),
+            TokenType::Declare if self.peek_next().token_type == TokenType::Enum => {
+                self.advance();
+                self.enum_declaration(false, true)
+            }
+            TokenType::Declare
+                if self.peek_next().token_type == TokenType::Const
+                    && self.peek_at(2).token_type == TokenType::Enum =>
+            {
+                self.advance();
+                self.advance();
+                self.enum_declaration(true, true)
+            }
+            TokenType::Type if is_identifier_token(self.peek_next().token_type) => {
+                self.type_alias_declaration()
+            }
+            TokenType::Namespace | TokenType::Module
+                if is_identifier_token(self.peek_next().token_type) =>
+            {
+                self.namespace_declaration(false)
+            }
+            TokenType::Declare
+                if self.peek_next().row == self.peek().row
+                    && (matches!(
+                        self.peek_next().token_type,
+                        TokenType::Namespace
+                            | TokenType::Module
+                            | TokenType::Function
+                            | TokenType::Class
+                            | TokenType::Let
+                            | TokenType::Const
+                            | TokenType::Var
+                            | TokenType::Interface
+                            | TokenType::Type
+                    ) || self.peek_next().value == "global") =>
+            {
+                self.ambient_declaration()
+            }
+            _ => self.statement(),
         }
     }
-    fn function_declaration(&mut self) -> Node {
-        todo!()
+
+    /// `name_optional` is set for `export default function () {}`, which is named `default`.
+    fn function_declaration(&mut self, name_optional: bool) -> ParseResult<Node> {
+        let is_async = self.matches(&[TokenType::Async]);
+        self.consume(TokenType::Function, "'function' expected")?;
+        let is_generator = self.matches(&[TokenType::Star]);
+        let span = self.span();
+        let name = self.declaration_name(name_optional)?;
+        let type_params = self.type_parameters()?;
+        let params = self.parameters()?;
+        let return_type = self.optional_return_type()?;
+        let body = if self.check(TokenType::LeftBrace) {
+            Some(Box::new(self.function_body(is_async, is_generator)?))
+        } else {
+            self.consume_semicolon()?;
+            None
+        };
+        Ok(Node::FunctionDeclaration {
+            name,
+            span,
+            type_params,
+            params,
+            return_type,
+            body,
+            is_async,
+            is_generator,
+        })
+    }
+
+    fn variable_declaration(&mut self) -> ParseResult<Node> {
+        let declaration = self.variable_declaration_list()?;
+        self.consume_semicolon()?;
+        Ok(declaration)
+    }
+
+    fn variable_declaration_list(&mut self) -> ParseResult<Node> {
+        let kind = match self.advance().token_type {
+            TokenType::Let => VariableKind::Let,
+            TokenType::Const => VariableKind::Const,
+            _ => VariableKind::Var,
+        };
+        let mut declarations = Vec::new();
+        loop {
+            let id = self.binding_target()?;
+            let type_annotation = self.optional_type_annotation()?;
+            let init = if self.matches(&[TokenType::Equal]) {
+                Some(Box::new(self.assignment()?))
+            } else {
+                None
+            };
+            declarations.push(VariableDeclarator {
+                id: Box::new(id),
+                type_annotation,
+                init,
+            });
+            if !self.matches(&[TokenType::Comma]) {
+                break;
+            }
+        }
+        Ok(Node::VariableDeclaration { kind, declarations })
+    }
+
+    fn class_declaration(&mut self, name_optional: bool) -> ParseResult<Node> {
+        self.consume(TokenType::Class, "'class' expected")?;
+        let span = self.span();
+        let name = self.declaration_name(name_optional)?;
+        let type_params = self.type_parameters()?;
+        let mut super_type_arguments = Vec::new();
+        let extends = if self.matches(&[TokenType::Extends]) {
+            let extends = self.call_member(false)?;
+            if self.check(TokenType::Less) {
+                super_type_arguments = self.type_arguments()?;
+            }
+            Some(Box::new(extends))
+        } else {
+            None
+        };
+        let mut implements = Vec::new();
+        if self.matches(&[TokenType::Implements]) {
+            loop {
+                implements.push(self.type_reference()?);
+                if !self.matches(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(TokenType::LeftBrace, "'{' expected")?;
+        let mut members = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            if self.matches(&[TokenType::Semicolon]) {
+                continue;
+            }
+            members.push(self.class_member()?);
+        }
+        self.consume(TokenType::RightBrace, "'}' expected")?;
+        Ok(Node::ClassDeclaration {
+            name,
+            span,
+            type_params,
+            extends,
+            super_type_arguments,
+            implements,
+            members,
+            decorators: Vec::new(),
+        })
+    }
+
+    /// `@dec class C {}`, where the decorators may also come before `export` or
+    /// `export default`.
+    fn decorated_declaration(&mut self) -> ParseResult<Node> {
+        let span = self.span();
+        let decorators = self.decorators()?;
+        let mut declaration = match self.peek().token_type {
+            TokenType::Class => self.class_declaration(false)?,
+            TokenType::Export => self.export_declaration()?,
+            _ => {
+                self.errors.push(CompilerError::new(
+                    "Decorators are not valid here.".to_string(),
+                    span,
+                ));
+                return self.declaration();
+            }
+        };
+        let class = match &mut declaration {
+            Node::ExportNamedDeclaration {
+                declaration: Some(class),
+                ..
+            }
+            | Node::ExportDefaultDeclaration(class) => class.as_mut(),
+            class => class,
+        };
+        match class {
+            Node::ClassDeclaration {
+                decorators: existing,
+                ..
+            } if existing.is_empty() => *existing = decorators,
+            Node::ClassDeclaration { .. } => self.errors.push(CompilerError::new(
+                "Decorators may not appear after 'export' or 'export default' if they also appear before 'export'.".to_string(),
+                span,
+            )),
+            _ => self
+                .errors
+                .push(CompilerError::new("Decorators are not valid here.".to_string(), span)),
+        }
+        Ok(declaration)
+    }
+
+    /// `@expression` before a class, class member or parameter. Unless it is parenthesized,
+    /// the expression is a dotted name, optionally called.
+    fn decorators(&mut self) -> ParseResult<Vec<Node>> {
+        let mut decorators = Vec::new();
+        while self.matches(&[TokenType::At]) {
+            if self.check(TokenType::LeftParen) {
+                decorators.push(self.primary()?);
+                continue;
+            }
+            let mut expression = self.identifier_node()?;
+            while self.matches(&[TokenType::Dot]) {
+                expression = Node::MemberExpression {
+                    object: Box::new(expression),
+                    property: Box::new(self.property_name()?),
+                    computed: false,
+                    optional: false,
+                };
+            }
+            let type_arguments = if self.check(TokenType::Less) {
+                self.try_call_type_arguments().unwrap_or_default()
+            } else {
+                Vec::new()
+            };
+            if self.check(TokenType::LeftParen) {
+                expression = Node::CallExpression {
+                    callee: Box::new(expression),
+                    type_arguments,
+                    arguments: self.arguments()?,
+                    optional: false,
+                };
+            }
+            decorators.push(expression);
+        }
+        Ok(decorators)
+    }
+
+    fn class_member(&mut self) -> ParseResult<Node> {
+        let span = self.span();
+        let decorators = self.decorators()?;
+        let mut modifiers = self.modifiers();
+        modifiers.decorators = decorators;
+        let (is_async, is_generator) = self.method_prefix();
+        let mut kind = MethodKind::Method;
+        if !is_async
+            && !is_generator
+            && self.check_any(&[TokenType::Get, TokenType::Set])
+            && self.next_starts_property_name()
+        {
+            kind = if self.advance().token_type == TokenType::Get {
+                MethodKind::Get
+            } else {
+                MethodKind::Set
+            };
+        }
+        let (key, computed) = self.property_key()?;
+        if kind == MethodKind::Method
+            && !computed
+            && matches!(&key, Node::Identifier(name, _) if name == "constructor")
+        {
+            kind = MethodKind::Constructor;
+        }
+
+        if kind != MethodKind::Method
+            || is_async
+            || is_generator
+            || self.check_any(&[TokenType::LeftParen, TokenType::Less])
+        {
+            let has_decorators = !modifiers.decorators.is_empty();
+            let method = self.method(key, computed, kind, modifiers, is_async, is_generator)?;
+            if has_decorators
+                && matches!(
+                    method,
+                    Node::MethodDefinition {
+                        kind: MethodKind::Constructor,
+                        ..
+                    } | Node::MethodDefinition { body: None, .. }
+                )
+            {
+                self.errors.push(CompilerError::new(
+                    "Decorators are not valid here.".to_string(),
+                    span,
+                ));
+            }
+            return Ok(method);
+        }
+
+        let optional = self.matches(&[TokenType::QuestionMark]);
+        self.matches(&[TokenType::Bang]); // definite assignment assertion
+        let type_annotation = self.optional_type_annotation()?;
+        let value = if self.matches(&[TokenType::Equal]) {
+            Some(Box::new(self.assignment()?))
+        } else {
+            None
+        };
+        self.consume_semicolon()?;
+        Ok(Node::PropertyDefinition {
+            key: Box::new(key),
+            computed,
+            type_annotation,
+            value,
+            optional,
+            modifiers,
+        })
+    }
+
+    /// `async` and `*` before a method key.
+    fn method_prefix(&mut self) -> (bool, bool) {
+        let is_async = self.check(TokenType::Async)
+            && self.peek_next().row == self.peek().row
+            && (self.peek_next().token_type == TokenType::Star || self.next_starts_property_name());
+        if is_async {
+            self.advance();
+        }
+        (is_async, self.matches(&[TokenType::Star]))
+    }
+
+    /// The signature and body of a method, after its key.
+    fn method(
+        &mut self,
+        key: Node,
+        computed: bool,
+        kind: MethodKind,
+        modifiers: Modifiers,
+        is_async: bool,
+        is_generator: bool,
+    ) -> ParseResult<Node> {
+        let type_params = self.type_parameters()?;
+        let params = self.parameters()?;
+        let return_type = self.optional_return_type()?;
+        let body = if self.check(TokenType::LeftBrace) {
+            Some(Box::new(self.function_body(is_async, is_generator)?))
+        } else {
+            self.consume_semicolon()?;
+            None
+        };
+        Ok(Node::MethodDefinition {
+            key: Box::new(key),
+            computed,
+            kind,
+            type_params,
+            params,
+            return_type,
+            body,
+            modifiers,
+            is_async,
+            is_generator,
+        })
+    }
+
+    fn modifiers(&mut self) -> Modifiers {
+        let mut modifiers = Modifiers::default();
+        loop {
+            let token_type = self.peek().token_type;
+            if !matches!(
+                token_type,
+                TokenType::Public
+                    | TokenType::Private
+                    | TokenType::Protected
+                    | TokenType::Static
+                    | TokenType::Readonly
+            ) || !self.next_starts_property_name()
+            {
+                return modifiers;
+            }
+            self.advance();
+            match token_type {
+                TokenType::Public => modifiers.accessibility = Some(Accessibility::Public),
+                TokenType::Private => modifiers.accessibility = Some(Accessibility::Private),
+                TokenType::Protected => modifiers.accessibility = Some(Accessibility::Protected),
+                TokenType::Static => modifiers.is_static = true,
+                _ => modifiers.readonly = true,
+            }
+        }
+    }
+
+    fn interface_declaration(&mut self) -> ParseResult<Node> {
+        self.consume(TokenType::Interface, "'interface' expected")?;
+        let span = self.span();
+        let name = self.identifier()?;
+        let type_params = self.type_parameters()?;
+        let mut extends = Vec::new();
+        if self.matches(&[TokenType::Extends]) {
+            loop {
+                extends.push(self.type_reference()?);
+                if !self.matches(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        let members = self.type_members()?;
+        Ok(Node::InterfaceDeclaration {
+            name,
+            span,
+            type_params,
+            extends,
+            members,
+        })
+    }
+
+    fn type_alias_declaration(&mut self) -> ParseResult<Node> {
+        self.consume(TokenType::Type, "'type' expected")?;
+        let span = self.span();
+        let name = self.identifier()?;
+        let type_params = self.type_parameters()?;
+        self.consume(TokenType::Equal, "'=' expected")?;
+        let type_annotation = self.type_annotation()?;
+        self.consume_semicolon()?;
+        Ok(Node::TypeAliasDeclaration {
+            name,
+            span,
+            type_params,
+            type_annotation: Box::new(type_annotation),
+        })
+    }
+
+    fn enum_declaration(&mut self, is_const: bool, is_declare: bool) -> ParseResult<Node> {
+        self.consume(TokenType::Enum, "'enum' expected")?;
+        let enum_span = self.span();
+        let name = self.identifier()?;
+        self.consume(TokenType::LeftBrace, "'{' expected")?;
+        let mut members = Vec::new();
+        while !self.check(TokenType::RightBrace) {
+            let span = self.span();
+            let name = if self.check(TokenType::StringLiteral) {
+                self.advance().value
+            } else {
+                self.identifier_name()?
+            };
+            let init = if self.matches(&[TokenType::Equal]) {
+                Some(Box::new(self.assignment()?))
+            } else {
+                None
+            };
+            members.push(EnumMember { name, init, span });
+            if !self.matches(&[TokenType::Comma]) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightBrace, "'}' expected")?;
+        Ok(Node::EnumDeclaration {
+            name,
+            span: enum_span,
+            members,
+            is_const,
+            is_declare,
+        })
+    }
+
+    /// `namespace A.B { ... }`, also spelled `module A.B { ... }`.
+    fn namespace_declaration(&mut self, is_declare: bool) -> ParseResult<Node> {
+        self.advance();
+        let mut names = vec![(self.span(), self.identifier()?)];
+        while self.matches(&[TokenType::Dot]) {
+            names.push((self.span(), self.identifier()?));
+        }
+        let mut body = self.declaration_block()?;
+        while names.len() > 1 {
+            let (span, name) = names.pop().unwrap();
+            let inner = Node::NamespaceDeclaration {
+                name,
+                span,
+                body,
+                is_declare,
+            };
+            body = vec![Node::ExportNamedDeclaration {
+                declaration: Some(Box::new(inner)),
+                specifiers: Vec::new(),
+                source: None,
+                is_type_only: false,
+            }];
+        }
+        let (span, name) = names.pop().unwrap();
+        Ok(Node::NamespaceDeclaration {
+            name,
+            span,
+            body,
+            is_declare,
+        })
+    }
+
+    /// Everything after `declare` other than enums, which record it on the declaration.
+    fn ambient_declaration(&mut self) -> ParseResult<Node> {
+        self.consume(TokenType::Declare, "'declare' expected")?;
+        match self.peek().token_type {
+            TokenType::Module if self.peek_next().token_type == TokenType::StringLiteral => {
+                self.advance();
+                let name = self.advance().value;
+                let body = if self.check(TokenType::LeftBrace) {
+                    Some(self.declaration_block()?)
+                } else {
+                    self.consume_semicolon()?;
+                    None
+                };
+                Ok(Node::AmbientModuleDeclaration { name, body })
+            }
+            TokenType::Namespace | TokenType::Module => self.namespace_declaration(true),
+            TokenType::Identifier
+                if self.peek().value == "global"
+                    && self.peek_next().token_type == TokenType::LeftBrace =>
+            {
+                self.advance();
+                Ok(Node::GlobalAugmentation(self.declaration_block()?))
+            }
+            TokenType::Function
+            | TokenType::Class
+            | TokenType::Let
+            | TokenType::Const
+            | TokenType::Var
+            | TokenType::Interface
+            | TokenType::Type => Ok(Node::AmbientDeclaration(Box::new(self.declaration()?))),
+            _ => Err(self.error_at_current("Declaration expected")),
+        }
+    }
+
+    /// The `{ ... }` body of a block, namespace or ambient module.
+    fn declaration_block(&mut self) -> ParseResult<Vec<Node>> {
+        self.consume(TokenType::LeftBrace, "'{' expected")?;
+        let mut body = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            match self.declaration() {
+                Ok(node) => body.push(node),
+                Err(error) => {
+                    self.errors.push(error);
+                    self.synchronize();
+                }
+            }
+        }
+        self.consume(TokenType::RightBrace, "'}' expected")?;
+        Ok(body)
+    }
+
+    fn declaration_name(&mut self, optional: bool) -> ParseResult<String> {
+        if optional && !is_identifier_token(self.peek().token_type) {
+            return Ok("default".to_string());
+        }
+        self.identifier()
+    }
+
+    // Modules
+
+    fn import_declaration(&mut self) -> ParseResult<Node> {
+        self.consume(TokenType::Import, "'import' expected")?;
+        if self.check(TokenType::StringLiteral) {
+            let source = self.advance().value;
+            self.consume_semicolon()?;
+            return Ok(Node::ImportDeclaration {
+                specifiers: Vec::new(),
+                source,
+                is_type_only: false,
+            });
+        }
+        // `import type from "mod"` imports a default binding called `type`
+        let is_type_only = self.check(TokenType::Type)
+            && match self.peek_next().token_type {
+                TokenType::LeftBrace | TokenType::Star => true,
+                TokenType::From => self.peek_at(2).token_type == TokenType::From,
+                token_type => is_identifier_token(token_type),
+            };
+        if is_type_only {
+            self.advance();
+        }
+
+        let mut specifiers = Vec::new();
+        if is_identifier_token(self.peek().token_type) {
+            let span = self.span();
+            specifiers.push(ImportSpecifier::Default(self.identifier()?, span));
+            if !self.matches(&[TokenType::Comma]) {
+                return self.import_source(specifiers, is_type_only);
+            }
+        }
+        if self.matches(&[TokenType::Star]) {
+            self.consume(TokenType::As, "'as' expected")?;
+            let span = self.span();
+            specifiers.push(ImportSpecifier::Namespace(self.identifier()?, span));
+        } else {
+            self.consume(TokenType::LeftBrace, "'{' expected")?;
+            while !self.check(TokenType::RightBrace) {
+                let is_type_only = self.check(TokenType::Type)
+                    && !matches!(
+                        self.peek_next().token_type,
+                        TokenType::Comma | TokenType::RightBrace | TokenType::As
+                    );
+                if is_type_only {
+                    self.advance();
+                }
+                let mut span = self.span();
+                let imported = self.module_export_name()?;
+                let local = if self.matches(&[TokenType::As]) {
+                    span = self.span();
+                    self.identifier()?
+                } else {
+                    imported.clone()
+                };
+                specifiers.push(ImportSpecifier::Named {
+                    imported,
+                    local,
+                    is_type_only,
+                    span,
+                });
+                if !self.matches(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+            self.consume(TokenType::RightBrace, "'}' expected")?;
+        }
+        self.import_source(specifiers, is_type_only)
+    }
+
+    fn import_source(
+        &mut self,
+        specifiers: Vec<ImportSpecifier>,
+        is_type_only: bool,
+    ) -> ParseResult<Node> {
+        let source = self.module_specifier()?;
+        self.consume_semicolon()?;
+        Ok(Node::ImportDeclaration {
+            specifiers,
+            source,
+            is_type_only,
+        })
+    }
+
+    fn export_declaration(&mut self) -> ParseResult<Node> {
+        self.consume(TokenType::Export, "'export' expected")?;
+        if self.matches(&[TokenType::Default]) {
+            let declaration = match self.peek().token_type {
+                TokenType::Function => self.function_declaration(true)?,
+                TokenType::Async if self.starts_async_function() => {
+                    self.function_declaration(true)?
+                }
+                TokenType::Class => self.class_declaration(true)?,
+                TokenType::At => {
+                    let decorators = self.decorators()?;
+                    let mut class = self.class_declaration(true)?;
+                    if let Node::ClassDeclaration {
+                        decorators: class_decorators,
+                        ..
+                    } = &mut class
+                    {
+                        *class_decorators = decorators;
+                    }
+                    class
+                }
+                TokenType::Interface => self.interface_declaration()?,
+                _ => {
+                    let expression = self.assignment()?;
+                    self.consume_semicolon()?;
+                    expression
+                }
+            };
+            return Ok(Node::ExportDefaultDeclaration(Box::new(declaration)));
+        }
+
+        let is_type_only = self.check(TokenType::Type)
+            && matches!(
+                self.peek_next().token_type,
+                TokenType::LeftBrace | TokenType::Star
+            );
+        if is_type_only {
+            self.advance();
+        }
+        if self.matches(&[TokenType::Star]) {
+            let exported = if self.matches(&[TokenType::As]) {
+                Some(self.module_export_name()?)
+            } else {
+                None
+            };
+            let source = self.module_specifier()?;
+            self.consume_semicolon()?;
+            return Ok(Node::ExportAllDeclaration {
+                exported,
+                source,
+                is_type_only,
+            });
+        }
+        if self.matches(&[TokenType::LeftBrace]) {
+            let mut specifiers = Vec::new();
+            while !self.check(TokenType::RightBrace) {
+                let is_type_only = self.check(TokenType::Type)
+                    && !matches!(
+                        self.peek_next().token_type,
+                        TokenType::Comma | TokenType::RightBrace | TokenType::As
+                    );
+                if is_type_only {
+                    self.advance();
+                }
+                let span = self.span();
+                let local = self.module_export_name()?;
+                let exported = if self.matches(&[TokenType::As]) {
+                    self.module_export_name()?
+                } else {
+                    local.clone()
+                };
+                specifiers.push(ExportSpecifier {
+                    local,
+                    exported,
+                    is_type_only,
+                    span,
+                });
+                if !self.matches(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+            self.consume(TokenType::RightBrace, "'}' expected")?;
+            let source = if self.check(TokenType::From) {
+                Some(self.module_specifier()?)
+            } else {
+                None
+            };
+            self.consume_semicolon()?;
+            return Ok(Node::ExportNamedDeclaration {
+                declaration: None,
+                specifiers,
+                source,
+                is_type_only,
+            });
+        }
+
+        let is_declaration = matches!(
+            self.peek().token_type,
+            TokenType::Function
+                | TokenType::Class
+                | TokenType::At
+                | TokenType::Interface
+                | TokenType::Enum
+                | TokenType::Let
+                | TokenType::Const
+                | TokenType::Var
+                | TokenType::Declare
+                | TokenType::Type
+                | TokenType::Namespace
+                | TokenType::Module
+        );
+        if !is_declaration {
+            return Err(self.error_at_current("Declaration expected"));
+        }
+        Ok(Node::ExportNamedDeclaration {
+            declaration: Some(Box::new(self.declaration()?)),
+            specifiers: Vec::new(),
+            source: None,
+            is_type_only: false,
+        })
+    }
+
+    /// `from "mod"`
+    fn module_specifier(&mut self) -> ParseResult<String> {
+        self.consume(TokenType::From, "'from' expected")?;
+        if self.check(TokenType::StringLiteral) {
+            return Ok(self.advance().value);
+        }
+        Err(self.error_at_current("String literal expected"))
+    }
+
+    /// Import and export specifiers may name any identifier, reserved words and string
+    /// literals included.
+    fn module_export_name(&mut self) -> ParseResult<String> {
+        if self.check(TokenType::StringLiteral) {
+            return Ok(self.advance().value);
+        }
+        self.identifier_name()
+    }
+
+    // Statements
+
+    fn statement(&mut self) -> ParseResult<Node> {
+        match self.peek().token_type {
+            TokenType::LeftBrace => self.block_statement(),
+            TokenType::If => self.if_statement(),
+            TokenType::For => self.for_statement(),
+            TokenType::While => self.while_statement(),
+            TokenType::Do => self.do_while_statement(),
+            TokenType::Switch => self.switch_statement(),
+            TokenType::Return => self.return_statement(),
+            TokenType::Break | TokenType::Continue => self.jump_statement(),
+            token_type
+                if is_identifier_token(token_type)
+                    && self.peek_next().token_type == TokenType::Colon =>
+            {
+                self.labeled_statement()
+            }
+            TokenType::Throw => {
+                self.advance();
+                if self.has_preceding_line_break() {
+                    self.errors
+                        .push(self.error_at_current("Line break not permitted here."));
+                }
+                let argument = self.expression()?;
+                self.consume_semicolon()?;
+                Ok(Node::ThrowStatement(Box::new(argument)))
+            }
+            TokenType::Try => self.try_statement(),
+            TokenType::Semicolon => {
+                self.advance();
+                Ok(Node::EmptyStatement)
+            }
+            _ => {
+                let expression = self.expression()?;
+                self.consume_semicolon()?;
+                Ok(Node::ExpressionStatement(Box::new(expression)))
+            }
+        }
+    }
+
+    fn block_statement(&mut self) -> ParseResult<Node> {
+        let span = self.span();
+        Ok(Node::BlockStatement(self.declaration_block()?, span))
+    }
+
+    /// Jumps cannot cross function boundaries, so a body starts with no enclosing labels.
+    fn function_body(&mut self, is_async: bool, is_generator: bool) -> ParseResult<Node> {
+        let context = FunctionContext {
+            is_async,
+            is_generator,
+            ..Default::default()
+        };
+        let outer = std::mem::replace(&mut self.function, context);
+        let no_in = std::mem::replace(&mut self.no_in, false);
+        let body = self.block_statement();
+        self.function = outer;
+        self.no_in = no_in;
+        body
+    }
+
+    fn if_statement(&mut self) -> ParseResult<Node> {
+        self.consume(TokenType::If, "'if' expected")?;
+        self.consume(TokenType::LeftParen, "'(' expected")?;
+        let condition = self.expression()?;
+        self.consume(TokenType::RightParen, "')' expected")?;
+        let then_branch = self.statement()?;
+        let else_branch = if self.matches(&[TokenType::Else]) {
+            Some(Box::new(self.statement()?))
+        } else {
+            None
+        };
+        Ok(Node::IfStatement {
+            condition: Box::new(condition),
+            then_branch: Box::new(then_branch),
+            else_branch,
+        })
+    }
+
+    fn for_statement(&mut self) -> ParseResult<Node> {
+        self.consume(TokenType::For, "'for' expected")?;
+        if self.check(TokenType::Await) && !self.function.is_async {
+            self.errors.push(self.error_at_current(
+                "'for await' loops are only allowed within async functions and at the top levels of modules.",
+            ));
+        }
+        let is_await = self.matches(&[TokenType::Await]);
+        self.consume(TokenType::LeftParen, "'(' expected")?;
+        self.no_in = true;
+        let init = self.for_init();
+        self.no_in = false;
+        let init = match init? {
+            Some(left) if self.check_any(&[TokenType::In, TokenType::Of]) => {
+                return self.for_in_of(*left, is_await);
+            }
+            init => init,
+        };
+        if is_await {
+            return Err(self.error_at_current("'of' expected"));
+        }
+        self.consume(TokenType::Semicolon, "';' expected")?;
+        let test = if self.check(TokenType::Semicolon) {
+            None
+        } else {
+            Some(Box::new(self.expression()?))
+        };
+        self.consume(TokenType::Semicolon, "';' expected")?;
+        let update = if self.check(TokenType::RightParen) {
+            None
+        } else {
+            Some(Box::new(self.expression()?))
+        };
+        self.consume(TokenType::RightParen, "')' expected")?;
+        let body = self.iteration_body()?;
+        Ok(Node::ForStatement {
+            init,
+            test,
+            update,
+            body: Box::new(body),
+        })
+    }
+
+    fn for_init(&mut self) -> ParseResult<Option<Box<Node>>> {
+        let init = match self.peek().token_type {
+            TokenType::Semicolon => return Ok(None),
+            TokenType::Let | TokenType::Const | TokenType::Var => {
+                self.variable_declaration_list()?
+            }
+            TokenType::LeftBracket | TokenType::LeftBrace => {
+                match self.try_assignment_pattern(&[TokenType::In, TokenType::Of]) {
+                    Some(pattern) => pattern,
+                    None => self.expression()?,
+                }
+            }
+            _ => self.expression()?,
+        };
+        Ok(Some(Box::new(init)))
+    }
+
+    /// The rest of `for (left in right)` or `for (left of right)`, after `left`.
+    fn for_in_of(&mut self, left: Node, is_await: bool) -> ParseResult<Node> {
+        let is_of = self.check(TokenType::Of);
+        let kind = if is_of { "for...of" } else { "for...in" };
+        if let Node::VariableDeclaration { declarations, .. } = &left {
+            if declarations.len() != 1 {
+                self.errors.push(self.error_at_current(&format!(
+                    "Only a single variable declaration is allowed in a '{}' statement.",
+                    kind
+                )));
+            } else if declarations[0].init.is_some() {
+                self.errors.push(self.error_at_current(&format!(
+                    "The variable declaration of a '{}' statement cannot have an initializer.",
+                    kind
+                )));
+            }
+        } else if !matches!(
+            left,
+            Node::Identifier(..)
+                | Node::MemberExpression { .. }
+                | Node::ObjectPattern(_)
+                | Node::ArrayPattern(_)
+        ) {
+            self.errors.push(self.error_at_current(&format!(
+                "The left-hand side of a '{}' statement must be a variable or a property access.",
+                kind
+            )));
+        }
+        if is_await && !is_of {
+            self.errors.push(self.error_at_current("'of' expected"));
+        }
+        self.advance();
+        // `for (x of a, b)` is not allowed, but `for (x in a, b)` is
+        let right = if is_of {
+            self.assignment()?
+        } else {
+            self.expression()?
+        };
+        self.consume(TokenType::RightParen, "')' expected")?;
+        let body = self.iteration_body()?;
+        let (left, right, body) = (Box::new(left), Box::new(right), Box::new(body));
+        Ok(if is_of {
+            Node::ForOfStatement {
+                left,
+                right,
+                body,
+                is_await,
+            }
+        } else {
+            Node::ForInStatement { left, right, body }
+        })
+    }
+
+    fn while_statement(&mut self) -> ParseResult<Node> {
+        self.consume(TokenType::While, "'while' expected")?;
+        self.consume(TokenType::LeftParen, "'(' expected")?;
+        let test = self.expression()?;
+        self.consume(TokenType::RightParen, "')' expected")?;
+        let body = self.iteration_body()?;
+        Ok(Node::WhileStatement {
+            test: Box::new(test),
+            body: Box::new(body),
+        })
+    }
+
+    fn do_while_statement(&mut self) -> ParseResult<Node> {
+        self.consume(TokenType::Do, "'do' expected")?;
+        let body = self.iteration_body()?;
+        self.consume(TokenType::While, "'while' expected")?;
+        self.consume(TokenType::LeftParen, "'(' expected")?;
+        let test = self.expression()?;
+        self.consume(TokenType::RightParen, "')' expected")?;
+        // A semicolon is always inserted after a do-while
+        self.matches(&[TokenType::Semicolon]);
+        Ok(Node::DoWhileStatement {
+            body: Box::new(body),
+            test: Box::new(test),
+        })
+    }
+
+    fn iteration_body(&mut self) -> ParseResult<Node> {
+        self.function.iterations += 1;
+        self.function.breakables += 1;
+        let body = self.statement();
+        self.function.iterations -= 1;
+        self.function.breakables -= 1;
+        body
+    }
+
+    fn switch_statement(&mut self) -> ParseResult<Node> {
+        self.consume(TokenType::Switch, "'switch' expected")?;
+        self.consume(TokenType::LeftParen, "'(' expected")?;
+        let discriminant = self.expression()?;
+        self.consume(TokenType::RightParen, "')' expected")?;
+        self.consume(TokenType::LeftBrace, "'{' expected")?;
+        self.function.breakables += 1;
+        let cases = self.switch_cases();
+        self.function.breakables -= 1;
+        let cases = cases?;
+        self.consume(TokenType::RightBrace, "'}' expected")?;
+        Ok(Node::SwitchStatement {
+            discriminant: Box::new(discriminant),
+            cases,
+        })
+    }
+
+    fn switch_cases(&mut self) -> ParseResult<Vec<SwitchCase>> {
+        let mut cases: Vec<SwitchCase> = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            let test = match self.peek().token_type {
+                TokenType::Case => {
+                    self.advance();
+                    Some(Box::new(self.expression()?))
+                }
+                TokenType::Default => {
+                    if cases.iter().any(|case| case.test.is_none()) {
+                        self.errors.push(self.error_at_current(
+                            "A 'default' clause cannot appear more than once in a 'switch' statement.",
+                        ));
+                    }
+                    self.advance();
+                    None
+                }
+                _ => return Err(self.error_at_current("'case' or 'default' expected")),
+            };
+            self.consume(TokenType::Colon, "':' expected")?;
+            let mut consequent = Vec::new();
+            while !self.check_any(&[TokenType::Case, TokenType::Default, TokenType::RightBrace])
+                && !self.is_at_end()
+            {
+                match self.declaration() {
+                    Ok(node) => consequent.push(node),
+                    Err(error) => {
+                        self.errors.push(error);
+                        self.synchronize();
+                    }
+                }
+            }
+            cases.push(SwitchCase { test, consequent });
+        }
+        Ok(cases)
+    }
+
+    fn labeled_statement(&mut self) -> ParseResult<Node> {
+        if self
+            .function
+            .labels
+            .iter()
+            .any(|(label, _)| *label == self.peek().value)
+        {
+            let message = format!("Duplicate label '{}'.", self.peek().value);
+            self.errors.push(self.error_at_current(&message));
+        }
+        let label = self.advance().value;
+        self.consume(TokenType::Colon, "':' expected")?;
+        let is_iteration = self.check_any(&[TokenType::For, TokenType::While, TokenType::Do]);
+        self.function.labels.push((label.clone(), is_iteration));
+        let body = self.statement();
+        self.function.labels.pop();
+        Ok(Node::LabeledStatement {
+            label,
+            body: Box::new(body?),
+        })
+    }
+
+    fn jump_statement(&mut self) -> ParseResult<Node> {
+        let is_break = self.advance().token_type == TokenType::Break;
+        let label =
+            if is_identifier_token(self.peek().token_type) && !self.has_preceding_line_break() {
+                Some(self.advance().value)
+            } else {
+                None
+            };
+        let message = match &label {
+            Some(label) => match self.function.labels.iter().rev().find(|(l, _)| l == label) {
+                None if is_break => Some("A 'break' statement can only jump to a label of an enclosing statement."),
+                Some((_, false)) | None if !is_break => Some("A 'continue' statement can only jump to a label of an enclosing iteration statement."),
+                _ => None,
+            },
+            None if is_break && self.function.breakables == 0 => Some("A 'break' statement can only be used within an enclosing iteration or switch statement."),
+            None if !is_break && self.function.iterations == 0 => Some("A 'continue' statement can only be used within an enclosing iteration statement."),
+            None => None,
+        };
+        if let Some(message) = message {
+            let token = &self.tokens[self.current - 1 - label.is_some() as usize];
+            self.errors.push(CompilerError::new(
+                message.to_string(),
+                Span {
+                    row: token.row,
+                    col: token.col,
+                    file: self.file,
+                },
+            ));
+        }
+        self.consume_semicolon()?;
+        Ok(if is_break {
+            Node::BreakStatement(label)
+        } else {
+            Node::ContinueStatement(label)
+        })
+    }
+
+    fn return_statement(&mut self) -> ParseResult<Node> {
+        self.consume(TokenType::Return, "'return' expected")?;
+        let argument = if self.check_any(&[TokenType::Semicolon, TokenType::RightBrace])
+            || self.is_at_end()
+            || self.has_preceding_line_break()
+        {
+            None
+        } else {
+            Some(Box::new(self.expression()?))
+        };
+        self.consume_semicolon()?;
+        Ok(Node::ReturnStatement(argument))
+    }
+
+    fn try_statement(&mut self) -> ParseResult<Node> {
+        self.consume(TokenType::Try, "'try' expected")?;
+        let block = self.block_statement()?;
+        let handler = if self.matches(&[TokenType::Catch]) {
+            let param = if self.matches(&[TokenType::LeftParen]) {
+                let param = self.binding_target()?;
+                // `catch (e: unknown)` annotations carry no information for the handler
+                self.optional_type_annotation()?;
+                self.consume(TokenType::RightParen, "')' expected")?;
+                Some(Box::new(param))
+            } else {
+                None
+            };
+            let body = self.block_statement()?;
+            Some(CatchClause {
+                param,
+                body: Box::new(body),
+            })
+        } else {
+            None
+        };
+        let finalizer = if self.matches(&[TokenType::Finally]) {
+            Some(Box::new(self.block_statement()?))
+        } else {
+            None
+        };
+        if handler.is_none() && finalizer.is_none() {
+            return Err(self.error_at_current("'catch' or 'finally' expected"));
+        }
+        Ok(Node::TryStatement {
+            block: Box::new(block),
+            handler,
+            finalizer,
+        })
+    }
+
+    // Expressions
+
+    fn expression(&mut self) -> ParseResult<Node> {
+        self.assignment()
+    }
+
+    fn assignment(&mut self) -> ParseResult<Node> {
+        if self.function.is_generator && self.check(TokenType::Yield) {
+            return self.yield_expression();
+        }
+        if let Some(arrow) = self.try_arrow_function()? {
+            return Ok(arrow);
+        }
+        if self.check_any(&[TokenType::LeftBracket, TokenType::LeftBrace]) {
+            if let Some(pattern) = self.try_assignment_pattern(&[TokenType::Equal]) {
+                self.consume(TokenType::Equal, "'=' expected")?;
+                let right = self.assignment()?;
+                return Ok(Node::AssignmentExpression {
+                    left: Box::new(pattern),
+                    operator: "=".to_string(),
+                    right: Box::new(right),
+                });
+            }
+        }
+        let left = self.conditional()?;
+        let Some((operator, length)) = self.assignment_operator() else {
+            return Ok(left);
+        };
+        if matches!(left, Node::ChainExpression(_)) {
+            self.errors.push(self.error_at_current(
+                "The left-hand side of an assignment expression may not be an optional property access.",
+            ));
+        }
+        for _ in 0..length {
+            self.advance();
+        }
+        let right = self.assignment()?;
+        Ok(Node::AssignmentExpression {
+            left: Box::new(left),
+            operator,
+            right: Box::new(right),
+        })
+    }
+
+    /// The assignment operator at the current token, and how many tokens it takes up.
+    fn assignment_operator(&self) -> Option<(String, usize)> {
+        if self.check_any(&[TokenType::Equal, TokenType::CompoundAssignment]) {
+            return Some((self.peek().value.clone(), 1));
+        }
+        self.shift_operator()
+            .filter(|(operator, _)| operator.ends_with('='))
+    }
+
+    /// A shift operator or shift assignment at the current token, and how many tokens it
+    /// takes up. The tokenizer never joins `<` and `>` with each other, so that
+    /// `Array<Array<T>>` closes two lists of type arguments; adjacent ones are joined here.
+    fn shift_operator(&self) -> Option<(String, usize)> {
+        let mut operator = String::new();
+        let mut shift = None;
+        for length in 1..=3 {
+            let token = self.peek_at(length - 1);
+            if length > 1 {
+                let previous = self.peek_at(length - 2);
+                if token.row != previous.row || token.col != previous.col + previous.value.len() {
+                    break;
+                }
+            }
+            operator.push_str(&token.value);
+            if matches!(
+                operator.as_str(),
+                "<<" | "<<=" | ">>" | ">>=" | ">>>" | ">>>="
+            ) {
+                shift = Some((operator.clone(), length));
+            }
+        }
+        shift
+    }
+
+    /// `x => ...`, `(params): T => ...` and their `async` forms. A parenthesized list is
+    /// only taken as parameters once `=>` follows it, so `(a, b)` still parses as an
+    /// expression.
+    fn try_arrow_function(&mut self) -> ParseResult<Option<Node>> {
+        let start = self.current;
+        let span = self.span();
+        let error_count = self.errors.len();
+        let is_async = self.check(TokenType::Async)
+            && self.peek_next().row == self.peek().row
+            && self.peek_next().token_type != TokenType::Arrow;
+        if is_async {
+            self.advance();
+        }
+        let head = if is_identifier_token(self.peek().token_type)
+            && self.peek_next().token_type == TokenType::Arrow
+        {
+            let span = self.span();
+            let name = self.advance().value;
+            Some((
+                Vec::new(),
+                vec![Parameter {
+                    binding: Box::new(Node::Identifier(name, span)),
+                    type_annotation: None,
+                    optional: false,
+                    rest: false,
+                    init: None,
+                    modifiers: Modifiers::default(),
+                }],
+                None,
+            ))
+        } else if self.check_any(&[TokenType::LeftParen, TokenType::Less]) {
+            match self.arrow_head() {
+                Ok(head) if self.check(TokenType::Arrow) => Some(head),
+                _ => None,
+            }
+        } else {
+            None
+        };
+        let Some((type_params, params, return_type)) = head else {
+            self.current = start;
+            self.errors.truncate(error_count);
+            return Ok(None);
+        };
+        if self.has_preceding_line_break() {
+            self.errors
+                .push(self.error_at_current("Line terminator not permitted before arrow."));
+        }
+        self.consume(TokenType::Arrow, "'=>' expected")?;
+        let body = if self.check(TokenType::LeftBrace) {
+            self.function_body(is_async, false)?
+        } else {
+            let context = FunctionContext {
+                is_async,
+                ..Default::default()
+            };
+            let outer = std::mem::replace(&mut self.function, context);
+            let body = self.assignment();
+            self.function = outer;
+            body?
+        };
+        Ok(Some(Node::ArrowFunctionExpression {
+            type_params,
+            params,
+            body: Box::new(body),
+            return_type,
+            is_async,
+            span,
+        }))
+    }
+
+    fn arrow_head(&mut self) -> ParseResult<ArrowHead> {
+        let type_params = self.type_parameters()?;
+        let params = self.parameters()?;
+        let return_type = self.optional_return_type()?;
+        Ok((type_params, params, return_type))
+    }
+
+    fn yield_expression(&mut self) -> ParseResult<Node> {
+        self.consume(TokenType::Yield, "'yield' expected")?;
+        let delegate = self.matches(&[TokenType::Star]);
+        let has_argument = delegate
+            || !(self.has_preceding_line_break()
+                || self.is_at_end()
+                || self.check_any(&[
+                    TokenType::RightParen,
+                    TokenType::RightBracket,
+                    TokenType::RightBrace,
+                    TokenType::Comma,
+                    TokenType::Semicolon,
+                    TokenType::Colon,
+                ]));
+        let argument = if has_argument {
+            Some(Box::new(self.assignment()?))
+        } else {
+            None
+        };
+        Ok(Node::YieldExpression { argument, delegate })
+    }
+
+    fn conditional(&mut self) -> ParseResult<Node> {
+        let test = self.binary(1)?;
+        if self.matches(&[TokenType::QuestionMark]) {
+            let consequent = self.assignment()?;
+            self.consume(TokenType::Colon, "':' expected")?;
+            let alternate = self.assignment()?;
+            return Ok(Node::ConditionalExpression {
+                test: Box::new(test),
+                consequent: Box::new(consequent),
+                alternate: Box::new(alternate),
+            });
+        }
+        Ok(test)
+    }
+
+    fn binary(&mut self, min_precedence: u8) -> ParseResult<Node> {
+        let mut left = self.unary()?;
+        loop {
+            let token_type = self.peek().token_type;
+            if token_type == TokenType::In && self.no_in {
+                break;
+            }
+            // `as` and `satisfies` bind like relational operators but take a type on the right
+            if self.is_type_operator_keyword() && AS_PRECEDENCE >= min_precedence {
+                let expression = Box::new(left);
+                left = if self.advance().token_type == TokenType::As {
+                    Node::AsExpression {
+                        expression,
+                        type_annotation: Box::new(self.const_or_type()?),
+                    }
+                } else {
+                    Node::SatisfiesExpression {
+                        expression,
+                        type_annotation: Box::new(self.type_annotation()?),
+                    }
+                };
+                continue;
+            }
+            if let Some((operator, length)) = self.shift_operator() {
+                // A shift assignment is left for `assignment`
+                if operator.ends_with('=') || SHIFT_PRECEDENCE < min_precedence {
+                    break;
+                }
+                for _ in 0..length {
+                    self.advance();
+                }
+                let right = self.binary(SHIFT_PRECEDENCE + 1)?;
+                left = Node::BinaryExpression {
+                    left: Box::new(left),
+                    operator,
+                    right: Box::new(right),
+                };
+                continue;
+            }
+            let precedence = match binary_precedence(token_type) {
+                Some(precedence) if precedence >= min_precedence => precedence,
+                _ => break,
+            };
+            let operator = self.advance().value;
+            // `**` is the only right-associative binary operator
+            let right = if token_type == TokenType::Power {
+                self.binary(precedence)?
+            } else {
+                self.binary(precedence + 1)?
+            };
+            left = if matches!(
+                token_type,
+                TokenType::AmpersandAmpersand | TokenType::PipePipe | TokenType::NullishCoalescing
+            ) {
+                Node::LogicalExpression {
+                    left: Box::new(left),
+                    operator,
+                    right: Box::new(right),
+                }
+            } else {
+                Node::BinaryExpression {
+                    left: Box::new(left),
+                    operator,
+                    right: Box::new(right),
+                }
+            };
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> ParseResult<Node> {
+        let token = self.peek().clone();
+        match token.token_type {
+            TokenType::Bang
+            | TokenType::Minus
+            | TokenType::Plus
+            | TokenType::BitwiseNot
+            | TokenType::Typeof
+            | TokenType::Void => {
+                self.advance();
+                let argument = self.unary()?;
+                Ok(Node::UnaryExpression {
+                    operator: token.value,
+                    argument: Box::new(argument),
+                })
+            }
+            TokenType::Identifier if token.value == "delete" => {
+                self.advance();
+                let argument = self.unary()?;
+                Ok(Node::UnaryExpression {
+                    operator: token.value,
+                    argument: Box::new(argument),
+                })
+            }
+            TokenType::Await if self.function.is_async || self.starts_misplaced_await() => {
+                if !self.function.is_async {
+                    self.errors.push(self.error_at_current(
+                        "'await' expressions are only allowed within async functions and at the top levels of modules.",
+                    ));
+                }
+                self.advance();
+                let argument = self.unary()?;
+                Ok(Node::AwaitExpression(Box::new(argument)))
+            }
+            TokenType::Increment | TokenType::Decrement => {
+                self.advance();
+                let argument = self.unary()?;
+                Ok(Node::UpdateExpression {
+                    operator: token.value,
+                    prefix: true,
+                    argument: Box::new(argument),
+                })
+            }
+            // `<T>x`; a generic arrow function was already tried before getting here
+            TokenType::Less => {
+                self.advance();
+                let type_annotation = self.const_or_type()?;
+                self.consume(TokenType::Greater, "'>' expected")?;
+                let expression = self.unary()?;
+                Ok(Node::TypeAssertion {
+                    type_annotation: Box::new(type_annotation),
+                    expression: Box::new(expression),
+                })
+            }
+            _ => self.postfix(),
+        }
+    }
+
+    /// `as`, or a contextual `satisfies`, on the same line as the expression before it.
+    fn is_type_operator_keyword(&self) -> bool {
+        let token = self.peek();
+        (token.token_type == TokenType::As
+            || token.token_type == TokenType::Identifier && token.value == "satisfies")
+            && !self.has_preceding_line_break()
+    }
+
+    /// The type of an assertion, where `const` asks for the narrowest literal type.
+    fn const_or_type(&mut self) -> ParseResult<Node> {
+        if self.matches(&[TokenType::Const]) {
+            return Ok(Node::KeywordType(TokenType::Const));
+        }
+        self.type_annotation()
+    }
+
+    fn postfix(&mut self) -> ParseResult<Node> {
+        let expression = self.call_member(true)?;
+        if self.check_any(&[TokenType::Increment, TokenType::Decrement])
+            && !self.has_preceding_line_break()
+        {
+            let operator = self.advance().value;
+            return Ok(Node::UpdateExpression {
+                operator,
+                prefix: false,
+                argument: Box::new(expression),
+            });
+        }
+        Ok(expression)
+    }
+
+    fn call_member(&mut self, allow_calls: bool) -> ParseResult<Node> {
+        let mut expression = if self.check(TokenType::New) {
+            self.new_expression()?
+        } else {
+            self.primary()?
+        };
+        let mut in_chain = false;
+        loop {
+            let optional = allow_calls && self.matches(&[TokenType::OptionalChain]);
+            in_chain |= optional;
+            if optional
+                && !self.check_any(&[
+                    TokenType::LeftBracket,
+                    TokenType::LeftParen,
+                    TokenType::Less,
+                ])
+                || self.matches(&[TokenType::Dot])
+            {
+                let property = self.property_name()?;
+                expression = Node::MemberExpression {
+                    object: Box::new(expression),
+                    property: Box::new(property),
+                    computed: false,
+                    optional,
+                };
+            } else if self.matches(&[TokenType::LeftBracket]) {
+                let property = self.expression()?;
+                self.consume(TokenType::RightBracket, "']' expected")?;
+                expression = Node::MemberExpression {
+                    object: Box::new(expression),
+                    property: Box::new(property),
+                    computed: true,
+                    optional,
+                };
+            } else if self.check(TokenType::Bang) && !self.has_preceding_line_break() {
+                self.advance();
+                expression = Node::NonNullExpression(Box::new(expression));
+            } else if allow_calls && self.check(TokenType::LeftParen) {
+                let arguments = self.arguments()?;
+                expression = Node::CallExpression {
+                    callee: Box::new(expression),
+                    type_arguments: Vec::new(),
+                    arguments,
+                    optional,
+                };
+            } else if allow_calls && self.check(TokenType::Less) {
+                match self.try_call_type_arguments() {
+                    Some(type_arguments) => {
+                        let arguments = self.arguments()?;
+                        expression = Node::CallExpression {
+                            callee: Box::new(expression),
+                            type_arguments,
+                            arguments,
+                            optional,
+                        };
+                    }
+                    None if optional => return Err(self.error_at_current("'(' expected")),
+                    None => break,
+                }
+            } else {
+                break;
+            }
+        }
+        if in_chain {
+            expression = Node::ChainExpression(Box::new(expression));
+        }
+        Ok(expression)
+    }
+
+    fn new_expression(&mut self) -> ParseResult<Node> {
+        self.consume(TokenType::New, "'new' expected")?;
+        let callee = self.call_member(false)?;
+        let type_arguments = if self.check(TokenType::Less) {
+            self.try_call_type_arguments().unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        let arguments = if self.check(TokenType::LeftParen) {
+            self.arguments()?
+        } else {
+            Vec::new()
+        };
+        Ok(Node::NewExpression {
+            callee: Box::new(callee),
+            type_arguments,
+            arguments,
+        })
+    }
+
+    /// Speculatively parses `<...>` as call type arguments. `f<T>(x)` is a call only when the
+    /// closing `>` is directly followed by `(`; otherwise `<` is a comparison and nothing is
+    /// consumed.
+    fn try_call_type_arguments(&mut self) -> Option<Vec<Node>> {
+        let start = self.current;
+        let error_count = self.errors.len();
+        match self.type_arguments() {
+            Ok(type_arguments) if self.check(TokenType::LeftParen) => Some(type_arguments),
+            _ => {
+                self.current = start;
+                self.errors.truncate(error_count);
+                None
+            }
+        }
+    }
+
+    fn spread_or_assignment(&mut self) -> ParseResult<Node> {
+        if self.matches(&[TokenType::Spread]) {
+            return Ok(Node::SpreadElement(Box::new(self.assignment()?)));
+        }
+        self.assignment()
+    }
+
+    fn array_literal(&mut self) -> ParseResult<Node> {
+        self.consume(TokenType::LeftBracket, "'[' expected")?;
+        let mut elements = Vec::new();
+        while !self.check(TokenType::RightBracket) {
+            if self.matches(&[TokenType::Comma]) {
+                elements.push(None);
+                continue;
+            }
+            elements.push(Some(self.spread_or_assignment()?));
+            if !self.matches(&[TokenType::Comma]) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightBracket, "']' expected")?;
+        Ok(Node::ArrayExpression(elements))
+    }
+
+    fn object_literal(&mut self) -> ParseResult<Node> {
+        self.consume(TokenType::LeftBrace, "'{' expected")?;
+        let mut elements = Vec::new();
+        while !self.check(TokenType::RightBrace) {
+            elements.push(self.object_literal_member()?);
+            if !self.matches(&[TokenType::Comma]) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightBrace, "'}' expected")?;
+        Ok(Node::ObjectExpression(elements))
+    }
+
+    fn object_literal_member(&mut self) -> ParseResult<Node> {
+        if self.matches(&[TokenType::Spread]) {
+            return Ok(Node::SpreadElement(Box::new(self.assignment()?)));
+        }
+        let (is_async, is_generator) = self.method_prefix();
+        let mut kind = MethodKind::Method;
+        if !is_async
+            && !is_generator
+            && self.check_any(&[TokenType::Get, TokenType::Set])
+            && self.next_starts_property_name()
+        {
+            kind = if self.advance().token_type == TokenType::Get {
+                MethodKind::Get
+            } else {
+                MethodKind::Set
+            };
+        }
+        let is_shorthand = kind == MethodKind::Method
+            && !is_async
+            && !is_generator
+            && is_identifier_token(self.peek().token_type)
+            && matches!(
+                self.peek_next().token_type,
+                TokenType::Comma | TokenType::RightBrace
+            );
+        let (key, computed) = self.property_key()?;
+
+        if kind != MethodKind::Method
+            || is_async
+            || is_generator
+            || self.check_any(&[TokenType::LeftParen, TokenType::Less])
+        {
+            let modifiers = Modifiers::default();
+            let method = self.method(key, computed, kind, modifiers, is_async, is_generator)?;
+            if let Node::MethodDefinition { body: None, .. } = method {
+                return Err(self.error_at_current("'{' expected"));
+            }
+            return Ok(method);
+        }
+        if is_shorthand {
+            return Ok(Node::Property {
+                value: Box::new(key.clone()),
+                key: Box::new(key),
+                computed,
+                shorthand: true,
+            });
+        }
+        self.consume(TokenType::Colon, "':' expected")?;
+        Ok(Node::Property {
+            key: Box::new(key),
+            computed,
+            value: Box::new(self.assignment()?),
+            shorthand: false,
+        })
+    }
+
+    fn arguments(&mut self) -> ParseResult<Vec<Node>> {
+        self.consume(TokenType::LeftParen, "'(' expected")?;
+        let mut arguments = Vec::new();
+        while !self.check(TokenType::RightParen) {
+            arguments.push(self.spread_or_assignment()?);
+            if !self.matches(&[TokenType::Comma]) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightParen, "')' expected")?;
+        Ok(arguments)
+    }
+
+    fn primary(&mut self) -> ParseResult<Node> {
+        let token = self.peek().clone();
+        match token.token_type {
+            TokenType::NumberLiteral
+            | TokenType::StringLiteral
+            | TokenType::TemplateLiteral
+            | TokenType::True
+            | TokenType::False
+            | TokenType::Null
+            | TokenType::Undefined => {
+                let span = self.span();
+                self.advance();
+                Ok(Node::Literal {
+                    token_type: token.token_type,
+                    value: token.value,
+                    span,
+                })
+            }
+            TokenType::BigInt if is_bigint_literal(&token) => {
+                let span = self.span();
+                self.advance();
+                Ok(Node::Literal {
+                    token_type: token.token_type,
+                    value: token.value,
+                    span,
+                })
+            }
+            TokenType::This => {
+                self.advance();
+                Ok(Node::ThisExpression)
+            }
+            TokenType::Super => {
+                self.advance();
+                Ok(Node::SuperExpression)
+            }
+            TokenType::LeftParen => {
+                self.advance();
+                let no_in = std::mem::replace(&mut self.no_in, false);
+                let expression = self.expression();
+                self.no_in = no_in;
+                let expression = expression?;
+                self.consume(TokenType::RightParen, "')' expected")?;
+                Ok(expression)
+            }
+            TokenType::LeftBracket => self.array_literal(),
+            TokenType::LeftBrace => self.object_literal(),
+            token_type if is_identifier_token(token_type) => {
+                let span = self.span();
+                self.advance();
+                Ok(Node::Identifier(token.value, span))
+            }
+            _ => Err(self.error_at_current("Expression expected")),
+        }
+    }
+
+    fn parameters(&mut self) -> ParseResult<Vec<Parameter>> {
+        self.consume(TokenType::LeftParen, "'(' expected")?;
+        let mut params = Vec::new();
+        while !self.check(TokenType::RightParen) {
+            params.push(self.parameter()?);
+            if !self.matches(&[TokenType::Comma]) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightParen, "')' expected")?;
+        Ok(params)
+    }
+
+    fn parameter(&mut self) -> ParseResult<Parameter> {
+        let decorators = self.decorators()?;
+        let mut modifiers = self.modifiers();
+        modifiers.decorators = decorators;
+        let rest = self.matches(&[TokenType::Spread]);
+        let span = self.span();
+        let binding = if self.matches(&[TokenType::This]) {
+            Node::Identifier("this".to_string(), span)
+        } else {
+            self.binding_target()?
+        };
+        let optional = self.matches(&[TokenType::QuestionMark]);
+        let type_annotation = self.optional_type_annotation()?;
+        let init = if self.matches(&[TokenType::Equal]) {
+            Some(Box::new(self.assignment()?))
+        } else {
+            None
+        };
+        Ok(Parameter {
+            binding: Box::new(binding),
+            type_annotation,
+            optional,
+            rest,
+            init,
+            modifiers,
+        })
+    }
+
+    // Patterns
+
+    /// A name or destructuring pattern in a declaration, parameter or catch clause.
+    fn binding_target(&mut self) -> ParseResult<Node> {
+        match self.peek().token_type {
+            TokenType::LeftBrace => self.object_pattern(false),
+            TokenType::LeftBracket => self.array_pattern(false),
+            _ => self.identifier_node(),
+        }
+    }
+
+    /// `[a, b] = ...` and `({ a } = ...)` look like literals until the `=`, so the pattern
+    /// is parsed speculatively.
+    /// Parses a pattern if it is followed by one of `followers`, otherwise rewinds.
+    fn try_assignment_pattern(&mut self, followers: &[TokenType]) -> Option<Node> {
+        let start = self.current;
+        let error_count = self.errors.len();
+        let pattern = if self.check(TokenType::LeftBrace) {
+            self.object_pattern(true)
+        } else {
+            self.array_pattern(true)
+        };
+        match pattern {
+            Ok(pattern) if self.check_any(followers) => Some(pattern),
+            _ => {
+                self.current = start;
+                self.errors.truncate(error_count);
+                None
+            }
+        }
+    }
+
+    /// In assignment patterns, targets may be any reference such as `obj.x`, not just names.
+    fn pattern_target(&mut self, assignment: bool) -> ParseResult<Node> {
+        match self.peek().token_type {
+            TokenType::LeftBrace => self.object_pattern(assignment),
+            TokenType::LeftBracket => self.array_pattern(assignment),
+            _ if assignment => {
+                let target = self.call_member(true)?;
+                match target {
+                    Node::Identifier(..) | Node::MemberExpression { .. } => Ok(target),
+                    _ => Err(self.error_at_current("Invalid destructuring assignment target")),
+                }
+            }
+            _ => self.identifier_node(),
+        }
+    }
+
+    fn pattern_default(&mut self, target: Node) -> ParseResult<Node> {
+        if !self.matches(&[TokenType::Equal]) {
+            return Ok(target);
+        }
+        Ok(Node::AssignmentPattern {
+            left: Box::new(target),
+            right: Box::new(self.assignment()?),
+        })
+    }
+
+    fn object_pattern(&mut self, assignment: bool) -> ParseResult<Node> {
+        self.consume(TokenType::LeftBrace, "'{' expected")?;
+        let mut elements = Vec::new();
+        while !self.check(TokenType::RightBrace) {
+            if self.matches(&[TokenType::Spread]) {
+                let argument = self.pattern_target(assignment)?;
+                elements.push(Node::RestElement(Box::new(argument)));
+                break;
+            }
+            let is_shorthand = is_identifier_token(self.peek().token_type)
+                && self.peek_next().token_type != TokenType::Colon;
+            let (key, computed) = self.property_key()?;
+            let value = if is_shorthand {
+                key.clone()
+            } else {
+                self.consume(TokenType::Colon, "':' expected")?;
+                self.pattern_target(assignment)?
+            };
+            elements.push(Node::PatternProperty {
+                key: Box::new(key),
+                computed,
+                value: Box::new(self.pattern_default(value)?),
+            });
+            if !self.matches(&[TokenType::Comma]) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightBrace, "'}' expected")?;
+        Ok(Node::ObjectPattern(elements))
+    }
+
+    fn array_pattern(&mut self, assignment: bool) -> ParseResult<Node> {
+        self.consume(TokenType::LeftBracket, "'[' expected")?;
+        let mut elements = Vec::new();
+        while !self.check(TokenType::RightBracket) {
+            if self.matches(&[TokenType::Comma]) {
+                elements.push(None);
+                continue;
+            }
+            if self.matches(&[TokenType::Spread]) {
+                let argument = self.pattern_target(assignment)?;
+                elements.push(Some(Node::RestElement(Box::new(argument))));
+                break;
+            }
+            let target = self.pattern_target(assignment)?;
+            elements.push(Some(self.pattern_default(target)?));
+            if !self.matches(&[TokenType::Comma]) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightBracket, "']' expected")?;
+        Ok(Node::ArrayPattern(elements))
+    }
+
+    fn property_key(&mut self) -> ParseResult<(Node, bool)> {
+        let token = self.peek().clone();
+        match token.token_type {
+            TokenType::LeftBracket => {
+                self.advance();
+                let key = self.assignment()?;
+                self.consume(TokenType::RightBracket, "']' expected")?;
+                Ok((key, true))
+            }
+            TokenType::StringLiteral | TokenType::NumberLiteral => {
+                let span = self.span();
+                self.advance();
+                Ok((
+                    Node::Literal {
+                        token_type: token.token_type,
+                        value: token.value,
+                        span,
+                    },
+                    false,
+                ))
+            }
+            _ => Ok((self.property_name()?, false)),
+        }
+    }
+
+    // Types
+
+    fn optional_type_annotation(&mut self) -> ParseResult<Option<Box<Node>>> {
+        if self.matches(&[TokenType::Colon]) {
+            Ok(Some(Box::new(self.type_annotation()?)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// The return type of a function or signature, which may be a type predicate.
+    fn optional_return_type(&mut self) -> ParseResult<Option<Box<Node>>> {
+        if self.matches(&[TokenType::Colon]) {
+            Ok(Some(Box::new(self.return_type()?)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn return_type(&mut self) -> ParseResult<Node> {
+        let is_parameter = |token: &Token| {
+            token.token_type == TokenType::This || is_identifier_token(token.token_type)
+        };
+        let asserts = self.peek().token_type == TokenType::Identifier
+            && self.peek().value == "asserts"
+            && is_parameter(self.peek_next())
+            && self.peek_next().row == self.peek().row;
+        let is_predicate = {
+            let (name, next) = if asserts {
+                (self.peek_next(), self.peek_at(2))
+            } else {
+                (self.peek(), self.peek_next())
+            };
+            is_parameter(name)
+                && next.token_type == TokenType::Identifier
+                && next.value == "is"
+                && next.row == name.row
+        };
+        if !asserts && !is_predicate {
+            return self.type_annotation();
+        }
+        if asserts {
+            self.advance();
+        }
+        let span = self.span();
+        let parameter = self.advance().value;
+        let type_annotation = if is_predicate {
+            self.advance();
+            Some(Box::new(self.type_annotation()?))
+        } else {
+            None
+        };
+        Ok(Node::TypePredicate {
+            parameter,
+            asserts,
+            type_annotation,
+            span,
+        })
+    }
+
+    fn type_annotation(&mut self) -> ParseResult<Node> {
+        if self.is_start_of_function_type() {
+            let type_params = self.type_parameters()?;
+            let params = self.parameters()?;
+            self.consume(TokenType::Arrow, "'=>' expected")?;
+            let return_type = self.return_type()?;
+            return Ok(Node::FunctionType {
+                type_params,
+                params,
+                return_type: Box::new(return_type),
+            });
+        }
+        if self.check(TokenType::New) {
+            self.advance();
+            let type_params = self.type_parameters()?;
+            let params = self.parameters()?;
+            self.consume(TokenType::Arrow, "'=>' expected")?;
+            let return_type = self.type_annotation()?;
+            return Ok(Node::ConstructorType {
+                type_params,
+                params,
+                return_type: Box::new(return_type),
+            });
+        }
+        self.union_type()
+    }
+
+    fn union_type(&mut self) -> ParseResult<Node> {
+        self.matches(&[TokenType::BitwiseOr]);
+        let mut types = vec![self.intersection_type()?];
+        while self.matches(&[TokenType::BitwiseOr]) {
+            types.push(self.intersection_type()?);
+        }
+        if types.len() == 1 {
+            return Ok(types.remove(0));
+        }
+        Ok(Node::UnionType(types))
+    }
+
+    fn intersection_type(&mut self) -> ParseResult<Node> {
+        self.matches(&[TokenType::BitwiseAnd]);
+        let mut types = vec![self.type_operator()?];
+        while self.matches(&[TokenType::BitwiseAnd]) {
+            types.push(self.type_operator()?);
+        }
+        if types.len() == 1 {
+            return Ok(types.remove(0));
+        }
+        Ok(Node::IntersectionType(types))
+    }
+
+    fn type_operator(&mut self) -> ParseResult<Node> {
+        let token = self.peek().clone();
+        let is_operator = match token.token_type {
+            TokenType::Readonly => true,
+            TokenType::Identifier => {
+                (token.value == "keyof" || token.value == "unique")
+                    && is_start_of_type(self.peek_next())
+            }
+            _ => false,
+        };
+        if !is_operator {
+            return self.postfix_type();
+        }
+        self.advance();
+        let type_annotation = self.type_operator()?;
+        Ok(Node::TypeOperator {
+            operator: token.value,
+            type_annotation: Box::new(type_annotation),
+        })
+    }
+
+    fn postfix_type(&mut self) -> ParseResult<Node> {
+        let mut type_annotation = self.primary_type()?;
+        while self.check(TokenType::LeftBracket) && !self.has_preceding_line_break() {
+            self.advance();
+            if self.matches(&[TokenType::RightBracket]) {
+                type_annotation = Node::ArrayType(Box::new(type_annotation));
+            } else {
+                let index_type = self.type_annotation()?;
+                self.consume(TokenType::RightBracket, "']' expected")?;
+                type_annotation = Node::IndexedAccessType {
+                    object_type: Box::new(type_annotation),
+                    index_type: Box::new(index_type),
+                };
+            }
+        }
+        Ok(type_annotation)
+    }
+
+    fn primary_type(&mut self) -> ParseResult<Node> {
+        let token = self.peek().clone();
+        match token.token_type {
+            TokenType::LeftParen => {
+                self.advance();
+                let inner = self.type_annotation()?;
+                self.consume(TokenType::RightParen, "')' expected")?;
+                Ok(Node::ParenthesizedType(Box::new(inner)))
+            }
+            TokenType::LeftBracket => self.tuple_type(),
+            TokenType::LeftBrace => Ok(Node::ObjectType {
+                members: self.type_members()?,
+            }),
+            TokenType::Typeof => {
+                self.advance();
+                Ok(Node::TypeQuery(Box::new(self.entity_name()?)))
+            }
+            TokenType::This => {
+                self.advance();
+                Ok(Node::ThisType)
+            }
+            TokenType::StringLiteral
+            | TokenType::NumberLiteral
+            | TokenType::True
+            | TokenType::False => {
+                let span = self.span();
+                self.advance();
+                Ok(Node::LiteralType(Box::new(Node::Literal {
+                    token_type: token.token_type,
+                    value: token.value,
+                    span,
+                })))
+            }
+            TokenType::BigInt if is_bigint_literal(&token) => {
+                let span = self.span();
+                self.advance();
+                Ok(Node::LiteralType(Box::new(Node::Literal {
+                    token_type: token.token_type,
+                    value: token.value,
+                    span,
+                })))
+            }
+            TokenType::Minus if self.peek_next().token_type == TokenType::NumberLiteral => {
+                let span = self.span();
+                self.advance();
+                let number = self.advance();
+                Ok(Node::LiteralType(Box::new(Node::Literal {
+                    token_type: TokenType::NumberLiteral,
+                    value: format!("-{}", number.value),
+                    span,
+                })))
+            }
+            TokenType::NumberType
+            | TokenType::StringType
+            | TokenType::BooleanType
+            | TokenType::Null
+            | TokenType::Undefined
+            | TokenType::Any
+            | TokenType::Unknown
+            | TokenType::Never
+            | TokenType::Void
+            | TokenType::BigInt
+            | TokenType::Symbol
+            | TokenType::Object => {
+                self.advance();
+                Ok(Node::KeywordType(token.token_type))
+            }
+            token_type if is_identifier_token(token_type) => self.type_reference(),
+            _ => Err(self.error_at_current("Type expected")),
+        }
+    }
+
+    fn type_reference(&mut self) -> ParseResult<Node> {
+        let span = self.span();
+        let mut name = self.identifier()?;
+        while self.matches(&[TokenType::Dot]) {
+            name.push('.');
+            name.push_str(&self.identifier_name()?);
+        }
+        let type_arguments = if self.check(TokenType::Less) {
+            self.type_arguments()?
+        } else {
+            Vec::new()
+        };
+        Ok(Node::TypeReference {
+            name,
+            type_arguments,
+            span,
+        })
+    }
+
+    fn type_parameters(&mut self) -> ParseResult<Vec<TypeParameter>> {
+        let mut type_params = Vec::new();
+        if !self.matches(&[TokenType::Less]) {
+            return Ok(type_params);
+        }
+        loop {
+            let span = self.span();
+            let name = self.identifier()?;
+            let constraint = if self.matches(&[TokenType::Extends]) {
+                Some(Box::new(self.type_annotation()?))
+            } else {
+                None
+            };
+            let default = if self.matches(&[TokenType::Equal]) {
+                Some(Box::new(self.type_annotation()?))
+            } else {
+                None
+            };
+            let after_optional = type_params
+                .iter()
+                .any(|type_param: &TypeParameter| type_param.default.is_some());
+            if default.is_none() && after_optional {
+                self.errors.push(
+                    CompilerError::new(
+                        "Required type parameters may not follow optional type parameters."
+                            .to_string(),
+                        span,
+                    )
+                    .with_code(2706),
+                );
+            }
+            type_params.push(TypeParameter {
+                name,
+                span,
+                constraint,
+                default,
+            });
+            if !self.matches(&[TokenType::Comma]) || self.check(TokenType::Greater) {
+                break;
+            }
+        }
+        self.consume(TokenType::Greater, "'>' expected")?;
+        Ok(type_params)
+    }
+
+    fn type_arguments(&mut self) -> ParseResult<Vec<Node>> {
+        self.consume(TokenType::Less, "'<' expected")?;
+        let mut arguments = vec![self.type_annotation()?];
+        while self.matches(&[TokenType::Comma]) {
+            arguments.push(self.type_annotation()?);
+        }
+        self.consume(TokenType::Greater, "'>' expected")?;
+        Ok(arguments)
+    }
+
+    /// `x`, `this` or a dotted path such as `a.b.c`, as used by `typeof` queries.
+    fn entity_name(&mut self) -> ParseResult<Node> {
+        let mut entity = if self.matches(&[TokenType::This]) {
+            Node::ThisExpression
+        } else {
+            self.identifier_node()?
+        };
+        while self.matches(&[TokenType::Dot]) {
+            let property = self.property_name()?;
+            entity = Node::MemberExpression {
+                object: Box::new(entity),
+                property: Box::new(property),
+                computed: false,
+                optional: false,
+            };
+        }
+        Ok(entity)
+    }
+
+    fn tuple_type(&mut self) -> ParseResult<Node> {
+        self.consume(TokenType::LeftBracket, "'[' expected")?;
+        let mut elements = Vec::new();
+        while !self.check(TokenType::RightBracket) {
+            elements.push(self.tuple_element()?);
+            if !self.matches(&[TokenType::Comma]) {
+                break;
+            }
+        }
+        self.consume(TokenType::RightBracket, "']' expected")?;
+        Ok(Node::TupleType(elements))
+    }
+
+    fn tuple_element(&mut self) -> ParseResult<Node> {
+        let rest = self.matches(&[TokenType::Spread]);
+        let is_named = is_identifier_token(self.peek().token_type)
+            && match self.peek_next().token_type {
+                TokenType::Colon => true,
+                TokenType::QuestionMark => self.peek_at(2).token_type == TokenType::Colon,
+                _ => false,
+            };
+        if is_named {
+            let name = self.identifier()?;
+            let optional = self.matches(&[TokenType::QuestionMark]);
+            self.consume(TokenType::Colon, "':' expected")?;
+            let element_type = self.type_annotation()?;
+            return Ok(Node::NamedTupleMember {
+                name,
+                element_type: Box::new(element_type),
+                optional,
+                rest,
+            });
+        }
+        let element_type = self.type_annotation()?;
+        if rest {
+            return Ok(Node::RestType(Box::new(element_type)));
+        }
+        if self.matches(&[TokenType::QuestionMark]) {
+            return Ok(Node::OptionalType(Box::new(element_type)));
+        }
+        Ok(element_type)
+    }
+
+    fn type_members(&mut self) -> ParseResult<Vec<Node>> {
+        self.consume(TokenType::LeftBrace, "'{' expected")?;
+        let mut members = Vec::new();
+        while !self.check(TokenType::RightBrace) && !self.is_at_end() {
+            members.push(self.type_member()?);
+            if !self.matches(&[TokenType::Semicolon, TokenType::Comma])
+                && !self.check(TokenType::RightBrace)
+                && !self.has_preceding_line_break()
+            {
+                return Err(self.error_at_current("';' expected"));
+            }
+        }
+        self.consume(TokenType::RightBrace, "'}' expected")?;
+        Ok(members)
+    }
+
+    fn type_member(&mut self) -> ParseResult<Node> {
+        if self.check_any(&[TokenType::LeftParen, TokenType::Less]) {
+            let type_params = self.type_parameters()?;
+            let params = self.parameters()?;
+            let return_type = self.optional_return_type()?;
+            return Ok(Node::CallSignature {
+                type_params,
+                params,
+                return_type,
+            });
+        }
+        if self.check(TokenType::New)
+            && matches!(
+                self.peek_next().token_type,
+                TokenType::LeftParen | TokenType::Less
+            )
+        {
+            self.advance();
+            let type_params = self.type_parameters()?;
+            let params = self.parameters()?;
+            let return_type = self.optional_type_annotation()?;
+            return Ok(Node::ConstructSignature {
+                type_params,
+                params,
+                return_type,
+            });
+        }
+
+        let readonly = self.check(TokenType::Readonly) && self.next_starts_property_name();
+        if readonly {
+            self.advance();
+        }
+
+        let is_index_signature = self.check(TokenType::LeftBracket)
+            && is_identifier_token(self.peek_next().token_type)
+            && self.peek_at(2).token_type == TokenType::Colon;
+        if is_index_signature {
+            self.advance();
+            let key_name = self.identifier()?;
+            self.consume(TokenType::Colon, "':' expected")?;
+            let key_type = self.type_annotation()?;
+            self.consume(TokenType::RightBracket, "']' expected")?;
+            self.consume(TokenType::Colon, "':' expected")?;
+            let value_type = self.type_annotation()?;
+            return Ok(Node::IndexSignature {
+                key_name,
+                key_type: Box::new(key_type),
+                value_type: Box::new(value_type),
+                readonly,
+            });
+        }
+
+        let (key, computed) = self.property_key()?;
+        let optional = self.matches(&[TokenType::QuestionMark]);
+        if self.check_any(&[TokenType::LeftParen, TokenType::Less]) {
+            let type_params = self.type_parameters()?;
+            let params = self.parameters()?;
+            let return_type = self.optional_return_type()?;
+            return Ok(Node::MethodSignature {
+                key: Box::new(key),
+                computed,
+                optional,
+                type_params,
+                params,
+                return_type,
+            });
+        }
+        let value = self.optional_type_annotation()?;
+        Ok(Node::PropertySignature {
+            key: Box::new(key),
+            value,
+            optional,
+            readonly,
+            computed,
+        })
+    }
+
+    /// A parenthesized list is a function type only when the matching `)` is followed by `=>`.
+    fn is_start_of_function_type(&self) -> bool {
+        if self.check(TokenType::Less) {
+            return true;
+        }
+        if !self.check(TokenType::LeftParen) {
+            return false;
+        }
+        let mut depth = 0;
+        for (offset, token) in self.tokens[self.current..].iter().enumerate() {
+            match token.token_type {
+                TokenType::LeftParen => depth += 1,
+                TokenType::RightParen => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return self.peek_at(offset + 1).token_type == TokenType::Arrow;
+                    }
+                }
+                TokenType::EOF => return false,
+                _ => {}
+            }
+        }
+        false
+    }
+
+    // Token helpers
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.current]
+    }
+
+    fn peek_next(&self) -> &Token {
+        self.peek_at(1)
+    }
+
+    fn peek_at(&self, offset: usize) -> &Token {
+        let index = (self.current + offset).min(self.tokens.len() - 1);
+        &self.tokens[index]
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens[self.current].clone();
+        if !self.is_at_end() {
+            self.current += 1;
+        }
+        token
+    }
+
+    fn check(&self, token_type: TokenType) -> bool {
+        self.peek().token_type == token_type
+    }
+
+    fn check_any(&self, token_types: &[TokenType]) -> bool {
+        token_types.contains(&self.peek().token_type)
+    }
+
+    fn matches(&mut self, token_types: &[TokenType]) -> bool {
+        if self.check_any(token_types) {
+            self.advance();
+            return true;
+        }
+        false
+    }
+
+    fn consume(&mut self, token_type: TokenType, message: &str) -> ParseResult<Token> {
+        if self.check(token_type) {
+            return Ok(self.advance());
+        }
+        Err(self.error_at_current(message))
+    }
+
+    fn consume_semicolon(&mut self) -> ParseResult<()> {
+        if self.matches(&[TokenType::Semicolon])
+            || self.check(TokenType::RightBrace)
+            || self.is_at_end()
+            || self.has_preceding_line_break()
+        {
+            return Ok(());
+        }
+        Err(self.error_at_current("';' expected"))
+    }
+
+    fn identifier(&mut self) -> ParseResult<String> {
+        if is_identifier_token(self.peek().token_type) {
+            return Ok(self.advance().value);
+        }
+        Err(self.error_at_current("Identifier expected"))
+    }
+
+    fn identifier_node(&mut self) -> ParseResult<Node> {
+        let span = self.span();
+        Ok(Node::Identifier(self.identifier()?, span))
+    }
+
+    /// An identifier name as a node, for the property after `.` and property keys.
+    fn property_name(&mut self) -> ParseResult<Node> {
+        let span = self.span();
+        Ok(Node::Identifier(self.identifier_name()?, span))
+    }
+
+    /// Any identifier or reserved word, as allowed after `.` and as a property key.
+    fn identifier_name(&mut self) -> ParseResult<String> {
+        let token = self.peek();
+        let is_word = !matches!(
+            token.token_type,
+            TokenType::StringLiteral
+                | TokenType::TemplateLiteral
+                | TokenType::NumberLiteral
+                | TokenType::EOF
+        ) && token
+            .value
+            .starts_with(|c: char| c.is_alphabetic() || c == '_' || c == '$');
+        if is_word {
+            return Ok(self.advance().value);
+        }
+        Err(self.error_at_current("Identifier expected"))
+    }
+
+    fn next_starts_property_name(&self) -> bool {
+        let next = self.peek_next();
+        match next.token_type {
+            TokenType::StringLiteral | TokenType::NumberLiteral | TokenType::LeftBracket => true,
+            TokenType::EOF | TokenType::TemplateLiteral => false,
+            _ => next
+                .value
+                .starts_with(|c: char| c.is_alphabetic() || c == '_' || c == '$'),
+        }
+    }
+
+    fn starts_async_function(&self) -> bool {
+        self.peek_next().token_type == TokenType::Function
+            && self.peek_next().row == self.peek().row
+    }
+
+    /// `await x` outside an async function, as opposed to an identifier named `await`.
+    fn starts_misplaced_await(&self) -> bool {
+        let next = self.peek_next();
+        next.row == self.peek().row
+            && (is_identifier_token(next.token_type)
+                || matches!(
+                    next.token_type,
+                    TokenType::NumberLiteral
+                        | TokenType::StringLiteral
+                        | TokenType::TemplateLiteral
+                        | TokenType::This
+                        | TokenType::New
+                ))
+    }
+
+    fn has_preceding_line_break(&self) -> bool {
+        self.current > 0 && self.tokens[self.current].row > self.tokens[self.current - 1].row
+    }
+
+    fn is_at_end(&self) -> bool {
+        self.peek().token_type == TokenType::EOF
+    }
+
+    fn span(&self) -> Span {
+        let token = self.peek();
+        Span {
+            row: token.row,
+            col: token.col,
+            file: self.file,
+        }
+    }
+
+    fn error_at_current(&self, message: &str) -> CompilerError {
+        CompilerError::new(message.to_string(), self.span())
+    }
+
+    /// Skips ahead to a likely statement boundary after a parse error.
+    fn synchronize(&mut self) {
+        let start = self.current;
+        while !self.is_at_end() {
+            if self.current > start {
+                if self.tokens[self.current - 1].token_type == TokenType::Semicolon {
+                    return;
+                }
+                if matches!(
+                    self.peek().token_type,
+                    TokenType::Function
+                        | TokenType::Class
+                        | TokenType::Interface
+                        | TokenType::Enum
+                        | TokenType::Import
+                        | TokenType::Export
+                        | TokenType::Namespace
+                        | TokenType::Declare
+                        | TokenType::Let
+                        | TokenType::Const
+                        | TokenType::Var
+                        | TokenType::If
+                        | TokenType::For
+                        | TokenType::While
+                        | TokenType::Do
+                        | TokenType::Switch
+                        | TokenType::Case
+                        | TokenType::Default
+                        | TokenType::Return
+                        | TokenType::Try
+                        | TokenType::Throw
+                        | TokenType::RightBrace
+                ) {
+                    return;
+                }
+            }
+            self.advance();
+        }
+    }
+}
+
+/// Identifiers plus the contextual keywords that are only reserved in specific positions.
+fn is_identifier_token(token_type: TokenType) -> bool {
+    matches!(
+        token_type,
+        TokenType::Identifier
+            | TokenType::Type
+            | TokenType::Namespace
+            | TokenType::Module
+            | TokenType::From
+            | TokenType::As
+            | TokenType::Async
+            | TokenType::Await
+            | TokenType::Yield
+            | TokenType::Of
+            | TokenType::Get
+            | TokenType::Set
+            | TokenType::Readonly
+            | TokenType::Public
+            | TokenType::Private
+            | TokenType::Protected
+            | TokenType::Static
+            | TokenType::Declare
+            | TokenType::Require
+            | TokenType::Any
+            | TokenType::Unknown
+            | TokenType::Never
+            | TokenType::NumberType
+            | TokenType::StringType
+            | TokenType::BooleanType
+            | TokenType::Symbol
+            | TokenType::Object
+    )
+}
+
+fn is_start_of_type(token: &Token) -> bool {
+    !matches!(
+        token.token_type,
+        TokenType::Comma
+            | TokenType::Semicolon
+            | TokenType::RightParen
+            | TokenType::RightBracket
+            | TokenType::RightBrace
+            | TokenType::Greater
+            | TokenType::Equal
+            | TokenType::BitwiseOr
+            | TokenType::BitwiseAnd
+            | TokenType::EOF
+    )
+}
+
+/// The tokenizer uses `TokenType::BigInt` for both the `bigint` keyword and `10n` literals.
+fn is_bigint_literal(token: &Token) -> bool {
+    token.value.starts_with(|c: char| c.is_ascii_digit())
+}
+
+const AS_PRECEDENCE: u8 = 7;
+/// `<<`, `>>` and `>>>`, which `binary_precedence` can't see as they span several tokens.
+const SHIFT_PRECEDENCE: u8 = 8;
+
+fn binary_precedence(token_type: TokenType) -> Option<u8> {
+    match token_type {
+        TokenType::PipePipe | TokenType::NullishCoalescing => Some(1),
+        TokenType::AmpersandAmpersand => Some(2),
+        TokenType::BitwiseOr => Some(3),
+        TokenType::BitwiseXor => Some(4),
+        TokenType::BitwiseAnd => Some(5),
+        TokenType::EqualEqual
+        | TokenType::BangEqual
+        | TokenType::TripleEqual
+        | TokenType::NotDoubleEqual => Some(6),
+        TokenType::Less
+        | TokenType::Greater
+        | TokenType::LessEqual
+        | TokenType::GreaterEqual
+        | TokenType::Instanceof
+        | TokenType::In => Some(7),
+        TokenType::Plus | TokenType::Minus => Some(9),
+        TokenType::Star | TokenType::Slash | TokenType::Modulo => Some(10),
+        TokenType::Power => Some(11),
+        _ => None,
     }
 }
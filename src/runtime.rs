@@ -0,0 +1,1581 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::rc::Rc;
+
+pub type ObjectRef = Rc<RefCell<Object>>;
+
+/// The outcome of running code: a value, or `Err` with a thrown value.
+pub type Completion = Result<Value, Value>;
+
+/// A function provided by the runtime, called with `this` and the arguments.
+pub type NativeFunction = Rc<dyn Fn(&Value, &[Value]) -> Completion>;
+
+#[derive(Clone)]
+pub enum Value {
+    Undefined,
+    Null,
+    Boolean(bool),
+    Number(f64),
+    String(Rc<str>),
+    Object(ObjectRef),
+    Function(NativeFunction),
+}
+
+#[derive(Clone)]
+pub enum Property {
+    Data(Value),
+    Accessor {
+        get: Option<NativeFunction>,
+        set: Option<NativeFunction>,
+    },
+}
+
+/// Property storage for objects and arrays.
+///
+/// String keys keep their insertion order. Keys that are array indices are enumerated
+/// first in ascending order, as JavaScript requires; arrays keep them in `elements`, with
+/// `None` for holes.
+#[derive(Default)]
+pub struct Object {
+    pub prototype: Option<ObjectRef>,
+    properties: HashMap<String, Property>,
+    order: Vec<String>,
+    elements: Vec<Option<Value>>,
+    is_array: bool,
+    slot: Slot,
+}
+
+/// State that promises and generators keep out of reach of property access.
+#[derive(Default)]
+enum Slot {
+    #[default]
+    None,
+    Promise(Promise),
+    Generator(Generator),
+}
+
+impl Object {
+    pub fn new(prototype: Option<ObjectRef>) -> Self {
+        Object {
+            prototype,
+            ..Default::default()
+        }
+    }
+
+    pub fn array(elements: Vec<Option<Value>>) -> Self {
+        Object {
+            elements,
+            is_array: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_array(&self) -> bool {
+        self.is_array
+    }
+
+    pub fn length(&self) -> usize {
+        self.elements.len()
+    }
+
+    pub fn get_own(&self, key: &str) -> Option<Property> {
+        if self.is_array {
+            if key == "length" {
+                return Some(Property::Data(Value::Number(self.elements.len() as f64)));
+            }
+            if let Some(index) = array_index(key) {
+                return self
+                    .elements
+                    .get(index as usize)
+                    .cloned()
+                    .flatten()
+                    .map(Property::Data);
+            }
+        }
+        self.properties.get(key).cloned()
+    }
+
+    /// Creates or replaces an own property. Defining a getter keeps an existing setter and
+    /// vice versa, as in `{ get x() {}, set x(v) {} }`.
+    pub fn define(&mut self, key: &str, property: Property) {
+        if self.is_array {
+            if key == "length" {
+                if let Property::Data(Value::Number(length)) = property {
+                    self.elements.resize(length as usize, None);
+                }
+                return;
+            }
+            if let (Some(index), Property::Data(value)) = (array_index(key), &property) {
+                let index = index as usize;
+                if index >= self.elements.len() {
+                    self.elements.resize(index + 1, None);
+                }
+                self.elements[index] = Some(value.clone());
+                return;
+            }
+        }
+        let property = match (self.properties.get(key), property) {
+            (
+                Some(Property::Accessor {
+                    get: old_get,
+                    set: old_set,
+                }),
+                Property::Accessor { get, set },
+            ) => Property::Accessor {
+                get: get.or_else(|| old_get.clone()),
+                set: set.or_else(|| old_set.clone()),
+            },
+            (_, property) => property,
+        };
+        if self.properties.insert(key.to_string(), property).is_none() {
+            self.order.push(key.to_string());
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) -> bool {
+        if self.is_array {
+            if let Some(slot) = array_index(key).and_then(|i| self.elements.get_mut(i as usize)) {
+                return slot.take().is_some();
+            }
+        }
+        if self.properties.remove(key).is_none() {
+            return false;
+        }
+        self.order.retain(|k| k != key);
+        true
+    }
+
+    /// Own keys in enumeration order: array indices ascending, then strings by insertion.
+    pub fn own_keys(&self) -> Vec<String> {
+        let mut indices: Vec<u32> = self
+            .elements
+            .iter()
+            .enumerate()
+            .filter(|(_, element)| element.is_some())
+            .map(|(index, _)| index as u32)
+            .chain(self.order.iter().filter_map(|key| array_index(key)))
+            .collect();
+        indices.sort_unstable();
+        let mut keys: Vec<String> = indices.iter().map(|index| index.to_string()).collect();
+        keys.extend(
+            self.order
+                .iter()
+                .filter(|key| array_index(key).is_none())
+                .cloned(),
+        );
+        keys
+    }
+}
+
+/// `key` as an array index, if it is the canonical spelling of an integer below 2^32 - 1.
+fn array_index(key: &str) -> Option<u32> {
+    let index: u32 = key.parse().ok()?;
+    (index != u32::MAX && index.to_string() == key).then_some(index)
+}
+
+pub fn allocate_object() -> ObjectRef {
+    Rc::new(RefCell::new(Object::new(None)))
+}
+
+pub fn allocate_array(elements: Vec<Option<Value>>) -> ObjectRef {
+    Rc::new(RefCell::new(Object::array(elements)))
+}
+
+/// Reads `object[key]`, following the prototype chain and calling getters with `receiver`.
+pub fn get(object: &ObjectRef, key: &str, receiver: &Value) -> Completion {
+    let mut current = Some(object.clone());
+    while let Some(object) = current {
+        let property = object.borrow().get_own(key);
+        match property {
+            Some(Property::Data(value)) => return Ok(value),
+            Some(Property::Accessor { get: Some(get), .. }) => return get(receiver, &[]),
+            Some(Property::Accessor { get: None, .. }) => return Ok(Value::Undefined),
+            None => current = object.borrow().prototype.clone(),
+        }
+    }
+    Ok(Value::Undefined)
+}
+
+/// Reads `value[key]` for any value, as `value.key` does.
+pub fn get_property(value: &Value, key: &str) -> Completion {
+    match value {
+        Value::Object(object) => get(object, key, value),
+        Value::String(string) if key == "length" => {
+            Ok(Value::Number(string.chars().count() as f64))
+        }
+        Value::String(string) => Ok(array_index(key)
+            .and_then(|index| string.chars().nth(index as usize))
+            .map_or(Value::Undefined, |c| Value::String(c.to_string().into()))),
+        Value::Undefined | Value::Null => Err(type_error(&format!(
+            "Cannot read properties of {} (reading '{}')",
+            value, key
+        ))),
+        _ => Ok(Value::Undefined),
+    }
+}
+
+/// Writes `object[key] = value`, calling an inherited or own setter if there is one.
+pub fn set(object: &ObjectRef, key: &str, value: Value) -> Result<(), Value> {
+    let mut current = Some(object.clone());
+    while let Some(candidate) = current {
+        let property = candidate.borrow().get_own(key);
+        match property {
+            Some(Property::Accessor { set, .. }) => {
+                if let Some(set) = set {
+                    set(&Value::Object(object.clone()), &[value])?;
+                }
+                return Ok(());
+            }
+            Some(Property::Data(_)) => break,
+            None => current = candidate.borrow().prototype.clone(),
+        }
+    }
+    object.borrow_mut().define(key, Property::Data(value));
+    Ok(())
+}
+
+pub fn call(function: &Value, this: &Value, arguments: &[Value]) -> Completion {
+    match function {
+        Value::Function(function) => function(this, arguments),
+        value => Err(type_error(&format!("{} is not a function", value))),
+    }
+}
+
+pub fn call_method(object: &Value, name: &str, arguments: &[Value]) -> Completion {
+    let method = get_property(object, name)?;
+    call(&method, object, arguments)
+}
+
+pub fn native(function: impl Fn(&Value, &[Value]) -> Completion + 'static) -> Value {
+    Value::Function(Rc::new(function))
+}
+
+pub fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Undefined | Value::Null => false,
+        Value::Boolean(value) => *value,
+        Value::Number(number) => *number != 0.0 && !number.is_nan(),
+        Value::String(string) => !string.is_empty(),
+        Value::Object(_) | Value::Function(_) => true,
+    }
+}
+
+/// A `TypeError` as thrown by the runtime itself.
+pub fn type_error(message: &str) -> Value {
+    create_error(ErrorKind::TypeError, message)
+}
+
+fn string(value: &str) -> Value {
+    Value::String(value.into())
+}
+
+/// Copies own properties of `source` into `target`, skipping `excluded` keys. This is
+/// `{ ...source }` in an object literal, and `__rest` for object rest patterns.
+pub fn copy_data_properties(
+    target: &ObjectRef,
+    source: &Value,
+    excluded: &[String],
+) -> Result<(), Value> {
+    // Spreading null, undefined or primitives other than strings copies nothing
+    let source = match source {
+        Value::Object(source) => source,
+        Value::String(string) => {
+            for (index, character) in string.chars().enumerate() {
+                let key = index.to_string();
+                if !excluded.contains(&key) {
+                    let value = Value::String(character.to_string().into());
+                    target.borrow_mut().define(&key, Property::Data(value));
+                }
+            }
+            return Ok(());
+        }
+        _ => return Ok(()),
+    };
+    let keys = source.borrow().own_keys();
+    let receiver = Value::Object(source.clone());
+    for key in keys {
+        if excluded.contains(&key) {
+            continue;
+        }
+        let value = get(source, &key, &receiver)?;
+        target.borrow_mut().define(&key, Property::Data(value));
+    }
+    Ok(())
+}
+
+/// `__rest(source, ...keys)`: a new object with the properties of `source` not listed.
+pub fn rest(source: &Value, excluded: &[Value]) -> Completion {
+    let excluded: Vec<String> = excluded.iter().map(to_property_key).collect();
+    let target = allocate_object();
+    copy_data_properties(&target, source, &excluded)?;
+    Ok(Value::Object(target))
+}
+
+/// The elements `...value` contributes to an array literal or argument list. Holes in
+/// arrays become `undefined`.
+pub fn spread_elements(value: &Value) -> Result<Vec<Value>, Value> {
+    match value {
+        Value::Object(object) if object.borrow().is_array() => {
+            let length = object.borrow().length();
+            (0..length)
+                .map(|index| get(object, &index.to_string(), value))
+                .collect()
+        }
+        Value::String(string) => Ok(string
+            .chars()
+            .map(|character| Value::String(character.to_string().into()))
+            .collect()),
+        value => {
+            let iterator = get_iterator(value)?;
+            let mut elements = Vec::new();
+            loop {
+                let result = call_method(&iterator, "next", &[])?;
+                if is_truthy(&get_property(&result, "done")?) {
+                    return Ok(elements);
+                }
+                elements.push(get_property(&result, "value")?);
+            }
+        }
+    }
+}
+
+/// Converts a computed key such as `[1 + 1]` to the string it names.
+pub fn to_property_key(value: &Value) -> String {
+    match value {
+        Value::String(string) => string.to_string(),
+        value => value.to_string(),
+    }
+}
+
+// Iteration
+
+/// `{ value, done }`, the result of an iterator's `next`.
+pub fn iterator_result(value: Value, done: bool) -> Value {
+    let result = allocate_object();
+    result.borrow_mut().define("value", Property::Data(value));
+    result
+        .borrow_mut()
+        .define("done", Property::Data(Value::Boolean(done)));
+    Value::Object(result)
+}
+
+/// `__iterator(value)`: the iterator a `for...of` loop or `yield*` walks. Arrays and
+/// strings get a fresh iterator; generators and other objects with `next` are their own.
+pub fn get_iterator(value: &Value) -> Completion {
+    let elements = match value {
+        Value::Object(object) if object.borrow().is_array() => value.clone(),
+        Value::String(_) => Value::Object(allocate_array(
+            spread_elements(value)?.into_iter().map(Some).collect(),
+        )),
+        Value::Object(_) if matches!(get_property(value, "next")?, Value::Function(_)) => {
+            return Ok(value.clone());
+        }
+        value => return Err(type_error(&format!("{} is not iterable", value))),
+    };
+    let index = Cell::new(0);
+    let next = native(move |_, _| {
+        let Value::Object(array) = &elements else {
+            return Ok(iterator_result(Value::Undefined, true));
+        };
+        let length = array.borrow().length();
+        if index.get() >= length {
+            return Ok(iterator_result(Value::Undefined, true));
+        }
+        let value = get(array, &index.get().to_string(), &elements)?;
+        index.set(index.get() + 1);
+        Ok(iterator_result(value, false))
+    });
+    let iterator = allocate_object();
+    iterator.borrow_mut().define("next", Property::Data(next));
+    Ok(Value::Object(iterator))
+}
+
+/// `__keys(value)`: the keys a `for...in` loop visits, own keys first and then inherited
+/// ones that are not shadowed.
+pub fn for_in_keys(value: &Value) -> Value {
+    let mut keys: Vec<String> = Vec::new();
+    let mut current = match value {
+        Value::Object(object) => Some(object.clone()),
+        Value::String(string) => {
+            keys.extend((0..string.chars().count()).map(|index| index.to_string()));
+            None
+        }
+        _ => None,
+    };
+    while let Some(object) = current {
+        for key in object.borrow().own_keys() {
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+        current = object.borrow().prototype.clone();
+    }
+    let keys = keys.iter().map(|key| Some(string(key))).collect();
+    Value::Object(allocate_array(keys))
+}
+
+// Errors
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ErrorKind {
+    Error,
+    TypeError,
+    RangeError,
+    ReferenceError,
+    SyntaxError,
+}
+
+impl ErrorKind {
+    const ALL: [ErrorKind; 5] = [
+        ErrorKind::Error,
+        ErrorKind::TypeError,
+        ErrorKind::RangeError,
+        ErrorKind::ReferenceError,
+        ErrorKind::SyntaxError,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ErrorKind::Error => "Error",
+            ErrorKind::TypeError => "TypeError",
+            ErrorKind::RangeError => "RangeError",
+            ErrorKind::ReferenceError => "ReferenceError",
+            ErrorKind::SyntaxError => "SyntaxError",
+        }
+    }
+}
+
+/// A call in progress. `row` and `col` are the TS source location it is executing, which
+/// compiled code keeps current with `set_location` before anything that can throw.
+struct Frame {
+    function: Rc<str>,
+    file: Rc<str>,
+    row: usize,
+    col: usize,
+}
+
+thread_local! {
+    static CALL_STACK: RefCell<Vec<Frame>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Pushes a frame when a compiled function is entered, at the location of its body.
+pub fn enter_frame(function: &str, file: &str, row: usize, col: usize) {
+    CALL_STACK.with(|stack| {
+        stack.borrow_mut().push(Frame {
+            function: function.into(),
+            file: file.into(),
+            row,
+            col,
+        })
+    });
+}
+
+/// Pops the frame of a function that returns or unwinds.
+pub fn leave_frame() {
+    CALL_STACK.with(|stack| stack.borrow_mut().pop());
+}
+
+pub fn set_location(row: usize, col: usize) {
+    CALL_STACK.with(|stack| {
+        if let Some(frame) = stack.borrow_mut().last_mut() {
+            frame.row = row;
+            frame.col = col;
+        }
+    });
+}
+
+/// The frames on the call stack, innermost first, as `    at f (main.ts:3:5)` lines.
+fn stack_trace() -> String {
+    CALL_STACK.with(|stack| {
+        stack
+            .borrow()
+            .iter()
+            .rev()
+            .map(|frame| match frame.function.as_ref() {
+                "" => format!("    at {}:{}:{}", frame.file, frame.row, frame.col),
+                function => format!(
+                    "    at {} ({}:{}:{})",
+                    function, frame.file, frame.row, frame.col
+                ),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    })
+}
+
+/// `new TypeError(message)` and friends: an error with `message` and a `stack` captured
+/// where it is created.
+pub fn create_error(kind: ErrorKind, message: &str) -> Value {
+    let mut error = Object::new(Some(error_prototype(kind)));
+    if !message.is_empty() {
+        error.define("message", Property::Data(string(message)));
+    }
+    let header = match message {
+        "" => kind.name().to_string(),
+        message => format!("{}: {}", kind.name(), message),
+    };
+    let stack = match stack_trace() {
+        trace if trace.is_empty() => header,
+        trace => format!("{}\n{}", header, trace),
+    };
+    error.define("stack", Property::Data(string(&stack)));
+    Value::Object(Rc::new(RefCell::new(error)))
+}
+
+pub fn error_prototype(kind: ErrorKind) -> ObjectRef {
+    PROTOTYPES.with(|prototypes| prototypes.errors[kind as usize].clone())
+}
+
+/// The `Error`, `TypeError`, ... constructors, which behave the same with or without `new`.
+pub fn error_constructor(kind: ErrorKind) -> Value {
+    native(move |_, arguments| {
+        let message = match argument(arguments, 0) {
+            Value::Undefined => String::new(),
+            message => message.to_string(),
+        };
+        Ok(create_error(kind, &message))
+    })
+}
+
+/// Whether `prototype` is on the prototype chain of `value`, as in `value instanceof C`.
+pub fn has_prototype(value: &Value, prototype: &ObjectRef) -> bool {
+    let Value::Object(object) = value else {
+        return false;
+    };
+    let mut current = object.borrow().prototype.clone();
+    while let Some(object) = current {
+        if Rc::ptr_eq(&object, prototype) {
+            return true;
+        }
+        current = object.borrow().prototype.clone();
+    }
+    false
+}
+
+/// `__tdz(name)`: throws for a `let`, `const` or class used before its declaration ran.
+pub fn tdz(name: &str) -> Completion {
+    Err(create_error(
+        ErrorKind::ReferenceError,
+        &format!("Cannot access '{}' before initialization", name),
+    ))
+}
+
+/// What a program prints for an exception nothing caught: the stack of an error, or the
+/// thrown value itself.
+pub fn uncaught_report(value: &Value) -> String {
+    let is_error = has_prototype(value, &error_prototype(ErrorKind::Error));
+    match get_property(value, "stack") {
+        Ok(Value::String(stack)) if is_error => format!("Uncaught {}", stack),
+        _ => format!("Uncaught {:?}", value),
+    }
+}
+
+fn error_to_string(this: &Value) -> Completion {
+    let name = match get_property(this, "name")? {
+        Value::Undefined => "Error".to_string(),
+        name => name.to_string(),
+    };
+    let message = match get_property(this, "message")? {
+        Value::Undefined => String::new(),
+        message => message.to_string(),
+    };
+    Ok(string(&match (name.as_str(), message.as_str()) {
+        (name, "") => name.to_string(),
+        ("", message) => message.to_string(),
+        (name, message) => format!("{}: {}", name, message),
+    }))
+}
+
+// Promises and the event loop
+
+#[derive(Clone)]
+enum PromiseState {
+    Pending,
+    Fulfilled(Value),
+    Rejected(Value),
+}
+
+/// Called once with the outcome of a settled promise, from a microtask.
+type Reaction = Box<dyn FnOnce(Completion)>;
+
+struct Promise {
+    state: PromiseState,
+    reactions: Vec<Reaction>,
+}
+
+thread_local! {
+    static MICROTASKS: RefCell<VecDeque<Box<dyn FnOnce()>>> = RefCell::new(VecDeque::new());
+    // Promises rejected while nothing was waiting on them
+    static UNHANDLED_REJECTIONS: RefCell<Vec<ObjectRef>> = const { RefCell::new(Vec::new()) };
+    static PROTOTYPES: Prototypes = Prototypes::new();
+}
+
+pub fn enqueue_microtask(task: impl FnOnce() + 'static) {
+    MICROTASKS.with(|queue| queue.borrow_mut().push_back(Box::new(task)));
+}
+
+/// Runs microtasks until none are left, which a program does after its top-level code and
+/// before the process exits. Returns the reasons of rejections that were never handled.
+pub fn run_event_loop() -> Vec<Value> {
+    while let Some(task) = MICROTASKS.with(|queue| queue.borrow_mut().pop_front()) {
+        task();
+    }
+    let unhandled =
+        UNHANDLED_REJECTIONS.with(|promises| std::mem::take(&mut *promises.borrow_mut()));
+    unhandled
+        .iter()
+        .filter_map(|promise| match promise_state(promise) {
+            Some(PromiseState::Rejected(reason)) => Some(reason),
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn new_promise() -> ObjectRef {
+    let prototype = PROTOTYPES.with(|prototypes| prototypes.promise.clone());
+    let mut object = Object::new(Some(prototype));
+    object.slot = Slot::Promise(Promise {
+        state: PromiseState::Pending,
+        reactions: Vec::new(),
+    });
+    Rc::new(RefCell::new(object))
+}
+
+pub fn is_promise(value: &Value) -> bool {
+    matches!(value, Value::Object(object) if matches!(object.borrow().slot, Slot::Promise(_)))
+}
+
+fn promise_state(promise: &ObjectRef) -> Option<PromiseState> {
+    match &promise.borrow().slot {
+        Slot::Promise(promise) => Some(promise.state.clone()),
+        _ => None,
+    }
+}
+
+/// Resolves `promise` with `value`, adopting the state of `value` if it is a thenable.
+pub fn resolve_promise(promise: &ObjectRef, value: Value) {
+    let Value::Object(object) = &value else {
+        return settle(promise, Ok(value));
+    };
+    if Rc::ptr_eq(object, promise) {
+        return settle(
+            promise,
+            Err(type_error("Chaining cycle detected for promise")),
+        );
+    }
+    let then = match get(object, "then", &value) {
+        Ok(then @ Value::Function(_)) => then,
+        Ok(_) => return settle(promise, Ok(value)),
+        Err(error) => return settle(promise, Err(error)),
+    };
+    let promise = promise.clone();
+    enqueue_microtask(move || {
+        let (resolve, reject) = resolving_functions(&promise);
+        if let Err(error) = call(&then, &value, &[resolve, reject.clone()]) {
+            let _ = call(&reject, &Value::Undefined, &[error]);
+        }
+    });
+}
+
+pub fn reject_promise(promise: &ObjectRef, reason: Value) {
+    settle(promise, Err(reason));
+}
+
+/// The `resolve` and `reject` functions handed to a thenable; only the first call counts.
+fn resolving_functions(promise: &ObjectRef) -> (Value, Value) {
+    let done = Rc::new(Cell::new(false));
+    let (resolved, rejected) = (promise.clone(), promise.clone());
+    let resolve_done = done.clone();
+    let resolve = native(move |_, arguments| {
+        if !resolve_done.replace(true) {
+            resolve_promise(&resolved, argument(arguments, 0));
+        }
+        Ok(Value::Undefined)
+    });
+    let reject = native(move |_, arguments| {
+        if !done.replace(true) {
+            reject_promise(&rejected, argument(arguments, 0));
+        }
+        Ok(Value::Undefined)
+    });
+    (resolve, reject)
+}
+
+fn settle(promise: &ObjectRef, outcome: Completion) {
+    let reactions = {
+        let mut object = promise.borrow_mut();
+        let Slot::Promise(state) = &mut object.slot else {
+            return;
+        };
+        if !matches!(state.state, PromiseState::Pending) {
+            return;
+        }
+        state.state = match &outcome {
+            Ok(value) => PromiseState::Fulfilled(value.clone()),
+            Err(reason) => PromiseState::Rejected(reason.clone()),
+        };
+        std::mem::take(&mut state.reactions)
+    };
+    if outcome.is_err() && reactions.is_empty() {
+        UNHANDLED_REJECTIONS.with(|promises| promises.borrow_mut().push(promise.clone()));
+    }
+    for reaction in reactions {
+        let outcome = outcome.clone();
+        enqueue_microtask(move || reaction(outcome));
+    }
+}
+
+/// Runs `reaction` in a microtask once `promise` settles, which also marks a rejection as
+/// handled.
+pub fn on_settled(promise: &ObjectRef, reaction: impl FnOnce(Completion) + 'static) {
+    let outcome = {
+        let mut object = promise.borrow_mut();
+        let Slot::Promise(state) = &mut object.slot else {
+            return;
+        };
+        match &state.state {
+            PromiseState::Pending => {
+                state.reactions.push(Box::new(reaction));
+                return;
+            }
+            PromiseState::Fulfilled(value) => Ok(value.clone()),
+            PromiseState::Rejected(reason) => Err(reason.clone()),
+        }
+    };
+    if outcome.is_err() {
+        UNHANDLED_REJECTIONS.with(|promises| {
+            promises
+                .borrow_mut()
+                .retain(|rejected| !Rc::ptr_eq(rejected, promise))
+        });
+    }
+    enqueue_microtask(move || reaction(outcome));
+}
+
+/// `Promise.resolve(value)`: `value` itself if it is a promise.
+pub fn promise_resolve(value: Value) -> ObjectRef {
+    if let Value::Object(object) = &value {
+        if is_promise(&value) {
+            return object.clone();
+        }
+    }
+    let promise = new_promise();
+    resolve_promise(&promise, value);
+    promise
+}
+
+/// `promise.then(on_fulfilled, on_rejected)`. Handlers that are not functions pass the
+/// outcome through to the returned promise.
+pub fn promise_then(promise: &ObjectRef, on_fulfilled: Value, on_rejected: Value) -> ObjectRef {
+    let derived = new_promise();
+    let result = derived.clone();
+    on_settled(promise, move |outcome| {
+        let handled = match (outcome, &on_fulfilled, &on_rejected) {
+            (Ok(value), Value::Function(handler), _)
+            | (Err(value), _, Value::Function(handler)) => handler(&Value::Undefined, &[value]),
+            (outcome, _, _) => outcome,
+        };
+        match handled {
+            Ok(value) => resolve_promise(&derived, value),
+            Err(reason) => reject_promise(&derived, reason),
+        }
+    });
+    result
+}
+
+fn argument(arguments: &[Value], index: usize) -> Value {
+    arguments.get(index).cloned().unwrap_or(Value::Undefined)
+}
+
+fn this_promise(this: &Value) -> Result<ObjectRef, Value> {
+    match this {
+        Value::Object(object) if is_promise(this) => Ok(object.clone()),
+        _ => Err(type_error("Receiver is not a promise")),
+    }
+}
+
+/// Prototypes of the objects the runtime creates itself.
+struct Prototypes {
+    promise: ObjectRef,
+    generator: ObjectRef,
+    async_generator: ObjectRef,
+    errors: Vec<ObjectRef>, // indexed by `ErrorKind`
+}
+
+impl Prototypes {
+    fn new() -> Self {
+        let promise = allocate_object();
+        let methods: [(&str, NativeFunction); 3] = [
+            (
+                "then",
+                Rc::new(|this, arguments| {
+                    let promise = this_promise(this)?;
+                    let derived =
+                        promise_then(&promise, argument(arguments, 0), argument(arguments, 1));
+                    Ok(Value::Object(derived))
+                }),
+            ),
+            (
+                "catch",
+                Rc::new(|this, arguments| {
+                    let promise = this_promise(this)?;
+                    let derived = promise_then(&promise, Value::Undefined, argument(arguments, 0));
+                    Ok(Value::Object(derived))
+                }),
+            ),
+            (
+                "finally",
+                Rc::new(|this, arguments| {
+                    let promise = this_promise(this)?;
+                    let on_finally = argument(arguments, 0);
+                    let derived = new_promise();
+                    let result = derived.clone();
+                    // The callback's own result only matters if it throws
+                    on_settled(&promise, move |outcome| {
+                        match call(&on_finally, &Value::Undefined, &[]).and(outcome) {
+                            Ok(value) => resolve_promise(&derived, value),
+                            Err(reason) => reject_promise(&derived, reason),
+                        }
+                    });
+                    Ok(Value::Object(result))
+                }),
+            ),
+        ];
+        for (name, method) in methods {
+            promise
+                .borrow_mut()
+                .define(name, Property::Data(Value::Function(method)));
+        }
+
+        let generator = allocate_object();
+        let async_generator = allocate_object();
+        for (name, resume) in [
+            ("next", Resume::Next as fn(Value) -> Resume),
+            ("return", Resume::Return),
+            ("throw", Resume::Throw),
+        ] {
+            let method = native(move |this, arguments| {
+                generator_resume(this, resume(argument(arguments, 0)))
+            });
+            generator.borrow_mut().define(name, Property::Data(method));
+            let method = native(move |this, arguments| {
+                Ok(async_generator_enqueue(
+                    this,
+                    resume(argument(arguments, 0)),
+                ))
+            });
+            async_generator
+                .borrow_mut()
+                .define(name, Property::Data(method));
+        }
+        // `Error.prototype` carries `toString` and an empty `message`; the other kinds
+        // inherit from it and only set `name`
+        let base = allocate_object();
+        base.borrow_mut()
+            .define("message", Property::Data(string("")));
+        base.borrow_mut().define(
+            "toString",
+            Property::Data(native(|this, _| error_to_string(this))),
+        );
+        let errors = ErrorKind::ALL
+            .iter()
+            .map(|kind| {
+                let prototype = match kind {
+                    ErrorKind::Error => base.clone(),
+                    _ => Rc::new(RefCell::new(Object::new(Some(base.clone())))),
+                };
+                prototype
+                    .borrow_mut()
+                    .define("name", Property::Data(string(kind.name())));
+                prototype
+            })
+            .collect();
+        Prototypes {
+            promise,
+            generator,
+            async_generator,
+            errors,
+        }
+    }
+}
+
+// Coroutines
+
+/// How a suspended coroutine is resumed: `next(value)`, `throw(error)` or `return(value)`.
+/// Awaits resume with `Next` or `Throw`.
+#[derive(Clone)]
+pub enum Resume {
+    Next(Value),
+    Throw(Value),
+    Return(Value),
+}
+
+/// Where a coroutine stopped.
+pub enum Step {
+    Yield(Value),
+    Delegate(Value), // yield*, with the iterable
+    Await(Value),
+    Return(Value),
+}
+
+/// A compiled state machine: each call runs it from its current state to the next
+/// suspension, and `Err` is an exception it did not catch.
+pub type Coroutine = Box<dyn FnMut(Resume) -> Result<Step, Value>>;
+
+#[derive(Clone, Copy, PartialEq)]
+enum GeneratorState {
+    SuspendedStart,
+    SuspendedYield,
+    Running,
+    Completed,
+}
+
+struct Generator {
+    body: Option<Coroutine>, // taken out while it runs
+    state: GeneratorState,
+    delegate: Option<Value>, // the iterator of an active yield*
+    requests: VecDeque<(Resume, ObjectRef)>, // async generators: calls waiting their turn
+}
+
+fn new_generator(body: Coroutine, prototype: ObjectRef) -> Value {
+    let mut object = Object::new(Some(prototype));
+    object.slot = Slot::Generator(Generator {
+        body: Some(body),
+        state: GeneratorState::SuspendedStart,
+        delegate: None,
+        requests: VecDeque::new(),
+    });
+    Value::Object(Rc::new(RefCell::new(object)))
+}
+
+/// `__generator`: the generator object a generator function returns.
+pub fn create_generator(body: Coroutine) -> Value {
+    let prototype = PROTOTYPES.with(|prototypes| prototypes.generator.clone());
+    new_generator(body, prototype)
+}
+
+/// `__asyncGenerator`: the object an async generator function returns.
+pub fn create_async_generator(body: Coroutine) -> Value {
+    let prototype = PROTOTYPES.with(|prototypes| prototypes.async_generator.clone());
+    new_generator(body, prototype)
+}
+
+fn with_generator<T>(this: &Value, f: impl FnOnce(&mut Generator) -> T) -> Result<T, Value> {
+    if let Value::Object(object) = this {
+        if let Slot::Generator(generator) = &mut object.borrow_mut().slot {
+            return Ok(f(generator));
+        }
+    }
+    Err(type_error("Receiver is not a generator"))
+}
+
+/// `generator.next()`, `.throw()` and `.return()`.
+pub fn generator_resume(this: &Value, mut resume: Resume) -> Completion {
+    loop {
+        let (state, delegate) = with_generator(this, |generator| {
+            (generator.state, generator.delegate.take())
+        })?;
+        match (state, &resume) {
+            (GeneratorState::Running, _) => {
+                return Err(type_error("Generator is already running"));
+            }
+            (GeneratorState::SuspendedStart, Resume::Return(_) | Resume::Throw(_)) => {
+                with_generator(this, |generator| {
+                    generator.state = GeneratorState::Completed
+                })?;
+                continue;
+            }
+            (GeneratorState::Completed, Resume::Next(_)) => {
+                return Ok(iterator_result(Value::Undefined, true));
+            }
+            (GeneratorState::Completed, Resume::Return(value)) => {
+                return Ok(iterator_result(value.clone(), true));
+            }
+            (GeneratorState::Completed, Resume::Throw(error)) => return Err(error.clone()),
+            _ => {}
+        }
+        // Forward to the iterator of a yield* until it is done
+        if let Some(iterator) = delegate {
+            let (method, value) = match &resume {
+                Resume::Next(value) => ("next", value),
+                Resume::Throw(value) => ("throw", value),
+                Resume::Return(value) => ("return", value),
+            };
+            let result = match get_property(&iterator, method)? {
+                Value::Undefined if method == "return" => iterator_result(value.clone(), true),
+                Value::Undefined => {
+                    return Err(type_error("The iterator does not provide a 'throw' method"));
+                }
+                function => call(&function, &iterator, std::slice::from_ref(value))?,
+            };
+            if !is_truthy(&get_property(&result, "done")?) {
+                with_generator(this, |generator| generator.delegate = Some(iterator))?;
+                return Ok(result);
+            }
+            let value = get_property(&result, "value")?;
+            resume = match resume {
+                Resume::Return(_) => Resume::Return(value),
+                _ => Resume::Next(value),
+            };
+        }
+        let body = with_generator(this, |generator| {
+            generator.state = GeneratorState::Running;
+            generator.body.take()
+        })?;
+        let Some(mut body) = body else {
+            return Err(type_error("Generator is already running"));
+        };
+        let step = body(resume);
+        let (state, result) = match step {
+            Ok(Step::Yield(value)) => (
+                GeneratorState::SuspendedYield,
+                Ok(iterator_result(value, false)),
+            ),
+            Ok(Step::Delegate(iterable)) => {
+                let iterator = get_iterator(&iterable);
+                with_generator(this, |generator| {
+                    generator.body = Some(body);
+                    generator.state = GeneratorState::SuspendedYield;
+                    generator.delegate = iterator.as_ref().ok().cloned();
+                })?;
+                resume = match iterator {
+                    Ok(_) => Resume::Next(Value::Undefined),
+                    Err(error) => Resume::Throw(error),
+                };
+                continue;
+            }
+            Ok(Step::Return(value)) => {
+                (GeneratorState::Completed, Ok(iterator_result(value, true)))
+            }
+            Ok(Step::Await(_)) => (
+                GeneratorState::Completed,
+                Err(type_error("'await' in a generator that is not async")),
+            ),
+            Err(error) => (GeneratorState::Completed, Err(error)),
+        };
+        with_generator(this, |generator| {
+            generator.body = Some(body);
+            generator.state = state;
+        })?;
+        return result;
+    }
+}
+
+/// `__async`: runs an async function's machine up to its first `await` and returns the
+/// promise for its result.
+pub fn run_async(body: Coroutine) -> Value {
+    let promise = new_promise();
+    async_step(
+        Rc::new(RefCell::new(body)),
+        promise.clone(),
+        Resume::Next(Value::Undefined),
+    );
+    Value::Object(promise)
+}
+
+fn async_step(body: Rc<RefCell<Coroutine>>, promise: ObjectRef, resume: Resume) {
+    let step = (body.borrow_mut())(resume);
+    match step {
+        Ok(Step::Await(value)) => {
+            await_value(value, move |resume| async_step(body, promise, resume))
+        }
+        Ok(Step::Return(value)) => resolve_promise(&promise, value),
+        Ok(Step::Yield(_) | Step::Delegate(_)) => reject_promise(
+            &promise,
+            type_error("'yield' in an async function that is not a generator"),
+        ),
+        Err(error) => reject_promise(&promise, error),
+    }
+}
+
+/// Resumes a coroutine with the outcome of `value` once it settles.
+fn await_value(value: Value, resume: impl FnOnce(Resume) + 'static) {
+    on_settled(&promise_resolve(value), move |outcome| {
+        resume(match outcome {
+            Ok(value) => Resume::Next(value),
+            Err(error) => Resume::Throw(error),
+        })
+    });
+}
+
+/// `next`, `throw` and `return` of an async generator queue up and are answered in order.
+fn async_generator_enqueue(this: &Value, resume: Resume) -> Value {
+    let promise = new_promise();
+    match with_generator(this, |generator| {
+        generator.requests.push_back((resume, promise.clone()));
+        generator.state != GeneratorState::Running
+    }) {
+        Ok(true) => async_generator_drain(this),
+        Ok(false) => {}
+        Err(error) => reject_promise(&promise, error),
+    }
+    Value::Object(promise)
+}
+
+/// Answers queued requests until one has to wait for an `await`.
+fn async_generator_drain(this: &Value) {
+    loop {
+        let Ok(Some((state, resume))) = with_generator(this, |generator| {
+            let (resume, _) = generator.requests.front()?;
+            Some((generator.state, resume.clone()))
+        }) else {
+            return;
+        };
+        match (state, resume) {
+            (GeneratorState::SuspendedStart, Resume::Return(_) | Resume::Throw(_)) => {
+                let _ = with_generator(this, |generator| {
+                    generator.state = GeneratorState::Completed
+                });
+            }
+            (GeneratorState::Completed, resume) => {
+                let outcome = match resume {
+                    Resume::Next(_) => Ok(iterator_result(Value::Undefined, true)),
+                    Resume::Return(value) => Ok(iterator_result(value, true)),
+                    Resume::Throw(error) => Err(error),
+                };
+                answer_request(this, outcome);
+            }
+            (_, resume) => {
+                let _ = with_generator(this, |generator| generator.state = GeneratorState::Running);
+                async_generator_step(this.clone(), resume);
+                return;
+            }
+        }
+    }
+}
+
+fn async_generator_step(this: Value, resume: Resume) {
+    if let Ok(Some(iterator)) = with_generator(&this, |generator| generator.delegate.take()) {
+        return async_generator_forward(this, iterator, resume);
+    }
+    let Ok(Some(mut body)) = with_generator(&this, |generator| generator.body.take()) else {
+        return;
+    };
+    let step = body(resume);
+    let _ = with_generator(&this, |generator| generator.body = Some(body));
+    let (state, outcome) = match step {
+        Ok(Step::Await(value)) => {
+            return await_value(value, move |resume| async_generator_step(this, resume));
+        }
+        Ok(Step::Yield(value)) => (
+            GeneratorState::SuspendedYield,
+            Ok(iterator_result(value, false)),
+        ),
+        Ok(Step::Return(value)) => (GeneratorState::Completed, Ok(iterator_result(value, true))),
+        Ok(Step::Delegate(iterable)) => {
+            return match get_iterator(&iterable) {
+                Ok(iterator) => {
+                    async_generator_forward(this, iterator, Resume::Next(Value::Undefined))
+                }
+                Err(error) => async_generator_step(this, Resume::Throw(error)),
+            };
+        }
+        Err(error) => (GeneratorState::Completed, Err(error)),
+    };
+    let _ = with_generator(&this, |generator| generator.state = state);
+    answer_request(&this, outcome);
+    async_generator_drain(&this);
+}
+
+/// Passes `resume` on to the iterator of a `yield*` and, once its result settles, answers
+/// the request with it, or resumes the body with its value when it is done.
+///
+/// The iterator is the iterable itself when it has a `next` method, as async generators
+/// do. Arrays and strings are walked synchronously, and their elements are not awaited.
+fn async_generator_forward(this: Value, iterator: Value, resume: Resume) {
+    let (method, value) = match &resume {
+        Resume::Next(value) => ("next", value),
+        Resume::Throw(value) => ("throw", value),
+        Resume::Return(value) => ("return", value),
+    };
+    let result = match get_property(&iterator, method) {
+        Ok(Value::Undefined) if method == "return" => Ok(iterator_result(value.clone(), true)),
+        Ok(Value::Undefined) => Err(type_error("The iterator does not provide a 'throw' method")),
+        Ok(function) => call(&function, &iterator, std::slice::from_ref(value)),
+        Err(error) => Err(error),
+    };
+    let result = match result {
+        Ok(result) => result,
+        Err(error) => return async_generator_step(this, Resume::Throw(error)),
+    };
+    await_value(result, move |settled| {
+        let result = match settled {
+            Resume::Next(result) => result,
+            resume => return async_generator_step(this, resume),
+        };
+        let done = get_property(&result, "done").map(|done| is_truthy(&done));
+        let value = get_property(&result, "value");
+        match (done, value) {
+            (Ok(false), Ok(value)) => {
+                let _ = with_generator(&this, |generator| {
+                    generator.delegate = Some(iterator);
+                    generator.state = GeneratorState::SuspendedYield;
+                });
+                answer_request(&this, Ok(iterator_result(value, false)));
+                async_generator_drain(&this);
+            }
+            (Ok(true), Ok(value)) => {
+                let resume = match resume {
+                    Resume::Return(_) => Resume::Return(value),
+                    _ => Resume::Next(value),
+                };
+                async_generator_step(this, resume);
+            }
+            (Err(error), _) | (_, Err(error)) => async_generator_step(this, Resume::Throw(error)),
+        }
+    });
+}
+
+fn answer_request(this: &Value, outcome: Completion) {
+    let Ok(Some((_, promise))) = with_generator(this, |generator| generator.requests.pop_front())
+    else {
+        return;
+    };
+    match outcome {
+        Ok(value) => resolve_promise(&promise, value),
+        Err(reason) => reject_promise(&promise, reason),
+    }
+}
+
+// Decorators
+
+/// `__esDecorate(ctor, descriptor, decorators, context, initializers, extraInitializers)`:
+/// applies the decorators of one class element, or of the class itself, last to first.
+///
+/// Methods and accessors are read from `ctor`, or its prototype unless `context.static`,
+/// and written back there once decorated. A class passes `{ value: ctor }` as
+/// `descriptor` and reads the decorated class back from it. Initializers that field
+/// decorators return go to the front of `initializers`, for `__runInitializers` to call
+/// when the field is defined, and `context.addInitializer` adds to `extra_initializers`.
+pub fn es_decorate(
+    constructor: &Value,
+    descriptor: &Value,
+    decorators: &Value,
+    context: &Value,
+    initializers: &Value,
+    extra_initializers: &Value,
+) -> Completion {
+    let kind = get_property(context, "kind")?.to_string();
+    let name = to_property_key(&get_property(context, "name")?);
+    let target = match (descriptor, constructor) {
+        (Value::Object(_), _) | (_, Value::Undefined | Value::Null) => Value::Undefined,
+        _ if is_truthy(&get_property(context, "static")?) => constructor.clone(),
+        _ => get_property(constructor, "prototype")?,
+    };
+    let descriptor = match (descriptor, &target) {
+        (Value::Object(descriptor), _) => descriptor.clone(),
+        (_, Value::Object(target)) => descriptor_object(target.borrow().get_own(&name)),
+        _ => allocate_object(),
+    };
+    // The part of the descriptor that decorators see and replace
+    let key = match kind.as_str() {
+        "getter" => "get",
+        "setter" => "set",
+        _ => "value",
+    };
+    let done = Rc::new(Cell::new(false));
+    for decorator in spread_elements(decorators)?.iter().rev() {
+        let element_context = allocate_object();
+        copy_data_properties(&element_context, context, &[])?;
+        element_context
+            .borrow_mut()
+            .define("access", Property::Data(access_object(&kind, &name)));
+        let extra_initializers = extra_initializers.clone();
+        let finished = done.clone();
+        let add_initializer = native(move |_, arguments| {
+            if finished.get() {
+                return Err(type_error(
+                    "Cannot add initializers after decoration has completed",
+                ));
+            }
+            let initializer = expect_function(argument(arguments, 0))?;
+            array_push(&extra_initializers, initializer);
+            Ok(Value::Undefined)
+        });
+        element_context
+            .borrow_mut()
+            .define("addInitializer", Property::Data(add_initializer));
+        let value = get(&descriptor, key, &Value::Object(descriptor.clone()))?;
+        let result = call(
+            decorator,
+            &Value::Undefined,
+            &[value, Value::Object(element_context)],
+        )?;
+        if let Value::Undefined = result {
+            continue;
+        }
+        let result = expect_function(result)?;
+        if kind == "field" {
+            if let Value::Object(initializers) = initializers {
+                initializers.borrow_mut().elements.insert(0, Some(result));
+            }
+        } else {
+            descriptor.borrow_mut().define(key, Property::Data(result));
+        }
+    }
+    if let Value::Object(target) = &target {
+        let property = descriptor_property(&descriptor);
+        target.borrow_mut().define(&name, property);
+    }
+    done.set(true);
+    Ok(Value::Undefined)
+}
+
+/// `__runInitializers(this, initializers, value)`: calls each initializer with `this`. With
+/// a `value`, as for a field, each one gets the previous result and the last is returned.
+pub fn run_initializers(this: &Value, initializers: &Value, value: Option<Value>) -> Completion {
+    let Some(mut value) = value else {
+        for initializer in spread_elements(initializers)? {
+            call(&initializer, this, &[])?;
+        }
+        return Ok(Value::Undefined);
+    };
+    for initializer in spread_elements(initializers)? {
+        value = call(&initializer, this, &[value])?;
+    }
+    Ok(value)
+}
+
+/// `__decorate(decorators, target, key, descriptor)` for `experimentalDecorators`: applies
+/// decorators last to first, each returning a replacement or nothing. Without a `member`
+/// they decorate the class `target`. A member's decorators get `target`, the key and its
+/// descriptor, which is looked up when `null` and written back if they produce one.
+pub fn decorate(
+    decorators: &Value,
+    target: &Value,
+    member: Option<(&Value, &Value)>,
+) -> Completion {
+    let Some((key, descriptor)) = member else {
+        let mut class = target.clone();
+        for decorator in spread_elements(decorators)?.iter().rev() {
+            match call(decorator, &Value::Undefined, &[class.clone()])? {
+                Value::Undefined | Value::Null => {}
+                result => class = result,
+            }
+        }
+        return Ok(class);
+    };
+    let name = to_property_key(key);
+    let mut descriptor = match (descriptor, target) {
+        (Value::Null, Value::Object(target)) => {
+            Value::Object(descriptor_object(target.borrow().get_own(&name)))
+        }
+        (descriptor, _) => descriptor.clone(),
+    };
+    for decorator in spread_elements(decorators)?.iter().rev() {
+        let arguments = [target.clone(), key.clone(), descriptor.clone()];
+        match call(decorator, &Value::Undefined, &arguments)? {
+            Value::Undefined | Value::Null => {}
+            result => descriptor = result,
+        }
+    }
+    if let (Value::Object(target), Value::Object(descriptor)) = (target, &descriptor) {
+        let property = descriptor_property(descriptor);
+        target.borrow_mut().define(&name, property);
+    }
+    Ok(descriptor)
+}
+
+/// `__param(index, decorator)`: a member decorator that passes a parameter decorator the
+/// index of its parameter.
+pub fn param(index: usize, decorator: Value) -> Value {
+    native(move |_, arguments| {
+        let arguments = [
+            argument(arguments, 0),
+            argument(arguments, 1),
+            Value::Number(index as f64),
+        ];
+        call(&decorator, &Value::Undefined, &arguments)
+    })
+}
+
+/// `context.access` for a decorated element: `has`, plus `get` and `set` where the
+/// element can be read or written.
+fn access_object(kind: &str, name: &str) -> Value {
+    let access = allocate_object();
+    let key = name.to_string();
+    let has = native(move |_, arguments| {
+        let mut current = match argument(arguments, 0) {
+            Value::Object(object) => Some(object),
+            value => {
+                return Err(type_error(&format!(
+                    "Cannot use 'in' operator to search for '{}' in {}",
+                    key, value
+                )))
+            }
+        };
+        while let Some(object) = current {
+            if object.borrow().get_own(&key).is_some() {
+                return Ok(Value::Boolean(true));
+            }
+            current = object.borrow().prototype.clone();
+        }
+        Ok(Value::Boolean(false))
+    });
+    access.borrow_mut().define("has", Property::Data(has));
+    if kind != "setter" {
+        let key = name.to_string();
+        let get = native(move |_, arguments| get_property(&argument(arguments, 0), &key));
+        access.borrow_mut().define("get", Property::Data(get));
+    }
+    if kind == "setter" || kind == "field" {
+        let key = name.to_string();
+        let set = native(move |_, arguments| match argument(arguments, 0) {
+            Value::Object(object) => {
+                set(&object, &key, argument(arguments, 1))?;
+                Ok(Value::Undefined)
+            }
+            value => Err(type_error(&format!(
+                "Cannot set properties of {} (setting '{}')",
+                value, key
+            ))),
+        });
+        access.borrow_mut().define("set", Property::Data(set));
+    }
+    Value::Object(access)
+}
+
+/// A property as a descriptor object: `{ value }` or `{ get, set }`, or empty if missing.
+fn descriptor_object(property: Option<Property>) -> ObjectRef {
+    let descriptor = allocate_object();
+    match property {
+        Some(Property::Data(value)) => descriptor
+            .borrow_mut()
+            .define("value", Property::Data(value)),
+        Some(Property::Accessor { get, set }) => {
+            let function = |f: Option<NativeFunction>| f.map_or(Value::Undefined, Value::Function);
+            let mut descriptor = descriptor.borrow_mut();
+            descriptor.define("get", Property::Data(function(get)));
+            descriptor.define("set", Property::Data(function(set)));
+        }
+        None => {}
+    }
+    descriptor
+}
+
+/// The property a descriptor object describes. Attributes such as `writable` aren't
+/// tracked, so only the value or accessors carry over.
+fn descriptor_property(descriptor: &ObjectRef) -> Property {
+    let descriptor = descriptor.borrow();
+    let function = |key| match descriptor.get_own(key) {
+        Some(Property::Data(Value::Function(f))) => Some(f),
+        _ => None,
+    };
+    match (function("get"), function("set")) {
+        (None, None) => match descriptor.get_own("value") {
+            Some(Property::Data(value)) => Property::Data(value),
+            _ => Property::Data(Value::Undefined),
+        },
+        (get, set) => Property::Accessor { get, set },
+    }
+}
+
+fn expect_function(value: Value) -> Completion {
+    match value {
+        Value::Function(_) => Ok(value),
+        _ => Err(type_error("Function expected")),
+    }
+}
+
+fn array_push(array: &Value, value: Value) {
+    if let Value::Object(array) = array {
+        let length = array.borrow().length();
+        array
+            .borrow_mut()
+            .define(&length.to_string(), Property::Data(value));
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Undefined => write!(f, "undefined"),
+            Value::Null => write!(f, "null"),
+            Value::Boolean(value) => write!(f, "{}", value),
+            Value::Number(number) if number.is_nan() => write!(f, "NaN"),
+            Value::Number(number) if number.is_infinite() => {
+                write!(f, "{}Infinity", if *number < 0.0 { "-" } else { "" })
+            }
+            Value::Number(number) if number.fract() == 0.0 && number.abs() < 1e21 => {
+                write!(f, "{}", *number as i64)
+            }
+            Value::Number(number) => write!(f, "{}", number),
+            Value::String(string) => write!(f, "{}", string),
+            Value::Object(object) if object.borrow().is_array() => {
+                let object = object.borrow();
+                let elements: Vec<String> = (0..object.length())
+                    .map(|index| match object.get_own(&index.to_string()) {
+                        Some(Property::Data(Value::Undefined | Value::Null)) | None => {
+                            String::new()
+                        }
+                        Some(Property::Data(value)) => value.to_string(),
+                        Some(Property::Accessor { .. }) => String::new(),
+                    })
+                    .collect();
+                write!(f, "{}", elements.join(","))
+            }
+            Value::Object(_) => write!(f, "[object Object]"),
+            Value::Function(_) => write!(f, "function () {{ [native code] }}"),
+        }
+    }
+}
+
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::String(string) => write!(f, "{:?}", string),
+            value => write!(f, "{}", value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Calls `method` on `generator` and records what its promise settles to.
+    fn request(generator: &Value, method: &str, results: &Rc<RefCell<Vec<String>>>) {
+        let Ok(Value::Object(promise)) = call_method(generator, method, &[]) else {
+            panic!("{} returns a promise", method);
+        };
+        let results = results.clone();
+        on_settled(&promise, move |outcome| {
+            let result = match outcome {
+                Ok(result) => format!(
+                    "{:?} {:?}",
+                    get_property(&result, "value").unwrap_or(Value::Undefined),
+                    get_property(&result, "done").unwrap_or(Value::Undefined)
+                ),
+                Err(reason) => format!("throws {:?}", reason),
+            };
+            results.borrow_mut().push(result);
+        });
+    }
+
+    #[test]
+    fn async_generators_forward_to_the_iterator_of_yield_star() {
+        // yield* [1, 2], then return what it produced
+        let mut started = false;
+        let body: Coroutine = Box::new(move |resume| {
+            if !std::mem::replace(&mut started, true) {
+                let elements = vec![Some(Value::Number(1.0)), Some(Value::Number(2.0))];
+                return Ok(Step::Delegate(Value::Object(allocate_array(elements))));
+            }
+            match resume {
+                Resume::Next(value) | Resume::Return(value) => Ok(Step::Return(value)),
+                Resume::Throw(error) => Err(error),
+            }
+        });
+        let generator = create_async_generator(body);
+        let results = Rc::new(RefCell::new(Vec::new()));
+        for _ in 0..4 {
+            request(&generator, "next", &results);
+        }
+        run_event_loop();
+        assert_eq!(
+            *results.borrow(),
+            ["1 false", "2 false", "undefined true", "undefined true"]
+        );
+    }
+}
@@ -0,0 +1,1822 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::{
+    assignment, binary, call, identifier, let_declaration, member, null, number, undefined,
+};
+use crate::error_handling::{CompilerError, CompilerResult, Span};
+use crate::names::{lexical_names, rename, replace_references, FreshNames};
+use crate::parser::{
+    CatchClause, ExportSpecifier, Modifiers, Node, Parameter, VariableDeclarator, VariableKind,
+};
+use crate::switches::{plan_switch, SwitchDispatch};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CoroutineKind {
+    Generator,
+    Async,
+    AsyncGenerator,
+}
+
+impl CoroutineKind {
+    /// The runtime function that wraps a machine in a generator object or promise.
+    pub fn runtime_helper(self) -> &'static str {
+        match self {
+            CoroutineKind::Generator => "__generator",
+            CoroutineKind::Async => "__async",
+            CoroutineKind::AsyncGenerator => "__asyncGenerator",
+        }
+    }
+}
+
+pub type StateId = usize;
+
+/// A coroutine body split into states at its suspension points. Execution starts at state 0.
+#[derive(Debug, PartialEq, Clone)]
+pub struct StateMachine {
+    pub kind: CoroutineKind,
+    pub states: Vec<State>,
+    /// The local an exception is stored in before continuing at a handler.
+    pub error: String,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct State {
+    /// Straight-line code without `yield` or `await`.
+    pub statements: Vec<Node>,
+    pub terminator: Terminator,
+    /// The state an exception thrown here continues at, with the exception in the machine's
+    /// `error` local.
+    pub handler: Option<StateId>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Terminator {
+    Goto(StateId),
+    Branch {
+        test: Node,
+        consequent: StateId,
+        alternate: StateId,
+    },
+    /// Suspends the coroutine. Resuming it with a value stores the value in `result` and
+    /// continues at `resume`; resuming it with an exception throws it from this state.
+    /// Resuming it with `return(value)` returns the value, or stores it in `result` and
+    /// continues at `on_return` when `finally` blocks have to run first.
+    Yield {
+        value: Option<Node>,
+        delegate: bool, // yield*
+        result: String,
+        resume: StateId,
+        on_return: Option<StateId>,
+    },
+    Await {
+        value: Node,
+        result: String,
+        resume: StateId,
+    },
+    /// Continues at `table[discriminant - low]` when the discriminant is an integer in
+    /// range, and at `default` otherwise. A `switch` only becomes one when `plan_switch`
+    /// gives it a jump table; other switches branch on each case test in turn.
+    Switch {
+        discriminant: Node,
+        low: i64,
+        table: Vec<StateId>,
+        default: StateId,
+    },
+    Return(Option<Node>),
+    Throw(Node),
+}
+
+impl State {
+    fn targets_mut(&mut self) -> Vec<&mut StateId> {
+        let mut targets: Vec<&mut StateId> = self.handler.iter_mut().collect();
+        match &mut self.terminator {
+            Terminator::Goto(target) | Terminator::Await { resume: target, .. } => {
+                targets.push(target)
+            }
+            Terminator::Yield {
+                resume, on_return, ..
+            } => {
+                targets.push(resume);
+                targets.extend(on_return);
+            }
+            Terminator::Branch {
+                consequent,
+                alternate,
+                ..
+            } => {
+                targets.push(consequent);
+                targets.push(alternate);
+            }
+            Terminator::Switch { table, default, .. } => {
+                targets.extend(table);
+                targets.push(default);
+            }
+            Terminator::Return(_) | Terminator::Throw(_) => {}
+        }
+        targets
+    }
+}
+
+/// Turns async functions, generators and async generators into state machines.
+///
+/// The machines are returned in a list, and each function keeps its parameters but its body
+/// becomes `let` declarations for the locals its states share, any nested declarations, and
+/// `return __async(n)` (or `__generator(n)`, `__asyncGenerator(n)`) where `n` is the index of
+/// its machine. Modules with top-level `await` are lowered the same way, with an
+/// `__async(n)` statement at the end.
+///
+/// Locals are hoisted out of the body so they survive suspension, and expressions are split
+/// around each `yield` and `await`, with operands evaluated before one stored in `$sN`
+/// temporaries. Statements that neither suspend nor jump out of themselves stay intact.
+pub fn lower_coroutines(program: &Node) -> CompilerResult<(Node, Vec<StateMachine>)> {
+    let mut lowering = Lowering {
+        machines: Vec::new(),
+        names: FreshNames::new(program),
+        errors: Vec::new(),
+    };
+    let mut program = program.clone();
+    separate_bindings(&mut program, &mut lowering.names);
+    lowering.lower(&mut program);
+    if let Node::Program(body) = &mut program {
+        if body.iter().any(contains_suspension) {
+            let statements = std::mem::take(body);
+            let (index, mut prelude) =
+                lowering.build(CoroutineKind::Async, HashSet::new(), statements);
+            prelude.push(Node::ExpressionStatement(Box::new(helper_call(
+                CoroutineKind::Async,
+                index,
+            ))));
+            *body = prelude;
+        }
+    }
+    CompilerResult::new(Some((program, lowering.machines)), lowering.errors)
+}
+
+/// Prepares the coroutines in `node` for having their locals hoisted to the function.
+///
+/// A block-scoped binding whose name is used anywhere in the function outside its scope,
+/// whether by a parameter, another binding or a reference to an outer one, is renamed to
+/// `name$N` first. The `let` and `const` bindings of a loop that is split are created once
+/// per iteration; when closures in the loop capture them, they become properties of an
+/// object `$bN` made for each iteration, and each closure is given the object of the
+/// iteration that created it: `() => x` becomes `(($b0) => () => $b0.x)($b0)`. Function and
+/// class declarations in a loop are hoisted and still see the latest iteration.
+fn separate_bindings(node: &mut Node, names: &mut FreshNames) {
+    let is_coroutine = match &*node {
+        Node::Program(body) => body.iter().any(contains_suspension),
+        Node::FunctionDeclaration {
+            is_async,
+            is_generator,
+            ..
+        }
+        | Node::MethodDefinition {
+            is_async,
+            is_generator,
+            ..
+        } => *is_async || *is_generator,
+        Node::ArrowFunctionExpression { is_async, .. } => *is_async,
+        _ => false,
+    };
+    if is_coroutine {
+        let totals = occurrences(node);
+        let body = match node {
+            Node::FunctionDeclaration {
+                body: Some(body), ..
+            }
+            | Node::MethodDefinition {
+                body: Some(body), ..
+            }
+            | Node::ArrowFunctionExpression { body, .. } => Some(body.as_mut()),
+            Node::Program(_) => Some(&mut *node),
+            _ => None,
+        };
+        if let Some(body) = body {
+            rename_shadowed(body, &totals, names, true);
+            box_loops(body, names);
+        }
+    }
+    for child in node.children_mut() {
+        separate_bindings(child, names);
+    }
+}
+
+/// How many times each name is spelled in `node`.
+fn occurrences(node: &Node) -> HashMap<String, usize> {
+    fn count(node: &Node, counts: &mut HashMap<String, usize>) {
+        if let Node::Identifier(name, _)
+        | Node::FunctionDeclaration { name, .. }
+        | Node::ClassDeclaration { name, .. }
+        | Node::EnumDeclaration { name, .. } = node
+        {
+            *counts.entry(name.clone()).or_default() += 1;
+        }
+        for child in node.children() {
+            count(child, counts);
+        }
+    }
+    let mut counts = HashMap::new();
+    count(node, &mut counts);
+    counts
+}
+
+/// Renames the bindings of the scopes in a function body whose names are spelled outside
+/// them, with `totals` counting the names of the whole function.
+fn rename_shadowed(
+    node: &mut Node,
+    totals: &HashMap<String, usize>,
+    names: &mut FreshNames,
+    is_body: bool,
+) {
+    if !is_body && is_function(node) {
+        return;
+    }
+    let declared: Vec<String> = match &*node {
+        _ if is_body => Vec::new(),
+        Node::BlockStatement(statements, _) => statements.iter().flat_map(lexical_names).collect(),
+        Node::ForStatement {
+            init: Some(head), ..
+        }
+        | Node::ForInStatement { left: head, .. }
+        | Node::ForOfStatement { left: head, .. } => lexical_names(head),
+        Node::SwitchStatement { cases, .. } => cases
+            .iter()
+            .flat_map(|case| &case.consequent)
+            .flat_map(lexical_names)
+            .collect(),
+        _ => Vec::new(),
+    };
+    let is_shared =
+        |inside: &HashMap<String, usize>, name: &String| inside.get(name) < totals.get(name);
+    for name in declared {
+        if is_shared(&occurrences(node), &name) {
+            let renamed = names.fresh(&format!("{}$", name));
+            rename(node, &name, &renamed);
+        }
+    }
+    if let Node::TryStatement {
+        handler: Some(CatchClause {
+            param: Some(param),
+            body,
+        }),
+        ..
+    } = node
+    {
+        for name in param.bound_names() {
+            let mut inside = occurrences(param);
+            for (body_name, count) in occurrences(body) {
+                *inside.entry(body_name).or_default() += count;
+            }
+            if is_shared(&inside, &name) {
+                let renamed = names.fresh(&format!("{}$", name));
+                rename(param, &name, &renamed);
+                rename(body, &name, &renamed);
+            }
+        }
+    }
+    for child in node.children_mut() {
+        rename_shadowed(child, totals, names, false);
+    }
+}
+
+/// Gives the loops in a function body that are split a per-iteration object for the
+/// bindings closures capture, inner loops first.
+fn box_loops(node: &mut Node, names: &mut FreshNames) {
+    if is_function(node) {
+        return;
+    }
+    // A labeled loop stays labeled, with any statements that have to run first before it
+    let mut target = &mut *node;
+    while let Node::LabeledStatement { body, .. } = target {
+        target = body;
+    }
+    for child in target.children_mut() {
+        box_loops(child, names);
+    }
+    if !is_iteration(target) || !needs_split(target) {
+        return;
+    }
+    let prelude = box_iteration(target, names);
+    if !prelude.is_empty() {
+        let statement = std::mem::replace(node, Node::EmptyStatement);
+        *node = Node::BlockStatement(
+            prelude.into_iter().chain([statement]).collect(),
+            Span::default(),
+        );
+    }
+}
+
+/// Moves the bindings of one iteration of `iteration` that closures capture into an object
+/// created for each iteration, and returns the statements to run before the loop.
+fn box_iteration(iteration: &mut Node, names: &mut FreshNames) -> Vec<Node> {
+    let mut bindings: Vec<String> = match &*iteration {
+        Node::ForStatement {
+            init: Some(head), ..
+        }
+        | Node::ForInStatement { left: head, .. }
+        | Node::ForOfStatement { left: head, .. } => lexical_names(head),
+        _ => Vec::new(),
+    };
+    let body = match iteration {
+        Node::ForStatement { body, .. }
+        | Node::ForInStatement { body, .. }
+        | Node::ForOfStatement { body, .. }
+        | Node::WhileStatement { body, .. }
+        | Node::DoWhileStatement { body, .. } => body,
+        _ => return Vec::new(),
+    };
+    iteration_bindings(body, &mut bindings);
+    let mut in_closures = HashSet::new();
+    closure_references(iteration, false, &mut in_closures);
+    let captured: Vec<String> = bindings
+        .into_iter()
+        .filter(|name| in_closures.contains(name) && !names.is_generated(name))
+        .collect();
+    if captured.is_empty() {
+        return Vec::new();
+    }
+    let boxed = names.fresh("$b");
+    let new_box = || Node::VariableDeclaration {
+        kind: VariableKind::Let,
+        declarations: vec![VariableDeclarator {
+            id: Box::new(identifier(&boxed)),
+            type_annotation: None,
+            init: Some(Box::new(Node::ObjectExpression(Vec::new()))),
+        }],
+    };
+    let mut prelude = Vec::new();
+    let mut first = Vec::new(); // statements to run at the start of each iteration
+    match iteration {
+        // Each iteration after the first copies the head bindings to a new object before
+        // the update; the object is truthy, so `&&` goes on to the update
+        Node::ForStatement { init, update, .. } => {
+            prelude.push(new_box());
+            match init.take().map(|init| *init) {
+                Some(head @ Node::VariableDeclaration { .. })
+                    if !lexical_names(&head).is_empty() =>
+                {
+                    let copied = lexical_names(&head)
+                        .into_iter()
+                        .filter(|name| captured.contains(name))
+                        .map(|name| Node::Property {
+                            key: Box::new(identifier(&name)),
+                            computed: false,
+                            value: Box::new(member(identifier(&boxed), &name)),
+                            shorthand: false,
+                        })
+                        .collect();
+                    prelude.extend(assign_captured(head, &captured));
+                    let copy = assignment(identifier(&boxed), Node::ObjectExpression(copied));
+                    *update = Some(Box::new(match update.take() {
+                        Some(update) => Node::LogicalExpression {
+                            left: Box::new(copy),
+                            operator: "&&".to_string(),
+                            right: update,
+                        },
+                        None => copy,
+                    }));
+                }
+                head => {
+                    *init = head.map(Box::new);
+                    let copy = assignment(identifier(&boxed), Node::ObjectExpression(Vec::new()));
+                    *update = Some(Box::new(match update.take() {
+                        Some(update) => Node::LogicalExpression {
+                            left: Box::new(copy),
+                            operator: "&&".to_string(),
+                            right: update,
+                        },
+                        None => copy,
+                    }));
+                }
+            }
+        }
+        // The head binds a temporary that is copied into the object
+        Node::ForInStatement { left, .. } | Node::ForOfStatement { left, .. } => {
+            first.push(new_box());
+            if let Node::VariableDeclaration { declarations, .. } = left.as_mut() {
+                for declarator in declarations {
+                    let Node::Identifier(name, _) = declarator.id.as_ref() else {
+                        continue;
+                    };
+                    if captured.contains(name) {
+                        let name = name.clone();
+                        let value = names.fresh(&format!("{}$", name));
+                        *declarator.id = identifier(&value);
+                        first.push(Node::ExpressionStatement(Box::new(assignment(
+                            identifier(&name),
+                            identifier(&value),
+                        ))));
+                    }
+                }
+            }
+        }
+        _ => first.push(new_box()),
+    }
+    let body = match iteration {
+        Node::ForStatement { body, .. }
+        | Node::ForInStatement { body, .. }
+        | Node::ForOfStatement { body, .. }
+        | Node::WhileStatement { body, .. }
+        | Node::DoWhileStatement { body, .. } => body,
+        _ => return prelude,
+    };
+    assign_declarations(body, &captured);
+    if !first.is_empty() {
+        match body.as_mut() {
+            Node::BlockStatement(statements, _) => {
+                statements.splice(0..0, first);
+            }
+            statement => {
+                let statement = std::mem::replace(statement, Node::EmptyStatement);
+                first.push(statement);
+                **body = Node::BlockStatement(first, Span::default());
+            }
+        }
+    }
+    for name in &captured {
+        let replacement = |span| Node::MemberExpression {
+            object: Box::new(identifier(&boxed)),
+            property: Box::new(Node::Identifier(name.clone(), span)),
+            computed: false,
+            optional: false,
+        };
+        replace_references(iteration, name, &replacement);
+        for statement in &mut prelude {
+            replace_references(statement, name, &replacement);
+        }
+    }
+    bind_closures(iteration, &boxed);
+    prelude
+}
+
+/// The `let` and `const` names declared in one iteration of a loop body, outside nested
+/// loops and functions.
+fn iteration_bindings(node: &Node, bindings: &mut Vec<String>) {
+    if is_function(node) || is_iteration(node) {
+        return;
+    }
+    if let Node::VariableDeclaration { .. } = node {
+        bindings.extend(lexical_names(node));
+    }
+    for child in node.children() {
+        iteration_bindings(child, bindings);
+    }
+}
+
+/// Collects the names spelled inside the closures in `node`.
+fn closure_references(node: &Node, in_closure: bool, references: &mut HashSet<String>) {
+    let in_closure = in_closure || is_function(node);
+    if let (true, Node::Identifier(name, _)) = (in_closure, node) {
+        references.insert(name.clone());
+    }
+    for child in node.children() {
+        closure_references(child, in_closure, references);
+    }
+}
+
+/// Turns the declarations of `captured` names in one iteration of a loop body into
+/// assignments.
+fn assign_declarations(node: &mut Node, captured: &[String]) {
+    if is_function(node) || is_iteration(node) {
+        return;
+    }
+    let lists: Vec<&mut Vec<Node>> = match node {
+        Node::BlockStatement(statements, _) => vec![statements],
+        Node::SwitchStatement { cases, .. } => {
+            cases.iter_mut().map(|case| &mut case.consequent).collect()
+        }
+        _ => Vec::new(),
+    };
+    for statements in lists {
+        *statements = std::mem::take(statements)
+            .into_iter()
+            .flat_map(|statement| assign_captured(statement, captured))
+            .collect();
+    }
+    for child in node.children_mut() {
+        assign_declarations(child, captured);
+    }
+}
+
+/// A `let` or `const` declaration with its `captured` declarators as assignments instead,
+/// in order.
+fn assign_captured(statement: Node, captured: &[String]) -> Vec<Node> {
+    let Node::VariableDeclaration { kind, declarations } = statement else {
+        return vec![statement];
+    };
+    let is_captured = |declarator: &VariableDeclarator| matches!(declarator.id.as_ref(), Node::Identifier(name, _) if captured.contains(name));
+    if kind == VariableKind::Var || !declarations.iter().any(is_captured) {
+        return vec![Node::VariableDeclaration { kind, declarations }];
+    }
+    let mut statements = Vec::new();
+    let mut kept = Vec::new();
+    for declarator in declarations {
+        if !is_captured(&declarator) {
+            kept.push(declarator);
+            continue;
+        }
+        if !kept.is_empty() {
+            statements.push(Node::VariableDeclaration {
+                kind: kind.clone(),
+                declarations: std::mem::take(&mut kept),
+            });
+        }
+        let value = declarator.init.map_or_else(undefined, |init| *init);
+        statements.push(Node::ExpressionStatement(Box::new(assignment(
+            *declarator.id,
+            value,
+        ))));
+    }
+    if !kept.is_empty() {
+        statements.push(Node::VariableDeclaration {
+            kind,
+            declarations: kept,
+        });
+    }
+    statements
+}
+
+/// Gives each closure in `node` that uses the per-iteration object `boxed` the object of the
+/// iteration that creates it. Object literals with methods are wrapped as a whole.
+fn bind_closures(node: &mut Node, boxed: &str) {
+    let binds = match node {
+        Node::ArrowFunctionExpression { .. } => mentions(node, boxed),
+        Node::ObjectExpression(properties) => {
+            properties.iter().any(|property| {
+                matches!(property, Node::MethodDefinition { .. }) && mentions(property, boxed)
+            }) && !contains_suspension(node)
+        }
+        Node::FunctionDeclaration { .. }
+        | Node::ClassDeclaration { .. }
+        | Node::MethodDefinition { .. } => return,
+        _ => false,
+    };
+    if !binds {
+        for child in node.children_mut() {
+            bind_closures(child, boxed);
+        }
+        return;
+    }
+    let closure = std::mem::replace(node, Node::EmptyStatement);
+    let bind = Node::ArrowFunctionExpression {
+        type_params: Vec::new(),
+        params: vec![Parameter {
+            binding: Box::new(identifier(boxed)),
+            type_annotation: None,
+            optional: false,
+            rest: false,
+            init: None,
+            modifiers: Modifiers::default(),
+        }],
+        body: Box::new(closure),
+        return_type: None,
+        is_async: false,
+        span: Span::default(),
+    };
+    *node = call(bind, vec![identifier(boxed)]);
+}
+
+fn mentions(node: &Node, name: &str) -> bool {
+    matches!(node, Node::Identifier(identifier, _) if identifier == name)
+        || node
+            .children()
+            .into_iter()
+            .any(|child| mentions(child, name))
+}
+
+fn is_function(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::FunctionDeclaration { .. }
+            | Node::MethodDefinition { .. }
+            | Node::ArrowFunctionExpression { .. }
+    )
+}
+
+struct Lowering {
+    machines: Vec<StateMachine>,
+    names: FreshNames,
+    errors: Vec<CompilerError>,
+}
+
+impl Lowering {
+    fn lower(&mut self, node: &mut Node) {
+        // Nested functions first, so their suspensions are gone before this body is split
+        for child in node.children_mut() {
+            self.lower(child);
+        }
+        let (params, body, is_async, is_generator) = match node {
+            Node::FunctionDeclaration {
+                params,
+                body: Some(body),
+                is_async,
+                is_generator,
+                ..
+            }
+            | Node::MethodDefinition {
+                params,
+                body: Some(body),
+                is_async,
+                is_generator,
+                ..
+            } => (params, body, *is_async, *is_generator),
+            Node::ArrowFunctionExpression {
+                params,
+                body,
+                is_async: true,
+                ..
+            } => {
+                // A concise body is the same as a block returning it
+                if !matches!(body.as_ref(), Node::BlockStatement(..)) {
+                    let expression = std::mem::replace(body.as_mut(), Node::EmptyStatement);
+                    **body = Node::BlockStatement(
+                        vec![Node::ReturnStatement(Some(Box::new(expression)))],
+                        Span::default(),
+                    );
+                }
+                (params, body, true, false)
+            }
+            _ => return,
+        };
+        let kind = match (is_async, is_generator) {
+            (false, false) => return,
+            (false, true) => CoroutineKind::Generator,
+            (true, false) => CoroutineKind::Async,
+            (true, true) => CoroutineKind::AsyncGenerator,
+        };
+        let Node::BlockStatement(statements, _) = body.as_mut() else {
+            return;
+        };
+        let params = params
+            .iter()
+            .flat_map(|param| param.binding.bound_names())
+            .collect();
+        let statements = std::mem::take(statements);
+        let (index, mut prelude) = self.build(kind, params, statements);
+        prelude.push(Node::ReturnStatement(Some(Box::new(helper_call(
+            kind, index,
+        )))));
+        **body = Node::BlockStatement(prelude, Span::default());
+    }
+
+    /// Builds the machine for a body and returns its index with the statements that run
+    /// before it starts: local declarations and hoisted declarations.
+    fn build(
+        &mut self,
+        kind: CoroutineKind,
+        params: HashSet<String>,
+        statements: Vec<Node>,
+    ) -> (usize, Vec<Node>) {
+        let error = self.names.fresh("$error");
+        let mut builder = Builder {
+            lowering: self,
+            error: error.clone(),
+            states: Vec::new(),
+            current: 0,
+            handler: None,
+            jumps: Vec::new(),
+            finalizers: Vec::new(),
+            params,
+            locals: Vec::new(),
+            exported: Vec::new(),
+            hoisted: Vec::new(),
+        };
+        let start = builder.new_state();
+        builder.enter(start);
+        for statement in statements {
+            builder.statement(statement);
+        }
+        builder.terminate(Terminator::Return(None));
+
+        let mut prelude = Vec::new();
+        let (exported, locals): (Vec<String>, Vec<String>) = builder
+            .locals
+            .iter()
+            .cloned()
+            .partition(|local| builder.exported.contains(local));
+        if !locals.is_empty() {
+            prelude.push(let_declaration(locals));
+        }
+        if !exported.is_empty() {
+            prelude.push(Node::ExportNamedDeclaration {
+                declaration: Some(Box::new(let_declaration(exported))),
+                specifiers: Vec::new(),
+                source: None,
+                is_type_only: false,
+            });
+        }
+        prelude.append(&mut builder.hoisted);
+        let machine = StateMachine {
+            kind,
+            states: compact(builder.states),
+            error,
+        };
+        self.machines.push(machine);
+        (self.machines.len() - 1, prelude)
+    }
+
+    fn error(&mut self, message: &str) {
+        self.errors
+            .push(CompilerError::new(message.to_string(), Span::default()));
+    }
+}
+
+struct JumpTarget {
+    labels: Vec<String>,
+    breakable: bool, // by an unlabeled break: loops and switches
+    break_to: StateId,
+    continue_to: Option<StateId>,
+}
+
+/// A `finally` block that exits from its `try` have to pass through. Each exit stores its
+/// index in `exits` into `completion` before jumping to `entry`, and the block continues
+/// with that exit when it is done.
+struct Finalizer {
+    entry: StateId,
+    completion: String,
+    value: String, // the return value or exception the exit carries
+    exits: Vec<Exit>,
+    depth: usize, // jump targets outside the `try`
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Exit {
+    Normal,
+    Return,
+    Throw,
+    Jump { target: StateId, depth: usize },
+}
+
+struct Builder<'a> {
+    lowering: &'a mut Lowering,
+    error: String, // the local exceptions are caught in
+    states: Vec<State>,
+    current: StateId,
+    handler: Option<StateId>,
+    jumps: Vec<JumpTarget>,
+    finalizers: Vec<Finalizer>,
+    params: HashSet<String>,
+    locals: Vec<String>,
+    exported: Vec<String>,
+    hoisted: Vec<Node>, // declarations that run before the machine starts
+}
+
+impl Builder<'_> {
+    fn new_state(&mut self) -> StateId {
+        self.states.push(State {
+            statements: Vec::new(),
+            terminator: Terminator::Return(None),
+            handler: None,
+        });
+        self.states.len() - 1
+    }
+
+    fn enter(&mut self, state: StateId) {
+        self.current = state;
+        self.states[state].handler = self.handler;
+    }
+
+    fn emit(&mut self, statement: Node) {
+        self.states[self.current].statements.push(statement);
+    }
+
+    /// Ends the current state. Anything emitted before the next `enter` is unreachable.
+    fn terminate(&mut self, terminator: Terminator) {
+        self.states[self.current].terminator = terminator;
+        let unreachable = self.new_state();
+        self.enter(unreachable);
+    }
+
+    fn goto(&mut self, target: StateId) {
+        self.terminate(Terminator::Goto(target));
+        self.enter(target);
+    }
+
+    fn branch(&mut self, test: Node, consequent: StateId, alternate: StateId) {
+        self.terminate(Terminator::Branch {
+            test,
+            consequent,
+            alternate,
+        });
+    }
+
+    fn local(&mut self, name: String) {
+        if !self.params.contains(&name) && !self.locals.contains(&name) {
+            self.locals.push(name);
+        }
+    }
+
+    fn temporary(&mut self) -> Node {
+        Node::Identifier(self.temporary_name(), Span::default())
+    }
+
+    fn temporary_name(&mut self) -> String {
+        let name = self.lowering.names.fresh("$s");
+        self.local(name.clone());
+        name
+    }
+
+    fn assign(&mut self, target: Node, value: Node) {
+        self.emit(Node::ExpressionStatement(Box::new(assignment(
+            target, value,
+        ))));
+    }
+
+    // Statements
+
+    fn statement(&mut self, statement: Node) {
+        self.labeled_statement(statement, Vec::new());
+    }
+
+    fn labeled_statement(&mut self, statement: Node, labels: Vec<String>) {
+        match statement {
+            Node::FunctionDeclaration { .. }
+            | Node::ClassDeclaration { .. }
+            | Node::InterfaceDeclaration { .. }
+            | Node::TypeAliasDeclaration { .. }
+            | Node::EnumDeclaration { .. }
+            | Node::NamespaceDeclaration { .. }
+            | Node::AmbientModuleDeclaration { .. }
+            | Node::GlobalAugmentation(_)
+            | Node::AmbientDeclaration(_)
+            | Node::ImportDeclaration { .. }
+            | Node::ExportAllDeclaration { .. } => self.hoisted.push(statement),
+            Node::ExportNamedDeclaration {
+                declaration: Some(declaration),
+                specifiers,
+                source,
+                is_type_only,
+            } => match *declaration {
+                Node::VariableDeclaration { kind, declarations } => {
+                    for declarator in &declarations {
+                        self.exported.extend(declarator.id.bound_names());
+                    }
+                    self.declare(kind, declarations);
+                }
+                declaration => self.hoisted.push(Node::ExportNamedDeclaration {
+                    declaration: Some(Box::new(declaration)),
+                    specifiers,
+                    source,
+                    is_type_only,
+                }),
+            },
+            Node::ExportNamedDeclaration { .. } => self.hoisted.push(statement),
+            Node::ExportDefaultDeclaration(declaration) => match *declaration {
+                declaration @ (Node::FunctionDeclaration { .. }
+                | Node::ClassDeclaration { .. }
+                | Node::InterfaceDeclaration { .. }) => self
+                    .hoisted
+                    .push(Node::ExportDefaultDeclaration(Box::new(declaration))),
+                expression => {
+                    let value = self.explode(expression);
+                    let local = self.temporary();
+                    self.assign(local.clone(), value);
+                    if let Node::Identifier(local, _) = local {
+                        self.hoisted.push(Node::ExportNamedDeclaration {
+                            declaration: None,
+                            specifiers: vec![ExportSpecifier {
+                                local,
+                                exported: "default".to_string(),
+                                is_type_only: false,
+                                span: Span::default(),
+                            }],
+                            source: None,
+                            is_type_only: false,
+                        });
+                    }
+                }
+            },
+            Node::VariableDeclaration { kind, declarations } => self.declare(kind, declarations),
+            statement if !needs_split(&statement) => self.emit(statement),
+            Node::ExpressionStatement(expression) => {
+                let expression = self.explode(*expression);
+                // A bare `await x;` leaves only the temporary holding its result
+                if !matches!(expression, Node::Identifier(..)) {
+                    self.emit(Node::ExpressionStatement(Box::new(expression)));
+                }
+            }
+            Node::BlockStatement(body, _) => {
+                if labels.is_empty() {
+                    for statement in body {
+                        self.statement(statement);
+                    }
+                } else {
+                    self.labeled_block(Node::BlockStatement(body, Span::default()), labels);
+                }
+            }
+            Node::LabeledStatement { label, body } => {
+                let mut labels = labels;
+                labels.push(label);
+                if is_iteration(&body) {
+                    self.labeled_statement(*body, labels);
+                } else {
+                    self.labeled_block(*body, labels);
+                }
+            }
+            Node::IfStatement {
+                condition,
+                then_branch,
+                else_branch,
+            } => {
+                let test = self.explode(*condition);
+                let consequent = self.new_state();
+                let alternate = self.new_state();
+                let after = self.new_state();
+                self.branch(test, consequent, alternate);
+                self.enter(consequent);
+                self.statement(*then_branch);
+                self.terminate(Terminator::Goto(after));
+                self.enter(alternate);
+                if let Some(else_branch) = else_branch {
+                    self.statement(*else_branch);
+                }
+                self.goto(after);
+            }
+            Node::WhileStatement { test, body } => {
+                let head = self.new_state();
+                let start = self.new_state();
+                let after = self.new_state();
+                self.goto(head);
+                let test = self.explode(*test);
+                self.branch(test, start, after);
+                self.enter(start);
+                self.loop_body(*body, labels, after, head);
+                self.terminate(Terminator::Goto(head));
+                self.enter(after);
+            }
+            Node::DoWhileStatement { body, test } => {
+                let start = self.new_state();
+                let head = self.new_state();
+                let after = self.new_state();
+                self.goto(start);
+                self.loop_body(*body, labels, after, head);
+                self.goto(head);
+                let test = self.explode(*test);
+                self.branch(test, start, after);
+                self.enter(after);
+            }
+            Node::ForStatement {
+                init,
+                test,
+                update,
+                body,
+            } => {
+                match init.map(|init| *init) {
+                    Some(Node::VariableDeclaration { kind, declarations }) => {
+                        self.declare(kind, declarations)
+                    }
+                    Some(init) => {
+                        let init = self.explode(init);
+                        self.emit(Node::ExpressionStatement(Box::new(init)));
+                    }
+                    None => {}
+                }
+                let head = self.new_state();
+                let next = self.new_state();
+                let after = self.new_state();
+                self.goto(head);
+                if let Some(test) = test {
+                    let test = self.explode(*test);
+                    let start = self.new_state();
+                    self.branch(test, start, after);
+                    self.enter(start);
+                }
+                self.loop_body(*body, labels, after, next);
+                self.goto(next);
+                if let Some(update) = update {
+                    let update = self.explode(*update);
+                    self.emit(Node::ExpressionStatement(Box::new(update)));
+                }
+                self.terminate(Terminator::Goto(head));
+                self.enter(after);
+            }
+            Node::ForOfStatement {
+                left,
+                right,
+                body,
+                is_await,
+            } => {
+                let iterable = self.explode(*right);
+                let iterator = self.temporary();
+                let helper = if is_await {
+                    "__asyncIterator"
+                } else {
+                    "__iterator"
+                };
+                self.assign(iterator.clone(), call(identifier(helper), vec![iterable]));
+                let head = self.new_state();
+                let start = self.new_state();
+                let after = self.new_state();
+                self.goto(head);
+                let next = call(member(iterator, "next"), Vec::new());
+                let step = if is_await {
+                    self.suspend(|result, resume| Terminator::Await {
+                        value: next,
+                        result,
+                        resume,
+                    })
+                } else {
+                    let step = self.temporary();
+                    self.assign(step.clone(), next);
+                    step
+                };
+                self.branch(member(step.clone(), "done"), after, start);
+                self.enter(start);
+                self.bind_loop_head(*left, member(step, "value"));
+                self.loop_body(*body, labels, after, head);
+                self.terminate(Terminator::Goto(head));
+                self.enter(after);
+            }
+            Node::ForInStatement { left, right, body } => {
+                let object = self.explode(*right);
+                let keys = self.temporary();
+                let index = self.temporary();
+                self.assign(keys.clone(), call(identifier("__keys"), vec![object]));
+                self.assign(index.clone(), number(0));
+                let head = self.new_state();
+                let start = self.new_state();
+                let next = self.new_state();
+                let after = self.new_state();
+                self.goto(head);
+                let test = binary(index.clone(), "<", member(keys.clone(), "length"));
+                self.branch(test, start, after);
+                self.enter(start);
+                let key = Node::MemberExpression {
+                    object: Box::new(keys),
+                    property: Box::new(index.clone()),
+                    computed: true,
+                    optional: false,
+                };
+                self.bind_loop_head(*left, key);
+                self.loop_body(*body, labels, after, next);
+                self.goto(next);
+                self.assign(index.clone(), binary(index, "+", number(1)));
+                self.terminate(Terminator::Goto(head));
+                self.enter(after);
+            }
+            Node::SwitchStatement {
+                discriminant,
+                cases,
+            } => {
+                let value = self.explode(*discriminant);
+                let discriminant = self.temporary();
+                self.assign(discriminant.clone(), value);
+                let after = self.new_state();
+                let bodies: Vec<StateId> = cases.iter().map(|_| self.new_state()).collect();
+                let mut consequents = Vec::new();
+                if let SwitchDispatch::JumpTable {
+                    low,
+                    table,
+                    default,
+                } = plan_switch(&cases)
+                {
+                    // The tests are integer literals, so skipping them has no effect
+                    let default = default.map_or(after, |case| bodies[case]);
+                    let table = table
+                        .into_iter()
+                        .map(|case| case.map_or(default, |case| bodies[case]))
+                        .collect();
+                    self.terminate(Terminator::Switch {
+                        discriminant,
+                        low,
+                        table,
+                        default,
+                    });
+                    consequents.extend(cases.into_iter().map(|case| case.consequent));
+                } else {
+                    let mut default = after;
+                    for (case, body) in cases.into_iter().zip(&bodies) {
+                        match case.test {
+                            Some(test) => {
+                                let test = self.explode(*test);
+                                let next = self.new_state();
+                                let test = binary(discriminant.clone(), "===", test);
+                                self.branch(test, *body, next);
+                                self.enter(next);
+                            }
+                            None => default = *body,
+                        }
+                        consequents.push(case.consequent);
+                    }
+                    self.terminate(Terminator::Goto(default));
+                }
+                self.jumps.push(JumpTarget {
+                    labels,
+                    breakable: true,
+                    break_to: after,
+                    continue_to: None,
+                });
+                // Each case falls through into the next one
+                for (index, consequent) in consequents.into_iter().enumerate() {
+                    self.enter(bodies[index]);
+                    for statement in consequent {
+                        self.statement(statement);
+                    }
+                    let next = bodies.get(index + 1).copied().unwrap_or(after);
+                    self.terminate(Terminator::Goto(next));
+                }
+                self.jumps.pop();
+                self.enter(after);
+            }
+            Node::TryStatement {
+                block,
+                handler,
+                finalizer,
+            } => self.try_statement(*block, handler, finalizer.map(|finalizer| *finalizer)),
+            Node::ReturnStatement(argument) => {
+                let value = argument.map(|argument| self.explode(*argument));
+                self.leave(Exit::Return, value);
+            }
+            Node::ThrowStatement(argument) => {
+                let value = self.explode(*argument);
+                self.terminate(Terminator::Throw(value));
+            }
+            Node::BreakStatement(label) => self.jump(label, true),
+            Node::ContinueStatement(label) => self.jump(label, false),
+            statement => self.emit(statement),
+        }
+    }
+
+    fn try_statement(
+        &mut self,
+        block: Node,
+        handler: Option<CatchClause>,
+        finalizer: Option<Node>,
+    ) {
+        let after = self.new_state();
+        let outer = self.handler;
+        let has_finalizer = finalizer.is_some();
+        if has_finalizer {
+            let entry = self.new_state();
+            let completion = self.temporary_name();
+            let value = self.temporary_name();
+            self.finalizers.push(Finalizer {
+                entry,
+                completion,
+                value,
+                exits: vec![Exit::Normal],
+                depth: self.jumps.len(),
+            });
+            // Exceptions from the `try` and `catch` blocks run the `finally` block first
+            let rethrow = self.new_state();
+            let current = self.current;
+            self.enter(rethrow);
+            self.local(self.error.clone());
+            self.leave(Exit::Throw, Some(identifier(&self.error)));
+            self.current = current;
+            self.handler = Some(rethrow);
+        }
+        let finish = |builder: &mut Self| match has_finalizer {
+            true => builder.leave(Exit::Normal, None),
+            false => builder.terminate(Terminator::Goto(after)),
+        };
+        let protected = self.handler;
+        let catch = handler.as_ref().map(|_| self.new_state());
+        let start = self.new_state();
+        self.handler = catch.or(protected);
+        self.goto(start);
+        self.statement(block);
+        self.handler = protected;
+        finish(self);
+        if let (Some(catch), Some(CatchClause { param, body })) = (catch, handler) {
+            self.enter(catch);
+            self.local(self.error.clone());
+            if let Some(param) = param {
+                for name in param.bound_names() {
+                    self.local(name);
+                }
+                let error = identifier(&self.error);
+                self.assign(*param, error);
+            }
+            self.statement(*body);
+            finish(self);
+        }
+        self.handler = outer;
+        if let Some(finalizer) = finalizer {
+            let Some(Finalizer {
+                entry,
+                completion,
+                value,
+                exits,
+                ..
+            }) = self.finalizers.pop()
+            else {
+                return;
+            };
+            self.enter(entry);
+            self.statement(finalizer);
+            // Continue with the exit that entered the block, or after it for `Normal`
+            for (index, exit) in exits.into_iter().enumerate().skip(1) {
+                let taken = self.new_state();
+                let next = self.new_state();
+                let test = binary(identifier(&completion), "===", number(index));
+                self.branch(test, taken, next);
+                self.enter(taken);
+                match exit {
+                    Exit::Throw => self.terminate(Terminator::Throw(identifier(&value))),
+                    Exit::Return => self.leave(Exit::Return, Some(identifier(&value))),
+                    exit => self.leave(exit, None),
+                }
+                self.enter(next);
+            }
+        }
+        self.goto(after);
+    }
+
+    /// Leaves the current state for `exit`, through any `finally` blocks in the way.
+    fn leave(&mut self, exit: Exit, value: Option<Node>) {
+        let crossed = match exit {
+            Exit::Jump { depth, .. } => self
+                .finalizers
+                .iter()
+                .rposition(|finalizer| finalizer.depth > depth),
+            _ => self.finalizers.len().checked_sub(1),
+        };
+        let Some(crossed) = crossed else {
+            return match exit {
+                Exit::Return => self.terminate(Terminator::Return(value)),
+                Exit::Jump { target, .. } => self.terminate(Terminator::Goto(target)),
+                Exit::Normal | Exit::Throw => {}
+            };
+        };
+        let finalizer = &mut self.finalizers[crossed];
+        let index = match finalizer.exits.iter().position(|known| *known == exit) {
+            Some(index) => index,
+            None => {
+                finalizer.exits.push(exit);
+                finalizer.exits.len() - 1
+            }
+        };
+        let (entry, completion, stored) = (
+            finalizer.entry,
+            identifier(&finalizer.completion),
+            identifier(&finalizer.value),
+        );
+        if let Some(value) = value {
+            self.assign(stored, value);
+        }
+        self.assign(completion, number(index));
+        self.terminate(Terminator::Goto(entry));
+    }
+
+    /// A labeled statement other than a loop, which only `break label` can leave.
+    fn labeled_block(&mut self, body: Node, labels: Vec<String>) {
+        let after = self.new_state();
+        self.jumps.push(JumpTarget {
+            labels,
+            breakable: false,
+            break_to: after,
+            continue_to: None,
+        });
+        self.statement(body);
+        self.jumps.pop();
+        self.goto(after);
+    }
+
+    fn loop_body(&mut self, body: Node, labels: Vec<String>, after: StateId, next: StateId) {
+        self.jumps.push(JumpTarget {
+            labels,
+            breakable: true,
+            break_to: after,
+            continue_to: Some(next),
+        });
+        self.statement(body);
+        self.jumps.pop();
+    }
+
+    fn jump(&mut self, label: Option<String>, is_break: bool) {
+        let target = self
+            .jumps
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(depth, target)| {
+                let matches = match &label {
+                    Some(label) => target.labels.contains(label),
+                    None => target.breakable,
+                };
+                let target = match matches {
+                    true if is_break => Some(target.break_to),
+                    true => target.continue_to,
+                    false => None,
+                };
+                target.map(|target| Exit::Jump { target, depth })
+            });
+        match target {
+            Some(exit) => self.leave(exit, None),
+            None => self
+                .lowering
+                .error("Jump target cannot cross function boundary."),
+        }
+    }
+
+    fn declare(&mut self, kind: VariableKind, declarations: Vec<VariableDeclarator>) {
+        for declarator in declarations {
+            for name in declarator.id.bound_names() {
+                self.local(name);
+            }
+            match declarator.init {
+                Some(init) => {
+                    let value = self.explode(*init);
+                    self.assign(*declarator.id, value);
+                }
+                // A `let` in a loop starts out undefined on every iteration
+                None if kind != VariableKind::Var
+                    && matches!(*declarator.id, Node::Identifier(..)) =>
+                {
+                    self.assign(*declarator.id, undefined())
+                }
+                None => {}
+            }
+        }
+    }
+
+    fn bind_loop_head(&mut self, left: Node, value: Node) {
+        match left {
+            Node::VariableDeclaration {
+                kind,
+                mut declarations,
+            } => {
+                for declarator in &mut declarations {
+                    declarator.init = Some(Box::new(value.clone()));
+                }
+                self.declare(kind, declarations);
+            }
+            target => self.assign(target, value),
+        }
+    }
+
+    // Expressions
+
+    /// Emits the parts of `expression` up to its last suspension and returns the rest.
+    fn explode(&mut self, expression: Node) -> Node {
+        if !contains_suspension(&expression) {
+            return expression;
+        }
+        match expression {
+            Node::AwaitExpression(argument) => {
+                let value = self.explode(*argument);
+                self.suspend(|result, resume| Terminator::Await {
+                    value,
+                    result,
+                    resume,
+                })
+            }
+            Node::YieldExpression { argument, delegate } => {
+                let value = argument.map(|argument| self.explode(*argument));
+                let on_return = (!self.finalizers.is_empty()).then(|| self.new_state());
+                let result = self.suspend(|result, resume| Terminator::Yield {
+                    value,
+                    delegate,
+                    result,
+                    resume,
+                    on_return,
+                });
+                if let Some(on_return) = on_return {
+                    let resume = self.current;
+                    self.enter(on_return);
+                    self.leave(Exit::Return, Some(result.clone()));
+                    self.enter(resume);
+                }
+                result
+            }
+            Node::BinaryExpression {
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.operand(*left, contains_suspension(&right));
+                let right = self.explode(*right);
+                binary(left, &operator, right)
+            }
+            Node::LogicalExpression {
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.explode(*left);
+                let result = self.temporary();
+                self.assign(result.clone(), left);
+                let evaluate = self.new_state();
+                let after = self.new_state();
+                match operator.as_str() {
+                    "&&" => self.branch(result.clone(), evaluate, after),
+                    "||" => self.branch(result.clone(), after, evaluate),
+                    _ => {
+                        let test = Node::LogicalExpression {
+                            left: Box::new(binary(result.clone(), "===", null())),
+                            operator: "||".to_string(),
+                            right: Box::new(binary(result.clone(), "===", undefined())),
+                        };
+                        self.branch(test, evaluate, after)
+                    }
+                }
+                self.enter(evaluate);
+                let right = self.explode(*right);
+                self.assign(result.clone(), right);
+                self.goto(after);
+                result
+            }
+            Node::ConditionalExpression {
+                test,
+                consequent,
+                alternate,
+            } => {
+                let test = self.explode(*test);
+                let result = self.temporary();
+                let then_state = self.new_state();
+                let else_state = self.new_state();
+                let after = self.new_state();
+                self.branch(test, then_state, else_state);
+                for (state, value) in [(then_state, consequent), (else_state, alternate)] {
+                    self.enter(state);
+                    let value = self.explode(*value);
+                    self.assign(result.clone(), value);
+                    self.terminate(Terminator::Goto(after));
+                }
+                self.enter(after);
+                result
+            }
+            Node::AssignmentExpression {
+                left,
+                operator,
+                right,
+            } => {
+                let left = self.reference(*left, contains_suspension(&right));
+                let right = self.explode(*right);
+                Node::AssignmentExpression {
+                    left: Box::new(left),
+                    operator,
+                    right: Box::new(right),
+                }
+            }
+            Node::UnaryExpression { operator, argument } => Node::UnaryExpression {
+                operator,
+                argument: Box::new(self.explode(*argument)),
+            },
+            Node::MemberExpression { .. } => self.reference(expression, false),
+            Node::CallExpression {
+                callee,
+                type_arguments,
+                arguments,
+                optional,
+            } => {
+                let later = arguments.iter().any(contains_suspension);
+                let callee = self.reference(*callee, later);
+                Node::CallExpression {
+                    callee: Box::new(callee),
+                    type_arguments,
+                    arguments: self.operands(arguments),
+                    optional,
+                }
+            }
+            Node::NewExpression {
+                callee,
+                type_arguments,
+                arguments,
+            } => {
+                let callee = self.operand(*callee, arguments.iter().any(contains_suspension));
+                Node::NewExpression {
+                    callee: Box::new(callee),
+                    type_arguments,
+                    arguments: self.operands(arguments),
+                }
+            }
+            Node::ArrayExpression(elements) => {
+                let holes: Vec<bool> = elements.iter().map(Option::is_none).collect();
+                let mut operands = self.operands(elements.into_iter().flatten().collect());
+                operands.reverse();
+                let elements = holes
+                    .into_iter()
+                    .map(|hole| if hole { None } else { operands.pop() })
+                    .collect();
+                Node::ArrayExpression(elements)
+            }
+            Node::ObjectExpression(elements) => {
+                let suspends: Vec<bool> = elements.iter().map(contains_suspension).collect();
+                let elements = elements
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, element)| {
+                        let later = suspends[index + 1..].contains(&true);
+                        match element {
+                            Node::Property {
+                                key,
+                                computed,
+                                value,
+                                shorthand,
+                            } => {
+                                let key = if computed {
+                                    self.operand(*key, later || contains_suspension(&value))
+                                } else {
+                                    *key
+                                };
+                                Node::Property {
+                                    key: Box::new(key),
+                                    computed,
+                                    value: Box::new(self.operand(*value, later)),
+                                    shorthand,
+                                }
+                            }
+                            element => self.operand(element, later),
+                        }
+                    })
+                    .collect();
+                Node::ObjectExpression(elements)
+            }
+            Node::SpreadElement(argument) => Node::SpreadElement(Box::new(self.explode(*argument))),
+            mut expression => {
+                for child in expression.children_mut() {
+                    let value = std::mem::replace(child, Node::EmptyStatement);
+                    *child = self.explode(value);
+                }
+                expression
+            }
+        }
+    }
+
+    fn suspend(&mut self, terminator: impl FnOnce(String, StateId) -> Terminator) -> Node {
+        let result = self.temporary_name();
+        let resume = self.new_state();
+        self.terminate(terminator(result.clone(), resume));
+        self.enter(resume);
+        Node::Identifier(result, Span::default())
+    }
+
+    /// An operand evaluated before a later suspension is stored, since the suspension
+    /// could change what it refers to.
+    fn operand(&mut self, expression: Node, later_suspends: bool) -> Node {
+        match expression {
+            Node::SpreadElement(argument) => {
+                Node::SpreadElement(Box::new(self.operand(*argument, later_suspends)))
+            }
+            Node::MethodDefinition { .. } => expression,
+            expression => {
+                let value = self.explode(expression);
+                if !later_suspends || is_stable(&value, &self.lowering.names) {
+                    return value;
+                }
+                let temporary = self.temporary();
+                self.assign(temporary.clone(), value);
+                temporary
+            }
+        }
+    }
+
+    fn operands(&mut self, expressions: Vec<Node>) -> Vec<Node> {
+        let last = expressions.iter().rposition(contains_suspension);
+        expressions
+            .into_iter()
+            .enumerate()
+            .map(|(index, expression)| {
+                let later = last.is_some_and(|last| index < last);
+                self.operand(expression, later)
+            })
+            .collect()
+    }
+
+    /// An assignment target or callee. Its object and key are evaluated before a later
+    /// suspension, but it stays a member expression so assignments and `this` still work.
+    fn reference(&mut self, expression: Node, later_suspends: bool) -> Node {
+        match expression {
+            Node::MemberExpression {
+                object,
+                property,
+                computed,
+                optional,
+            } => {
+                let property_suspends = computed && contains_suspension(&property);
+                let object = self.operand(*object, later_suspends || property_suspends);
+                let property = if computed {
+                    self.operand(*property, later_suspends)
+                } else {
+                    *property
+                };
+                Node::MemberExpression {
+                    object: Box::new(object),
+                    property: Box::new(property),
+                    computed,
+                    optional,
+                }
+            }
+            Node::Identifier(..) | Node::ObjectPattern(_) | Node::ArrayPattern(_) => expression,
+            expression => self.operand(expression, later_suspends),
+        }
+    }
+}
+
+/// Whether `node` contains a `yield`, `await` or `for await` outside nested functions.
+fn contains_suspension(node: &Node) -> bool {
+    match node {
+        Node::AwaitExpression(_)
+        | Node::YieldExpression { .. }
+        | Node::ForOfStatement { is_await: true, .. } => true,
+        Node::FunctionDeclaration { .. }
+        | Node::MethodDefinition { .. }
+        | Node::ArrowFunctionExpression { .. }
+        | Node::ClassDeclaration { .. } => false,
+        _ => node.children().into_iter().any(contains_suspension),
+    }
+}
+
+/// Statements that suspend, return, or jump out of themselves cannot run as a unit inside
+/// one state.
+fn needs_split(statement: &Node) -> bool {
+    contains_suspension(statement) || escapes(statement, &mut Vec::new(), false, false)
+}
+
+fn escapes(node: &Node, labels: &mut Vec<String>, in_iteration: bool, in_breakable: bool) -> bool {
+    match node {
+        Node::ReturnStatement(_) => true,
+        Node::BreakStatement(None) => !in_breakable,
+        Node::ContinueStatement(None) => !in_iteration,
+        Node::BreakStatement(Some(label)) | Node::ContinueStatement(Some(label)) => {
+            !labels.contains(label)
+        }
+        Node::FunctionDeclaration { .. }
+        | Node::MethodDefinition { .. }
+        | Node::ArrowFunctionExpression { .. }
+        | Node::ClassDeclaration { .. } => false,
+        Node::LabeledStatement { label, body } => {
+            labels.push(label.clone());
+            let escapes = escapes(body, labels, in_iteration, in_breakable);
+            labels.pop();
+            escapes
+        }
+        _ if is_iteration(node) => node
+            .children()
+            .into_iter()
+            .any(|child| escapes(child, labels, true, true)),
+        Node::SwitchStatement { .. } => node
+            .children()
+            .into_iter()
+            .any(|child| escapes(child, labels, in_iteration, true)),
+        _ => node
+            .children()
+            .into_iter()
+            .any(|child| escapes(child, labels, in_iteration, in_breakable)),
+    }
+}
+
+fn is_iteration(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::WhileStatement { .. }
+            | Node::DoWhileStatement { .. }
+            | Node::ForStatement { .. }
+            | Node::ForInStatement { .. }
+            | Node::ForOfStatement { .. }
+    )
+}
+
+/// Values that no suspension can change: literals and the machine's own temporaries.
+fn is_stable(node: &Node, names: &FreshNames) -> bool {
+    match node {
+        Node::Literal { .. } | Node::ThisExpression | Node::SuperExpression => true,
+        Node::Identifier(name, _) => names.is_generated(name),
+        _ => false,
+    }
+}
+
+/// Drops unreachable states and numbers the rest in the order they are reached.
+fn compact(states: Vec<State>) -> Vec<State> {
+    let mut states: Vec<Option<State>> = states.into_iter().map(Some).collect();
+    let mut ids: Vec<Option<StateId>> = vec![None; states.len()];
+    let mut order = vec![0];
+    ids[0] = Some(0);
+    let mut compacted = Vec::new();
+    while let Some(&old) = order.get(compacted.len()) {
+        let Some(mut state) = states[old].take() else {
+            break;
+        };
+        for target in state.targets_mut() {
+            let id = *ids[*target].get_or_insert_with(|| {
+                order.push(*target);
+                order.len() - 1
+            });
+            *target = id;
+        }
+        compacted.push(state);
+    }
+    compacted
+}
+
+fn helper_call(kind: CoroutineKind, index: usize) -> Node {
+    call(identifier(kind.runtime_helper()), vec![number(index)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::tokenizer::Tokenizer;
+
+    fn lower(source: &str) -> (Node, Vec<StateMachine>) {
+        let tokens = Tokenizer::new(source.to_string()).tokenize();
+        let program = Parser::new(tokens).parse().result.expect("parses");
+        let lowered = lower_coroutines(&program);
+        assert!(lowered.errors.is_empty(), "{:?}", lowered.errors);
+        lowered.result.expect("lowers")
+    }
+
+    /// The locals the first function of `program` declares before its machine starts.
+    fn locals(program: &Node) -> Vec<String> {
+        let Node::Program(body) = program else {
+            return Vec::new();
+        };
+        let Some(Node::FunctionDeclaration {
+            body: Some(body), ..
+        }) = body.first()
+        else {
+            return Vec::new();
+        };
+        let Node::BlockStatement(statements, _) = body.as_ref() else {
+            return Vec::new();
+        };
+        match statements.first() {
+            Some(declaration @ Node::VariableDeclaration { .. }) => lexical_names(declaration),
+            _ => Vec::new(),
+        }
+    }
+
+    fn returned(machine: &StateMachine) -> Vec<&Node> {
+        machine
+            .states
+            .iter()
+            .filter_map(|state| match &state.terminator {
+                Terminator::Return(Some(value)) => Some(value),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn shadowing_bindings_get_their_own_locals() {
+        let (program, machines) =
+            lower("async function r() { let a = 1; { let a = 2; await null; } return a; }");
+        assert_eq!(locals(&program), ["a", "a$0", "$s0"]);
+        assert!(matches!(returned(&machines[0])[..], [Node::Identifier(name, _)] if name == "a"));
+    }
+
+    #[test]
+    fn catch_parameters_do_not_replace_outer_bindings() {
+        let (program, _) =
+            lower("async function f(x) { let e = 1; try { await x; } catch (e) { e; } return e; }");
+        assert!(locals(&program).contains(&"e$0".to_string()));
+    }
+
+    #[test]
+    fn captured_loop_bindings_get_an_object_per_iteration() {
+        let (program, machines) = lower(
+            "async function f(fs) { for (let i = 0; i < 3; i++) { fs.push(() => i); await null; } }",
+        );
+        assert!(locals(&program).contains(&"$b0".to_string()));
+        let states = format!("{:?}", machines[0].states);
+        // The closure is given the object of its own iteration
+        assert!(states.contains(r#"params: [Parameter { binding: Identifier("$b0""#));
+        assert!(!states.contains(r#"left: Identifier("i""#));
+    }
+
+    #[test]
+    fn temporaries_skip_names_the_program_uses() {
+        let (_, machines) = lower("async function f($s0) { return g($s0, await 1); }");
+        let states = format!("{:?}", machines[0].states);
+        // The parameter is saved before the `await`, in a temporary of another name
+        assert!(states.contains(r#"left: Identifier("$s1", Span { row: 0, col: 0, file: 0 }), operator: "=", right: Identifier("$s0""#));
+        assert!(!states.contains(r#"left: Identifier("$s0""#));
+    }
+
+    #[test]
+    fn dense_switches_dispatch_through_a_table() {
+        let (_, machines) = lower(
+            "async function f(x) { switch (x) { case 1: case 2: await x; case 4: break; default: return 0; case 3: } }",
+        );
+        let switch = machines[0]
+            .states
+            .iter()
+            .find_map(|state| match &state.terminator {
+                Terminator::Switch {
+                    low,
+                    table,
+                    default,
+                    ..
+                } => Some((*low, table.clone(), *default)),
+                _ => None,
+            });
+        let (low, table, default) = switch.expect("a jump table");
+        assert_eq!(low, 1);
+        assert_eq!(table.len(), 4);
+        // `case 1` falls through into `case 2`, so both have states of their own
+        assert_ne!(table[0], table[1]);
+        assert!(!table.contains(&default));
+    }
+
+    #[test]
+    fn async_generators_delegate() {
+        let (_, machines) = lower("async function* f(g) { const r = yield* g; return r; }");
+        assert_eq!(machines[0].kind, CoroutineKind::AsyncGenerator);
+        assert!(machines[0]
+            .states
+            .iter()
+            .any(|state| matches!(state.terminator, Terminator::Yield { delegate: true, .. })));
+    }
+}
@@ -0,0 +1,167 @@
+use crate::enums::parse_number;
+use crate::parser::{Node, SwitchCase};
+use crate::tokenizer::TokenType;
+
+/// Fewer cases than this are cheaper to compare one by one.
+const MIN_TABLE_CASES: usize = 4;
+
+/// How a `switch` picks the case it starts executing at. Execution then runs through the
+/// following cases until a `break`, so fallthrough needs no extra bookkeeping: every target
+/// is an index into the statement's cases, and `None` means past the last case.
+#[derive(Debug, PartialEq, Clone)]
+pub enum SwitchDispatch {
+    /// `table[discriminant - low]` for integer discriminants in range, `default` otherwise.
+    JumpTable {
+        low: i64,
+        table: Vec<Option<usize>>,
+        default: Option<usize>,
+    },
+    /// Strict comparison with each case test in source order, then `default`.
+    Comparisons { default: Option<usize> },
+}
+
+impl SwitchDispatch {
+    /// The case a jump table dispatches `discriminant` to. Comparisons need the case values
+    /// at runtime, so they have no static target.
+    pub fn target(&self, discriminant: f64) -> Option<usize> {
+        let SwitchDispatch::JumpTable {
+            low,
+            table,
+            default,
+        } = self
+        else {
+            return None;
+        };
+        if discriminant.fract() != 0.0 {
+            return *default;
+        }
+        // Saturates for discriminants beyond the range of i64, which are out of range anyway
+        let offset = (discriminant as i64).checked_sub(*low);
+        match offset.and_then(|offset| table.get(usize::try_from(offset).ok()?)) {
+            Some(Some(case)) => Some(*case),
+            _ => *default,
+        }
+    }
+}
+
+/// Chooses between a jump table and a chain of comparisons for a switch's cases.
+///
+/// A table is used when every test is an integer literal (const enum members have been
+/// inlined to literals by then) and the values are dense: at least half the slots between
+/// the smallest and largest value are cases.
+pub fn plan_switch(cases: &[SwitchCase]) -> SwitchDispatch {
+    let default = cases.iter().position(|case| case.test.is_none());
+    let values: Option<Vec<(i64, usize)>> = cases
+        .iter()
+        .enumerate()
+        .filter_map(|(index, case)| case.test.as_deref().map(|test| (index, test)))
+        .map(|(index, test)| integer_value(test).map(|value| (value, index)))
+        .collect();
+    let comparisons = SwitchDispatch::Comparisons { default };
+    let Some(values) = values.filter(|values| values.len() >= MIN_TABLE_CASES) else {
+        return comparisons;
+    };
+    let low = values.iter().map(|(value, _)| *value).min().unwrap_or(0);
+    let high = values.iter().map(|(value, _)| *value).max().unwrap_or(0);
+    let slots = high.abs_diff(low) as usize + 1;
+    if slots > values.len() * 2 {
+        return comparisons;
+    }
+    let mut table = vec![None; slots];
+    for (value, index) in values {
+        // A repeated value never matches its later cases
+        table[(value - low) as usize].get_or_insert(index);
+    }
+    SwitchDispatch::JumpTable {
+        low,
+        table,
+        default,
+    }
+}
+
+fn integer_value(test: &Node) -> Option<i64> {
+    let value = match test {
+        Node::Literal {
+            token_type: TokenType::NumberLiteral,
+            value,
+            ..
+        } => parse_number(value)?,
+        Node::UnaryExpression { operator, argument } if operator == "-" => {
+            -(integer_value(argument)? as f64)
+        }
+        _ => return None,
+    };
+    // Integers beyond 2^53 are not exact
+    (value.fract() == 0.0 && value.abs() <= 9007199254740992.0).then_some(value as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error_handling::Span;
+
+    fn case(value: Option<i64>) -> SwitchCase {
+        let test = value.map(|value| {
+            let literal = Node::Literal {
+                token_type: TokenType::NumberLiteral,
+                value: value.abs().to_string(),
+                span: Span::default(),
+            };
+            if value < 0 {
+                Node::UnaryExpression {
+                    operator: "-".to_string(),
+                    argument: Box::new(literal),
+                }
+            } else {
+                literal
+            }
+        });
+        SwitchCase {
+            test: test.map(Box::new),
+            consequent: Vec::new(),
+        }
+    }
+
+    fn cases(values: &[Option<i64>]) -> Vec<SwitchCase> {
+        values.iter().copied().map(case).collect()
+    }
+
+    #[test]
+    fn dense_integer_cases_get_a_table() {
+        let dispatch = plan_switch(&cases(&[Some(-5), Some(-4), None, Some(-2), Some(-1)]));
+        assert_eq!(
+            dispatch,
+            SwitchDispatch::JumpTable {
+                low: -5,
+                table: vec![Some(0), Some(1), None, Some(3), Some(4)],
+                default: Some(2),
+            }
+        );
+        assert_eq!(dispatch.target(-4.0), Some(1));
+        assert_eq!(dispatch.target(-3.0), Some(2));
+        assert_eq!(dispatch.target(-1.5), Some(2));
+    }
+
+    #[test]
+    fn sparse_or_few_cases_are_compared() {
+        let sparse = plan_switch(&cases(&[Some(1), Some(2), Some(3), Some(100)]));
+        assert_eq!(sparse, SwitchDispatch::Comparisons { default: None });
+        let few = plan_switch(&cases(&[Some(1), None, Some(2)]));
+        assert_eq!(few, SwitchDispatch::Comparisons { default: Some(1) });
+        assert_eq!(few.target(1.0), None);
+    }
+
+    #[test]
+    fn repeated_values_go_to_their_first_case() {
+        let dispatch = plan_switch(&cases(&[Some(0), Some(1), Some(1), Some(2)]));
+        assert_eq!(dispatch.target(1.0), Some(1));
+    }
+
+    #[test]
+    fn out_of_range_discriminants_go_to_the_default() {
+        let dispatch = plan_switch(&cases(&[Some(-5), Some(-4), Some(-3), Some(-2), None]));
+        for discriminant in [1e300, -1e300, f64::INFINITY, f64::NAN, 1.0, -6.0] {
+            assert_eq!(dispatch.target(discriminant), Some(4), "{}", discriminant);
+        }
+    }
+}
@@ -1,13 +1,175 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::mem::{discriminant, Discriminant};
+use std::ops::{BitOr, BitOrAssign};
 
+use crate::error_handling::Span;
 use crate::parser::Node;
+use crate::types_checking::{Shapes, Type};
+
+/// A scope in a `SymbolTable`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ScopeId(pub usize);
+
+/// A symbol in a `SymbolTable`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SymbolId(pub usize);
+
+/// How the table finds a node again: where it starts in the source, file included, and what
+/// kind of node it is. Unlike its address, that stays the same when the tree is cloned or
+/// moved, or linked with other modules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct NodeKey(Span, Discriminant<Node>);
+
+impl NodeKey {
+    fn of(node: &Node) -> Self {
+        NodeKey(node.span(), discriminant(node))
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Symbol {
     pub name: String,
-    pub type_annotation: Option<Node>,
+    pub flags: SymbolFlags,
+    pub type_annotation: Option<Node>, // as declared, naming types in the declaring scope
     pub scope: Scope,
     pub is_constant: bool,
+    pub span: Span,                  // of the declaring identifier, when it has one
+    pub value_type: Option<Type>,    // resolved by `type_lowering`, for names with a value
+    pub declared_type: Option<Type>, // the type a type name stands for
+}
+
+/// The kinds of declaration a symbol has. Declarations of the same name in a scope merge
+/// into one symbol when their kinds allow it, as `interface Foo` and `const Foo` do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SymbolFlags(u16);
+
+impl SymbolFlags {
+    pub const NONE: SymbolFlags = SymbolFlags(0);
+    pub const VARIABLE: SymbolFlags = SymbolFlags(1); // `var`
+    pub const BLOCK_SCOPED_VARIABLE: SymbolFlags = SymbolFlags(1 << 1); // `let` and `const`
+    pub const PARAMETER: SymbolFlags = SymbolFlags(1 << 2);
+    pub const FUNCTION: SymbolFlags = SymbolFlags(1 << 3);
+    pub const CLASS: SymbolFlags = SymbolFlags(1 << 4);
+    pub const INTERFACE: SymbolFlags = SymbolFlags(1 << 5);
+    pub const TYPE_ALIAS: SymbolFlags = SymbolFlags(1 << 6);
+    pub const ENUM: SymbolFlags = SymbolFlags(1 << 7);
+    pub const ENUM_MEMBER: SymbolFlags = SymbolFlags(1 << 8);
+    pub const NAMESPACE: SymbolFlags = SymbolFlags(1 << 9); // with a runtime object
+    pub const TYPE_NAMESPACE: SymbolFlags = SymbolFlags(1 << 10); // only declaring types
+    pub const TYPE_PARAMETER: SymbolFlags = SymbolFlags(1 << 11);
+    pub const IMPORT: SymbolFlags = SymbolFlags(1 << 12); // may have any meaning
+    pub const CONST_ENUM: SymbolFlags = SymbolFlags(1 << 13); // set along with `ENUM`
+
+    const VALUES: SymbolFlags = SymbolFlags(
+        Self::VARIABLE.0
+            | Self::BLOCK_SCOPED_VARIABLE.0
+            | Self::PARAMETER.0
+            | Self::FUNCTION.0
+            | Self::CLASS.0
+            | Self::ENUM.0
+            | Self::ENUM_MEMBER.0
+            | Self::NAMESPACE.0
+            | Self::IMPORT.0,
+    );
+    const TYPES: SymbolFlags = SymbolFlags(
+        Self::CLASS.0
+            | Self::INTERFACE.0
+            | Self::TYPE_ALIAS.0
+            | Self::ENUM.0
+            | Self::ENUM_MEMBER.0
+            | Self::TYPE_PARAMETER.0
+            | Self::IMPORT.0,
+    );
+    const NAMESPACES: SymbolFlags =
+        SymbolFlags(Self::NAMESPACE.0 | Self::TYPE_NAMESPACE.0 | Self::ENUM.0 | Self::IMPORT.0);
+
+    pub fn contains(self, other: SymbolFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn intersects(self, other: SymbolFlags) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    pub fn has_meaning(self, meaning: Meaning) -> bool {
+        self.intersects(match meaning {
+            Meaning::Value => Self::VALUES,
+            Meaning::Type => Self::TYPES,
+            Meaning::Namespace => Self::NAMESPACES,
+        })
+    }
+
+    /// The declarations a declaration with these flags can't merge into.
+    fn excludes(self) -> SymbolFlags {
+        let mut excludes = SymbolFlags::NONE;
+        for (flag, excluded) in [
+            (
+                Self::VARIABLE,
+                Self::VALUES.without(Self::VARIABLE | Self::PARAMETER),
+            ),
+            (Self::BLOCK_SCOPED_VARIABLE, Self::VALUES),
+            (Self::PARAMETER, Self::VALUES),
+            (
+                Self::FUNCTION,
+                Self::VALUES.without(Self::FUNCTION | Self::NAMESPACE),
+            ),
+            (
+                Self::CLASS,
+                (Self::VALUES | Self::TYPES).without(Self::INTERFACE | Self::NAMESPACE),
+            ),
+            (
+                Self::INTERFACE,
+                Self::TYPES.without(Self::CLASS | Self::INTERFACE),
+            ),
+            (Self::TYPE_ALIAS, Self::TYPES),
+            (
+                Self::ENUM,
+                (Self::VALUES | Self::TYPES).without(Self::ENUM | Self::NAMESPACE),
+            ),
+            (Self::ENUM_MEMBER, Self::VALUES | Self::TYPES),
+            (
+                Self::NAMESPACE,
+                Self::VALUES.without(Self::FUNCTION | Self::CLASS | Self::ENUM | Self::NAMESPACE),
+            ),
+            (
+                Self::TYPE_PARAMETER,
+                Self::TYPES.without(Self::TYPE_PARAMETER),
+            ),
+            (Self::IMPORT, Self::VALUES | Self::TYPES | Self::NAMESPACES),
+        ] {
+            if self.intersects(flag) {
+                excludes |= excluded;
+            }
+        }
+        excludes
+    }
+
+    fn without(self, other: SymbolFlags) -> SymbolFlags {
+        SymbolFlags(self.0 & !other.0)
+    }
+}
+
+impl BitOr for SymbolFlags {
+    type Output = SymbolFlags;
+
+    fn bitor(self, other: SymbolFlags) -> SymbolFlags {
+        SymbolFlags(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for SymbolFlags {
+    fn bitor_assign(&mut self, other: SymbolFlags) {
+        self.0 |= other.0;
+    }
+}
+
+/// What a name is being looked up as. A type annotation, a `typeof` query and the left of
+/// `A.B` in a type each ask for a different one, and see past symbols without it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Meaning {
+    Value,
+    Type,
+    Namespace,
 }
 
 #[derive(Debug, Clone)]
@@ -17,31 +179,222 @@ pub enum Scope {
     Block,
 }
 
+#[derive(Debug)]
+pub struct ScopeData {
+    pub kind: Scope,
+    pub parent: Option<ScopeId>,
+    pub children: Vec<ScopeId>, // in the order they were added
+    pub symbols: HashMap<String, SymbolId>,
+}
+
+/// Every scope of a program and the symbols declared in them, as a tree of `ScopeId`s
+/// rooted at the scope of the built-in globals, which holds the global scope.
+///
+/// Scopes stay in the table after the code that entered them is done with them, so passes
+/// after binding can walk the whole tree, find the scope a node introduced with `scope_of`,
+/// or the symbol an identifier was bound to with `symbol_of`. Nodes are told apart by
+/// their span and kind, so lookups work on a clone of the bound tree, or a tree lowered
+/// from it, as long as the nodes keep their spans. Nodes made up by passes have no span
+/// and can't be told apart, so a tree is bound before any pass adds to it.
 #[derive(Debug)]
 pub struct SymbolTable {
-    pub symbols: HashMap<String, Symbol>,
-    pub parent: Option<Box<SymbolTable>>,
+    scopes: Vec<ScopeData>,
+    symbols: Vec<(Symbol, ScopeId)>, // with the scope declaring each
+    node_scopes: HashMap<NodeKey, ScopeId>,
+    node_symbols: HashMap<NodeKey, SymbolId>,
+    tdz_checks: HashSet<NodeKey>,
+    shapes: Shapes, // of the types resolved for the symbols, and any made while checking
 }
 
 impl SymbolTable {
-    pub fn new(parent: Option<Box<SymbolTable>>) -> Self {
+    /// Where built-ins like `Map` and `console` are declared, outside the global scope so
+    /// a program can declare the same names.
+    pub const BUILTINS: ScopeId = ScopeId(0);
+    pub const GLOBAL: ScopeId = ScopeId(1);
+
+    pub fn new() -> Self {
         SymbolTable {
+            scopes: vec![
+                ScopeData {
+                    kind: Scope::Global,
+                    parent: None,
+                    children: vec![SymbolTable::GLOBAL],
+                    symbols: HashMap::new(),
+                },
+                ScopeData {
+                    kind: Scope::Global,
+                    parent: Some(SymbolTable::BUILTINS),
+                    children: Vec::new(),
+                    symbols: HashMap::new(),
+                },
+            ],
+            symbols: Vec::new(),
+            node_scopes: HashMap::new(),
+            node_symbols: HashMap::new(),
+            tdz_checks: HashSet::new(),
+            shapes: Shapes::default(),
+        }
+    }
+
+    /// Adds a scope inside `parent`. `node` is the function, block or other node that
+    /// introduces it, if any.
+    pub fn add_scope(&mut self, kind: Scope, parent: ScopeId, node: Option<&Node>) -> ScopeId {
+        let id = ScopeId(self.scopes.len());
+        self.scopes.push(ScopeData {
+            kind,
+            parent: Some(parent),
+            children: Vec::new(),
             symbols: HashMap::new(),
-            parent,
+        });
+        self.scopes[parent.0].children.push(id);
+        if let Some(node) = node {
+            self.node_scopes.insert(NodeKey::of(node), id);
         }
+        id
+    }
+
+    pub fn scope(&self, id: ScopeId) -> &ScopeData {
+        &self.scopes[id.0]
+    }
+
+    /// The scope `node` introduced, if it was given to `add_scope`.
+    pub fn scope_of(&self, node: &Node) -> Option<ScopeId> {
+        self.node_scopes.get(&NodeKey::of(node)).copied()
+    }
+
+    /// Records that the identifier or declaration `node` declares or refers to `symbol`.
+    pub fn bind_node(&mut self, node: &Node, symbol: SymbolId) {
+        self.node_symbols.insert(NodeKey::of(node), symbol);
     }
 
-    pub fn insert(&mut self, symbol: Symbol) -> Result<(), String> {
-        if self.symbols.contains_key(&symbol.name) {
-            return Err(format!("Symbol '{}' already exists", symbol.name));
+    /// The symbol an identifier or declaration was bound to with `bind_node`.
+    pub fn symbol_of(&self, node: &Node) -> Option<SymbolId> {
+        self.node_symbols.get(&NodeKey::of(node)).copied()
+    }
+
+    /// Records that the identifier `node` may be reached before its `let`, `const` or class
+    /// declaration has run, so it has to be checked at runtime.
+    pub fn add_tdz_check(&mut self, node: &Node) {
+        self.tdz_checks.insert(NodeKey::of(node));
+    }
+
+    pub fn needs_tdz_check(&self, node: &Node) -> bool {
+        self.tdz_checks.contains(&NodeKey::of(node))
+    }
+
+    pub fn shapes(&self) -> &Shapes {
+        &self.shapes
+    }
+
+    /// `scope` and the scopes enclosing it, innermost first.
+    pub fn ancestors(&self, scope: ScopeId) -> impl Iterator<Item = ScopeId> + '_ {
+        std::iter::successors(Some(scope), |id| self.scopes[id.0].parent)
+    }
+
+    /// Declares `symbol` in `scope`, merging it into a symbol of the same name already
+    /// there if their flags allow it, and returns the symbol the name now refers to.
+    /// The error is a message with the code tsc gives it.
+    pub fn insert(&mut self, scope: ScopeId, symbol: Symbol) -> Result<SymbolId, (String, u32)> {
+        if let Some(&existing) = self.scopes[scope.0].symbols.get(&symbol.name) {
+            let merged = &mut self.symbols[existing.0].0;
+            if merged.flags.contains(SymbolFlags::ENUM)
+                && symbol.flags.contains(SymbolFlags::ENUM)
+                && merged.flags.contains(SymbolFlags::CONST_ENUM)
+                    != symbol.flags.contains(SymbolFlags::CONST_ENUM)
+            {
+                return Err((
+                    "Enum declarations must all be const or non-const.".to_string(),
+                    2473,
+                ));
+            }
+            if merged.flags.intersects(symbol.flags.excludes()) {
+                let flags = merged.flags | symbol.flags;
+                return Err(if flags.contains(SymbolFlags::IMPORT) {
+                    (
+                        format!(
+                            "Import declaration conflicts with local declaration of '{}'.",
+                            symbol.name
+                        ),
+                        2440,
+                    )
+                } else if flags.contains(SymbolFlags::BLOCK_SCOPED_VARIABLE) {
+                    (
+                        format!("Cannot redeclare block-scoped variable '{}'.", symbol.name),
+                        2451,
+                    )
+                } else {
+                    (format!("Duplicate identifier '{}'.", symbol.name), 2300)
+                });
+            }
+            // The value declaration decides how the value can be used
+            if symbol.flags.has_meaning(Meaning::Value) && !merged.flags.has_meaning(Meaning::Value)
+            {
+                merged.is_constant = symbol.is_constant;
+                merged.span = symbol.span;
+            }
+            merged.flags |= symbol.flags;
+            return Ok(existing);
         }
-        self.symbols.insert(symbol.name.clone(), symbol);
-        Ok(())
+        let id = SymbolId(self.symbols.len());
+        self.scopes[scope.0].symbols.insert(symbol.name.clone(), id);
+        self.symbols.push((symbol, scope));
+        Ok(id)
+    }
+
+    /// Makes `symbol` visible in `scope` as well as the scope declaring it, unless `scope`
+    /// already has a symbol of that name.
+    pub fn share(&mut self, scope: ScopeId, symbol: SymbolId) {
+        let name = self.symbols[symbol.0].0.name.clone();
+        self.scopes[scope.0].symbols.entry(name).or_insert(symbol);
+    }
+
+    pub fn symbol(&self, id: SymbolId) -> &Symbol {
+        &self.symbols[id.0].0
+    }
+
+    pub fn symbol_mut(&mut self, id: SymbolId) -> &mut Symbol {
+        &mut self.symbols[id.0].0
+    }
+
+    /// Every symbol in the table, in the order they were declared.
+    pub fn symbols(&self) -> impl Iterator<Item = (SymbolId, &Symbol)> + '_ {
+        self.symbols
+            .iter()
+            .enumerate()
+            .map(|(index, (symbol, _))| (SymbolId(index), symbol))
     }
 
-    pub fn lookup(&self, name: &str) -> Option<&Symbol> {
-        self.symbols.get(name).or_else(|| {
-            self.parent.as_ref().and_then(|parent| parent.lookup(name))
+    /// The scope `id` was declared in.
+    pub fn declaring_scope(&self, id: SymbolId) -> ScopeId {
+        self.symbols[id.0].1
+    }
+
+    /// Resolves `name` as `meaning` from `scope`, looking outwards through the enclosing
+    /// scopes.
+    pub fn lookup(&self, scope: ScopeId, name: &str, meaning: Meaning) -> Option<SymbolId> {
+        self.lookup_with_depth(scope, name, meaning)
+            .map(|(symbol, _)| symbol)
+    }
+
+    /// Like `lookup`, also returning how many scopes out the symbol was found.
+    pub fn lookup_with_depth(
+        &self,
+        scope: ScopeId,
+        name: &str,
+        meaning: Meaning,
+    ) -> Option<(SymbolId, usize)> {
+        self.ancestors(scope).enumerate().find_map(|(depth, id)| {
+            self.scopes[id.0]
+                .symbols
+                .get(name)
+                .filter(|symbol| self.symbol(**symbol).flags.has_meaning(meaning))
+                .map(|symbol| (*symbol, depth))
         })
     }
-}
\ No newline at end of file
+}
+
+impl Default for SymbolTable {
+    fn default() -> Self {
+        SymbolTable::new()
+    }
+}
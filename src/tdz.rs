@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+
+use crate::ast::{assignment, boolean, call, identifier, statement, string};
+use crate::binder::bind;
+use crate::error_handling::{CompilerResult, Span};
+use crate::names::FreshNames;
+use crate::parser::{Node, VariableDeclarator, VariableKind};
+use crate::symboltable::{SymbolId, SymbolTable};
+
+/// Checks the uses `bind` couldn't prove run after their `let`, `const` or class
+/// declaration, and throws a `ReferenceError` for the ones that don't.
+///
+/// Each declaration with such uses gets a flag (`$t0`, `$t1`, ...), declared `false` at the
+/// start of its scope and set once the declaration has run. A checked use `x` becomes
+/// `$t0 ? x : __tdz("x")`, and an assignment `x = 1` becomes `$t0 ? x = 1 : __tdz("x")`.
+/// The flags of a switch's declarations are declared before the switch, and those of a
+/// loop head before the loop and set at the start of its body.
+///
+/// `table` must come from binding `program`, or a tree `program` was cloned from.
+pub fn insert_tdz_checks(program: &Node, table: &SymbolTable) -> CompilerResult<Node> {
+    let mut lowering = Lowering {
+        table,
+        names: FreshNames::new(program),
+        flags: HashMap::new(),
+        pending: Vec::new(),
+    };
+    lowering.collect_flags(program);
+    let mut lowered = program.clone();
+    lowering.lower(program, &mut lowered);
+    CompilerResult::new(Some(lowered), Vec::new())
+}
+
+/// Binds `program` and inserts its runtime checks.
+pub fn lower_tdz(program: &Node) -> CompilerResult<Node> {
+    let bound = bind(program);
+    let Some(table) = bound.result else {
+        return CompilerResult::new(None, bound.errors);
+    };
+    let lowered = insert_tdz_checks(program, &table);
+    CompilerResult::new(lowered.result, bound.errors)
+}
+
+struct Lowering<'a> {
+    table: &'a SymbolTable,
+    names: FreshNames,
+    flags: HashMap<SymbolId, String>,
+    pending: Vec<String>, // flags to declare before the statement being lowered
+}
+
+impl Lowering<'_> {
+    fn collect_flags(&mut self, node: &Node) {
+        if self.table.needs_tdz_check(node) {
+            if let Some(symbol) = self.table.symbol_of(node) {
+                if !self.flags.contains_key(&symbol) {
+                    let flag = self.names.fresh("$t");
+                    self.flags.insert(symbol, flag);
+                }
+            }
+        }
+        for child in node.children() {
+            self.collect_flags(child);
+        }
+    }
+
+    fn flag_of(&self, identifier: &Node) -> Option<&String> {
+        if !matches!(identifier, Node::Identifier(..)) || !self.table.needs_tdz_check(identifier) {
+            return None;
+        }
+        self.flags.get(&self.table.symbol_of(identifier)?)
+    }
+
+    /// The flags set once `statement` has run.
+    fn declared_flags(&self, statement: &Node) -> Vec<String> {
+        match statement {
+            Node::VariableDeclaration {
+                kind: VariableKind::Let | VariableKind::Const,
+                declarations,
+            } => declarations
+                .iter()
+                .flat_map(|declarator| declarator.id.binding_identifiers())
+                .filter_map(|identifier| self.table.symbol_of(identifier))
+                .filter_map(|symbol| self.flags.get(&symbol).cloned())
+                .collect(),
+            Node::ClassDeclaration { .. } => self
+                .table
+                .symbol_of(statement)
+                .and_then(|symbol| self.flags.get(&symbol).cloned())
+                .into_iter()
+                .collect(),
+            Node::ExportNamedDeclaration {
+                declaration: Some(declaration),
+                ..
+            }
+            | Node::ExportDefaultDeclaration(declaration) => self.declared_flags(declaration),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Lowers `node`, a copy of `original` that the table doesn't know about.
+    fn lower(&mut self, original: &Node, node: &mut Node) {
+        match (original, &mut *node) {
+            (Node::Identifier(name, _), _) => {
+                if let Some(flag) = self.flag_of(original) {
+                    *node = checked(flag, name, node.clone());
+                }
+            }
+            (
+                Node::AssignmentExpression {
+                    left: target,
+                    right: original_value,
+                    ..
+                },
+                Node::AssignmentExpression { right: value, .. },
+            ) if self.flag_of(target).is_some() => {
+                self.lower(original_value, value);
+                self.check_target(target, node);
+            }
+            (
+                Node::UpdateExpression {
+                    argument: target, ..
+                },
+                _,
+            ) if self.flag_of(target).is_some() => self.check_target(target, node),
+            (Node::Program(originals), Node::Program(body))
+            | (Node::BlockStatement(originals, _), Node::BlockStatement(body, _))
+            | (
+                Node::NamespaceDeclaration {
+                    body: originals, ..
+                },
+                Node::NamespaceDeclaration { body, .. },
+            ) => self.statements(originals, body, true),
+            (
+                Node::SwitchStatement {
+                    discriminant: original_discriminant,
+                    cases: original_cases,
+                },
+                Node::SwitchStatement {
+                    discriminant,
+                    cases,
+                },
+            ) => {
+                self.lower(original_discriminant, discriminant);
+                for (original, case) in original_cases.iter().zip(cases) {
+                    if let (Some(original), Some(test)) = (&original.test, &mut case.test) {
+                        self.lower(original, test);
+                    }
+                    // Any case can run first, so the flags go before the switch
+                    self.statements(&original.consequent, &mut case.consequent, false);
+                }
+            }
+            (
+                Node::ForStatement {
+                    init: Some(head), ..
+                }
+                | Node::ForInStatement { left: head, .. }
+                | Node::ForOfStatement { left: head, .. },
+                _,
+            ) => {
+                let flags = self.declared_flags(head);
+                self.lower_children(original, node);
+                if flags.is_empty() {
+                    return;
+                }
+                let (Node::ForStatement { body, .. }
+                | Node::ForInStatement { body, .. }
+                | Node::ForOfStatement { body, .. }) = node
+                else {
+                    return;
+                };
+                let mut statements: Vec<Node> = flags.iter().map(|flag| set_flag(flag)).collect();
+                match std::mem::replace(body.as_mut(), Node::EmptyStatement) {
+                    Node::BlockStatement(block, _) => statements.extend(block),
+                    statement => statements.push(statement),
+                }
+                **body = Node::BlockStatement(statements, Span::default());
+                self.pending.extend(flags);
+            }
+            _ => self.lower_children(original, node),
+        }
+    }
+
+    /// Checks an assignment or update as a whole, since its target can't be wrapped.
+    fn check_target(&self, target: &Node, node: &mut Node) {
+        if let (Some(flag), Node::Identifier(name, _)) = (self.flag_of(target), target) {
+            *node = checked(flag, name, node.clone());
+        }
+    }
+
+    fn lower_children(&mut self, original: &Node, node: &mut Node) {
+        for (original, child) in original.children().into_iter().zip(node.children_mut()) {
+            self.lower(original, child);
+        }
+    }
+
+    /// Lowers a statement list, setting flags after the declarations they are for. The
+    /// flags are declared at its start, or left for the enclosing statement list when
+    /// `declare_flags` is false.
+    fn statements(&mut self, originals: &[Node], body: &mut Vec<Node>, declare_flags: bool) {
+        let mut output = Vec::with_capacity(body.len());
+        let mut flags = Vec::new();
+        for (original, mut statement) in originals.iter().zip(std::mem::take(body)) {
+            let outer = std::mem::take(&mut self.pending);
+            self.lower(original, &mut statement);
+            let pending = std::mem::replace(&mut self.pending, outer);
+            output.extend(pending.iter().map(|flag| declare_flag(flag)));
+            output.push(statement);
+            for flag in self.declared_flags(original) {
+                output.push(set_flag(&flag));
+                flags.push(flag);
+            }
+        }
+        if declare_flags {
+            output.splice(0..0, flags.iter().map(|flag| declare_flag(flag)));
+        } else {
+            self.pending.extend(flags);
+        }
+        *body = output;
+    }
+}
+
+/// `flag ? expression : __tdz("name")`
+fn checked(flag: &str, name: &str, expression: Node) -> Node {
+    Node::ConditionalExpression {
+        test: Box::new(identifier(flag)),
+        consequent: Box::new(expression),
+        alternate: Box::new(call(identifier("__tdz"), vec![string(name)])),
+    }
+}
+
+fn declare_flag(flag: &str) -> Node {
+    Node::VariableDeclaration {
+        kind: VariableKind::Let,
+        declarations: vec![VariableDeclarator {
+            id: Box::new(identifier(flag)),
+            type_annotation: None,
+            init: Some(Box::new(boolean(false))),
+        }],
+    }
+}
+
+fn set_flag(flag: &str) -> Node {
+    statement(assignment(identifier(flag), boolean(true)))
+}
@@ -1,6 +1,4 @@
-use std::collections::VecDeque;
-
-#[derive(Debug, PartialEq, Clone,Copy)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum TokenType {
     // Primitive Types
     NumberType,
@@ -22,6 +20,7 @@ pub enum TokenType {
     Function,
     Class,
     Interface,
+    Enum,
     Let,
     Const,
     Var,
@@ -34,6 +33,7 @@ pub enum TokenType {
     As,
     Async,
     Await,
+    Yield,
     Return,
     If,
     Else,
@@ -103,6 +103,7 @@ pub enum TokenType {
     BitwiseOr,
     BitwiseXor,
     BitwiseNot,
+    CompoundAssignment, // `+=`, `??=` and the rest, with the operator as the value
 
     // Punctuation
     LeftParen,
@@ -238,6 +239,8 @@ impl Tokenizer {
             self.next_char();
             self.next_char();
             self.create_token(TokenType::Spread, "...".to_string())
+        } else if self.peek_char().is_some_and(|c| c.is_ascii_digit()) {
+            self.read_number('.')
         } else {
             self.read_punctuation('.')
         }
@@ -271,19 +274,20 @@ impl Tokenizer {
             ('&', Some('&')) => {
                 self.next_char();
                 value.push('&');
-                return self.create_token(TokenType::AmpersandAmpersand, value);
+                return self.compound_or(TokenType::AmpersandAmpersand, value);
             }
             ('|', Some('|')) => {
                 self.next_char();
                 value.push('|');
-                return self.create_token(TokenType::PipePipe, value);
+                return self.compound_or(TokenType::PipePipe, value);
             }
             ('?', Some('?')) => {
                 self.next_char();
                 value.push('?');
-                return self.create_token(TokenType::NullishCoalescing, value);
+                return self.compound_or(TokenType::NullishCoalescing, value);
             }
-            ('?', Some('.')) => {
+            // `a ? .5 : b` is a conditional, not an optional chain
+            ('?', Some('.')) if !self.peek_next_char().is_some_and(|c| c.is_ascii_digit()) => {
                 self.next_char();
                 value.push('.');
                 return self.create_token(TokenType::OptionalChain, value);
@@ -298,15 +302,30 @@ impl Tokenizer {
                 value.push('-');
                 return self.create_token(TokenType::Decrement, value);
             }
-            ('-', Some('>')) => {
+            ('=', Some('>')) => {
                 self.next_char();
                 value.push('>');
                 return self.create_token(TokenType::Arrow, value);
             }
+            ('<', Some('=')) => {
+                self.next_char();
+                value.push('=');
+                return self.create_token(TokenType::LessEqual, value);
+            }
+            ('>', Some('=')) => {
+                self.next_char();
+                value.push('=');
+                return self.create_token(TokenType::GreaterEqual, value);
+            }
             ('*', Some('*')) => {
                 self.next_char();
                 value.push('*');
-                return self.create_token(TokenType::Power, value);
+                return self.compound_or(TokenType::Power, value);
+            }
+            ('+' | '-' | '*' | '/' | '%' | '&' | '|' | '^', Some('=')) => {
+                self.next_char();
+                value.push('=');
+                return self.create_token(TokenType::CompoundAssignment, value);
             }
             _ => {}
         }
@@ -325,12 +344,23 @@ impl Tokenizer {
             "*" => TokenType::Star,
             "/" => TokenType::Slash,
             "%" => TokenType::Modulo,
+            "?" => TokenType::QuestionMark,
             _ => TokenType::EOF,
         };
 
         self.create_token(token_type, value)
     }
 
+    /// The operator in `value`, or its compound assignment if `=` follows, as in `**=`.
+    fn compound_or(&mut self, token_type: TokenType, mut value: String) -> Token {
+        if self.peek_char() == Some('=') {
+            self.next_char();
+            value.push('=');
+            return self.create_token(TokenType::CompoundAssignment, value);
+        }
+        self.create_token(token_type, value)
+    }
+
     fn read_identifier(&mut self, first: char) -> Token {
         let mut value = String::from(first);
         while let Some(c) = self.peek_char() {
@@ -361,6 +391,7 @@ impl Tokenizer {
             "function" => TokenType::Function,
             "class" => TokenType::Class,
             "interface" => TokenType::Interface,
+            "enum" => TokenType::Enum,
             "let" => TokenType::Let,
             "const" => TokenType::Const,
             "var" => TokenType::Var,
@@ -373,6 +404,7 @@ impl Tokenizer {
             "as" => TokenType::As,
             "async" => TokenType::Async,
             "await" => TokenType::Await,
+            "yield" => TokenType::Yield,
             "return" => TokenType::Return,
             "if" => TokenType::If,
             "else" => TokenType::Else,
@@ -0,0 +1,1677 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::ast::key_name;
+use crate::binder::is_type;
+use crate::error_handling::{CompilerError, Span};
+use crate::parser::{MethodKind, Node, Parameter, TypeParameter, VariableKind};
+use crate::symboltable::{Meaning, ScopeId, SymbolFlags, SymbolId, SymbolTable};
+use crate::tokenizer::TokenType;
+use crate::types_checking::{
+    const_assertion, literal_type, pattern_bindings, required_type_params, IndexType, ObjectType,
+    PropertyType, ShapeId, Type, TypeParam,
+};
+
+/// Global types that aren't declared in the program, with their type parameters and how
+/// many of those are required.
+const BUILTIN_TYPES: &[(&str, &[&str], usize)] = &[
+    ("Array", &["T"], 1),
+    ("ReadonlyArray", &["T"], 1),
+    ("ArrayLike", &["T"], 1),
+    ("Readonly", &["T"], 1),
+    ("Partial", &["T"], 1),
+    ("Required", &["T"], 1),
+    ("NonNullable", &["T"], 1),
+    ("Exclude", &["T", "U"], 2),
+    ("Extract", &["T", "U"], 2),
+    ("Record", &["K", "T"], 2),
+    ("Pick", &["T", "K"], 2),
+    ("Omit", &["T", "K"], 2),
+    ("ReturnType", &["T"], 1),
+    ("Parameters", &["T"], 1),
+    ("InstanceType", &["T"], 1),
+    ("Awaited", &["T"], 1),
+    ("Uppercase", &["S"], 1),
+    ("Lowercase", &["S"], 1),
+    ("Capitalize", &["S"], 1),
+    ("Uncapitalize", &["S"], 1),
+    ("Promise", &["T"], 1),
+    ("PromiseLike", &["T"], 1),
+    ("Map", &["K", "V"], 2),
+    ("ReadonlyMap", &["K", "V"], 2),
+    ("WeakMap", &["K", "V"], 2),
+    ("Set", &["T"], 1),
+    ("ReadonlySet", &["T"], 1),
+    ("WeakSet", &["T"], 1),
+    ("Iterable", &["T"], 1),
+    ("Iterator", &["T"], 1),
+    ("IterableIterator", &["T"], 1),
+    ("AsyncIterable", &["T"], 1),
+    ("Generator", &["T", "TReturn", "TNext"], 0),
+    ("AsyncGenerator", &["T", "TReturn", "TNext"], 0),
+    ("PropertyKey", &[], 0),
+    ("Object", &[], 0),
+    ("Function", &[], 0),
+    ("String", &[], 0),
+    ("Number", &[], 0),
+    ("Boolean", &[], 0),
+    ("Symbol", &[], 0),
+    ("BigInt", &[], 0),
+    ("Date", &[], 0),
+    ("RegExp", &[], 0),
+    ("Error", &[], 0),
+    ("TypeError", &[], 0),
+    ("RangeError", &[], 0),
+    ("SyntaxError", &[], 0),
+];
+
+/// The type of the values the built-in constructor `name` creates, with any type arguments
+/// `any`, for `instanceof` to narrow to. `None` when `name` isn't a built-in type.
+pub fn builtin_instance_type(name: &str) -> Option<Type> {
+    let (_, names, _) = BUILTIN_TYPES
+        .iter()
+        .find(|(builtin, ..)| *builtin == name)?;
+    Some(match name {
+        "Array" => Type::Array(Box::new(Type::Any)),
+        "Object" => Type::Object,
+        _ => Type::Custom(name.to_string(), vec![Type::Any; names.len()]),
+    })
+}
+
+/// Resolves the types of the program's declarations and stores them on their symbols: the
+/// declared type of each variable, parameter and function as its `value_type`, and what
+/// each class, interface, type alias, enum, enum member and type parameter stands for as
+/// its `declared_type`. Names declared without an annotation are left for the checker to
+/// infer, except parameters, which are `any`, and the return types of functions.
+///
+/// Every type in the program is lowered on the way, so the errors returned cover names
+/// that can't be found and generic types given the wrong number of type arguments
+/// anywhere, not only in declarations.
+///
+/// `table` must come from binding `program`, or a tree `program` was cloned from.
+pub fn resolve_types(program: &Node, table: &mut SymbolTable) -> Vec<CompilerError> {
+    let mut lowering = TypeLowering::new(program, table);
+    lowering.visit(program, SymbolTable::GLOBAL);
+    let TypeLowering {
+        values,
+        declared,
+        errors,
+        ..
+    } = lowering;
+    for (symbol, value_type) in values {
+        table.symbol_mut(symbol).value_type = Some(value_type);
+    }
+    for (symbol, declared_type) in declared {
+        table.symbol_mut(symbol).declared_type = Some(declared_type);
+    }
+    errors
+}
+
+/// Lowers type nodes into `Type`s, resolving the names in them from the scope they are
+/// written in.
+///
+/// A type alias stands for its body, instantiated with the type arguments it is given.
+/// Aliases may refer to themselves from inside an array, tuple, function or type argument,
+/// as in `type Json = string | Json[]`; such a reference stays a `Custom` type naming the
+/// alias, unless the body is an object type, which refers to itself by its shape.
+///
+/// Classes and interfaces are the shapes of their instances, built from every declaration
+/// of the name and the members they inherit, and instantiated with the type arguments
+/// they are given, with their defaults filled in.
+pub struct TypeLowering<'a> {
+    table: &'a SymbolTable,
+    declarations: HashMap<SymbolId, Vec<&'a Node>>, // of functions and named types
+    // Of variables without an annotation: the pattern declaring them, its initializer and
+    // whether it is `const`
+    initializers: HashMap<SymbolId, (&'a Node, &'a Node, bool)>,
+    aliases: HashMap<SymbolId, Type>, // bodies, with their type parameters as `Param`s
+    generics: HashMap<SymbolId, Vec<TypeParam>>, // of classes, interfaces and aliases
+    shapes: HashMap<SymbolId, ShapeId>, // of classes and interfaces, with `Param`s
+    instances: Vec<(SymbolId, Vec<Type>, Type)>, // of generic classes and interfaces
+    building: Vec<SymbolId>,          // classes and interfaces whose shapes are being built
+    resolving: Vec<SymbolId>,         // aliases whose bodies are being lowered
+    querying: HashSet<SymbolId>,      // names whose value types are being lowered
+    local_type_params: Vec<String>,   // of the function types and signatures being lowered
+    deferred: bool, // lowering a type that is only looked into later, like an array's
+    values: HashMap<SymbolId, Type>,
+    declared: HashMap<SymbolId, Type>,
+    pub errors: Vec<CompilerError>,
+}
+
+impl<'a> TypeLowering<'a> {
+    pub fn new(program: &'a Node, table: &'a SymbolTable) -> Self {
+        let mut lowering = TypeLowering {
+            table,
+            declarations: HashMap::new(),
+            initializers: HashMap::new(),
+            aliases: HashMap::new(),
+            generics: HashMap::new(),
+            shapes: HashMap::new(),
+            instances: Vec::new(),
+            building: Vec::new(),
+            resolving: Vec::new(),
+            querying: HashSet::new(),
+            local_type_params: Vec::new(),
+            deferred: false,
+            values: HashMap::new(),
+            declared: HashMap::new(),
+            errors: Vec::new(),
+        };
+        lowering.collect_declarations(program);
+        lowering
+    }
+
+    fn collect_declarations(&mut self, node: &'a Node) {
+        if matches!(
+            node,
+            Node::FunctionDeclaration { .. }
+                | Node::ClassDeclaration { .. }
+                | Node::InterfaceDeclaration { .. }
+                | Node::TypeAliasDeclaration { .. }
+                | Node::EnumDeclaration { .. }
+                | Node::NamespaceDeclaration { .. }
+        ) {
+            if let Some(symbol) = self.table.symbol_of(node) {
+                self.declarations.entry(symbol).or_default().push(node);
+            }
+        }
+        if let Node::VariableDeclaration { kind, declarations } = node {
+            for declarator in declarations {
+                let (None, Some(init)) = (&declarator.type_annotation, &declarator.init) else {
+                    continue;
+                };
+                for identifier in declarator.id.binding_identifiers() {
+                    if let Some(symbol) = self.table.symbol_of(identifier) {
+                        let is_const = *kind == VariableKind::Const;
+                        self.initializers
+                            .insert(symbol, (&*declarator.id, &**init, is_const));
+                    }
+                }
+            }
+        }
+        for child in node.children() {
+            self.collect_declarations(child);
+        }
+    }
+
+    /// The type `node` stands for when written in `scope`.
+    pub fn lower(&mut self, node: &Node, scope: ScopeId) -> Type {
+        match node {
+            Node::KeywordType(token_type) => match token_type {
+                TokenType::NumberType => Type::Number,
+                TokenType::StringType => Type::String,
+                TokenType::BooleanType => Type::Boolean,
+                TokenType::Null => Type::Null,
+                TokenType::Undefined => Type::Undefined,
+                TokenType::Unknown => Type::Unknown,
+                TokenType::Never => Type::Never,
+                TokenType::Void => Type::Void,
+                TokenType::BigInt => Type::BigInt,
+                TokenType::Symbol => Type::Symbol,
+                TokenType::Object => Type::Object,
+                _ => Type::Any,
+            },
+            Node::LiteralType(literal) => match literal.as_ref() {
+                Node::Literal {
+                    token_type: TokenType::StringLiteral,
+                    value,
+                    ..
+                } => Type::Literal(format!("\"{}\"", value)),
+                Node::Literal { value, .. } => Type::Literal(value.clone()),
+                _ => Type::Any,
+            },
+            Node::ParenthesizedType(inner) => self.lower(inner, scope),
+            Node::UnionType(types) => {
+                let types = types.iter().map(|t| self.lower(t, scope)).collect();
+                Type::union(types)
+            }
+            Node::IntersectionType(types) => {
+                let types = types.iter().map(|t| self.lower(t, scope)).collect();
+                Type::intersection(types)
+            }
+            Node::ArrayType(element) => Type::Array(Box::new(self.deferred(element, scope))),
+            Node::TupleType(elements) => self.tuple(elements, scope),
+            Node::FunctionType {
+                type_params,
+                params,
+                return_type,
+            } => self.signature(type_params, params, Some(return_type), false, scope),
+            Node::ConstructorType {
+                type_params,
+                params,
+                return_type,
+            } => {
+                let signature =
+                    self.signature(type_params, params, Some(return_type), false, scope);
+                Type::Shape(self.table.shapes().intern(ObjectType {
+                    construct_signatures: vec![signature],
+                    ..ObjectType::default()
+                }))
+            }
+            Node::ObjectType { members } => {
+                let mut object = ObjectType::default();
+                for member in members {
+                    self.member(&mut object, member, scope);
+                }
+                Type::Shape(self.table.shapes().intern(object))
+            }
+            Node::TypeOperator {
+                operator,
+                type_annotation,
+            } => {
+                let operand = self.lower(type_annotation, scope);
+                match operator.as_str() {
+                    "keyof" => keys(&operand),
+                    "unique" => Type::Symbol,
+                    // Readonly arrays and tuples are left to `check_assignments`
+                    _ => operand,
+                }
+            }
+            Node::TypeQuery(entity) => self.query(entity, scope),
+            Node::IndexedAccessType {
+                object_type,
+                index_type,
+            } => {
+                let object = self.lower(object_type, scope);
+                let index = self.lower(index_type, scope);
+                let element = match &index {
+                    Type::Literal(key) => object.property_type(key.trim_matches('"')),
+                    Type::Number => object.element_type(None).ok(),
+                    _ => None,
+                };
+                element.unwrap_or(Type::Any)
+            }
+            Node::TypeReference {
+                name,
+                type_arguments,
+                span,
+            } => self.reference(name, type_arguments, *span, scope),
+            // Outside of a signature, a type predicate is only what the function returns
+            Node::TypePredicate {
+                asserts,
+                type_annotation,
+                ..
+            } => {
+                if let Some(annotation) = type_annotation {
+                    self.lower(annotation, scope);
+                }
+                match asserts {
+                    true => Type::Void,
+                    false => Type::Boolean,
+                }
+            }
+            // `this` types need the shapes of classes
+            _ => Type::Any,
+        }
+    }
+
+    /// Lowers a type that is only looked into once it's used, so an alias can refer to
+    /// itself inside it.
+    fn deferred(&mut self, node: &Node, scope: ScopeId) -> Type {
+        let deferred = std::mem::replace(&mut self.deferred, true);
+        let lowered = self.lower(node, scope);
+        self.deferred = deferred;
+        lowered
+    }
+
+    /// A tuple type. Optional elements may be `undefined`, and elements after a rest element
+    /// are added to what it may hold, so only the length of the part before it is known.
+    fn tuple(&mut self, elements: &[Node], scope: ScopeId) -> Type {
+        let mut types = Vec::new();
+        let mut required = 0;
+        let mut rest: Option<Vec<Type>> = None; // the types the rest element may hold
+        for element in elements {
+            let (element, optional, is_rest) = match element {
+                Node::NamedTupleMember {
+                    element_type,
+                    optional,
+                    rest,
+                    ..
+                } => (element_type.as_ref(), *optional, *rest),
+                Node::OptionalType(element) => (element.as_ref(), true, false),
+                Node::RestType(element) => (element.as_ref(), false, true),
+                element => (element, false, false),
+            };
+            let lowered = self.deferred(element, scope);
+            // The elements a rest element spreads, and what it holds after them
+            let (spread, spread_required, spread_rest) = match (lowered, is_rest) {
+                (
+                    Type::Tuple {
+                        elements,
+                        required,
+                        rest,
+                    },
+                    true,
+                ) => (elements, required, rest.map(|rest| *rest)),
+                (Type::Array(element), true) => (Vec::new(), 0, Some(Type::Array(element))),
+                (_, true) => (Vec::new(), 0, Some(Type::Array(Box::new(Type::Any)))),
+                (lowered, false) if optional => {
+                    (vec![Type::union(vec![lowered, Type::Undefined])], 0, None)
+                }
+                (lowered, false) => (vec![lowered], 1, None),
+            };
+            if let Some(held) = &mut rest {
+                held.extend(spread);
+                held.extend(spread_rest.and_then(|rest| rest.element_type(None).ok()));
+                continue;
+            }
+            if required == types.len() {
+                required += spread_required;
+            }
+            types.extend(spread);
+            rest = spread_rest.map(|rest| vec![rest.element_type(None).unwrap_or(Type::Any)]);
+        }
+        Type::Tuple {
+            elements: types,
+            required,
+            rest: rest.map(|held| Box::new(Type::Array(Box::new(Type::union(held))))),
+        }
+    }
+
+    /// The signature of a function, method or function type written in `scope`.
+    pub fn signature(
+        &mut self,
+        type_params: &[TypeParameter],
+        params: &[Parameter],
+        return_type: Option<&Node>,
+        method: bool,
+        scope: ScopeId,
+    ) -> Type {
+        self.annotated_signature(type_params, params, return_type, method, scope)
+            .0
+    }
+
+    /// A signature, and the types its parameters are annotated with. Type parameters are in
+    /// scope for the rest of the signature.
+    fn annotated_signature(
+        &mut self,
+        type_params: &[TypeParameter],
+        params: &[Parameter],
+        return_type: Option<&Node>,
+        method: bool,
+        scope: ScopeId,
+    ) -> (Type, Vec<Option<Type>>) {
+        let depth = self.local_type_params.len();
+        self.local_type_params
+            .extend(type_params.iter().map(|type_param| type_param.name.clone()));
+        let lowered_params = self.type_params(type_params, scope);
+        let deferred = std::mem::replace(&mut self.deferred, true);
+        let annotations: Vec<Option<Type>> = params
+            .iter()
+            .map(|param| {
+                param
+                    .type_annotation
+                    .as_ref()
+                    .map(|annotation| self.lower(annotation, scope))
+            })
+            .collect();
+        let return_type = return_type.map_or(Type::Any, |node| match node {
+            Node::TypePredicate { .. } => self.predicate(node, params, scope),
+            node => self.lower(node, scope),
+        });
+        self.deferred = deferred;
+        self.local_type_params.truncate(depth);
+
+        // `this` parameters only give `this` a type, and a parameter with a default may be
+        // left out like an optional one
+        let mut param_types = Vec::new();
+        let mut required = 0;
+        let mut rest = None;
+        for (param, annotation) in params.iter().zip(&annotations) {
+            if param.name() == Some("this") {
+                continue;
+            }
+            let declared = annotation.clone().unwrap_or(Type::Any);
+            if param.rest {
+                rest = Some(Box::new(match declared {
+                    Type::Any => Type::Array(Box::new(Type::Any)),
+                    declared => declared,
+                }));
+            } else if param.optional || param.init.is_some() {
+                param_types.push(Type::union(vec![declared, Type::Undefined]));
+            } else {
+                param_types.push(declared);
+                required = param_types.len();
+            }
+        }
+        let signature = Type::Function {
+            type_params: lowered_params,
+            params: param_types,
+            required,
+            rest,
+            return_type: Box::new(return_type),
+            method,
+        };
+        (signature, annotations)
+    }
+
+    /// The return type `x is T`, `asserts x is T` or `asserts x` of a signature with
+    /// `params`, which `x` must be one of unless it is `this`.
+    fn predicate(&mut self, node: &Node, params: &[Parameter], scope: ScopeId) -> Type {
+        let Node::TypePredicate {
+            parameter,
+            asserts,
+            type_annotation,
+            span,
+        } = node
+        else {
+            return self.lower(node, scope);
+        };
+        let guarded = type_annotation
+            .as_ref()
+            .map(|annotation| Box::new(self.lower(annotation, scope)));
+        let index = params
+            .iter()
+            .filter(|param| param.name() != Some("this"))
+            .position(|param| param.name() == Some(parameter));
+        let parameter = match index {
+            _ if parameter == "this" => None,
+            Some(index) => Some(index),
+            None => {
+                self.errors.push(
+                    CompilerError::new(format!("Cannot find parameter '{}'.", parameter), *span)
+                        .with_code(1225),
+                );
+                return self.lower(node, scope);
+            }
+        };
+        Type::Predicate {
+            parameter,
+            asserts: *asserts,
+            guarded,
+        }
+    }
+
+    fn type_params(&mut self, type_params: &[TypeParameter], scope: ScopeId) -> Vec<TypeParam> {
+        type_params
+            .iter()
+            .map(|type_param| TypeParam {
+                name: type_param.name.clone(),
+                constraint: type_param
+                    .constraint
+                    .as_ref()
+                    .map(|constraint| self.lower(constraint, scope)),
+                default: type_param
+                    .default
+                    .as_ref()
+                    .map(|default| self.lower(default, scope)),
+            })
+            .collect()
+    }
+
+    /// Adds a member of an interface or object type to `object`. Members with computed
+    /// names are lowered but left out.
+    fn member(&mut self, object: &mut ObjectType, member: &Node, scope: ScopeId) {
+        match member {
+            Node::MethodSignature {
+                key,
+                computed,
+                optional,
+                type_params,
+                params,
+                return_type,
+            } => {
+                let signature =
+                    self.signature(type_params, params, return_type.as_deref(), true, scope);
+                if let Some(name) = key_name(key, *computed) {
+                    self.add_method(object, name, signature, *optional);
+                }
+            }
+            Node::CallSignature {
+                type_params,
+                params,
+                return_type,
+            } => {
+                let signature =
+                    self.signature(type_params, params, return_type.as_deref(), false, scope);
+                object.call_signatures.push(signature);
+            }
+            Node::ConstructSignature {
+                type_params,
+                params,
+                return_type,
+            } => {
+                let signature =
+                    self.signature(type_params, params, return_type.as_deref(), false, scope);
+                object.construct_signatures.push(signature);
+            }
+            Node::PropertySignature {
+                key,
+                value,
+                optional,
+                readonly,
+                computed,
+            } => {
+                let value_type = value
+                    .as_ref()
+                    .map_or(Type::Any, |value| self.deferred(value, scope));
+                if let Some(name) = key_name(key, *computed) {
+                    add_property(object, name, value_type, *optional, *readonly);
+                }
+            }
+            Node::IndexSignature {
+                key_type,
+                value_type,
+                readonly,
+                ..
+            } => {
+                let key_type = self.lower(key_type, scope);
+                let index = Some(IndexType {
+                    value_type: self.deferred(value_type, scope),
+                    readonly: *readonly,
+                });
+                match key_type {
+                    Type::Number => object.number_index = index,
+                    _ => object.string_index = index,
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Adds the members of a class's instances to `object`: its properties, methods and
+    /// accessors that aren't static, and the parameter properties of its constructor.
+    /// Properties without an annotation are `any`. A method with overloads is seen with
+    /// them rather than the signature of its implementation.
+    fn class_members(&mut self, object: &mut ObjectType, members: &[Node], scope: ScopeId) {
+        let overloaded: Vec<&str> = members
+            .iter()
+            .filter_map(|member| match member {
+                Node::MethodDefinition {
+                    key,
+                    computed,
+                    kind: MethodKind::Method,
+                    body: None,
+                    modifiers,
+                    ..
+                } if !modifiers.is_static => key_name(key, *computed),
+                _ => None,
+            })
+            .collect();
+        let setters: Vec<&str> = members
+            .iter()
+            .filter_map(|member| match member {
+                Node::MethodDefinition {
+                    key,
+                    computed,
+                    kind: MethodKind::Set,
+                    ..
+                } => key_name(key, *computed),
+                _ => None,
+            })
+            .collect();
+        for member in members {
+            match member {
+                Node::PropertyDefinition {
+                    key,
+                    computed,
+                    type_annotation,
+                    optional,
+                    modifiers,
+                    ..
+                } if !modifiers.is_static => {
+                    let value_type = type_annotation
+                        .as_ref()
+                        .map_or(Type::Any, |annotation| self.deferred(annotation, scope));
+                    if let Some(name) = key_name(key, *computed) {
+                        add_property(object, name, value_type, *optional, modifiers.readonly);
+                    }
+                }
+                Node::MethodDefinition {
+                    key,
+                    computed,
+                    kind,
+                    type_params,
+                    params,
+                    return_type,
+                    body,
+                    modifiers,
+                    ..
+                } if !modifiers.is_static => {
+                    let scope = self.table.scope_of(member).unwrap_or(scope);
+                    let name = key_name(key, *computed);
+                    match kind {
+                        MethodKind::Method => {
+                            let signature = self.signature(
+                                type_params,
+                                params,
+                                return_type.as_deref(),
+                                true,
+                                scope,
+                            );
+                            let implements_overloads = body.is_some()
+                                && name.is_some_and(|name| overloaded.contains(&name));
+                            if let (Some(name), false) = (name, implements_overloads) {
+                                self.add_method(object, name, signature, false);
+                            }
+                        }
+                        MethodKind::Get => {
+                            let value_type = return_type
+                                .as_ref()
+                                .map_or(Type::Any, |annotation| self.deferred(annotation, scope));
+                            if let Some(name) = name {
+                                let readonly = !setters.contains(&name);
+                                add_property(object, name, value_type, false, readonly);
+                            }
+                        }
+                        MethodKind::Set => {
+                            let value_type = params
+                                .first()
+                                .and_then(|param| param.type_annotation.as_ref())
+                                .map_or(Type::Any, |annotation| self.deferred(annotation, scope));
+                            if let Some(name) = name {
+                                add_property(object, name, value_type, false, false);
+                            }
+                        }
+                        MethodKind::Constructor => {
+                            let properties = params.iter().filter(|param| {
+                                param.modifiers.accessibility.is_some() || param.modifiers.readonly
+                            });
+                            for param in properties {
+                                let value_type = param
+                                    .type_annotation
+                                    .as_ref()
+                                    .map_or(Type::Any, |annotation| {
+                                        self.deferred(annotation, scope)
+                                    });
+                                if let Some(name) = param.name() {
+                                    let readonly = param.modifiers.readonly;
+                                    add_property(
+                                        object,
+                                        name,
+                                        value_type,
+                                        param.optional,
+                                        readonly,
+                                    );
+                                }
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Adds a method to `object`, or adds `signature` to the overloads of the method
+    /// declared before it.
+    fn add_method(&self, object: &mut ObjectType, name: &str, signature: Type, optional: bool) {
+        let Some(property) = object
+            .properties
+            .iter_mut()
+            .find(|property| property.name == name)
+        else {
+            add_property(object, name, signature, optional, false);
+            return;
+        };
+        if let Some(mut overloads) = property.value_type.overloads() {
+            overloads.push(signature);
+            property.value_type = self.table.shapes().overloaded(overloads);
+        }
+    }
+
+    /// The shape of a class's instances or an interface, with its type parameters as
+    /// `Param`s. It's built from every declaration of the name the first time it's needed,
+    /// and may refer to itself by its ID while it is.
+    fn shape(&mut self, symbol: SymbolId) -> ShapeId {
+        if let Some(shape) = self.shapes.get(&symbol) {
+            return shape.clone();
+        }
+        let shape = self.table.shapes().reserve();
+        self.shapes.insert(symbol, shape.clone());
+        let type_arguments = self
+            .generic_params(symbol)
+            .into_iter()
+            .map(|type_param| Type::Param(type_param.name))
+            .collect();
+        let mut object = ObjectType {
+            name: Some(self.table.symbol(symbol).name.clone()),
+            type_arguments,
+            ..ObjectType::default()
+        };
+
+        // Errors in the members are reported where they are visited
+        let errors = std::mem::take(&mut self.errors);
+        let local_type_params = std::mem::take(&mut self.local_type_params);
+        let deferred = std::mem::replace(&mut self.deferred, false);
+        self.building.push(symbol);
+        let mut bases = Vec::new();
+        let declarations = self.declarations.get(&symbol).cloned().unwrap_or_default();
+        for declaration in declarations {
+            let scope = self
+                .table
+                .scope_of(declaration)
+                .unwrap_or(self.table.declaring_scope(symbol));
+            match declaration {
+                Node::InterfaceDeclaration {
+                    extends, members, ..
+                } => {
+                    for member in members {
+                        self.member(&mut object, member, scope);
+                    }
+                    bases.extend(extends.iter().map(|base| self.lower(base, scope)));
+                }
+                Node::ClassDeclaration {
+                    extends,
+                    super_type_arguments,
+                    members,
+                    ..
+                } => {
+                    self.class_members(&mut object, members, scope);
+                    if let Some(base) = extends {
+                        let base = self.base_class(base, super_type_arguments, scope);
+                        bases.push(base.unwrap_or(Type::Any));
+                    }
+                }
+                _ => {}
+            }
+        }
+        for base in bases {
+            match base {
+                Type::Shape(base) => object.inherit(&base.get()),
+                // What's inherited from a type whose members aren't known could be anything
+                _ if object.string_index.is_none() => {
+                    object.string_index = Some(IndexType {
+                        value_type: Type::Any,
+                        readonly: false,
+                    })
+                }
+                _ => {}
+            }
+        }
+        self.building.pop();
+        self.deferred = deferred;
+        self.local_type_params = local_type_params;
+        self.errors = errors;
+        shape.define(object);
+        shape
+    }
+
+    /// The instances of the class a class extends, if it names one declared in the program.
+    fn base_class(&mut self, base: &Node, type_arguments: &[Node], scope: ScopeId) -> Option<Type> {
+        let symbol = self.table.symbol_of(base)?;
+        if !self.table.symbol(symbol).flags.contains(SymbolFlags::CLASS) {
+            return None;
+        }
+        let arguments = type_arguments
+            .iter()
+            .map(|argument| self.deferred(argument, scope))
+            .collect();
+        let name = self.table.symbol(symbol).name.clone();
+        let type_params = self.generic_params(symbol);
+        let arguments = self.type_arguments(&name, &type_params, arguments, base.span())?;
+        Some(self.instance(symbol, arguments))
+    }
+
+    /// A class or interface given `arguments`. Where the shape is being built and refers to
+    /// itself with other arguments, it stays a `Custom` type naming it.
+    fn instance(&mut self, symbol: SymbolId, arguments: Vec<Type>) -> Type {
+        let shape = self.shape(symbol);
+        let type_params = self.generic_params(symbol);
+        let identity = type_params
+            .iter()
+            .zip(&arguments)
+            .all(|(type_param, argument)| *argument == Type::Param(type_param.name.clone()));
+        if identity {
+            return Type::Shape(shape);
+        }
+        if self.building.contains(&symbol) {
+            return Type::Custom(self.table.symbol(symbol).name.clone(), arguments);
+        }
+        let instance = self
+            .instances
+            .iter()
+            .find(|(instantiated, with, _)| *instantiated == symbol && *with == arguments);
+        if let Some((.., instance)) = instance {
+            return instance.clone();
+        }
+        let mapping = type_params
+            .iter()
+            .map(|type_param| type_param.name.clone())
+            .zip(arguments.iter().cloned())
+            .collect();
+        let instance = Type::Shape(shape).substitute(&mapping);
+        self.instances.push((symbol, arguments, instance.clone()));
+        instance
+    }
+
+    /// The type of `typeof entity`.
+    fn query(&mut self, entity: &Node, scope: ScopeId) -> Type {
+        match entity {
+            Node::Identifier(name, span) => match self.table.lookup(scope, name, Meaning::Value) {
+                Some(symbol) => self.value_of(symbol).unwrap_or_else(|| {
+                    self.errors.push(CompilerError::new(
+                        format!(
+                            "The type of '{}' can't be inferred from its initializer here; give it a type annotation.",
+                            name
+                        ),
+                        *span,
+                    ));
+                    Type::Any
+                }),
+                // Reported by the binder
+                None => Type::Any,
+            },
+            Node::MemberExpression {
+                object, property, ..
+            } => {
+                let object = self.query(object, scope);
+                match property.as_ref() {
+                    Node::Identifier(name, _) => object.property_type(name).unwrap_or(Type::Any),
+                    _ => Type::Any,
+                }
+            }
+            _ => Type::Any,
+        }
+    }
+
+    /// The declared type of a value, lowering its declaration if it hasn't been reached
+    /// yet, or the type of its initializer if it has no annotation. `None` for an
+    /// initializer `initializer_type` can't type. Errors in the declaration are reported
+    /// when it is reached.
+    fn value_of(&mut self, symbol: SymbolId) -> Option<Type> {
+        if let Some(value_type) = self.values.get(&symbol) {
+            return Some(value_type.clone());
+        }
+        if !self.querying.insert(symbol) {
+            return Some(Type::Any);
+        }
+        let errors = std::mem::take(&mut self.errors);
+        let declared_in = self.table.declaring_scope(symbol);
+        let value_type = if let Some(annotation) = &self.table.symbol(symbol).type_annotation {
+            Some(self.lower(annotation, declared_in))
+        } else if let Some(function) = self.function_value(symbol) {
+            Some(function)
+        } else if let Some(&(pattern, init, is_const)) = self.initializers.get(&symbol) {
+            // `let` and `var` widen literals as the checker does, and so do their patterns
+            let name = &self.table.symbol(symbol).name;
+            self.initializer_type(init, declared_in)
+                .map(|value| match is_const {
+                    true => value,
+                    false => value.widened(),
+                })
+                .and_then(|value| match pattern {
+                    Node::Identifier(..) => Some(value),
+                    pattern => pattern_bindings(pattern, &value)
+                        .ok()?
+                        .into_iter()
+                        .find(|(bound, _)| bound == name)
+                        .map(|(_, bound)| bound),
+                })
+        } else {
+            Some(Type::Any)
+        };
+        self.errors = errors;
+        self.querying.remove(&symbol);
+        value_type
+    }
+
+    /// The value of a function: its overloads, the declarations without a body, or the
+    /// signature of its (first) implementation when it has none. `None` for a name no function
+    /// declares. Errors in the signatures are reported where each declaration is visited.
+    fn function_value(&mut self, symbol: SymbolId) -> Option<Type> {
+        let functions: Vec<&Node> = self
+            .declarations
+            .get(&symbol)?
+            .iter()
+            .copied()
+            .filter(|declaration| matches!(declaration, Node::FunctionDeclaration { .. }))
+            .collect();
+        let is_overload =
+            |function: &&Node| matches!(function, Node::FunctionDeclaration { body: None, .. });
+        let has_overloads = functions.iter().any(is_overload);
+        let errors = std::mem::take(&mut self.errors);
+        let declared_in = self.table.declaring_scope(symbol);
+        let mut signatures = Vec::new();
+        for function in functions {
+            let Node::FunctionDeclaration {
+                type_params,
+                params,
+                return_type,
+                body,
+                ..
+            } = function
+            else {
+                continue;
+            };
+            if has_overloads && body.is_some() || !has_overloads && !signatures.is_empty() {
+                continue;
+            }
+            let scope = self.table.scope_of(function).unwrap_or(declared_in);
+            let signature =
+                self.signature(type_params, params, return_type.as_deref(), false, scope);
+            signatures.push(signature);
+        }
+        self.errors = errors;
+        (!signatures.is_empty()).then(|| self.table.shapes().overloaded(signatures))
+    }
+
+    /// The type of a variable's initializer, as far as it is known before the checker runs:
+    /// literals, object and array literals of known types, other names, `new` of a class,
+    /// type assertions and functions with annotated return types. `None` for anything
+    /// else, such as calls and operators other than unary ones.
+    fn initializer_type(&mut self, init: &Node, scope: ScopeId) -> Option<Type> {
+        let value = match init {
+            Node::Literal {
+                token_type, value, ..
+            } => literal_type(token_type, value),
+            Node::Identifier(name, _) => {
+                let symbol = self
+                    .table
+                    .symbol_of(init)
+                    .or_else(|| self.table.lookup(scope, name, Meaning::Value));
+                match symbol {
+                    Some(symbol) => self.value_of(symbol)?,
+                    None if name == "undefined" => Type::Undefined,
+                    None => return None,
+                }
+            }
+            Node::UnaryExpression { operator, argument } => match operator.as_str() {
+                "!" | "delete" => Type::Boolean,
+                "typeof" => Type::String,
+                "void" => Type::Undefined,
+                "-" => match argument.as_ref() {
+                    Node::Literal {
+                        token_type: TokenType::NumberLiteral | TokenType::BigInt,
+                        value,
+                        ..
+                    } => Type::Literal(format!("-{}", value)),
+                    argument => match self.initializer_type(argument, scope)?.widened() {
+                        Type::BigInt => Type::BigInt,
+                        _ => Type::Number,
+                    },
+                },
+                _ => Type::Number,
+            },
+            Node::ArrayExpression(elements) => {
+                let mut types = Vec::new();
+                for element in elements {
+                    types.push(match element {
+                        Some(Node::SpreadElement(_)) => return None,
+                        Some(element) => self.initializer_type(element, scope)?.widened(),
+                        None => Type::Undefined,
+                    });
+                }
+                match types.is_empty() {
+                    true => Type::Array(Box::new(Type::Any)),
+                    false => Type::Array(Box::new(Type::union(types))),
+                }
+            }
+            Node::ObjectExpression(properties) => {
+                let mut object = ObjectType::default();
+                for property in properties {
+                    let Node::Property {
+                        key,
+                        computed,
+                        value,
+                        ..
+                    } = property
+                    else {
+                        return None;
+                    };
+                    let name = key_name(key, *computed)?.to_string();
+                    let value_type = self.initializer_type(value, scope)?.widened();
+                    object.properties.retain(|property| property.name != name);
+                    object.properties.push(PropertyType {
+                        name,
+                        value_type,
+                        optional: false,
+                        readonly: false,
+                    });
+                }
+                Type::Shape(self.table.shapes().intern(object))
+            }
+            Node::NewExpression {
+                callee,
+                type_arguments,
+                ..
+            } => {
+                let Node::Identifier(name, span) = callee.as_ref() else {
+                    return None;
+                };
+                let symbol = self.table.lookup(scope, name, Meaning::Value)?;
+                if !self.table.symbol(symbol).flags.contains(SymbolFlags::CLASS) {
+                    return None;
+                }
+                // Type arguments inferred from the constructor's arguments aren't known
+                if type_arguments.is_empty() && !self.generic_params(symbol).is_empty() {
+                    return None;
+                }
+                self.reference(name, type_arguments, *span, scope)
+            }
+            Node::AsExpression {
+                expression,
+                type_annotation,
+            }
+            | Node::TypeAssertion {
+                type_annotation,
+                expression,
+            } => match type_annotation.as_ref() {
+                Node::KeywordType(TokenType::Const) => {
+                    const_assertion(expression, self.table.shapes()).ok()?
+                }
+                type_annotation => self.lower(type_annotation, scope),
+            },
+            Node::SatisfiesExpression { expression, .. } => {
+                self.initializer_type(expression, scope)?
+            }
+            Node::ArrowFunctionExpression {
+                type_params,
+                params,
+                return_type: Some(return_type),
+                ..
+            } => {
+                let scope = self.table.scope_of(init).unwrap_or(scope);
+                self.signature(type_params, params, Some(return_type), false, scope)
+            }
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    fn reference(
+        &mut self,
+        name: &str,
+        type_arguments: &[Node],
+        span: Span,
+        scope: ScopeId,
+    ) -> Type {
+        let arguments: Vec<Type> = type_arguments
+            .iter()
+            .map(|argument| self.deferred(argument, scope))
+            .collect();
+        if self.local_type_params.iter().any(|local| local == name) {
+            if !arguments.is_empty() {
+                self.not_generic(name, span);
+            }
+            return Type::Param(name.to_string());
+        }
+        let symbol = if name.contains('.') {
+            self.qualified(name, span, scope)
+        } else {
+            let symbol = self.table.lookup(scope, name, Meaning::Type);
+            if symbol.is_none() {
+                return self.builtin(name, arguments, span).unwrap_or_else(|| {
+                    self.errors.push(
+                        CompilerError::new(format!("Cannot find name '{}'.", name), span)
+                            .with_code(2304),
+                    );
+                    Type::Any
+                });
+            }
+            symbol
+        };
+        let Some(symbol) = symbol else {
+            return Type::Any;
+        };
+
+        let declared = self.table.symbol(symbol);
+        let flags = declared.flags;
+        if flags.contains(SymbolFlags::TYPE_ALIAS) {
+            return self.alias(symbol, name, arguments, span);
+        }
+        if flags.intersects(SymbolFlags::CLASS | SymbolFlags::INTERFACE) {
+            let type_params = self.generic_params(symbol);
+            return match self.type_arguments(name, &type_params, arguments, span) {
+                Some(arguments) => self.instance(symbol, arguments),
+                None => Type::Any,
+            };
+        }
+        if !arguments.is_empty() {
+            self.not_generic(name, span);
+            return Type::Any;
+        }
+        if flags.contains(SymbolFlags::TYPE_PARAMETER) {
+            Type::Param(declared.name.clone())
+        } else if flags.contains(SymbolFlags::ENUM) {
+            Type::Enum(declared.name.clone())
+        } else {
+            match name.rsplit('.').nth(1) {
+                Some(enum_name) if flags.contains(SymbolFlags::ENUM_MEMBER) => {
+                    Type::EnumMember(enum_name.to_string(), declared.name.clone())
+                }
+                // Imports, until modules are checked together
+                _ => Type::Any,
+            }
+        }
+    }
+
+    /// Resolves `A.B.C` to the symbol of `C`: `A` and `B` are namespaces, or `B` is an
+    /// enum whose member `C` is. Names reached through an import aren't known.
+    fn qualified(&mut self, name: &str, span: Span, scope: ScopeId) -> Option<SymbolId> {
+        let mut segments = name.split('.');
+        let first = segments.next()?;
+        let Some(mut symbol) = self.table.lookup(scope, first, Meaning::Namespace) else {
+            self.errors.push(
+                CompilerError::new(format!("Cannot find namespace '{}'.", first), span)
+                    .with_code(2503),
+            );
+            return None;
+        };
+        let mut qualifier = first.to_string();
+        let mut segments = segments.peekable();
+        while let Some(segment) = segments.next() {
+            let meaning = if segments.peek().is_some() {
+                Meaning::Namespace
+            } else {
+                Meaning::Type
+            };
+            let members = self.members(symbol)?;
+            let member = self
+                .table
+                .scope(members)
+                .symbols
+                .get(segment)
+                .filter(|member| self.table.symbol(**member).flags.has_meaning(meaning));
+            let Some(member) = member else {
+                self.errors.push(
+                    CompilerError::new(
+                        format!(
+                            "Namespace '{}' has no exported member '{}'.",
+                            qualifier, segment
+                        ),
+                        span,
+                    )
+                    .with_code(2694),
+                );
+                return None;
+            };
+            symbol = *member;
+            qualifier = format!("{}.{}", qualifier, segment);
+        }
+        Some(symbol)
+    }
+
+    /// The scope holding the exports of a namespace or the members of an enum.
+    fn members(&self, symbol: SymbolId) -> Option<ScopeId> {
+        let declarations = self.declarations.get(&symbol)?;
+        declarations.iter().find_map(|declaration| {
+            let scope = self.table.scope_of(declaration)?;
+            match declaration {
+                // Its body scope is inside the one its declarations share
+                Node::NamespaceDeclaration { .. } => self.table.scope(scope).parent,
+                Node::EnumDeclaration { .. } => Some(scope),
+                _ => None,
+            }
+        })
+    }
+
+    fn builtin(&mut self, name: &str, arguments: Vec<Type>, span: Span) -> Option<Type> {
+        let (_, names, required) = BUILTIN_TYPES
+            .iter()
+            .find(|(builtin, ..)| *builtin == name)?;
+        if arguments.len() < *required || arguments.len() > names.len() {
+            self.arity_error(name, names, *required, span);
+            return Some(Type::Any);
+        }
+        let members = |t: &Type| match t {
+            Type::Union(types) => types.clone(),
+            t => vec![t.clone()],
+        };
+        Some(match (name, arguments.as_slice()) {
+            ("Array" | "ReadonlyArray", [element]) => Type::Array(Box::new(element.clone())),
+            ("Readonly", [t]) => t.clone(),
+            ("NonNullable", [t]) => t.non_nullable(),
+            ("Exclude", [t, excluded]) => Type::union(
+                members(t)
+                    .into_iter()
+                    .filter(|member| !member.is_assignable_to(excluded))
+                    .collect(),
+            ),
+            ("Extract", [t, extracted]) => Type::union(
+                members(t)
+                    .into_iter()
+                    .filter(|member| member.is_assignable_to(extracted))
+                    .collect(),
+            ),
+            ("PropertyKey", []) => Type::union(vec![Type::String, Type::Number, Type::Symbol]),
+            ("Uppercase" | "Lowercase" | "Capitalize" | "Uncapitalize", _) => Type::String,
+            _ => Type::Custom(name.to_string(), arguments),
+        })
+    }
+
+    /// The body of a type alias given `arguments`.
+    fn alias(&mut self, symbol: SymbolId, name: &str, arguments: Vec<Type>, span: Span) -> Type {
+        // An object type refers to itself by its shape, which is in `aliases` already
+        if self.resolving.contains(&symbol) && !self.aliases.contains_key(&symbol) {
+            if self.deferred {
+                return Type::Custom(self.table.symbol(symbol).name.clone(), arguments);
+            }
+            self.errors.push(
+                CompilerError::new(
+                    format!("Type alias '{}' circularly references itself.", name),
+                    span,
+                )
+                .with_code(2456),
+            );
+            return Type::Any;
+        }
+        let type_params = self.generic_params(symbol);
+        let Some(arguments) = self.type_arguments(name, &type_params, arguments, span) else {
+            return Type::Any;
+        };
+        let mapping = type_params
+            .iter()
+            .map(|type_param| type_param.name.clone())
+            .zip(arguments)
+            .collect();
+        self.alias_body(symbol).substitute(&mapping)
+    }
+
+    fn alias_body(&mut self, symbol: SymbolId) -> Type {
+        if let Some(body) = self.aliases.get(&symbol) {
+            return body.clone();
+        }
+        let declaration = self
+            .declarations
+            .get(&symbol)
+            .and_then(|declarations| declarations.first().copied());
+        let Some(
+            declaration @ Node::TypeAliasDeclaration {
+                type_annotation, ..
+            },
+        ) = declaration
+        else {
+            return Type::Any;
+        };
+        let scope = self
+            .table
+            .scope_of(declaration)
+            .unwrap_or(self.table.declaring_scope(symbol));
+        // The body sees none of the type parameters of where it's used
+        let local_type_params = std::mem::take(&mut self.local_type_params);
+        let deferred = std::mem::replace(&mut self.deferred, false);
+        self.resolving.push(symbol);
+        let body = match type_annotation.as_ref() {
+            Node::ObjectType { members } => {
+                let shape = self.table.shapes().reserve();
+                self.aliases.insert(symbol, Type::Shape(shape.clone()));
+                let type_arguments = self
+                    .generic_params(symbol)
+                    .into_iter()
+                    .map(|type_param| Type::Param(type_param.name))
+                    .collect();
+                let mut object = ObjectType {
+                    name: Some(self.table.symbol(symbol).name.clone()),
+                    type_arguments,
+                    ..ObjectType::default()
+                };
+                for member in members {
+                    self.member(&mut object, member, scope);
+                }
+                shape.define(object);
+                Type::Shape(shape)
+            }
+            type_annotation => self.lower(type_annotation, scope),
+        };
+        self.resolving.pop();
+        self.deferred = deferred;
+        self.local_type_params = local_type_params;
+        self.aliases.insert(symbol, body.clone());
+        body
+    }
+
+    /// The type parameters of a class, interface or type alias.
+    fn generic_params(&mut self, symbol: SymbolId) -> Vec<TypeParam> {
+        if let Some(type_params) = self.generics.get(&symbol) {
+            return type_params.clone();
+        }
+        let declaration = self.declarations.get(&symbol).and_then(|declarations| {
+            declarations
+                .iter()
+                .find_map(|declaration| match declaration {
+                    Node::ClassDeclaration { type_params, .. }
+                    | Node::InterfaceDeclaration { type_params, .. }
+                    | Node::TypeAliasDeclaration { type_params, .. }
+                        if !type_params.is_empty() =>
+                    {
+                        Some((*declaration, type_params))
+                    }
+                    _ => None,
+                })
+        });
+        let Some((declaration, type_params)) = declaration else {
+            self.generics.insert(symbol, Vec::new());
+            return Vec::new();
+        };
+        // Constraints may refer to the type being declared: `T extends Tree<T>`
+        let unconstrained = type_params
+            .iter()
+            .map(|type_param| TypeParam {
+                name: type_param.name.clone(),
+                constraint: None,
+                default: type_param.default.as_ref().map(|_| Type::Unknown),
+            })
+            .collect();
+        self.generics.insert(symbol, unconstrained);
+        let scope = self
+            .table
+            .scope_of(declaration)
+            .unwrap_or(self.table.declaring_scope(symbol));
+        let local_type_params = std::mem::take(&mut self.local_type_params);
+        let lowered = self.type_params(type_params, scope);
+        self.local_type_params = local_type_params;
+        self.generics.insert(symbol, lowered.clone());
+        lowered
+    }
+
+    /// `arguments` with the defaults of the type parameters they leave out, if there are as
+    /// many as `type_params` needs. Constraints are checked with the rest of the program.
+    fn type_arguments(
+        &mut self,
+        name: &str,
+        type_params: &[TypeParam],
+        mut arguments: Vec<Type>,
+        span: Span,
+    ) -> Option<Vec<Type>> {
+        if type_params.is_empty() {
+            if !arguments.is_empty() {
+                self.not_generic(name, span);
+                return None;
+            }
+            return Some(arguments);
+        }
+        let required = required_type_params(type_params);
+        if arguments.len() < required || arguments.len() > type_params.len() {
+            let names: Vec<&str> = type_params
+                .iter()
+                .map(|type_param| type_param.name.as_str())
+                .collect();
+            self.arity_error(name, &names, required, span);
+            return None;
+        }
+        let mut mapping = HashMap::new();
+        for (index, type_param) in type_params.iter().enumerate() {
+            if index == arguments.len() {
+                // Defaults may refer to earlier parameters: `<T, U = T[]>`
+                let default = type_param.default.as_ref()?.substitute(&mapping);
+                arguments.push(default);
+            }
+            mapping.insert(type_param.name.clone(), arguments[index].clone());
+        }
+        Some(arguments)
+    }
+
+    fn arity_error(&mut self, name: &str, type_params: &[&str], required: usize, span: Span) {
+        if type_params.is_empty() {
+            return self.not_generic(name, span);
+        }
+        let generic = format!("{}<{}>", name, type_params.join(", "));
+        let error = if required == type_params.len() {
+            CompilerError::new(
+                format!(
+                    "Generic type '{}' requires {} type argument(s).",
+                    generic, required
+                ),
+                span,
+            )
+            .with_code(2314)
+        } else {
+            CompilerError::new(
+                format!(
+                    "Generic type '{}' requires between {} and {} type arguments.",
+                    generic,
+                    required,
+                    type_params.len()
+                ),
+                span,
+            )
+            .with_code(2707)
+        };
+        self.errors.push(error);
+    }
+
+    fn not_generic(&mut self, name: &str, span: Span) {
+        self.errors.push(
+            CompilerError::new(format!("Type '{}' is not generic.", name), span).with_code(2315),
+        );
+    }
+
+    /// Gives the names declared by `binding` the parts of `value_type` they get, unless an
+    /// earlier declaration gave them a type.
+    fn bind(&mut self, binding: &Node, value_type: Type) {
+        if let Some(symbol) = self.table.symbol_of(binding) {
+            self.values.entry(symbol).or_insert(value_type);
+            return;
+        }
+        // Mismatched patterns are reported by the checker
+        let Ok(bindings) = pattern_bindings(binding, &value_type) else {
+            return;
+        };
+        for identifier in binding.binding_identifiers() {
+            let Node::Identifier(name, _) = identifier else {
+                continue;
+            };
+            let bound = bindings.iter().find(|(bound, _)| bound == name);
+            if let (Some(symbol), Some((_, bound))) = (self.table.symbol_of(identifier), bound) {
+                self.values.entry(symbol).or_insert_with(|| bound.clone());
+            }
+        }
+    }
+
+    /// Gives the type parameters declared in `scope` their types.
+    fn declare_type_params(&mut self, type_params: &[TypeParameter], scope: ScopeId) {
+        for type_param in type_params {
+            if let Some(&symbol) = self.table.scope(scope).symbols.get(&type_param.name) {
+                self.declared
+                    .insert(symbol, Type::Param(type_param.name.clone()));
+            }
+        }
+    }
+
+    fn function(
+        &mut self,
+        node: &Node,
+        type_params: &[TypeParameter],
+        params: &[Parameter],
+        return_type: Option<&Node>,
+        body: Option<&Node>,
+        scope: ScopeId,
+    ) {
+        self.declare_type_params(type_params, scope);
+        let method = matches!(node, Node::MethodDefinition { .. });
+        let (signature, annotations) =
+            self.annotated_signature(type_params, params, return_type, method, scope);
+        if let Some(symbol) = self.table.symbol_of(node) {
+            if !self.values.contains_key(&symbol) {
+                let value = self.function_value(symbol).unwrap_or(signature);
+                self.values.insert(symbol, value);
+            }
+        }
+        for (param, annotation) in params.iter().zip(annotations) {
+            let declared = annotation.unwrap_or(Type::Any);
+            let declared = if param.optional {
+                Type::union(vec![declared, Type::Undefined])
+            } else {
+                declared
+            };
+            self.bind(&param.binding, declared);
+            self.visit(&param.binding, scope);
+            if let Some(init) = &param.init {
+                self.visit(init, scope);
+            }
+            for decorator in &param.modifiers.decorators {
+                self.visit(decorator, scope);
+            }
+        }
+        if let Some(body) = body {
+            self.visit(body, scope);
+        }
+    }
+
+    fn visit(&mut self, node: &Node, scope: ScopeId) {
+        let scope = self.table.scope_of(node).unwrap_or(scope);
+        match node {
+            node if is_type(node) => {
+                self.lower(node, scope);
+            }
+            Node::VariableDeclaration { declarations, .. } => {
+                for declarator in declarations {
+                    if let Some(annotation) = &declarator.type_annotation {
+                        let declared = self.lower(annotation, scope);
+                        self.bind(&declarator.id, declared);
+                    }
+                    self.visit(&declarator.id, scope);
+                    if let Some(init) = &declarator.init {
+                        self.visit(init, scope);
+                    }
+                }
+            }
+            Node::FunctionDeclaration {
+                type_params,
+                params,
+                return_type,
+                body,
+                ..
+            }
+            | Node::MethodDefinition {
+                type_params,
+                params,
+                return_type,
+                body,
+                ..
+            } => {
+                if let Node::MethodDefinition { key, modifiers, .. } = node {
+                    self.visit(key, scope);
+                    for decorator in &modifiers.decorators {
+                        self.visit(decorator, scope);
+                    }
+                }
+                self.function(
+                    node,
+                    type_params,
+                    params,
+                    return_type.as_deref(),
+                    body.as_deref(),
+                    scope,
+                );
+            }
+            Node::ArrowFunctionExpression {
+                type_params,
+                params,
+                return_type,
+                body,
+                ..
+            } => self.function(
+                node,
+                type_params,
+                params,
+                return_type.as_deref(),
+                Some(body),
+                scope,
+            ),
+            Node::ClassDeclaration { type_params, .. }
+            | Node::InterfaceDeclaration { type_params, .. } => {
+                if let Some(symbol) = self.table.symbol_of(node) {
+                    let shape = self.shape(symbol);
+                    self.declared.insert(symbol, Type::Shape(shape));
+                }
+                self.declare_type_params(type_params, scope);
+                // Type parameters were lowered with `generic_params`
+                match node {
+                    Node::InterfaceDeclaration {
+                        extends, members, ..
+                    } => {
+                        for base in extends {
+                            self.lower(base, scope);
+                        }
+                        // For their errors, as the shape was built without them
+                        for member in members {
+                            self.member(&mut ObjectType::default(), member, scope);
+                        }
+                    }
+                    Node::ClassDeclaration {
+                        extends,
+                        super_type_arguments,
+                        implements,
+                        members,
+                        decorators,
+                        ..
+                    } => {
+                        let children = decorators
+                            .iter()
+                            .chain(extends.as_deref())
+                            .chain(super_type_arguments)
+                            .chain(implements)
+                            .chain(members);
+                        for child in children {
+                            self.visit(child, scope);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Node::TypeAliasDeclaration { type_params, .. } => {
+                if let Some(symbol) = self.table.symbol_of(node) {
+                    self.generic_params(symbol);
+                    let body = self.alias_body(symbol);
+                    self.declared.insert(symbol, body);
+                }
+                self.declare_type_params(type_params, scope);
+            }
+            Node::EnumDeclaration { name, members, .. } => {
+                let Some(symbol) = self.table.symbol_of(node) else {
+                    return;
+                };
+                self.declared.insert(symbol, Type::Enum(name.clone()));
+                if let Some(scope) = self.members(symbol) {
+                    for member in members {
+                        if let Some(&member_symbol) =
+                            self.table.scope(scope).symbols.get(&member.name)
+                        {
+                            let member_type = Type::EnumMember(name.clone(), member.name.clone());
+                            self.declared.insert(member_symbol, member_type.clone());
+                            self.values.insert(member_symbol, member_type);
+                        }
+                    }
+                }
+                for init in members.iter().filter_map(|member| member.init.as_deref()) {
+                    self.visit(init, scope);
+                }
+            }
+            node => {
+                for child in node.children() {
+                    self.visit(child, scope);
+                }
+            }
+        }
+    }
+}
+
+/// Adds a property to `object`, unless it has one by that name already.
+fn add_property(
+    object: &mut ObjectType,
+    name: &str,
+    value_type: Type,
+    optional: bool,
+    readonly: bool,
+) {
+    if object.property(name).is_none() {
+        object.properties.push(PropertyType {
+            name: name.to_string(),
+            value_type,
+            optional,
+            readonly,
+        });
+    }
+}
+
+/// The type of `keyof t`: the names of an object type's properties, or any property key
+/// where they aren't known.
+fn keys(t: &Type) -> Type {
+    let Type::Shape(shape) = t else {
+        return Type::union(vec![Type::String, Type::Number, Type::Symbol]);
+    };
+    let object = shape.get();
+    if object.string_index.is_some() {
+        return Type::union(vec![Type::String, Type::Number]);
+    }
+    let names = object.properties.iter().map(|property| {
+        if property.name.parse::<f64>().is_ok() {
+            Type::Literal(property.name.clone())
+        } else {
+            Type::Literal(format!("\"{}\"", property.name))
+        }
+    });
+    let number = object.number_index.as_ref().map(|_| Type::Number);
+    Type::union(number.into_iter().chain(names).collect())
+}
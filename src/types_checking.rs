@@ -1,4 +1,13 @@
-#[derive(Debug, PartialEq, Clone)]
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::rc::{Rc, Weak};
+
+use crate::parser::Node;
+use crate::tokenizer::TokenType;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum Type {
     Number,
     String,
@@ -11,15 +20,2139 @@ pub enum Type {
     Void,
     BigInt,
     Symbol,
-    Object,
+    Object, // `object`: any value that isn't a primitive
     Function {
+        type_params: Vec<TypeParam>,
         params: Vec<Type>,
+        required: usize,         // how many of `params` a call has to pass
+        rest: Option<Box<Type>>, // the array type of a rest parameter
         return_type: Box<Type>,
+        method: bool, // declared with method syntax, which makes its parameters bivariant
     },
     Union(Vec<Type>),
     Intersection(Vec<Type>),
-    Literal(String),
+    Literal(String), // As written in source: `"a"`, `1`, `true`, `10n`
     Array(Box<Type>),
-    Tuple(Vec<Type>),
-    Custom(String), // For user-defined types
-}
\ No newline at end of file
+    Tuple {
+        elements: Vec<Type>,     // optional ones may be `undefined`
+        required: usize,         // how many of `elements` a value has to have
+        rest: Option<Box<Type>>, // the array type of a rest element, after `elements`
+    },
+    Custom(String, Vec<Type>), // A type whose members aren't known, with type arguments
+    Param(String),             // A type variable bound by an enclosing TypeParam
+    Enum(String),              // The union of all members of an enum
+    EnumMember(String, String), // A single member, `E.A`
+    Shape(ShapeId),            // An object type with known members
+    // What a type guard (`x is T`) or assertion function (`asserts x is T`, `asserts x`)
+    // returns: the index of the parameter it tells about, `None` for `this`, and its type
+    Predicate {
+        parameter: Option<usize>,
+        asserts: bool,
+        guarded: Option<Box<Type>>,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct TypeParam {
+    pub name: String,
+    pub constraint: Option<Type>,
+    pub default: Option<Type>,
+}
+
+/// The members of an object type: an interface, the instances of a class, an object type
+/// literal or the type of an object literal. Object types are interned and referred to by
+/// `Type::Shape`, so a recursive one refers to itself by its ID.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Default)]
+pub struct ObjectType {
+    pub name: Option<String>, // of the interface, class or alias, shown instead of the members
+    pub type_arguments: Vec<Type>, // it was instantiated with
+    pub properties: Vec<PropertyType>, // methods too, with method signatures as their types
+    pub call_signatures: Vec<Type>,
+    pub construct_signatures: Vec<Type>,
+    pub string_index: Option<IndexType>, // `[key: string]: T`
+    pub number_index: Option<IndexType>, // `[index: number]: T`
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct PropertyType {
+    pub name: String,
+    pub value_type: Type,
+    pub optional: bool,
+    pub readonly: bool,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct IndexType {
+    pub value_type: Type,
+    pub readonly: bool,
+}
+
+/// An object type in the `Shapes` of the compilation that made it.
+#[derive(Clone)]
+pub struct ShapeId {
+    index: usize,
+    shapes: Weak<ShapeTable>,
+}
+
+// Shapes of different compilations are never equal, even at the same index
+impl PartialEq for ShapeId {
+    fn eq(&self, other: &ShapeId) -> bool {
+        self.index == other.index && self.shapes.ptr_eq(&other.shapes)
+    }
+}
+
+impl Eq for ShapeId {}
+
+impl Hash for ShapeId {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+        self.shapes.as_ptr().hash(state);
+    }
+}
+
+impl fmt::Debug for ShapeId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ShapeId({})", self.index)
+    }
+}
+
+/// Shapes being substituted, with the mapping they are substituted with and the shape
+/// they become.
+type Substituted = Vec<(ShapeId, HashMap<String, Type>, ShapeId)>;
+
+/// The object types of one compilation. The symbol table the types are stored in owns
+/// them, and a `ShapeId` only refers back to them weakly, so they're freed along with the
+/// table; a type kept after that can't be looked into any more.
+#[derive(Clone, Default)]
+pub struct Shapes(Rc<ShapeTable>);
+
+#[derive(Default)]
+struct ShapeTable {
+    // Defined object types by index; `None` for those reserved but not defined yet
+    objects: RefCell<Vec<Option<Rc<ObjectType>>>>,
+    indices: RefCell<HashMap<Rc<ObjectType>, usize>>,
+    // Pairs of shapes being related, which a recursive type is assumed to relate when it
+    // comes back to them
+    relating: RefCell<Vec<(ShapeId, ShapeId)>>,
+    inferring: RefCell<Vec<(ShapeId, ShapeId)>>,
+    displaying: RefCell<Vec<ShapeId>>,
+}
+
+impl Shapes {
+    /// The ID of `object`, shared with every equal object type interned or defined before.
+    pub fn intern(&self, object: ObjectType) -> ShapeId {
+        if let Some(&index) = self.0.indices.borrow().get(&object) {
+            return self.id(index);
+        }
+        let object = Rc::new(object);
+        let mut objects = self.0.objects.borrow_mut();
+        objects.push(Some(object.clone()));
+        self.0
+            .indices
+            .borrow_mut()
+            .insert(object, objects.len() - 1);
+        self.id(objects.len() - 1)
+    }
+
+    /// A function with the overloads `signatures`, which calls try in order: the signature
+    /// itself when there is one, or an object type with them as its call signatures.
+    pub fn overloaded(&self, mut signatures: Vec<Type>) -> Type {
+        if signatures.len() == 1 {
+            return signatures.remove(0);
+        }
+        Type::Shape(self.intern(ObjectType {
+            call_signatures: signatures,
+            ..ObjectType::default()
+        }))
+    }
+
+    /// A new ID for an object type whose members may refer to it, to be given them with
+    /// `define`. Until then it has no members.
+    pub fn reserve(&self) -> ShapeId {
+        let mut objects = self.0.objects.borrow_mut();
+        objects.push(None);
+        self.id(objects.len() - 1)
+    }
+
+    fn id(&self, index: usize) -> ShapeId {
+        ShapeId {
+            index,
+            shapes: Rc::downgrade(&self.0),
+        }
+    }
+}
+
+impl fmt::Debug for Shapes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Shapes({})", self.0.objects.borrow().len())
+    }
+}
+
+impl ShapeId {
+    fn shapes(&self) -> Shapes {
+        Shapes(
+            self.shapes
+                .upgrade()
+                .expect("a shape is only used while its symbol table exists"),
+        )
+    }
+
+    pub fn define(&self, object: ObjectType) {
+        let shapes = self.shapes();
+        let object = Rc::new(object);
+        shapes.0.objects.borrow_mut()[self.index] = Some(object.clone());
+        shapes
+            .0
+            .indices
+            .borrow_mut()
+            .entry(object)
+            .or_insert(self.index);
+    }
+
+    pub fn get(&self) -> Rc<ObjectType> {
+        self.shapes().0.objects.borrow()[self.index]
+            .clone()
+            .unwrap_or_default()
+    }
+
+    /// This shape with the type variables in its members replaced. A shape that doesn't
+    /// mention them stays the same.
+    fn substitute(
+        &self,
+        mapping: &HashMap<String, Type>,
+        substituted: &mut Substituted,
+    ) -> ShapeId {
+        if let Some((.., to)) = substituted
+            .iter()
+            .find(|(from, with, _)| from == self && with == mapping)
+        {
+            return to.clone();
+        }
+        let names: Vec<&str> = mapping.keys().map(String::as_str).collect();
+        if !Type::Shape(self.clone()).mentions_any(&names) {
+            return self.clone();
+        }
+        let to = self.shapes().reserve();
+        substituted.push((self.clone(), mapping.clone(), to.clone()));
+        let object = self.get();
+        let mut substitute = |t: &Type| t.substitute_with(mapping, substituted);
+        let index = |index: &Option<IndexType>, substitute: &mut dyn FnMut(&Type) -> Type| {
+            index.as_ref().map(|index| IndexType {
+                value_type: substitute(&index.value_type),
+                readonly: index.readonly,
+            })
+        };
+        let object = ObjectType {
+            name: object.name.clone(),
+            type_arguments: object.type_arguments.iter().map(&mut substitute).collect(),
+            properties: object
+                .properties
+                .iter()
+                .map(|property| PropertyType {
+                    value_type: substitute(&property.value_type),
+                    ..property.clone()
+                })
+                .collect(),
+            call_signatures: object.call_signatures.iter().map(&mut substitute).collect(),
+            construct_signatures: object
+                .construct_signatures
+                .iter()
+                .map(&mut substitute)
+                .collect(),
+            string_index: index(&object.string_index, &mut substitute),
+            number_index: index(&object.number_index, &mut substitute),
+        };
+        to.define(object);
+        to
+    }
+}
+
+impl ObjectType {
+    pub fn property(&self, name: &str) -> Option<&PropertyType> {
+        self.properties
+            .iter()
+            .find(|property| property.name == name)
+    }
+
+    /// Adds the members of `base` this object type doesn't declare itself, as an interface
+    /// or class inherits them.
+    pub fn inherit(&mut self, base: &ObjectType) {
+        for property in &base.properties {
+            if self.property(&property.name).is_none() {
+                self.properties.push(property.clone());
+            }
+        }
+        if self.call_signatures.is_empty() {
+            self.call_signatures = base.call_signatures.clone();
+        }
+        if self.construct_signatures.is_empty() {
+            self.construct_signatures = base.construct_signatures.clone();
+        }
+        if self.string_index.is_none() {
+            self.string_index = base.string_index.clone();
+        }
+        if self.number_index.is_none() {
+            self.number_index = base.number_index.clone();
+        }
+    }
+
+    fn member_types(&self) -> impl Iterator<Item = &Type> {
+        self.type_arguments
+            .iter()
+            .chain(self.properties.iter().map(|property| &property.value_type))
+            .chain(&self.call_signatures)
+            .chain(&self.construct_signatures)
+            .chain(self.string_index.iter().map(|index| &index.value_type))
+            .chain(self.number_index.iter().map(|index| &index.value_type))
+    }
+}
+
+impl Type {
+    /// Replaces type variables with their bindings in `mapping`.
+    pub fn substitute(&self, mapping: &HashMap<String, Type>) -> Type {
+        self.substitute_with(mapping, &mut Vec::new())
+    }
+
+    /// `substitute`, where `substituted` holds the shapes already being substituted with
+    /// each mapping, so recursive ones end.
+    fn substitute_with(
+        &self,
+        mapping: &HashMap<String, Type>,
+        substituted: &mut Substituted,
+    ) -> Type {
+        match self {
+            Type::Param(name) => mapping.get(name).cloned().unwrap_or_else(|| self.clone()),
+            Type::Function {
+                type_params,
+                params,
+                required,
+                rest,
+                return_type,
+                method,
+            } => {
+                // A generic signature's own parameters shadow outer bindings of the same name
+                let mut inner = mapping.clone();
+                for type_param in type_params {
+                    inner.remove(&type_param.name);
+                }
+                Type::Function {
+                    type_params: type_params
+                        .iter()
+                        .map(|type_param| TypeParam {
+                            name: type_param.name.clone(),
+                            constraint: type_param
+                                .constraint
+                                .as_ref()
+                                .map(|t| t.substitute_with(&inner, substituted)),
+                            default: type_param
+                                .default
+                                .as_ref()
+                                .map(|t| t.substitute_with(&inner, substituted)),
+                        })
+                        .collect(),
+                    params: params
+                        .iter()
+                        .map(|t| t.substitute_with(&inner, substituted))
+                        .collect(),
+                    required: *required,
+                    rest: rest
+                        .as_ref()
+                        .map(|t| Box::new(t.substitute_with(&inner, substituted))),
+                    return_type: Box::new(return_type.substitute_with(&inner, substituted)),
+                    method: *method,
+                }
+            }
+            Type::Union(types) => Type::Union(
+                types
+                    .iter()
+                    .map(|t| t.substitute_with(mapping, substituted))
+                    .collect(),
+            ),
+            Type::Intersection(types) => Type::Intersection(
+                types
+                    .iter()
+                    .map(|t| t.substitute_with(mapping, substituted))
+                    .collect(),
+            ),
+            Type::Array(element) => {
+                Type::Array(Box::new(element.substitute_with(mapping, substituted)))
+            }
+            Type::Tuple {
+                elements,
+                required,
+                rest,
+            } => Type::Tuple {
+                elements: elements
+                    .iter()
+                    .map(|t| t.substitute_with(mapping, substituted))
+                    .collect(),
+                required: *required,
+                rest: rest
+                    .as_ref()
+                    .map(|rest| Box::new(rest.substitute_with(mapping, substituted))),
+            },
+            Type::Custom(name, arguments) => Type::Custom(
+                name.clone(),
+                arguments
+                    .iter()
+                    .map(|t| t.substitute_with(mapping, substituted))
+                    .collect(),
+            ),
+            Type::Shape(shape) => Type::Shape(shape.substitute(mapping, substituted)),
+            Type::Predicate {
+                parameter,
+                asserts,
+                guarded,
+            } => Type::Predicate {
+                parameter: *parameter,
+                asserts: *asserts,
+                guarded: guarded
+                    .as_ref()
+                    .map(|t| Box::new(t.substitute_with(mapping, substituted))),
+            },
+            _ => self.clone(),
+        }
+    }
+
+    /// The primitive a literal type widens to, e.g. `"a"` to `string`.
+    pub fn widen_literal(&self) -> Type {
+        match self {
+            Type::Literal(value) if value.starts_with(['"', '\'']) => Type::String,
+            Type::Literal(value) if value == "true" || value == "false" => Type::Boolean,
+            Type::Literal(value) if value.ends_with('n') => Type::BigInt,
+            Type::Literal(_) => Type::Number,
+            _ => self.clone(),
+        }
+    }
+
+    /// This type with its literals widened to their primitives and enum members to their
+    /// enums, as a mutable variable initialized with a value of it is declared.
+    pub fn widened(&self) -> Type {
+        match self {
+            Type::Literal(_) => self.widen_literal(),
+            Type::EnumMember(name, _) => Type::Enum(name.clone()),
+            Type::Union(types) => Type::union(types.iter().map(Type::widened).collect()),
+            _ => self.clone(),
+        }
+    }
+
+    /// What a call of a function with this return type gives: a type guard gives a
+    /// `boolean`, and an assertion function nothing.
+    pub fn returned_value(&self) -> Type {
+        match self {
+            Type::Predicate { asserts: false, .. } => Type::Boolean,
+            Type::Predicate { asserts: true, .. } => Type::Void,
+            _ => self.clone(),
+        }
+    }
+
+    /// The signatures of a function, or the overloads of one `Shapes::overloaded` made.
+    pub fn overloads(&self) -> Option<Vec<Type>> {
+        match self {
+            Type::Function { .. } => Some(vec![self.clone()]),
+            Type::Shape(shape) => {
+                let object = shape.get();
+                let overloads = ObjectType {
+                    call_signatures: object.call_signatures.clone(),
+                    ..ObjectType::default()
+                };
+                (object.call_signatures.len() > 1 && *object == overloads)
+                    .then_some(overloads.call_signatures)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn is_primitive(&self) -> bool {
+        matches!(
+            self,
+            Type::Number
+                | Type::String
+                | Type::Boolean
+                | Type::BigInt
+                | Type::Symbol
+                | Type::Null
+                | Type::Undefined
+                | Type::Literal(_)
+        )
+    }
+
+    fn mentions_any(&self, names: &[&str]) -> bool {
+        self.mentions(names, &mut Vec::new())
+    }
+
+    /// `mentions_any`, where `visited` holds the shapes already looked into.
+    fn mentions(&self, names: &[&str], visited: &mut Vec<ShapeId>) -> bool {
+        match self {
+            Type::Param(name) => names.contains(&name.as_str()),
+            Type::Function {
+                params,
+                rest,
+                return_type,
+                ..
+            } => {
+                params
+                    .iter()
+                    .chain(rest.as_deref())
+                    .any(|t| t.mentions(names, visited))
+                    || return_type.mentions(names, visited)
+            }
+            Type::Union(types) | Type::Intersection(types) => {
+                types.iter().any(|t| t.mentions(names, visited))
+            }
+            Type::Tuple { elements, rest, .. } => elements
+                .iter()
+                .chain(rest.as_deref())
+                .any(|t| t.mentions(names, visited)),
+            Type::Custom(_, arguments) => arguments.iter().any(|t| t.mentions(names, visited)),
+            Type::Array(element) => element.mentions(names, visited),
+            Type::Predicate {
+                guarded: Some(guarded),
+                ..
+            } => guarded.mentions(names, visited),
+            Type::Shape(shape) => {
+                if visited.contains(shape) {
+                    return false;
+                }
+                visited.push(shape.clone());
+                shape
+                    .get()
+                    .member_types()
+                    .any(|t| t.mentions(names, visited))
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether a value of this type can be used where one of `target` is expected.
+    ///
+    /// A function can take fewer parameters than the target passes, but can't require
+    /// more, and each of the target's parameters must be assignable to the source's
+    /// parameter at the same position. Methods are compared in either direction, as tsc
+    /// does under `strictFunctionTypes`. Generic functions are compared with their type
+    /// parameters erased to `any`.
+    ///
+    /// A value is assignable to an object type if it has each of the object type's
+    /// properties, unless they are optional, with a type assignable to the property's, and
+    /// signatures assignable to each of its call and construct signatures. Recursive types
+    /// are assumed to be assignable where they come back to a pair of types already being
+    /// compared. The members of built-in types like `Promise` aren't known, so they are
+    /// assignable to object types and the other way around.
+    pub fn is_assignable_to(&self, target: &Type) -> bool {
+        if self == target {
+            return true;
+        }
+        match (self, target) {
+            (_, Type::Any) | (_, Type::Unknown) | (Type::Never, _) => true,
+            (Type::Any, _) => *target != Type::Never,
+            (Type::Union(sources), _) => sources.iter().all(|t| t.is_assignable_to(target)),
+            (_, Type::Union(targets)) => targets.iter().any(|t| self.is_assignable_to(t)),
+            (_, Type::Intersection(targets)) => targets.iter().all(|t| self.is_assignable_to(t)),
+            // The members of an intersection come from all of its types together
+            (Type::Intersection(_), Type::Shape(target)) => self.is_assignable_to_shape(target),
+            (Type::Intersection(sources), _) => sources.iter().any(|t| t.is_assignable_to(target)),
+            (Type::Literal(_), _) => self.widen_literal().is_assignable_to(target),
+            (Type::Undefined, Type::Void) => true,
+            (Type::EnumMember(source, _), Type::Enum(target)) => source == target,
+            (Type::Array(source), Type::Array(target)) => source.is_assignable_to(target),
+            (Type::Tuple { .. }, Type::Tuple { .. }) => self.is_tuple_assignable_to(target),
+            (Type::Tuple { elements, rest, .. }, Type::Array(element)) => {
+                elements.iter().all(|t| t.is_assignable_to(element))
+                    && rest
+                        .as_deref()
+                        .is_none_or(|rest| rest.is_assignable_to(target))
+            }
+            // An array can be any length, which only a tuple of nothing but a rest element takes
+            (
+                Type::Array(_),
+                Type::Tuple {
+                    elements,
+                    rest: Some(rest),
+                    ..
+                },
+            ) if elements.is_empty() => self.is_assignable_to(rest),
+            (Type::Function { .. }, Type::Function { .. }) => {
+                self.is_signature_assignable_to(target)
+            }
+            (
+                Type::Predicate {
+                    parameter,
+                    asserts,
+                    guarded,
+                },
+                Type::Predicate {
+                    parameter: target_parameter,
+                    asserts: target_asserts,
+                    guarded: target_guarded,
+                },
+            ) => {
+                parameter == target_parameter
+                    && asserts == target_asserts
+                    && match (guarded, target_guarded) {
+                        (Some(guarded), Some(target)) => guarded.is_assignable_to(target),
+                        (None, None) => true,
+                        _ => false,
+                    }
+            }
+            // A type guard can be used where a function returns a `boolean`
+            (Type::Predicate { .. }, _) => self.returned_value().is_assignable_to(target),
+            (Type::Custom(source, source_args), Type::Custom(target, target_args))
+                if source == target =>
+            {
+                source_args.len() == target_args.len()
+                    && source_args
+                        .iter()
+                        .zip(target_args)
+                        .all(|(s, t)| s.is_assignable_to(t))
+            }
+            (_, Type::Shape(target)) => self.is_assignable_to_shape(target),
+            (Type::Shape(source), Type::Function { .. }) => source
+                .get()
+                .call_signatures
+                .iter()
+                .any(|signature| signature.is_assignable_to(target)),
+            (Type::Custom(..) | Type::Object | Type::Shape(_), Type::Custom(..)) => true,
+            (Type::Array(_) | Type::Tuple { .. } | Type::String, Type::Custom(name, arguments))
+                if matches!(name.as_str(), "Iterable" | "ArrayLike") =>
+            {
+                match (self.element_type(None), arguments.as_slice()) {
+                    (Ok(element), [argument]) => element.is_assignable_to(argument),
+                    _ => false,
+                }
+            }
+            (Type::Function { .. }, Type::Custom(name, _)) => name == "Function",
+            (
+                Type::Function { .. }
+                | Type::Array(_)
+                | Type::Tuple { .. }
+                | Type::Custom(..)
+                | Type::Shape(_),
+                Type::Object,
+            ) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether this tuple can be used where the tuple `target` is expected: it has at least
+    /// as many elements as `target` requires and no more than it allows, and each element
+    /// it may have is assignable to the element of `target` at its position.
+    fn is_tuple_assignable_to(&self, target: &Type) -> bool {
+        let (
+            Type::Tuple {
+                elements,
+                required,
+                rest,
+            },
+            Type::Tuple {
+                elements: targets,
+                required: target_required,
+                rest: target_rest,
+            },
+        ) = (self, target)
+        else {
+            return false;
+        };
+        let target_rest_element = target_rest
+            .as_ref()
+            .map(|rest| rest.element_type(None).unwrap_or(Type::Any));
+        if required < target_required
+            || target_rest.is_none() && (rest.is_some() || elements.len() > targets.len())
+        {
+            return false;
+        }
+        let fixed = elements.iter().enumerate().all(|(index, element)| {
+            targets
+                .get(index)
+                .or(target_rest_element.as_ref())
+                .is_some_and(|target| element.is_assignable_to(target))
+        });
+        // A rest element may fill any of the places left, and the target's rest
+        let rest = rest.as_ref().is_none_or(|rest| {
+            let element = rest.element_type(None).unwrap_or(Type::Any);
+            targets
+                .iter()
+                .skip(elements.len())
+                .all(|target| element.is_assignable_to(target))
+                && target_rest
+                    .as_ref()
+                    .is_some_and(|target| rest.is_assignable_to(target))
+        });
+        fixed && rest
+    }
+
+    fn is_assignable_to_shape(&self, target: &ShapeId) -> bool {
+        let shapes = target.shapes();
+        let pair = match self {
+            Type::Shape(source) => Some((source.clone(), target.clone())),
+            _ => None,
+        };
+        if let Some(pair) = pair.clone() {
+            let mut relating = shapes.0.relating.borrow_mut();
+            let assumed = relating.contains(&pair);
+            relating.push(pair);
+            if assumed {
+                relating.pop();
+                return true;
+            }
+        }
+        let assignable = self.has_members_of(&target.get());
+        if pair.is_some() {
+            shapes.0.relating.borrow_mut().pop();
+        }
+        assignable
+    }
+
+    fn has_members_of(&self, target: &ObjectType) -> bool {
+        match self {
+            Type::Null | Type::Undefined | Type::Void | Type::Param(_) => return false,
+            Type::Custom(..) => return true,
+            // `object` has no known members
+            Type::Object => {
+                return target.properties.iter().all(|property| property.optional)
+                    && target.call_signatures.is_empty()
+                    && target.construct_signatures.is_empty()
+            }
+            _ => {}
+        }
+        let properties =
+            target
+                .properties
+                .iter()
+                .all(|property| match self.property_type(&property.name) {
+                    Some(source) if property.optional => {
+                        source.is_assignable_to(&Type::union(vec![
+                            property.value_type.clone(),
+                            Type::Undefined,
+                        ]))
+                    }
+                    Some(source) => source.is_assignable_to(&property.value_type),
+                    None => property.optional,
+                });
+        let (calls, constructs) = match self {
+            Type::Function { .. } => (vec![self.clone()], Vec::new()),
+            Type::Shape(source) => {
+                let source = source.get();
+                (
+                    source.call_signatures.clone(),
+                    source.construct_signatures.clone(),
+                )
+            }
+            _ => (Vec::new(), Vec::new()),
+        };
+        let signatures = |sources: &[Type], targets: &[Type]| {
+            targets
+                .iter()
+                .all(|target| sources.iter().any(|source| source.is_assignable_to(target)))
+        };
+        let indexes = match self {
+            Type::Shape(source) => {
+                let source = source.get();
+                let fits = |index: &Option<IndexType>, numeric: bool| {
+                    let Some(index) = index else {
+                        return true;
+                    };
+                    let keys = source
+                        .properties
+                        .iter()
+                        .filter(|property| !numeric || property.name.parse::<f64>().is_ok());
+                    let mut types = keys
+                        .map(|property| &property.value_type)
+                        .chain(source.number_index.iter().map(|index| &index.value_type));
+                    let string_index = source.string_index.iter().map(|index| &index.value_type);
+                    types.all(|t| t.is_assignable_to(&index.value_type))
+                        && string_index
+                            .filter(|_| !numeric || source.number_index.is_none())
+                            .all(|t| t.is_assignable_to(&index.value_type))
+                };
+                fits(&target.string_index, false) && fits(&target.number_index, true)
+            }
+            Type::Array(_) | Type::Tuple { .. } => target.number_index.iter().all(|index| {
+                self.element_type(None)
+                    .is_ok_and(|element| element.is_assignable_to(&index.value_type))
+            }),
+            _ => true,
+        };
+        properties
+            && signatures(&calls, &target.call_signatures)
+            && signatures(&constructs, &target.construct_signatures)
+            && indexes
+    }
+
+    fn is_signature_assignable_to(&self, target: &Type) -> bool {
+        let (source, target) = (self.erase_type_params(), target.erase_type_params());
+        let (
+            Type::Function {
+                params: source_params,
+                required,
+                rest: source_rest,
+                return_type: source_return,
+                ..
+            },
+            Type::Function {
+                params: target_params,
+                rest: target_rest,
+                return_type: target_return,
+                method,
+                ..
+            },
+        ) = (&source, &target)
+        else {
+            return false;
+        };
+        if target_rest.is_none() && *required > target_params.len() {
+            return false;
+        }
+        let rest_element = |rest: &Option<Box<Type>>| {
+            rest.as_ref()
+                .map(|rest| rest.element_type(None).unwrap_or(Type::Any))
+        };
+        let compatible =
+            |s: &Type, t: &Type| t.is_assignable_to(s) || (*method && s.is_assignable_to(t));
+        for index in 0..source_params.len().max(target_params.len()) {
+            let s = source_params
+                .get(index)
+                .cloned()
+                .or_else(|| rest_element(source_rest));
+            let t = target_params
+                .get(index)
+                .cloned()
+                .or_else(|| rest_element(target_rest));
+            if let (Some(s), Some(t)) = (s, t) {
+                if !compatible(&s, &t) {
+                    return false;
+                }
+            }
+        }
+        if let (Some(s), Some(t)) = (source_rest, target_rest) {
+            if !compatible(s, t) {
+                return false;
+            }
+        }
+        **target_return == Type::Void || source_return.is_assignable_to(target_return)
+    }
+
+    /// A generic function with its type parameters replaced by `any`.
+    fn erase_type_params(&self) -> Type {
+        match self {
+            Type::Function {
+                type_params,
+                params,
+                required,
+                rest,
+                return_type,
+                method,
+            } if !type_params.is_empty() => {
+                let mapping = type_params
+                    .iter()
+                    .map(|type_param| (type_param.name.clone(), Type::Any))
+                    .collect();
+                Type::Function {
+                    type_params: Vec::new(),
+                    params: params.iter().map(|t| t.substitute(&mapping)).collect(),
+                    required: *required,
+                    rest: rest.as_ref().map(|t| Box::new(t.substitute(&mapping))),
+                    return_type: Box::new(return_type.substitute(&mapping)),
+                    method: *method,
+                }
+            }
+            _ => self.clone(),
+        }
+    }
+}
+
+/// Instantiates a generic signature for a call site. Explicit type arguments are used when
+/// given (`f<number>(x)`); otherwise they are inferred from the argument types.
+pub fn instantiate_call(
+    signature: &Type,
+    type_arguments: &[Type],
+    argument_types: &[Type],
+) -> Result<Type, String> {
+    let Type::Function {
+        type_params,
+        params,
+        required,
+        rest,
+        return_type,
+        method,
+    } = signature
+    else {
+        return Err(format!("Type '{}' has no call signatures", signature));
+    };
+    if type_params.is_empty() {
+        if !type_arguments.is_empty() {
+            return Err(format!(
+                "Expected 0 type arguments, but got {}",
+                type_arguments.len()
+            ));
+        }
+        return Ok(signature.clone());
+    }
+
+    let type_arguments = if type_arguments.is_empty() {
+        // Arguments past the parameters are matched with the rest parameter's elements
+        let mut expected = params.clone();
+        if let Some(rest) = rest {
+            let element = rest.element_type(None).unwrap_or(Type::Any);
+            expected.resize(argument_types.len().max(params.len()), element);
+        }
+        infer_type_arguments(type_params, &expected, argument_types)
+    } else {
+        type_arguments.to_vec()
+    };
+    let mapping = instantiate(type_params, &type_arguments)?;
+    Ok(Type::Function {
+        type_params: Vec::new(),
+        params: params.iter().map(|t| t.substitute(&mapping)).collect(),
+        required: *required,
+        rest: rest.as_ref().map(|t| Box::new(t.substitute(&mapping))),
+        return_type: Box::new(return_type.substitute(&mapping)),
+        method: *method,
+    })
+}
+
+/// Binds `type_arguments` to `type_params`, filling in defaults and checking constraints.
+pub fn instantiate(
+    type_params: &[TypeParam],
+    type_arguments: &[Type],
+) -> Result<HashMap<String, Type>, String> {
+    let required = required_type_params(type_params);
+    if type_arguments.len() < required || type_arguments.len() > type_params.len() {
+        let expected = if required == type_params.len() {
+            required.to_string()
+        } else {
+            format!("{}-{}", required, type_params.len())
+        };
+        return Err(format!(
+            "Expected {} type arguments, but got {}",
+            expected,
+            type_arguments.len()
+        ));
+    }
+
+    let mut mapping = HashMap::new();
+    for (index, type_param) in type_params.iter().enumerate() {
+        let argument = match type_arguments.get(index) {
+            Some(argument) => argument.clone(),
+            // Defaults may refer to earlier parameters: `<T, U = T[]>`
+            None => match &type_param.default {
+                Some(default) => default.substitute(&mapping),
+                None => {
+                    return Err(format!(
+                        "Type parameter '{}' has no default",
+                        type_param.name
+                    ))
+                }
+            },
+        };
+        if let Some(constraint) = &type_param.constraint {
+            let constraint = constraint.substitute(&mapping);
+            if !argument.is_assignable_to(&constraint) {
+                return Err(format!(
+                    "Type '{}' does not satisfy the constraint '{}'",
+                    argument, constraint
+                ));
+            }
+        }
+        mapping.insert(type_param.name.clone(), argument);
+    }
+    Ok(mapping)
+}
+
+/// How many type arguments `type_params` needs: up to the last one without a default, as
+/// a required parameter after an optional one (which the parser reports) is still required.
+pub fn required_type_params(type_params: &[TypeParam]) -> usize {
+    type_params
+        .iter()
+        .rposition(|type_param| type_param.default.is_none())
+        .map_or(0, |index| index + 1)
+}
+
+/// Infers type arguments for a call from the types of its arguments.
+///
+/// Each parameter type is matched structurally against the corresponding argument type to
+/// collect candidates for every type parameter. Literal candidates are widened unless the
+/// constraint asks for primitives, and the first candidate that all others are assignable to
+/// wins. Type parameters with no candidates fall back to their default, then their
+/// constraint, then `unknown`. A candidate that breaks the constraint is replaced by the
+/// constraint too, as in tsc, so the argument it came from is what gets reported.
+pub fn infer_type_arguments(
+    type_params: &[TypeParam],
+    params: &[Type],
+    arguments: &[Type],
+) -> Vec<Type> {
+    let names: Vec<&str> = type_params.iter().map(|p| p.name.as_str()).collect();
+    let mut candidates: HashMap<String, Vec<Type>> = HashMap::new();
+    for (param, argument) in params.iter().zip(arguments) {
+        infer_from(param, argument, &names, &mut candidates);
+    }
+
+    let mut inferred: HashMap<String, Type> = HashMap::new();
+    for type_param in type_params {
+        let keep_literals = type_param
+            .constraint
+            .as_ref()
+            .is_some_and(constraint_wants_literals);
+        let mut found: Vec<Type> = Vec::new();
+        for candidate in candidates.remove(&type_param.name).unwrap_or_default() {
+            let candidate = if keep_literals {
+                candidate
+            } else {
+                candidate.widen_literal()
+            };
+            if !found.contains(&candidate) {
+                found.push(candidate);
+            }
+        }
+
+        let chosen = found
+            .iter()
+            .find(|candidate| found.iter().all(|other| other.is_assignable_to(candidate)))
+            .or(found.first())
+            .cloned()
+            .or_else(|| type_param.default.as_ref().map(|t| t.substitute(&inferred)))
+            .or_else(|| {
+                type_param
+                    .constraint
+                    .as_ref()
+                    .map(|t| t.substitute(&inferred))
+            })
+            .unwrap_or(Type::Unknown);
+        let chosen = match &type_param.constraint {
+            Some(constraint) => {
+                let constraint = constraint.substitute(&inferred);
+                if chosen.is_assignable_to(&constraint) {
+                    chosen
+                } else {
+                    constraint
+                }
+            }
+            None => chosen,
+        };
+        inferred.insert(type_param.name.clone(), chosen);
+    }
+
+    type_params
+        .iter()
+        .map(|p| inferred.remove(&p.name).unwrap())
+        .collect()
+}
+
+fn infer_from(
+    param: &Type,
+    argument: &Type,
+    names: &[&str],
+    candidates: &mut HashMap<String, Vec<Type>>,
+) {
+    match (param, argument) {
+        (Type::Param(name), _) if names.contains(&name.as_str()) => {
+            candidates
+                .entry(name.clone())
+                .or_default()
+                .push(argument.clone());
+        }
+        (Type::Array(param), Type::Array(argument)) => {
+            infer_from(param, argument, names, candidates)
+        }
+        (Type::Array(element), Type::Tuple { elements, rest, .. }) => {
+            for argument in elements {
+                infer_from(element, argument, names, candidates);
+            }
+            if let Some(rest) = rest {
+                infer_from(param, rest, names, candidates);
+            }
+        }
+        (
+            Type::Tuple {
+                elements: params,
+                rest: param_rest,
+                ..
+            },
+            Type::Tuple {
+                elements: arguments,
+                rest,
+                ..
+            },
+        ) => {
+            for (param, argument) in params.iter().zip(arguments) {
+                infer_from(param, argument, names, candidates);
+            }
+            if let (Some(param), Some(argument)) = (param_rest, rest) {
+                infer_from(param, argument, names, candidates);
+            }
+        }
+        (Type::Union(params), _) => {
+            // Members that match a fixed part of the union (`T | undefined`) say nothing about T
+            let arguments = match argument {
+                Type::Union(arguments) => arguments.clone(),
+                _ => vec![argument.clone()],
+            };
+            let remaining: Vec<Type> = arguments
+                .into_iter()
+                .filter(|argument| !params.contains(argument))
+                .collect();
+            if remaining.is_empty() {
+                return;
+            }
+            let argument = if remaining.len() == 1 {
+                remaining[0].clone()
+            } else {
+                Type::Union(remaining)
+            };
+            for param in params.iter().filter(|p| p.mentions_any(names)) {
+                infer_from(param, &argument, names, candidates);
+            }
+        }
+        (
+            Type::Function {
+                params,
+                return_type,
+                ..
+            },
+            Type::Function {
+                params: arguments,
+                return_type: argument_return,
+                ..
+            },
+        ) => {
+            for (param, argument) in params.iter().zip(arguments) {
+                infer_from(param, argument, names, candidates);
+            }
+            infer_from(return_type, argument_return, names, candidates);
+        }
+        (Type::Custom(name, params), Type::Custom(argument_name, arguments))
+            if name == argument_name =>
+        {
+            for (param, argument) in params.iter().zip(arguments) {
+                infer_from(param, argument, names, candidates);
+            }
+        }
+        (Type::Shape(param), Type::Shape(argument)) => {
+            let shapes = param.shapes();
+            let pair = (param.clone(), argument.clone());
+            if shapes.0.inferring.borrow().contains(&pair) {
+                return;
+            }
+            shapes.0.inferring.borrow_mut().push(pair);
+            let (param, argument) = (param.get(), argument.get());
+            for property in &param.properties {
+                if let Some(argument) = argument.property(&property.name) {
+                    infer_from(
+                        &property.value_type,
+                        &argument.value_type,
+                        names,
+                        candidates,
+                    );
+                }
+            }
+            let signatures = param.call_signatures.iter().zip(&argument.call_signatures);
+            let indexes = [
+                (&param.string_index, &argument.string_index),
+                (&param.number_index, &argument.number_index),
+            ];
+            let indexes = indexes.into_iter().filter_map(|indexes| match indexes {
+                (Some(param), Some(argument)) => Some((&param.value_type, &argument.value_type)),
+                _ => None,
+            });
+            for (param, argument) in signatures.chain(indexes) {
+                infer_from(param, argument, names, candidates);
+            }
+            shapes.0.inferring.borrow_mut().pop();
+        }
+        (
+            Type::Predicate {
+                guarded: Some(param),
+                ..
+            },
+            Type::Predicate {
+                guarded: Some(argument),
+                ..
+            },
+        ) => infer_from(param, argument, names, candidates),
+        (Type::Shape(param), Type::Function { .. }) => {
+            if let [signature] = param.get().call_signatures.as_slice() {
+                infer_from(signature, argument, names, candidates);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn constraint_wants_literals(constraint: &Type) -> bool {
+    match constraint {
+        Type::Union(types) => types.iter().any(constraint_wants_literals),
+        _ => constraint.is_primitive(),
+    }
+}
+
+/// The type each name in a destructuring pattern gets when a value of type `source` is
+/// destructured, in pattern order.
+pub fn pattern_bindings(pattern: &Node, source: &Type) -> Result<Vec<(String, Type)>, String> {
+    let mut bindings = Vec::new();
+    bind_pattern(pattern, source, &mut bindings)?;
+    Ok(bindings)
+}
+
+fn bind_pattern(
+    pattern: &Node,
+    source: &Type,
+    bindings: &mut Vec<(String, Type)>,
+) -> Result<(), String> {
+    match pattern {
+        Node::Identifier(name, _) => bindings.push((name.clone(), source.clone())),
+        // A default only applies when the value is undefined, so it can't be afterwards
+        Node::AssignmentPattern { left, .. } => {
+            bind_pattern(left, &source.without_undefined(), bindings)?
+        }
+        Node::ObjectPattern(elements) => {
+            if matches!(source, Type::Null | Type::Undefined | Type::Void) {
+                return Err(format!(
+                    "Cannot destructure '{}' as it is {}",
+                    source, source
+                ));
+            }
+            for element in elements {
+                match element {
+                    Node::PatternProperty {
+                        key,
+                        computed,
+                        value,
+                    } => {
+                        let property = match (key.as_ref(), computed) {
+                            (Node::Identifier(name, _), false) => Some(name.as_str()),
+                            (Node::Literal { value, .. }, _) => Some(value.as_str()),
+                            _ => None,
+                        };
+                        let property_type = match property {
+                            Some(property) => source.property_type(property).ok_or_else(|| {
+                                format!(
+                                    "Property '{}' does not exist on type '{}'",
+                                    property, source
+                                )
+                            })?,
+                            None => Type::Any,
+                        };
+                        bind_pattern(value, &property_type, bindings)?;
+                    }
+                    Node::RestElement(argument) => {
+                        let rest = if *source == Type::Any {
+                            Type::Any
+                        } else {
+                            Type::Object
+                        };
+                        bind_pattern(argument, &rest, bindings)?;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Node::ArrayPattern(elements) => {
+            for (index, element) in elements.iter().enumerate() {
+                let Some(element) = element else {
+                    continue;
+                };
+                match element {
+                    Node::RestElement(argument) => {
+                        let rest = match source {
+                            Type::Tuple {
+                                elements,
+                                required,
+                                rest,
+                            } => Type::Tuple {
+                                elements: elements.iter().skip(index).cloned().collect(),
+                                required: required.saturating_sub(index),
+                                rest: rest.clone(),
+                            },
+                            Type::Any => Type::Any,
+                            _ => Type::Array(Box::new(source.element_type(None)?)),
+                        };
+                        bind_pattern(argument, &rest, bindings)?;
+                    }
+                    element => bind_pattern(element, &source.element_type(Some(index))?, bindings)?,
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+impl Type {
+    /// The type of `.name` on a value of this type, if the property is known to exist.
+    pub fn property_type(&self, name: &str) -> Option<Type> {
+        match self {
+            Type::Any => Some(Type::Any),
+            Type::String | Type::Array(_) | Type::Tuple { .. } if name == "length" => {
+                Some(match self {
+                    Type::Tuple {
+                        elements,
+                        required,
+                        rest: None,
+                    } => Type::union(
+                        (*required..=elements.len())
+                            .map(|length| Type::Literal(length.to_string()))
+                            .collect(),
+                    ),
+                    _ => Type::Number,
+                })
+            }
+            Type::Tuple { .. } => name
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| self.element_type(Some(index)).ok()),
+            Type::Union(types) => {
+                let types: Option<Vec<Type>> =
+                    types.iter().map(|t| t.property_type(name)).collect();
+                types.map(Type::Union)
+            }
+            Type::Intersection(types) => {
+                let types: Vec<Type> = types.iter().filter_map(|t| t.property_type(name)).collect();
+                (!types.is_empty()).then(|| Type::intersection(types))
+            }
+            Type::Shape(shape) => {
+                let object = shape.get();
+                match object.property(name) {
+                    Some(property) if property.optional => Some(Type::union(vec![
+                        property.value_type.clone(),
+                        Type::Undefined,
+                    ])),
+                    Some(property) => Some(property.value_type.clone()),
+                    None => {
+                        let number_index = object
+                            .number_index
+                            .as_ref()
+                            .filter(|_| name.parse::<f64>().is_ok());
+                        number_index
+                            .or(object.string_index.as_ref())
+                            .map(|index| index.value_type.clone())
+                    }
+                }
+            }
+            // The members of these aren't known, so any property may exist on them
+            Type::Object | Type::Custom(..) | Type::Param(_) => Some(Type::Any),
+            _ => None,
+        }
+    }
+
+    /// Whether `.name` on a value of this type is a `readonly` property, or is read through
+    /// a readonly index signature. Through a union it is if any member makes it so, and
+    /// through an intersection if every member declaring it does.
+    pub fn is_readonly_property(&self, name: &str) -> bool {
+        match self {
+            Type::Union(types) => types.iter().any(|t| t.is_readonly_property(name)),
+            Type::Intersection(types) => {
+                let declaring: Vec<&Type> = types
+                    .iter()
+                    .filter(|t| t.property_type(name).is_some())
+                    .collect();
+                !declaring.is_empty() && declaring.iter().all(|t| t.is_readonly_property(name))
+            }
+            Type::Shape(shape) => {
+                let object = shape.get();
+                match object.property(name) {
+                    Some(property) => property.readonly,
+                    None => object
+                        .number_index
+                        .as_ref()
+                        .filter(|_| name.parse::<f64>().is_ok())
+                        .or(object.string_index.as_ref())
+                        .is_some_and(|index| index.readonly),
+                }
+            }
+            _ => false,
+        }
+    }
+
+    /// The type of the element at `index` when iterating a value of this type, or of every
+    /// element when `index` is `None`.
+    pub fn element_type(&self, index: Option<usize>) -> Result<Type, String> {
+        match (self, index) {
+            (Type::Any, _) => Ok(Type::Any),
+            (Type::Array(element), _) => Ok((**element).clone()),
+            (Type::String, _) => Ok(Type::String),
+            (Type::Tuple { elements, rest, .. }, Some(index)) => {
+                match (elements.get(index), rest) {
+                    (Some(element), _) => Ok(element.clone()),
+                    (None, Some(rest)) => rest.element_type(None),
+                    (None, None) => Err(format!(
+                        "Tuple type '{}' of length '{}' has no element at index '{}'",
+                        self,
+                        elements.len(),
+                        index
+                    )),
+                }
+            }
+            (Type::Tuple { elements, rest, .. }, None) => {
+                let mut types = elements.clone();
+                if let Some(rest) = rest {
+                    types.push(rest.element_type(None)?);
+                }
+                Ok(match types.len() {
+                    0 => Type::Never,
+                    1 => types.remove(0),
+                    _ => Type::Union(types),
+                })
+            }
+            (Type::Union(types), _) => Ok(Type::Union(
+                types
+                    .iter()
+                    .map(|t| t.element_type(index))
+                    .collect::<Result<_, _>>()?,
+            )),
+            (Type::Shape(shape), _) if shape.get().number_index.is_some() => Ok(shape
+                .get()
+                .number_index
+                .as_ref()
+                .unwrap()
+                .value_type
+                .clone()),
+            _ => Err(format!(
+                "Type '{}' must have a '[Symbol.iterator]()' method that returns an iterator",
+                self
+            )),
+        }
+    }
+
+    /// This type with `undefined` removed from it.
+    pub fn without_undefined(&self) -> Type {
+        match self {
+            Type::Union(types) => {
+                let mut types: Vec<Type> = types
+                    .iter()
+                    .filter(|t| **t != Type::Undefined)
+                    .cloned()
+                    .collect();
+                match types.len() {
+                    0 => Type::Never,
+                    1 => types.pop().unwrap(),
+                    _ => Type::Union(types),
+                }
+            }
+            Type::Undefined => Type::Never,
+            _ => self.clone(),
+        }
+    }
+
+    /// Whether a value of this type can be `null` or `undefined`.
+    pub fn is_nullable(&self) -> bool {
+        match self {
+            Type::Null | Type::Undefined | Type::Void => true,
+            Type::Union(types) => types.iter().any(Type::is_nullable),
+            _ => false,
+        }
+    }
+
+    /// This type with `null` and `undefined` removed from it, as the object of a `?.` is
+    /// once the chain goes on.
+    pub fn non_nullable(&self) -> Type {
+        match self {
+            Type::Union(types) => {
+                Type::union(types.iter().filter(|t| !t.is_nullable()).cloned().collect())
+            }
+            t if t.is_nullable() => Type::Never,
+            _ => self.clone(),
+        }
+    }
+
+    /// A tuple of exactly `elements`.
+    pub fn tuple(elements: Vec<Type>) -> Type {
+        Type::Tuple {
+            required: elements.len(),
+            elements,
+            rest: None,
+        }
+    }
+
+    /// A union of `types`, flattening nested unions and dropping duplicates and `never`.
+    /// `any` and `unknown` absorb every other member, as a primitive does its literals and
+    /// an enum its members, and `true | false` is `boolean`.
+    pub fn union(types: Vec<Type>) -> Type {
+        let mut members: Vec<Type> = Vec::new();
+        for t in types {
+            let flattened = match t {
+                Type::Union(inner) => inner,
+                Type::Never => Vec::new(),
+                t => vec![t],
+            };
+            for t in flattened {
+                if !members.contains(&t) {
+                    members.push(t);
+                }
+            }
+        }
+        for top in [Type::Any, Type::Unknown] {
+            if members.contains(&top) {
+                return top;
+            }
+        }
+        let literal = |value: &str| Type::Literal(value.to_string());
+        if let Some(index) = members.iter().position(|t| *t == literal("true")) {
+            if members.contains(&literal("false")) {
+                members[index] = Type::Boolean;
+                members.retain(|t| *t != literal("false"));
+            }
+        }
+        let mut members: Vec<Type> = members
+            .iter()
+            .filter(|t| {
+                let wider = match t {
+                    Type::EnumMember(name, _) => Type::Enum(name.clone()),
+                    t => t.widen_literal(),
+                };
+                wider == **t || !members.contains(&wider)
+            })
+            .cloned()
+            .collect();
+        match members.len() {
+            0 => Type::Never,
+            1 => members.pop().unwrap(),
+            _ => Type::Union(members),
+        }
+    }
+
+    /// An intersection of `types`, flattening nested intersections and dropping duplicates
+    /// and `unknown`. Intersections with a union are distributed over its members. `never`
+    /// absorbs every other member, then `any` does; a literal absorbs its primitive, and
+    /// two primitives no value can be both of make `never`.
+    pub fn intersection(types: Vec<Type>) -> Type {
+        let mut members: Vec<Type> = Vec::new();
+        for t in types {
+            let flattened = match t {
+                Type::Intersection(inner) => inner,
+                Type::Unknown => Vec::new(),
+                t => vec![t],
+            };
+            for t in flattened {
+                if !members.contains(&t) {
+                    members.push(t);
+                }
+            }
+        }
+        if let Some(index) = members.iter().position(|t| matches!(t, Type::Union(_))) {
+            let Type::Union(alternatives) = members.remove(index) else {
+                unreachable!()
+            };
+            return Type::union(
+                alternatives
+                    .into_iter()
+                    .map(|alternative| {
+                        let mut product = members.clone();
+                        product.push(alternative);
+                        Type::intersection(product)
+                    })
+                    .collect(),
+            );
+        }
+        for bottom in [Type::Never, Type::Any] {
+            if members.contains(&bottom) {
+                return bottom;
+            }
+        }
+        let primitives: Vec<Type> = members
+            .iter()
+            .filter(|t| t.is_primitive())
+            .cloned()
+            .collect();
+        for (index, t) in primitives.iter().enumerate() {
+            for other in &primitives[index + 1..] {
+                if !t.is_assignable_to(other) && !other.is_assignable_to(t) {
+                    return Type::Never;
+                }
+            }
+        }
+        members.retain(|t| {
+            !primitives
+                .iter()
+                .any(|other| other != t && other.is_assignable_to(t))
+        });
+        match members.len() {
+            0 => Type::Unknown,
+            1 => members.pop().unwrap(),
+            _ => Type::Intersection(members),
+        }
+    }
+
+    /// The type of an optional chain whose `?.` links have objects of type `objects`, when
+    /// the access at its end has type `result`: `undefined` joins it if any link can
+    /// short-circuit.
+    pub fn optional_chain(objects: &[Type], result: Type) -> Type {
+        if result != Type::Any && objects.iter().any(Type::is_nullable) {
+            Type::union(vec![result, Type::Undefined])
+        } else {
+            result
+        }
+    }
+
+    /// The type of `left ?? right`: the right side is only used when the left is nullish.
+    pub fn nullish_coalescing(left: &Type, right: &Type) -> Type {
+        if !left.is_nullable() {
+            return left.clone();
+        }
+        Type::union(vec![left.non_nullable(), right.clone()])
+    }
+
+    /// Whether a value of this type could also be one of `other`, in either direction, as
+    /// a type assertion between them requires.
+    pub fn is_comparable_to(&self, other: &Type) -> bool {
+        match (self, other) {
+            (Type::Union(types), _) => types.iter().any(|t| t.is_comparable_to(other)),
+            (_, Type::Union(types)) => types.iter().any(|t| self.is_comparable_to(t)),
+            // An unconstrained type variable could be anything
+            (Type::Param(_), _) | (_, Type::Param(_)) => true,
+            // The members of built-in types aren't known, so they may overlap with any object
+            (Type::Custom(..), t) | (t, Type::Custom(..)) if !t.is_primitive() => true,
+            _ => self.is_assignable_to(other) || other.is_assignable_to(self),
+        }
+    }
+}
+
+/// The type of `expression as target` or `<target>expression`, where the expression has
+/// type `source`. A fresh literal should be widened first, so `"a" as "b"` is allowed but
+/// asserting a `"a"`-typed variable to `"b"` is not.
+pub fn type_assertion(source: &Type, target: &Type) -> Result<Type, String> {
+    if source.is_comparable_to(target) {
+        Ok(target.clone())
+    } else {
+        Err(format!(
+            "Conversion of type '{}' to type '{}' may be a mistake because neither type sufficiently overlaps with the other. If this was intentional, convert the expression to 'unknown' first.",
+            source, target
+        ))
+    }
+}
+
+/// The type of a literal, as written.
+pub fn literal_type(token_type: &TokenType, value: &str) -> Type {
+    match token_type {
+        TokenType::NumberLiteral | TokenType::BigInt => Type::Literal(value.to_string()),
+        TokenType::StringLiteral => Type::Literal(format!("\"{}\"", value)),
+        TokenType::TemplateLiteral if !value.contains("${") => {
+            Type::Literal(format!("\"{}\"", value))
+        }
+        TokenType::TemplateLiteral => Type::String,
+        TokenType::True => Type::Literal("true".to_string()),
+        TokenType::False => Type::Literal("false".to_string()),
+        TokenType::Null => Type::Null,
+        TokenType::Undefined => Type::Undefined,
+        _ => Type::Any,
+    }
+}
+
+/// The type of `expression satisfies target`: the expression keeps its own, unwidened type
+/// once it is known to fit `target`.
+pub fn satisfies(source: &Type, target: &Type) -> Result<Type, String> {
+    if source.is_assignable_to(target) {
+        Ok(source.clone())
+    } else {
+        Err(format!(
+            "Type '{}' does not satisfy the expected type '{}'.",
+            source, target
+        ))
+    }
+}
+
+/// The type of `expression as const`: literals keep their literal types and array literals
+/// become tuples of them. Object literals become object types with readonly properties of
+/// those types; properties with other values, like names, are `any`, and object literals
+/// with methods or spreads only become `object`.
+pub fn const_assertion(expression: &Node, shapes: &Shapes) -> Result<Type, String> {
+    match expression {
+        Node::Literal {
+            token_type, value, ..
+        } => match token_type {
+            TokenType::StringLiteral => Ok(Type::Literal(format!("\"{}\"", value))),
+            TokenType::TemplateLiteral if !value.contains("${") => {
+                Ok(Type::Literal(format!("\"{}\"", value)))
+            }
+            TokenType::TemplateLiteral => Ok(Type::String),
+            TokenType::NumberLiteral | TokenType::BigInt | TokenType::True | TokenType::False => {
+                Ok(Type::Literal(value.clone()))
+            }
+            _ => Err(CONST_ASSERTION_ERROR.to_string()),
+        },
+        Node::UnaryExpression { operator, argument } if operator == "-" => {
+            match argument.as_ref() {
+                Node::Literal {
+                    token_type: TokenType::NumberLiteral | TokenType::BigInt,
+                    value,
+                    ..
+                } => Ok(Type::Literal(format!("-{}", value))),
+                _ => Err(CONST_ASSERTION_ERROR.to_string()),
+            }
+        }
+        Node::ArrayExpression(elements) => {
+            let mut types = Vec::new();
+            for element in elements {
+                match element {
+                    None => types.push(Type::Undefined),
+                    // The length of a spread array isn't known
+                    Some(Node::SpreadElement(_)) => return Ok(Type::Array(Box::new(Type::Any))),
+                    Some(element) => types.push(const_assertion(element, shapes)?),
+                }
+            }
+            Ok(Type::tuple(types))
+        }
+        Node::ObjectExpression(properties) => {
+            let mut object = ObjectType::default();
+            for property in properties {
+                let Node::Property {
+                    key,
+                    computed,
+                    value,
+                    ..
+                } = property
+                else {
+                    return Ok(Type::Object);
+                };
+                let name = match (key.as_ref(), computed) {
+                    (Node::Identifier(name, _), false) => name,
+                    (Node::Literal { value, .. }, _) => value,
+                    _ => return Ok(Type::Object),
+                };
+                object.properties.retain(|property| property.name != *name);
+                object.properties.push(PropertyType {
+                    name: name.clone(),
+                    value_type: const_assertion(value, shapes).unwrap_or(Type::Any),
+                    optional: false,
+                    readonly: true,
+                });
+            }
+            Ok(Type::Shape(shapes.intern(object)))
+        }
+        Node::ConditionalExpression {
+            consequent,
+            alternate,
+            ..
+        } => Ok(Type::union(vec![
+            const_assertion(consequent, shapes)?,
+            const_assertion(alternate, shapes)?,
+        ])),
+        // Only enum members may be referenced; whether `E` is an enum is the caller's to check
+        Node::MemberExpression {
+            object,
+            property,
+            computed: false,
+            ..
+        } => match (object.as_ref(), property.as_ref()) {
+            (Node::Identifier(enum_name, _), Node::Identifier(member, _)) => {
+                Ok(Type::EnumMember(enum_name.clone(), member.clone()))
+            }
+            _ => Err(CONST_ASSERTION_ERROR.to_string()),
+        },
+        _ => Err(CONST_ASSERTION_ERROR.to_string()),
+    }
+}
+
+const CONST_ASSERTION_ERROR: &str = "A 'const' assertions can only be applied to references to enum members, or string, number, boolean, array, or object literals.";
+
+impl fmt::Display for TypeParam {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(constraint) = &self.constraint {
+            write!(f, " extends {}", constraint)?;
+        }
+        if let Some(default) = &self.default {
+            write!(f, " = {}", default)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Number => write!(f, "number"),
+            Type::String => write!(f, "string"),
+            Type::Boolean => write!(f, "boolean"),
+            Type::Null => write!(f, "null"),
+            Type::Undefined => write!(f, "undefined"),
+            Type::Any => write!(f, "any"),
+            Type::Unknown => write!(f, "unknown"),
+            Type::Never => write!(f, "never"),
+            Type::Void => write!(f, "void"),
+            Type::BigInt => write!(f, "bigint"),
+            Type::Symbol => write!(f, "symbol"),
+            Type::Object => write!(f, "object"),
+            Type::Function { .. } => write!(f, "{}", signature(self, " => ")),
+            Type::Union(types) => write!(f, "{}", join_operands(types, " | ")),
+            Type::Intersection(types) => write!(f, "{}", join_operands(types, " & ")),
+            Type::Literal(value) => write!(f, "{}", value),
+            Type::Array(element) => match **element {
+                Type::Union(_) | Type::Intersection(_) | Type::Function { .. } => {
+                    write!(f, "({})[]", element)
+                }
+                _ => write!(f, "{}[]", element),
+            },
+            Type::Tuple {
+                elements,
+                required,
+                rest,
+            } => {
+                let mut shown: Vec<String> = elements
+                    .iter()
+                    .enumerate()
+                    .map(|(index, element)| match index < *required {
+                        true => element.to_string(),
+                        false => format!("{}?", element.without_undefined()),
+                    })
+                    .collect();
+                shown.extend(rest.iter().map(|rest| format!("...{}", rest)));
+                write!(f, "[{}]", shown.join(", "))
+            }
+            Type::Custom(name, arguments) if arguments.is_empty() => write!(f, "{}", name),
+            Type::Custom(name, arguments) => write!(f, "{}<{}>", name, join(arguments, ", ")),
+            Type::Param(name) => write!(f, "{}", name),
+            Type::Enum(name) => write!(f, "{}", name),
+            Type::EnumMember(name, member) => write!(f, "{}.{}", name, member),
+            Type::Predicate {
+                parameter,
+                asserts,
+                guarded,
+            } => {
+                if *asserts {
+                    write!(f, "asserts ")?;
+                }
+                match parameter {
+                    Some(index) => write!(f, "arg{}", index)?,
+                    None => write!(f, "this")?,
+                }
+                match guarded {
+                    Some(guarded) => write!(f, " is {}", guarded),
+                    None => Ok(()),
+                }
+            }
+            Type::Shape(shape) => {
+                let object = shape.get();
+                match &object.name {
+                    Some(name) if object.type_arguments.is_empty() => write!(f, "{}", name),
+                    Some(name) => write!(f, "{}<{}>", name, join(&object.type_arguments, ", ")),
+                    None => {
+                        // Where a recursive type comes back to itself its members are elided
+                        let shapes = shape.shapes();
+                        let recursive = shapes.0.displaying.borrow().contains(shape);
+                        shapes.0.displaying.borrow_mut().push(shape.clone());
+                        let written = match recursive {
+                            true => write!(f, "..."),
+                            false => write!(f, "{}", members(&object)),
+                        };
+                        shapes.0.displaying.borrow_mut().pop();
+                        written
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A signature as a function type shows it with `" => "` before its return type, or as a
+/// member of an object type with `": "`.
+fn signature(function: &Type, separator: &str) -> String {
+    let Type::Function {
+        type_params,
+        params,
+        required,
+        rest,
+        return_type,
+        ..
+    } = function
+    else {
+        return function.to_string();
+    };
+    let type_params = match type_params.is_empty() {
+        true => String::new(),
+        false => format!("<{}>", join(type_params, ", ")),
+    };
+    let mut params: Vec<String> = params
+        .iter()
+        .enumerate()
+        .map(|(index, t)| match index < *required {
+            true => format!("arg{}: {}", index, t),
+            false => format!("arg{}?: {}", index, t.without_undefined()),
+        })
+        .collect();
+    if let Some(rest) = rest {
+        params.push(format!("...arg{}: {}", params.len(), rest));
+    }
+    format!(
+        "{}({}){}{}",
+        type_params,
+        params.join(", "),
+        separator,
+        return_type
+    )
+}
+
+/// The members of an anonymous object type, as tsc shows them: `{ a: number; m(): void; }`.
+fn members(object: &ObjectType) -> String {
+    let mut members: Vec<String> = Vec::new();
+    for call_signature in &object.call_signatures {
+        members.push(signature(call_signature, ": "));
+    }
+    for construct_signature in &object.construct_signatures {
+        members.push(format!("new {}", signature(construct_signature, ": ")));
+    }
+    let indexes = [
+        ("key: string", &object.string_index),
+        ("index: number", &object.number_index),
+    ];
+    for (key, index) in indexes {
+        if let Some(index) = index {
+            let readonly = if index.readonly { "readonly " } else { "" };
+            members.push(format!("{}[{}]: {}", readonly, key, index.value_type));
+        }
+    }
+    for property in &object.properties {
+        let optional = if property.optional { "?" } else { "" };
+        members.push(match &property.value_type {
+            method @ Type::Function { method: true, .. } => {
+                format!("{}{}{}", property.name, optional, signature(method, ": "))
+            }
+            value_type => {
+                let readonly = if property.readonly { "readonly " } else { "" };
+                format!("{}{}{}: {}", readonly, property.name, optional, value_type)
+            }
+        });
+    }
+    match members.is_empty() {
+        true => "{}".to_string(),
+        false => format!("{{ {}; }}", members.join("; ")),
+    }
+}
+
+fn join<T: fmt::Display>(items: &[T], separator: &str) -> String {
+    items
+        .iter()
+        .map(|item| item.to_string())
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+fn join_operands(types: &[Type], separator: &str) -> String {
+    types
+        .iter()
+        .map(|t| match t {
+            Type::Function { .. } | Type::Union(_) | Type::Intersection(_) => format!("({})", t),
+            _ => t.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn literal(value: &str) -> Type {
+        Type::Literal(value.to_string())
+    }
+
+    fn function(params: Vec<Type>, required: usize, return_type: Type) -> Type {
+        Type::Function {
+            type_params: Vec::new(),
+            params,
+            required,
+            rest: None,
+            return_type: Box::new(return_type),
+            method: false,
+        }
+    }
+
+    fn type_param(name: &str, constraint: Option<Type>, default: Option<Type>) -> TypeParam {
+        TypeParam {
+            name: name.to_string(),
+            constraint,
+            default,
+        }
+    }
+
+    fn shape(shapes: &Shapes, properties: Vec<(&str, Type, bool)>) -> Type {
+        Type::Shape(
+            shapes.intern(ObjectType {
+                properties: properties
+                    .into_iter()
+                    .map(|(name, value_type, optional)| PropertyType {
+                        name: name.to_string(),
+                        value_type,
+                        optional,
+                        readonly: false,
+                    })
+                    .collect(),
+                ..ObjectType::default()
+            }),
+        )
+    }
+
+    #[test]
+    fn literals_and_unions_are_assignable_to_what_they_widen_to() {
+        assert!(literal("1").is_assignable_to(&Type::Number));
+        assert!(!literal("1").is_assignable_to(&Type::String));
+        assert!(!Type::Number.is_assignable_to(&literal("1")));
+        let union = Type::Union(vec![Type::Number, Type::String]);
+        assert!(Type::String.is_assignable_to(&union));
+        assert!(!union.is_assignable_to(&Type::String));
+        assert!(Type::Never.is_assignable_to(&Type::String));
+        assert!(!Type::Any.is_assignable_to(&Type::Never));
+        assert!(Type::Undefined.is_assignable_to(&Type::Void));
+    }
+
+    #[test]
+    fn tuples_are_assignable_to_arrays_of_their_elements() {
+        let pair = Type::tuple(vec![Type::Number, literal("2")]);
+        assert!(pair.is_assignable_to(&Type::Array(Box::new(Type::Number))));
+        assert!(!pair.is_assignable_to(&Type::tuple(vec![Type::Number])));
+        assert!(!Type::Array(Box::new(Type::Number)).is_assignable_to(&pair));
+    }
+
+    #[test]
+    fn tuples_relate_by_how_many_elements_they_may_have() {
+        let optional = Type::Tuple {
+            elements: vec![
+                Type::Number,
+                Type::union(vec![Type::String, Type::Undefined]),
+            ],
+            required: 1,
+            rest: None,
+        };
+        assert_eq!(optional.to_string(), "[number, string?]");
+        assert!(Type::tuple(vec![Type::Number]).is_assignable_to(&optional));
+        assert!(Type::tuple(vec![Type::Number, Type::String]).is_assignable_to(&optional));
+        let triple = Type::tuple(vec![Type::Number, Type::String, Type::String]);
+        assert!(!triple.is_assignable_to(&optional));
+        assert!(!optional.is_assignable_to(&Type::tuple(vec![Type::Number, Type::String])));
+
+        let rest = Type::Tuple {
+            elements: vec![Type::Number],
+            required: 1,
+            rest: Some(Box::new(Type::Array(Box::new(Type::String)))),
+        };
+        assert_eq!(rest.to_string(), "[number, ...string[]]");
+        assert!(triple.is_assignable_to(&rest));
+        assert!(!Type::tuple(vec![Type::Number, Type::Number]).is_assignable_to(&rest));
+        assert!(!Type::tuple(Vec::new()).is_assignable_to(&rest));
+        assert!(!rest.is_assignable_to(&triple));
+    }
+
+    #[test]
+    fn functions_take_fewer_parameters_and_compare_them_contravariantly() {
+        let target = function(vec![Type::Number, Type::String], 2, Type::Void);
+        assert!(function(vec![Type::Number], 1, Type::Number).is_assignable_to(&target));
+        assert!(!function(vec![literal("1")], 1, Type::Void).is_assignable_to(&target));
+        let needs_three = function(vec![Type::Number, Type::String, Type::Any], 3, Type::Void);
+        assert!(!needs_three.is_assignable_to(&target));
+        let returns_number = function(Vec::new(), 0, Type::Number);
+        assert!(!returns_number.is_assignable_to(&function(Vec::new(), 0, Type::String)));
+    }
+
+    #[test]
+    fn object_types_need_every_required_property() {
+        let shapes = Shapes::default();
+        let target = shape(
+            &shapes,
+            vec![("a", Type::Number, false), ("b", Type::String, true)],
+        );
+        assert!(shape(&shapes, vec![("a", literal("1"), false)]).is_assignable_to(&target));
+        assert!(!shape(&shapes, vec![("b", Type::String, false)]).is_assignable_to(&target));
+        let wrong = shape(&shapes, vec![("a", Type::String, false)]);
+        assert!(!wrong.is_assignable_to(&target));
+    }
+
+    #[test]
+    fn equal_object_types_share_a_shape_within_their_compilation() {
+        let shapes = Shapes::default();
+        let a = shape(&shapes, vec![("a", Type::Number, false)]);
+        assert_eq!(a, shape(&shapes, vec![("a", Type::Number, false)]));
+        assert_ne!(a, shape(&shapes, vec![("a", Type::String, false)]));
+        let other = Shapes::default();
+        assert_ne!(a, shape(&other, vec![("a", Type::Number, false)]));
+    }
+
+    #[test]
+    fn recursive_object_types_relate_and_display() {
+        let shapes = Shapes::default();
+        let list = |value_type: Type| {
+            let shape = shapes.reserve();
+            shape.define(ObjectType {
+                properties: vec![
+                    PropertyType {
+                        name: "value".to_string(),
+                        value_type,
+                        optional: false,
+                        readonly: false,
+                    },
+                    PropertyType {
+                        name: "next".to_string(),
+                        value_type: Type::Shape(shape.clone()),
+                        optional: true,
+                        readonly: false,
+                    },
+                ],
+                ..ObjectType::default()
+            });
+            Type::Shape(shape)
+        };
+        let (literals, numbers) = (list(literal("1")), list(Type::Number));
+        assert!(literals.is_assignable_to(&numbers));
+        assert!(!numbers.is_assignable_to(&literals));
+        assert_eq!(numbers.to_string(), "{ value: number; next?: ...; }");
+    }
+
+    #[test]
+    fn instantiate_fills_in_defaults_from_earlier_arguments() {
+        let type_params = [
+            type_param("T", None, None),
+            type_param(
+                "U",
+                None,
+                Some(Type::Array(Box::new(Type::Param("T".into())))),
+            ),
+        ];
+        let mapping = instantiate(&type_params, &[Type::String]).unwrap();
+        assert_eq!(mapping["T"], Type::String);
+        assert_eq!(mapping["U"], Type::Array(Box::new(Type::String)));
+    }
+
+    #[test]
+    fn instantiate_checks_arity_and_constraints() {
+        let type_params = [
+            type_param("T", Some(Type::Number), None),
+            type_param("U", None, Some(Type::String)),
+        ];
+        assert_eq!(
+            instantiate(&type_params, &[]).unwrap_err(),
+            "Expected 1-2 type arguments, but got 0"
+        );
+        assert_eq!(
+            instantiate(&type_params, &vec![Type::Number; 3]).unwrap_err(),
+            "Expected 1-2 type arguments, but got 3"
+        );
+        assert_eq!(
+            instantiate(&type_params, &[Type::String]).unwrap_err(),
+            "Type 'string' does not satisfy the constraint 'number'"
+        );
+        assert!(instantiate(&type_params, &[literal("1")]).is_ok());
+    }
+
+    #[test]
+    fn instantiate_call_infers_type_arguments_from_arguments() {
+        let identity = Type::Function {
+            type_params: vec![type_param("T", None, None)],
+            params: vec![Type::Param("T".into())],
+            required: 1,
+            rest: None,
+            return_type: Box::new(Type::Param("T".into())),
+            method: false,
+        };
+        let call = instantiate_call(&identity, &[], &[literal("1")]).unwrap();
+        assert_eq!(call, function(vec![Type::Number], 1, Type::Number));
+        let explicit = instantiate_call(&identity, &[Type::String], &[]).unwrap();
+        assert_eq!(explicit, function(vec![Type::String], 1, Type::String));
+    }
+
+    #[test]
+    fn inferred_type_arguments_breaking_their_constraint_become_the_constraint() {
+        let type_params = [type_param("T", Some(Type::String), None)];
+        let params = [Type::Param("T".into())];
+        let inferred = infer_type_arguments(&type_params, &params, &[literal("1")]);
+        assert_eq!(inferred, [Type::String]);
+        let inferred = infer_type_arguments(&type_params, &params, &[literal("'a'")]);
+        assert_eq!(inferred, [literal("'a'")]);
+    }
+}